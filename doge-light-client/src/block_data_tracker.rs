@@ -84,6 +84,104 @@ pub struct BlockDataTracker<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, con
 }
 
 
+/// Ring buffer of the block data records of the most recent blocks.
+///
+/// Implemented by [`BlockDataTracker`] and by chain states whose cache size is only known at runtime,
+/// which all share the logic below.
+pub trait BlockDataStorage {
+    fn get_required_confirmations(&self) -> usize;
+    fn get_tip_block_number(&self) -> u32;
+    fn get_tip_internal_index(&self) -> u16;
+    fn set_tip(&mut self, tip_block_number: u32, tip_internal_index: u16);
+    fn get_records(&self) -> &[BlockDataRecord];
+    fn get_records_mut(&mut self) -> &mut [BlockDataRecord];
+
+    fn get_block_hash_cache_size(&self) -> usize {
+        self.get_records().len()
+    }
+    fn get_finalized_block_number(&self) -> u32 {
+        self.get_tip_block_number() - self.get_required_confirmations() as u32
+    }
+    fn contains_block_range(&self, start_block_number_inclusive: u32, end_block_number_inclusive: u32) -> bool {
+        self.get_tip_block_number() <= end_block_number_inclusive && start_block_number_inclusive > self.get_tip_block_number() - self.get_block_hash_cache_size() as u32
+    }
+    fn contains_block(&self, block_number: u32) -> bool {
+        block_number <= self.get_tip_block_number() && block_number > self.get_tip_block_number() - self.get_block_hash_cache_size() as u32
+    }
+    fn get_index_for_block_unchecked(&self, block_number: u32) -> usize {
+        let cache_size = self.get_block_hash_cache_size();
+        (cache_size + self.get_tip_internal_index() as usize - (self.get_tip_block_number() - block_number) as usize) % cache_size
+    }
+    fn get_record(&self, block_number: u32) -> QDogeResult<BlockDataRecord> {
+        if !self.contains_block(block_number) {
+            return Err(DogeBridgeError::BlockNotInCache);
+        }
+        Ok(self.get_records()[self.get_index_for_block_unchecked(block_number)])
+    }
+    fn get_block_hash(&self, block_number: u32) -> QDogeResult<QHash256> {
+        Ok(self.get_record(block_number)?.block_hash)
+    }
+    fn get_pow_context(&self, block_number: u32) -> QDogeResult<PoWBlockContext> {
+        if block_number < 2 || !self.contains_block_range(block_number-2, block_number-1) {
+            return Err(DogeBridgeError::BlockNotInCache);
+        }
+        let last_record = &self.get_records()[self.get_index_for_block_unchecked(block_number-1)];
+        let first_record = &self.get_records()[self.get_index_for_block_unchecked(block_number-2)];
+        Ok(PoWBlockContext {
+            last_height: block_number-1,
+            last_block_time: last_record.timestamp.into(),
+            last_bits: last_record.bits.into(),
+            first_block_time: first_record.timestamp.into(),
+        })
+    }
+    fn add_record(&mut self, record: BlockDataRecord) {
+        let new_tip_index = (self.get_tip_internal_index() as usize +1)%self.get_block_hash_cache_size();
+        self.get_records_mut()[new_tip_index] = record;
+        self.set_tip(self.get_tip_block_number() + 1, new_tip_index as u16);
+    }
+    fn rollback_first(&mut self, last_good_block_number: u32, num_blocks_to_insert: usize) -> QDogeResult<()> {
+        if last_good_block_number == self.get_tip_block_number() {
+            return Ok(());
+        }
+        if !self.contains_block(last_good_block_number) {
+            return Err(DogeBridgeError::BlockNotInCache);
+        }
+        let offset = (self.get_tip_block_number() - last_good_block_number) as usize;
+        if offset >= self.get_required_confirmations() {
+            return Err(DogeBridgeError::AttemptedToModifiyFinalizedBlock);
+        }
+        if offset < num_blocks_to_insert {
+            return Err(DogeBridgeError::InsufficientBlocksProvidedForRollback);
+        }
+        let cache_size = self.get_block_hash_cache_size();
+        let tip_internal_index = ((self.get_tip_internal_index() as usize + cache_size - offset) % cache_size) as u16;
+        self.set_tip(last_good_block_number, tip_internal_index);
+        Ok(())
+    }
+}
+
+impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize> BlockDataStorage for BlockDataTracker<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS> {
+    fn get_required_confirmations(&self) -> usize {
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS
+    }
+    fn get_tip_block_number(&self) -> u32 {
+        self.tip_block_number.into()
+    }
+    fn get_tip_internal_index(&self) -> u16 {
+        self.tip_internal_index.into()
+    }
+    fn set_tip(&mut self, tip_block_number: u32, tip_internal_index: u16) {
+        self.tip_block_number = tip_block_number.into();
+        self.tip_internal_index = tip_internal_index.into();
+    }
+    fn get_records(&self) -> &[BlockDataRecord] {
+        &self.records
+    }
+    fn get_records_mut(&mut self) -> &mut [BlockDataRecord] {
+        &mut self.records
+    }
+}
+
 impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize> BlockDataTracker<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS> {
     /*
     fn new_empty() -> Self {
@@ -118,55 +216,6 @@ impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRE
         Some(self.get_record_if_exists(block_number)?.block_hash)
     }
 
-    pub fn get_block_hash(&self, block_number: u32) -> QDogeResult<QHash256> {
-        if !self.contains_block(block_number) {
-            return Err(DogeBridgeError::BlockNotInCache);
-        }
-        Ok(self.get_record(block_number)?.block_hash)
-    }
-
-    pub fn get_pow_context(&self, block_number: u32) -> QDogeResult<PoWBlockContext> {
-        if block_number < 2 || !self.contains_block_range(block_number-2, block_number-1) {
-            return Err(DogeBridgeError::BlockNotInCache);
-        }
-        let last_index  = self.get_index_for_block_unchecked(block_number-1);
-        let first_index  = self.get_index_for_block_unchecked(block_number-2);
-        Ok(PoWBlockContext {
-            last_height: block_number-1,
-            last_block_time: self.records[last_index].timestamp.into(),
-            last_bits: self.records[last_index].bits.into(),
-            first_block_time: self.records[first_index].timestamp.into(),
-        })
-    }
-    pub fn get_tip_block_number(&self) -> u32 {
-        self.tip_block_number.into()
-    }
-
-    pub fn get_finalized_block_number(&self) -> u32 {
-        self.get_tip_block_number() - QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS as u32
-    }
-    pub fn get_tip_internal_index(&self) -> u16 {
-        self.tip_internal_index.into()
-    }
-
-
-
-    pub fn add_record(&mut self, record: BlockDataRecord) {
-        let new_tip_index = (self.get_tip_internal_index() as usize +1)%(QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE);
-        self.records[new_tip_index] = record;
-        self.tip_internal_index = (new_tip_index as u16).into();
-        self.tip_block_number += 1;
-    }
-    pub fn contains_block_range(&self, start_block_number_inclusive: u32, end_block_number_inclusive: u32) -> bool {
-        self.tip_block_number <= end_block_number_inclusive && start_block_number_inclusive > self.get_tip_block_number() - QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32
-    }
-    pub fn contains_block(&self, block_number: u32) -> bool {
-        block_number <= self.get_tip_block_number() && block_number > self.get_tip_block_number() - QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32
-    }
-    fn get_index_for_block_unchecked(&self, block_number: u32) -> usize {
-        (QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE + self.get_tip_internal_index() as usize - (self.get_tip_block_number() - block_number) as usize) % QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE
-    }
-
     pub fn get_record_if_exists(&self, block_number: u32) -> Option<BlockDataRecord> {
         if !self.contains_block(block_number) {
             return None;
@@ -174,31 +223,6 @@ impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRE
         Some(self.records[self.get_index_for_block_unchecked(block_number)])
     }
 
-    pub fn get_record(&self, block_number: u32) -> QDogeResult<BlockDataRecord> {
-        if !self.contains_block(block_number) {
-            return Err(DogeBridgeError::BlockNotInCache);
-        }else{
-            Ok(self.records[self.get_index_for_block_unchecked(block_number)])
-        }
-    }
-    pub fn rollback_first(&mut self, last_good_block_number: u32, num_blocks_to_insert: usize) -> QDogeResult<()> {
-        if last_good_block_number == self.get_tip_block_number() {
-            return Ok(());
-        }
-        if !self.contains_block(last_good_block_number) {
-            return Err(DogeBridgeError::BlockNotInCache);
-        }
-        let offset = (self.get_tip_block_number() - last_good_block_number) as usize;
-        if offset >= QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS as usize {
-            return Err(DogeBridgeError::AttemptedToModifiyFinalizedBlock);
-        }
-        if offset < num_blocks_to_insert {
-            return Err(DogeBridgeError::InsufficientBlocksProvidedForRollback);
-        }
-        self.tip_internal_index = (((self.get_tip_internal_index() as usize + QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE - offset as usize) % QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE) as u16).into();
-        self.tip_block_number = last_good_block_number.into();
-        Ok(())
-    }
     pub fn rollback_insert(&mut self, last_good_block_number: u32, blocks: &[BlockDataRecord]) -> QDogeResult<()> {
         self.rollback_first(last_good_block_number, blocks.len())?;
         for block in blocks {
@@ -211,9 +235,7 @@ impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRE
         if !self.contains_block(block_number) {
             return None;
         }
-
-        let index = (QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE + self.get_tip_internal_index() as usize - (self.get_tip_block_number() - block_number) as usize) % QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE;
-        Some(&self.records[index])
+        Some(&self.records[self.get_index_for_block_unchecked(block_number)])
     }
 
 
@@ -221,8 +243,6 @@ impl<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, const QDOGE_BRIDGE_REQUIRE
         if !self.contains_block(block_number) {
             return Err(DogeBridgeError::BlockNotInCache);
         }
-
-        let index = (QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE + self.get_tip_internal_index() as usize - (self.get_tip_block_number() - block_number) as usize) % QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE;
-        Ok(&self.records[index])
+        Ok(&self.records[self.get_index_for_block_unchecked(block_number)])
     }
-}
\ No newline at end of file
+}
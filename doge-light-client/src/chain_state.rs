@@ -30,97 +30,79 @@ use borsh::{BorshSerialize, BorshDeserialize};
 use serde::{Serialize, Deserialize};
 
 
+use core::marker::PhantomData;
+
+use zerocopy::{FromBytes, Immutable, IntoBytes};
 use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
 
 use crate::{
-    block_data_tracker::{BlockDataRecord, BlockDataTracker}, constants::DogeNetworkConfig, core_data::{QDogeBlockHeader, QHash256}, error::{DogeBridgeError, QDogeResult}, hash::{merkle::fixed_append_tree::FixedMerkleAppendTree, sha256::QSha256Hasher}, init_params::InitBlockDataIBC, logic::check_doge_block::check_block_header_err
+    block_data_tracker::{BlockDataRecord, BlockDataStorage, BlockDataTracker}, constants::DogeNetworkConfig, core_data::{QDogeBlockHeader, QHash256}, error::{DogeBridgeError, QDogeResult}, hash::{merkle::{accumulator::MerkleAppendAccumulator, fixed_append_tree::FixedMerkleAppendTree, mmr::MerkleMountainRange}, sha256::QSha256Hasher, traits::{BytesHasher, MerkleHasher, MerkleZeroHasher}}, init_params::InitBlockDataIBC, logic::check_doge_block::check_block_header_err, state_commitment::{QEDDogeStateCommitmentBuilder, QEDDogeStateSummary}, state_diff::{get_state_hash, QEDDogeStateDiff}
 };
 
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromBytes, Immutable, KnownLayout, IntoBytes, Unaligned)]
-#[repr(C)]
-pub struct QEDDogeChainStateCore<
-    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
-    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
-    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
-> {
-    pub block_data_tracker:
-        BlockDataTracker<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS>,
-    pub block_tree_tracker: FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
-}
-
-type QBlockTreeTrackerHasher = QSha256Hasher;
-
-impl<
-        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
-        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
-        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
-    >
-    QEDDogeChainStateCore<
-        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
-        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
-        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
-    >
-{
-    pub fn new(
-        block_data_tracker: BlockDataTracker<
-            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
-            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
-        >,
-        block_tree_tracker: FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
-    ) -> Self {
-        Self {
-            block_data_tracker,
-            block_tree_tracker,
-        }
-    }
-
-    pub fn contains_block(&self, block_number: u32) -> bool {
-        self.block_data_tracker.contains_block(block_number)
+/// Storage of a chain state: the block data records of the most recent blocks and an accumulator committing to
+/// every processed block hash.
+///
+/// The block processing logic is implemented once on top of this trait and shared by [`QEDDogeChainStateCore`]
+/// and [`crate::chain_state_view::QEDDogeChainStateView`].
+pub trait QEDDogeChainStateStorage {
+    type BlockData: BlockDataStorage;
+
+    fn get_block_data(&self) -> &Self::BlockData;
+    fn get_block_data_mut(&mut self) -> &mut Self::BlockData;
+    fn get_block_tree_next_index(&self) -> u64;
+    fn get_block_tree_value(&self) -> QHash256;
+    fn get_block_tree_root(&self) -> QHash256;
+    fn append_to_block_tree(&mut self, value: QHash256) -> QDogeResult<()>;
+    fn revert_block_tree_to_index(&mut self, index: u64, restore_data: &[QHash256], value: QHash256) -> QDogeResult<()>;
+
+    fn contains_block(&self, block_number: u32) -> bool {
+        self.get_block_data().contains_block(block_number)
     }
-    pub fn contains_block_range(
+    fn contains_block_range(
         &self,
         start_block_number_inclusive: u32,
         end_block_number_inclusive: u32,
     ) -> bool {
-        self.block_data_tracker
+        self.get_block_data()
             .contains_block_range(start_block_number_inclusive, end_block_number_inclusive)
     }
-    pub fn get_block_hash(&self, block_number: u32) -> QDogeResult<QHash256> {
-        self.block_data_tracker.get_block_hash(block_number)
+    fn get_record(&self, block_number: u32) -> QDogeResult<BlockDataRecord> {
+        self.get_block_data().get_record(block_number)
     }
-    pub fn get_finalized_block_number(&self) -> u32 {
-        self.block_data_tracker.get_finalized_block_number()
+    fn get_block_hash(&self, block_number: u32) -> QDogeResult<QHash256> {
+        self.get_block_data().get_block_hash(block_number)
     }
-    pub fn get_tip_block_number(&self) -> u32 {
-        self.block_data_tracker.get_tip_block_number()
+    fn get_finalized_block_number(&self) -> u32 {
+        self.get_block_data().get_finalized_block_number()
     }
-    pub fn get_tip_block_hash(&self) -> QHash256 {
-        self.block_data_tracker
+    fn get_tip_block_number(&self) -> u32 {
+        self.get_block_data().get_tip_block_number()
+    }
+    fn get_tip_block_hash(&self) -> QHash256 {
+        self.get_block_data()
             .get_block_hash(self.get_tip_block_number())
             .unwrap()
     }
-    pub fn get_finalized_block_hash(&self) -> QHash256 {
-        self.block_data_tracker
+    fn get_finalized_block_hash(&self) -> QHash256 {
+        self.get_block_data()
             .get_block_hash(self.get_finalized_block_number())
             .unwrap()
     }
-    pub fn ensure_internal_consistency(&self) -> QDogeResult<()> {
+    fn ensure_internal_consistency(&self) -> QDogeResult<()> {
         // sanity checks
-        if (self.block_data_tracker.get_tip_block_number() + 1)
-            != (self.block_tree_tracker.get_next_index()) as u32
+        if (self.get_tip_block_number() + 1)
+            != (self.get_block_tree_next_index()) as u32
         {
             return Err(DogeBridgeError::BlockTipSyncMismatch);
         }
 
-        if self.get_tip_block_hash() != self.block_tree_tracker.get_value() {
+        if self.get_tip_block_hash() != self.get_block_tree_value() {
             return Err(DogeBridgeError::BlockTipSyncMismatch);
         }
         Ok(())
     }
 
-    pub fn rollback_insert_blocks<NC: DogeNetworkConfig>(
+    fn rollback_insert_blocks<NC: DogeNetworkConfig>(
         &mut self,
         last_good_block_number: u32,
         tree_tracker_changed_left_siblings: &[QHash256],
@@ -132,26 +114,21 @@ impl<
             return Err(DogeBridgeError::AuxPowMissing);
         }
 
-        let good_record = self.block_data_tracker.get_record(last_good_block_number)?;
-        self.block_tree_tracker
-            .revert_to_index::<QBlockTreeTrackerHasher>(
-                last_good_block_number as u64,
-                tree_tracker_changed_left_siblings,
-                good_record.block_hash,
-            )?;
-        if self
-            .block_tree_tracker
-            .get_root::<QBlockTreeTrackerHasher>()
-            != good_record.block_hash_tree_root
-        {
+        let good_record = self.get_record(last_good_block_number)?;
+        self.revert_block_tree_to_index(
+            last_good_block_number as u64,
+            tree_tracker_changed_left_siblings,
+            good_record.block_hash,
+        )?;
+        if self.get_block_tree_root() != good_record.block_hash_tree_root {
             return Err(DogeBridgeError::RollbackBlockTreeRootMismatch);
         }
 
-        if self.block_tree_tracker.next_index != (last_good_block_number + 1) as u64 {
+        if self.get_block_tree_next_index() != (last_good_block_number + 1) as u64 {
             return Err(DogeBridgeError::RollbackBlockTreeIndexMismatch);
         }
 
-        self.block_data_tracker
+        self.get_block_data_mut()
             .rollback_first(last_good_block_number, blocks.len())?;
         for (i, (block, optional_aux_pow_hash)) in blocks.iter().zip(known_aux_pow_block_hashes).enumerate() {
             self.append_block::<NC>(last_good_block_number + i as u32 + 1, block, *optional_aux_pow_hash)?;
@@ -161,7 +138,7 @@ impl<
         Ok(())
     }
 
-    pub fn append_block<NC: DogeNetworkConfig>(
+    fn append_block<NC: DogeNetworkConfig>(
         &mut self,
         block_number: u32,
         block_header: &QDogeBlockHeader,
@@ -181,7 +158,7 @@ impl<
 
         self.ensure_internal_consistency()?;
 
-        let pow_context = self.block_data_tracker.get_pow_context(block_number)?;
+        let pow_context = self.get_block_data().get_pow_context(block_number)?;
         check_block_header_err::<NC>(
             pow_context.last_height,
             block_header,
@@ -193,11 +170,8 @@ impl<
 
         let new_block_hash = block_header.header.get_hash();
 
-        self.block_tree_tracker
-            .append::<QBlockTreeTrackerHasher>(new_block_hash);
-        let block_hash_tree_root = self
-            .block_tree_tracker
-            .get_root::<QBlockTreeTrackerHasher>();
+        self.append_to_block_tree(new_block_hash)?;
+        let block_hash_tree_root = self.get_block_tree_root();
 
         let block_data_record = BlockDataRecord {
             block_hash_tree_root,
@@ -207,43 +181,100 @@ impl<
             bits: block_header.header.bits.into(),
         };
 
-        self.block_data_tracker.add_record(block_data_record);
+        self.get_block_data_mut().add_record(block_data_record);
 
         self.ensure_internal_consistency()?;
 
         Ok(())
     }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromBytes, Immutable, KnownLayout, IntoBytes, Unaligned)]
+#[repr(C)]
+pub struct QEDDogeChainStateCore<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeTracker = FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+    BlockTreeHasher = QSha256Hasher,
+> {
+    pub block_data_tracker:
+        BlockDataTracker<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS>,
+    pub block_tree_tracker: BlockTreeTracker,
+    _block_tree_hasher: PhantomData<BlockTreeHasher>,
+}
+
+/// Chain state which commits to the processed block hashes with a merkle mountain range instead of a fixed height tree.
+pub type QEDDogeChainStateCoreMMR<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_MMR_MAX_PEAKS: usize,
+    BlockTreeHasher = QSha256Hasher,
+> = QEDDogeChainStateCore<
+    QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+    QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+    QDOGE_BRIDGE_MMR_MAX_PEAKS,
+    MerkleMountainRange<QHash256, QDOGE_BRIDGE_MMR_MAX_PEAKS>,
+    BlockTreeHasher,
+>;
+
+impl<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+        BlockTreeTracker: MerkleAppendAccumulator<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        BlockTreeHasher: MerkleZeroHasher<QHash256>,
+    >
+    QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >
+{
+    pub fn new(
+        block_data_tracker: BlockDataTracker<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        >,
+        block_tree_tracker: BlockTreeTracker,
+    ) -> Self {
+        Self {
+            block_data_tracker,
+            block_tree_tracker,
+            _block_tree_hasher: PhantomData,
+        }
+    }
 
-    
     pub fn from_init_data(
         init_data: &InitBlockDataIBC<
             QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
             QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+            BlockTreeHasher,
         >,
-    ) -> Self {
+    ) -> QDogeResult<Self> {
         let tip_block_number = init_data.tip_block_number;
 
         let start_block = tip_block_number.saturating_sub(QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32 - 1u32);
 
         let mut append_tree =
-            FixedMerkleAppendTree::<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>::new_from_hasher::<
-                QBlockTreeTrackerHasher,
-            >(
+            BlockTreeTracker::new_from_siblings::<BlockTreeHasher>(
                 start_block as u64 + 1,
                 init_data.tracker_tree_siblings,
                 init_data.records[0].block_hash,
-            );
+            )?;
 
         let mut records: [BlockDataRecord; QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE] =
             init_data.records.map(|x| x.into());
 
-        records[0].block_hash_tree_root = append_tree.get_root::<QBlockTreeTrackerHasher>();
+        records[0].block_hash_tree_root = append_tree.get_root::<BlockTreeHasher>();
 
         for i in 1..QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE {
-            let new_root = append_tree
-                .append_delta_merkle_proof::<QBlockTreeTrackerHasher>(records[i].block_hash)
-                .new_root;
-            records[i].block_hash_tree_root = new_root;
+            append_tree.append::<BlockTreeHasher>(records[i].block_hash)?;
+            records[i].block_hash_tree_root = append_tree.get_root::<BlockTreeHasher>();
         }
         let block_data_tracker = BlockDataTracker::new_with_data(
             tip_block_number,
@@ -251,13 +282,169 @@ impl<
             records,
         );
     
-        Self::new(block_data_tracker, append_tree)
+        Ok(Self::new(block_data_tracker, append_tree))
+    }
+
+    // inherent forwarders of the QEDDogeChainStateStorage methods, so callers do not need the trait in scope
+    pub fn contains_block(&self, block_number: u32) -> bool {
+        QEDDogeChainStateStorage::contains_block(self, block_number)
+    }
+    pub fn contains_block_range(
+        &self,
+        start_block_number_inclusive: u32,
+        end_block_number_inclusive: u32,
+    ) -> bool {
+        QEDDogeChainStateStorage::contains_block_range(self, start_block_number_inclusive, end_block_number_inclusive)
+    }
+    pub fn get_block_hash(&self, block_number: u32) -> QDogeResult<QHash256> {
+        QEDDogeChainStateStorage::get_block_hash(self, block_number)
+    }
+    pub fn get_finalized_block_number(&self) -> u32 {
+        QEDDogeChainStateStorage::get_finalized_block_number(self)
+    }
+    pub fn get_tip_block_number(&self) -> u32 {
+        QEDDogeChainStateStorage::get_tip_block_number(self)
+    }
+    pub fn get_tip_block_hash(&self) -> QHash256 {
+        QEDDogeChainStateStorage::get_tip_block_hash(self)
+    }
+    pub fn get_finalized_block_hash(&self) -> QHash256 {
+        QEDDogeChainStateStorage::get_finalized_block_hash(self)
+    }
+    pub fn ensure_internal_consistency(&self) -> QDogeResult<()> {
+        QEDDogeChainStateStorage::ensure_internal_consistency(self)
+    }
+    pub fn rollback_insert_blocks<NC: DogeNetworkConfig>(
+        &mut self,
+        last_good_block_number: u32,
+        tree_tracker_changed_left_siblings: &[QHash256],
+        blocks: &[QDogeBlockHeader],
+        known_aux_pow_block_hashes: &[Option<QHash256>],
+    ) -> QDogeResult<()> {
+        QEDDogeChainStateStorage::rollback_insert_blocks::<NC>(
+            self,
+            last_good_block_number,
+            tree_tracker_changed_left_siblings,
+            blocks,
+            known_aux_pow_block_hashes,
+        )
     }
+    pub fn append_block<NC: DogeNetworkConfig>(
+        &mut self,
+        block_number: u32,
+        block_header: &QDogeBlockHeader,
+        known_aux_pow_block_hash: Option<QHash256>,
+    ) -> QDogeResult<()> {
+        QEDDogeChainStateStorage::append_block::<NC>(self, block_number, block_header, known_aux_pow_block_hash)
+    }
+}
+
+impl<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+        BlockTreeTracker: MerkleAppendAccumulator<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        BlockTreeHasher: MerkleZeroHasher<QHash256>,
+    > QEDDogeChainStateStorage
+    for QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >
+{
+    type BlockData = BlockDataTracker<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS>;
 
+    fn get_block_data(&self) -> &Self::BlockData {
+        &self.block_data_tracker
+    }
+    fn get_block_data_mut(&mut self) -> &mut Self::BlockData {
+        &mut self.block_data_tracker
+    }
+    fn get_block_tree_next_index(&self) -> u64 {
+        self.block_tree_tracker.get_next_index()
+    }
+    fn get_block_tree_value(&self) -> QHash256 {
+        self.block_tree_tracker.get_value()
+    }
+    fn get_block_tree_root(&self) -> QHash256 {
+        self.block_tree_tracker.get_root::<BlockTreeHasher>()
+    }
+    fn append_to_block_tree(&mut self, value: QHash256) -> QDogeResult<()> {
+        self.block_tree_tracker.append::<BlockTreeHasher>(value)
+    }
+    fn revert_block_tree_to_index(&mut self, index: u64, restore_data: &[QHash256], value: QHash256) -> QDogeResult<()> {
+        self.block_tree_tracker
+            .revert_to_index::<BlockTreeHasher>(index, restore_data, value)
+    }
+}
 
+impl<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >
+    QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >
+where
+    Self: FromBytes + IntoBytes + Immutable,
+{
+    pub fn get_state_hash(&self) -> QHash256 {
+        get_state_hash(self.as_bytes())
+    }
+    /// Diff which transitions `old_state` to this state.
+    pub fn get_state_diff(&self, old_state: &Self) -> QDogeResult<QEDDogeStateDiff> {
+        QEDDogeStateDiff::new(old_state.as_bytes(), self.as_bytes())
+    }
+    pub fn apply_state_diff(&mut self, diff: &QEDDogeStateDiff) -> QDogeResult<()> {
+        diff.apply(self.as_mut_bytes())
+    }
 }
 
 
+impl<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+        BlockTreeHasher: MerkleHasher<QHash256>,
+    >
+    QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        BlockTreeHasher,
+    >
+{
+    pub fn get_state_commitment_builder(&self) -> QEDDogeStateCommitmentBuilder<'_> {
+        QEDDogeStateCommitmentBuilder {
+            summary: QEDDogeStateSummary {
+                tip_block_number: self.block_data_tracker.tip_block_number,
+                finalized_block_number: self.block_data_tracker.get_finalized_block_number().into(),
+                tip_internal_index: self.block_data_tracker.tip_internal_index,
+                block_hash_cache_size: (QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32).into(),
+                block_tree_height: (QDOGE_BRIDGE_BLOCK_TREE_HEIGHT as u32).into(),
+                block_tree_next_index: self.block_tree_tracker.next_index,
+                block_tree_root: self.block_tree_tracker.get_root::<BlockTreeHasher>(),
+            },
+            records: &self.block_data_tracker.records,
+            levels: &self.block_tree_tracker.levels,
+        }
+    }
+    /// Hash committing to the whole state, see [`crate::state_commitment`].
+    pub fn get_state_commitment<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self) -> QHash256 {
+        self.get_state_commitment_builder().get_commitment::<Hasher>()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -265,4 +452,4 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,753 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Runtime-sized chain state operating directly on account data.
+//!
+//! The data starts with a [`QEDDogeChainStateLayout`] header holding the cache size, required confirmations and
+//! block tree height, followed by exactly the bytes of the equivalent `QEDDogeChainStateCore<CACHE, CONFIRMATIONS, HEIGHT>`,
+//! so a view and a const generic state can be converted into each other with a plain copy.
+
+use core::marker::PhantomData;
+
+use zerocopy::{
+    little_endian::{U16, U32, U64},
+    FromBytes, FromZeros, IntoBytes,
+};
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{
+    block_data_tracker::{BlockDataRecord, BlockDataStorage},
+    chain_state::{QEDDogeChainStateCore, QEDDogeChainStateStorage},
+    core_data::{QHash256, QStandardBlockHeader},
+    error::{DogeBridgeError, QDogeResult},
+    hash::{
+        merkle::{
+            append_tree::MerkleAppendTreeLevel,
+            fixed_append_tree::{
+                append_to_levels, get_levels_value, revert_levels_to_index, set_levels_from_siblings,
+                FixedMerkleAppendTree,
+            },
+        },
+        sha256::QSha256Hasher,
+        traits::{BytesHasher, MerkleHasher, MerkleZeroHasher},
+    },
+    init_params::InitBlockDataRecord,
+    state_commitment::{QEDDogeStateCommitmentBuilder, QEDDogeStateSummary},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct QEDDogeChainStateLayout {
+    pub block_hash_cache_size: U32,
+    pub required_confirmations: U32,
+    pub block_tree_height: U32,
+}
+
+pub const QDOGE_CHAIN_STATE_LAYOUT_SIZE: usize = core::mem::size_of::<QEDDogeChainStateLayout>();
+
+// same layout as the fields of BlockDataTracker before its records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+struct BlockDataTrackerHeader {
+    tip_block_number: U32,
+    tip_internal_index: U16,
+}
+
+// same layout as the fields of FixedMerkleAppendTree before its levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+struct BlockTreeHeader {
+    next_index: U64,
+}
+
+impl QEDDogeChainStateLayout {
+    pub fn new(block_hash_cache_size: u32, required_confirmations: u32, block_tree_height: u32) -> QDogeResult<Self> {
+        let layout = Self {
+            block_hash_cache_size: block_hash_cache_size.into(),
+            required_confirmations: required_confirmations.into(),
+            block_tree_height: block_tree_height.into(),
+        };
+        layout.validate()?;
+        Ok(layout)
+    }
+    pub fn new_for<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    >() -> QDogeResult<Self> {
+        Self::new(
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS as u32,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT as u32,
+        )
+    }
+    pub fn validate(&self) -> QDogeResult<()> {
+        let cache_size = self.get_block_hash_cache_size();
+        let tree_height = self.get_block_tree_height();
+        // the ring buffer index is stored as a u16 and the tree index as a u64
+        if cache_size == 0
+            || cache_size > (u16::MAX as usize + 1)
+            || self.get_required_confirmations() >= cache_size
+            || tree_height == 0
+            || tree_height > 64
+        {
+            return Err(DogeBridgeError::ChainStateLayoutInvalid);
+        }
+        Ok(())
+    }
+    pub fn get_block_hash_cache_size(&self) -> usize {
+        self.block_hash_cache_size.get() as usize
+    }
+    pub fn get_required_confirmations(&self) -> usize {
+        self.required_confirmations.get() as usize
+    }
+    pub fn get_block_tree_height(&self) -> usize {
+        self.block_tree_height.get() as usize
+    }
+    /// size of the chain state following the layout header
+    pub fn get_state_size(&self) -> usize {
+        core::mem::size_of::<BlockDataTrackerHeader>()
+            + self.get_block_hash_cache_size() * core::mem::size_of::<BlockDataRecord>()
+            + core::mem::size_of::<BlockTreeHeader>()
+            + self.get_block_tree_height() * core::mem::size_of::<MerkleAppendTreeLevel<QHash256>>()
+    }
+    /// size of the account data holding the layout header and the chain state
+    pub fn get_data_size(&self) -> usize {
+        QDOGE_CHAIN_STATE_LAYOUT_SIZE + self.get_state_size()
+    }
+    pub fn read_from_data(data: &[u8]) -> QDogeResult<Self> {
+        let (layout, _) =
+            Self::read_from_prefix(data).map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        layout.validate()?;
+        Ok(layout)
+    }
+}
+
+/// The block data records of a [`QEDDogeChainStateView`], laid out like a [`crate::block_data_tracker::BlockDataTracker`].
+pub struct QEDDogeBlockDataView<'a> {
+    required_confirmations: usize,
+    header: &'a mut BlockDataTrackerHeader,
+    records: &'a mut [BlockDataRecord],
+}
+
+impl BlockDataStorage for QEDDogeBlockDataView<'_> {
+    fn get_required_confirmations(&self) -> usize {
+        self.required_confirmations
+    }
+    fn get_tip_block_number(&self) -> u32 {
+        self.header.tip_block_number.into()
+    }
+    fn get_tip_internal_index(&self) -> u16 {
+        self.header.tip_internal_index.into()
+    }
+    fn set_tip(&mut self, tip_block_number: u32, tip_internal_index: u16) {
+        self.header.tip_block_number = tip_block_number.into();
+        self.header.tip_internal_index = tip_internal_index.into();
+    }
+    fn get_records(&self) -> &[BlockDataRecord] {
+        self.records
+    }
+    fn get_records_mut(&mut self) -> &mut [BlockDataRecord] {
+        self.records
+    }
+}
+
+/// A chain state with a fixed height block tree whose sizes are read from the account data at runtime.
+///
+/// Shares the block processing logic of [`QEDDogeChainStateCore`] through [`QEDDogeChainStateStorage`].
+pub struct QEDDogeChainStateView<'a, BlockTreeHasher = QSha256Hasher> {
+    layout: QEDDogeChainStateLayout,
+    block_data: QEDDogeBlockDataView<'a>,
+    block_tree_header: &'a mut BlockTreeHeader,
+    levels: &'a mut [MerkleAppendTreeLevel<QHash256>],
+    _block_tree_hasher: PhantomData<BlockTreeHasher>,
+}
+
+impl<'a, BlockTreeHasher: MerkleZeroHasher<QHash256>> QEDDogeChainStateStorage for QEDDogeChainStateView<'a, BlockTreeHasher> {
+    type BlockData = QEDDogeBlockDataView<'a>;
+
+    fn get_block_data(&self) -> &Self::BlockData {
+        &self.block_data
+    }
+    fn get_block_data_mut(&mut self) -> &mut Self::BlockData {
+        &mut self.block_data
+    }
+    fn get_block_tree_next_index(&self) -> u64 {
+        self.block_tree_header.next_index.into()
+    }
+    fn get_block_tree_value(&self) -> QHash256 {
+        get_levels_value(self.get_block_tree_next_index(), self.levels)
+    }
+    fn get_block_tree_root(&self) -> QHash256 {
+        self.levels.last().unwrap().get_hash::<BlockTreeHasher>()
+    }
+    fn append_to_block_tree(&mut self, value: QHash256) -> QDogeResult<()> {
+        append_to_levels::<QHash256, BlockTreeHasher>(&mut self.block_tree_header.next_index, self.levels, value);
+        Ok(())
+    }
+    fn revert_block_tree_to_index(&mut self, index: u64, restore_data: &[QHash256], value: QHash256) -> QDogeResult<()> {
+        revert_levels_to_index::<QHash256, BlockTreeHasher>(
+            &mut self.block_tree_header.next_index,
+            self.levels,
+            index,
+            restore_data,
+            value,
+        )
+    }
+}
+
+impl<'a, BlockTreeHasher: MerkleZeroHasher<QHash256>> QEDDogeChainStateView<'a, BlockTreeHasher> {
+    fn from_state_bytes_mut(layout: QEDDogeChainStateLayout, state: &'a mut [u8]) -> QDogeResult<Self> {
+        let (header, rest) = BlockDataTrackerHeader::mut_from_prefix(state)
+            .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        let (records, rest) =
+            <[BlockDataRecord]>::mut_from_prefix_with_elems(rest, layout.get_block_hash_cache_size())
+                .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        let (block_tree_header, rest) = BlockTreeHeader::mut_from_prefix(rest)
+            .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        let (levels, _) = <[MerkleAppendTreeLevel<QHash256>]>::mut_from_prefix_with_elems(
+            rest,
+            layout.get_block_tree_height(),
+        )
+        .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        Ok(Self {
+            layout,
+            block_data: QEDDogeBlockDataView {
+                required_confirmations: layout.get_required_confirmations(),
+                header,
+                records,
+            },
+            block_tree_header,
+            levels,
+            _block_tree_hasher: PhantomData,
+        })
+    }
+    fn split_data_mut(data: &'a mut [u8]) -> QDogeResult<(QEDDogeChainStateLayout, &'a mut [u8])> {
+        let layout = QEDDogeChainStateLayout::read_from_data(data)?;
+        if data.len() < layout.get_data_size() {
+            return Err(DogeBridgeError::ChainStateDataTooShort);
+        }
+        let (_, state) = data.split_at_mut(QDOGE_CHAIN_STATE_LAYOUT_SIZE);
+        Ok((layout, state))
+    }
+
+    /// Checks the zero hashes of the block tree levels and the block tree root recorded for the tip against `BlockTreeHasher`.
+    pub(crate) fn ensure_block_tree_hasher(&self) -> QDogeResult<()> {
+        let zero_hashes_match = self
+            .levels
+            .iter()
+            .enumerate()
+            .all(|(i, level)| level.zero_hash == BlockTreeHasher::get_zero_hash(i));
+        if !zero_hashes_match || self.get_record(self.get_tip_block_number())?.block_hash_tree_root != self.get_block_tree_root() {
+            return Err(DogeBridgeError::StateEnvelopeBlockTreeHasherMismatch);
+        }
+        Ok(())
+    }
+
+    /// Opens a chain state previously written to `data`.
+    pub fn from_data_mut(data: &'a mut [u8]) -> QDogeResult<Self> {
+        let (layout, state) = Self::split_data_mut(data)?;
+        Self::from_state_bytes_mut(layout, state)
+    }
+
+    /// Writes `layout` and a fresh chain state to `data`, mirroring [`QEDDogeChainStateCore::from_init_data`].
+    pub fn init_from_init_data(
+        data: &'a mut [u8],
+        layout: QEDDogeChainStateLayout,
+        records: &[InitBlockDataRecord],
+        tracker_tree_siblings: &[QHash256],
+        tip_block_number: u32,
+    ) -> QDogeResult<Self> {
+        layout.validate()?;
+        let cache_size = layout.get_block_hash_cache_size();
+        if records.len() != cache_size || tracker_tree_siblings.len() != layout.get_block_tree_height() {
+            return Err(DogeBridgeError::ChainStateLayoutMismatch);
+        }
+        if data.len() < layout.get_data_size() {
+            return Err(DogeBridgeError::ChainStateDataTooShort);
+        }
+        layout
+            .write_to_prefix(data)
+            .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+
+        let mut view = Self::from_data_mut(data)?;
+        let start_block = tip_block_number.saturating_sub(cache_size as u32 - 1u32);
+        view.init_block_tree(start_block as u64 + 1, tracker_tree_siblings, records[0].block_hash);
+
+        for (i, init_record) in records.iter().enumerate() {
+            if i > 0 {
+                view.append_to_block_tree(init_record.block_hash)?;
+            }
+            let mut record: BlockDataRecord = init_record.into();
+            record.block_hash_tree_root = view.get_block_tree_root();
+            view.block_data.records[i] = record;
+        }
+        view.block_data.set_tip(tip_block_number, (cache_size - 1) as u16);
+        Ok(view)
+    }
+
+    fn init_block_tree(&mut self, next_index: u64, siblings: &[QHash256], value: QHash256) {
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            let zero_hash = BlockTreeHasher::get_zero_hash(i);
+            *level = MerkleAppendTreeLevel {
+                left: zero_hash,
+                right: zero_hash,
+                zero_hash,
+            };
+        }
+        self.block_tree_header.next_index = next_index.into();
+        set_levels_from_siblings::<QHash256, BlockTreeHasher>(self.levels, next_index, siblings, value);
+    }
+
+    /// Writes the layout and bytes of a const generic chain state to `data`.
+    pub fn init_from_chain_state<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    >(
+        data: &'a mut [u8],
+        state: &QEDDogeChainStateCore<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+            FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+            BlockTreeHasher,
+        >,
+    ) -> QDogeResult<Self> {
+        let layout = QEDDogeChainStateLayout::new_for::<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        >()?;
+        if data.len() < layout.get_data_size() {
+            return Err(DogeBridgeError::ChainStateDataTooShort);
+        }
+        layout
+            .write_to_prefix(data)
+            .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        state
+            .as_bytes()
+            .write_to_prefix(&mut data[QDOGE_CHAIN_STATE_LAYOUT_SIZE..])
+            .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        Self::from_data_mut(data)
+    }
+
+    /// Copies the view into a const generic chain state with the same sizes.
+    pub fn to_chain_state<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    >(
+        &self,
+    ) -> QDogeResult<
+        QEDDogeChainStateCore<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+            FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+            BlockTreeHasher,
+        >,
+    > {
+        let expected_layout = QEDDogeChainStateLayout::new_for::<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        >()?;
+        if expected_layout != self.layout {
+            return Err(DogeBridgeError::ChainStateLayoutMismatch);
+        }
+        let mut state = QEDDogeChainStateCore::<
+            QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+            QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+            FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+            BlockTreeHasher,
+        >::new_zeroed();
+        let bytes = state.as_mut_bytes();
+        let (block_data_bytes, rest) = bytes.split_at_mut(core::mem::size_of::<BlockDataTrackerHeader>());
+        block_data_bytes.copy_from_slice(self.block_data.header.as_bytes());
+        let (records_bytes, rest) = rest.split_at_mut(core::mem::size_of_val(&*self.block_data.records));
+        records_bytes.copy_from_slice(self.block_data.records.as_bytes());
+        let (block_tree_bytes, levels_bytes) = rest.split_at_mut(core::mem::size_of::<BlockTreeHeader>());
+        block_tree_bytes.copy_from_slice(self.block_tree_header.as_bytes());
+        levels_bytes.copy_from_slice(self.levels.as_bytes());
+        Ok(state)
+    }
+
+    pub fn get_layout(&self) -> QEDDogeChainStateLayout {
+        self.layout
+    }
+
+    pub fn get_state_commitment_builder(&self) -> QEDDogeStateCommitmentBuilder<'_> {
+        QEDDogeStateCommitmentBuilder {
+            summary: QEDDogeStateSummary {
+                tip_block_number: self.block_data.header.tip_block_number,
+                finalized_block_number: self.get_finalized_block_number().into(),
+                tip_internal_index: self.block_data.header.tip_internal_index,
+                block_hash_cache_size: self.layout.block_hash_cache_size,
+                block_tree_height: self.layout.block_tree_height,
+                block_tree_next_index: self.block_tree_header.next_index,
+                block_tree_root: self.get_block_tree_root(),
+            },
+            records: self.block_data.records,
+            levels: self.levels,
+        }
+    }
+    /// Hash committing to the whole state, equal to the commitment of the matching [`QEDDogeChainStateCore`].
+    pub fn get_state_commitment<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self) -> QHash256 {
+        self.get_state_commitment_builder().get_commitment::<Hasher>()
+    }
+}
+
+/// Copies the chain state in `source` into `destination` using `new_layout`.
+///
+/// - shrinking the block hash cache drops the oldest records and takes no `headers`
+/// - growing it requires the headers of every block in the new cache (oldest first, ending at the tip) and the
+///   block tree siblings of the oldest of them in the source tree; the headers have to link up to the current tip and
+///   every record and block tree root is recomputed from them and checked against the source before it is written
+/// - the block tree can only grow, the recorded block tree roots are extended with zero subtrees accordingly
+/// - the required confirmations can be changed freely as long as they stay below the cache size
+pub fn migrate_chain_state_layout<BlockTreeHasher: MerkleZeroHasher<QHash256>>(
+    source: &[u8],
+    destination: &mut [u8],
+    new_layout: QEDDogeChainStateLayout,
+    headers: &[QStandardBlockHeader],
+    block_tree_siblings: &[QHash256],
+) -> QDogeResult<()> {
+    let old_layout = QEDDogeChainStateLayout::read_from_data(source)?;
+    new_layout.validate()?;
+    if source.len() < old_layout.get_data_size() {
+        return Err(DogeBridgeError::ChainStateDataTooShort);
+    }
+    let old_height = old_layout.get_block_tree_height();
+    let new_height = new_layout.get_block_tree_height();
+    if new_height < old_height {
+        return Err(DogeBridgeError::ChainStateMigrationTreeHeightDecrease);
+    }
+    let old_cache_size = old_layout.get_block_hash_cache_size();
+    let new_cache_size = new_layout.get_block_hash_cache_size();
+    let grows_cache = new_cache_size > old_cache_size;
+    if (grows_cache && (headers.len() != new_cache_size || block_tree_siblings.len() != old_height))
+        || (!grows_cache && (!headers.is_empty() || !block_tree_siblings.is_empty()))
+    {
+        return Err(DogeBridgeError::ChainStateMigrationHeadersMismatch);
+    }
+
+    let state = &source[QDOGE_CHAIN_STATE_LAYOUT_SIZE..];
+    let (old_block_data_header, rest) =
+        BlockDataTrackerHeader::ref_from_prefix(state).map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+    let (old_records, rest) = <[BlockDataRecord]>::ref_from_prefix_with_elems(rest, old_cache_size)
+        .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+    let (old_block_tree_header, rest) =
+        BlockTreeHeader::ref_from_prefix(rest).map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+    let (old_levels, _) = <[MerkleAppendTreeLevel<QHash256>]>::ref_from_prefix_with_elems(rest, old_height)
+        .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+
+    if destination.len() < new_layout.get_data_size() {
+        return Err(DogeBridgeError::ChainStateDataTooShort);
+    }
+    new_layout
+        .write_to_prefix(destination)
+        .map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+    let mut view = QEDDogeChainStateView::<BlockTreeHasher>::from_data_mut(destination)?;
+
+    // a root of the old tree is the left-most subtree of the taller tree
+    let extend_root = |mut root: QHash256| {
+        for level in old_height..new_height {
+            root = BlockTreeHasher::two_to_one(&root, &BlockTreeHasher::get_zero_hash(level));
+        }
+        root
+    };
+
+    let tip_block_number = old_block_data_header.tip_block_number.get();
+    let old_tip_internal_index: usize = old_block_data_header.tip_internal_index.get() as usize;
+    // the source record of a block in its cache, oldest first
+    let old_record = |age: usize| old_records[(old_tip_internal_index + old_cache_size - age) % old_cache_size];
+
+    if grows_cache {
+        let first_block_number = (tip_block_number + 1)
+            .checked_sub(new_cache_size as u32)
+            .ok_or(DogeBridgeError::ChainStateMigrationHeadersMismatch)?;
+        for (i, header) in headers.iter().enumerate() {
+            let block_hash = header.get_hash();
+            let is_linked = match headers.get(i + 1) {
+                Some(child) => child.previous_block_hash == block_hash,
+                None => block_hash == old_record(0).block_hash,
+            };
+            if !is_linked {
+                return Err(DogeBridgeError::ChainStateMigrationHeadersNotLinked);
+            }
+            if i == 0 {
+                view.init_block_tree(first_block_number as u64 + 1, block_tree_siblings, block_hash);
+            } else {
+                view.append_to_block_tree(block_hash)?;
+            }
+            let record = BlockDataRecord {
+                block_hash_tree_root: view.get_block_tree_root(),
+                block_hash,
+                tx_tree_merkle_root: header.merkle_root,
+                timestamp: header.timestamp.into(),
+                bits: header.bits.into(),
+            };
+            // blocks still in the source cache have to match their records
+            let age = new_cache_size - 1 - i;
+            if age < old_cache_size {
+                let old = old_record(age);
+                if record != (BlockDataRecord { block_hash_tree_root: extend_root(old.block_hash_tree_root), ..old }) {
+                    return Err(DogeBridgeError::ChainStateMigrationRecordMismatch);
+                }
+            }
+            view.block_data.records[i] = record;
+        }
+        if view.get_block_tree_next_index() != old_block_tree_header.next_index.get()
+            || view.get_block_tree_root() != extend_root(old_levels.last().unwrap().get_hash::<BlockTreeHasher>())
+        {
+            return Err(DogeBridgeError::ChainStateMigrationRecordMismatch);
+        }
+    } else {
+        for (i, record) in view.block_data.records.iter_mut().enumerate() {
+            let old = old_record(new_cache_size - 1 - i);
+            *record = BlockDataRecord {
+                block_hash_tree_root: extend_root(old.block_hash_tree_root),
+                ..old
+            };
+        }
+        view.block_tree_header.next_index = old_block_tree_header.next_index;
+        let mut current = old_levels.last().unwrap().get_hash::<BlockTreeHasher>();
+        for (i, level) in view.levels.iter_mut().enumerate() {
+            if i < old_height {
+                *level = old_levels[i];
+            } else {
+                let zero_hash = BlockTreeHasher::get_zero_hash(i);
+                *level = MerkleAppendTreeLevel {
+                    left: current,
+                    right: zero_hash,
+                    zero_hash,
+                };
+                current = level.get_hash::<BlockTreeHasher>();
+            }
+        }
+    }
+    view.block_data.set_tip(tip_block_number, (new_cache_size - 1) as u16);
+    view.ensure_internal_consistency()
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use crate::{
+        test_fixtures::{load_mainnet_headers, BlockHeaderWithIndex},
+        chain_state::{QEDDogeChainStateCore, QEDDogeChainStateStorage}, constants::DogeMainNetConfig, core_data::{QDogeBlockHeader, QHash256, QStandardBlockHeader}, error::DogeBridgeError, hash::{merkle::fixed_append_tree::FixedMerkleAppendTree, sha256::QSha256Hasher}, init_params::{get_empty_siblings, InitBlockDataIBC}
+    };
+
+    use super::{migrate_chain_state_layout, QEDDogeChainStateLayout, QEDDogeChainStateView, QDOGE_CHAIN_STATE_LAYOUT_SIZE};
+
+    const CACHE_SIZE: usize = 32;
+    const REQUIRED_CONFIRMATIONS: usize = 4;
+    const TREE_HEIGHT: usize = 32;
+    type TestChainState = QEDDogeChainStateCore<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>;
+
+    fn init_data(headers: &[BlockHeaderWithIndex]) -> InitBlockDataIBC<CACHE_SIZE, TREE_HEIGHT> {
+        let base: [QDogeBlockHeader; CACHE_SIZE] = core::array::from_fn(|i| headers[i].block_header.clone());
+        InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers_empty_tree(&base, headers[CACHE_SIZE - 1].height)
+    }
+
+    fn new_view_data(layout: QEDDogeChainStateLayout) -> alloc::vec::Vec<u8> {
+        alloc::vec![0u8; layout.get_data_size()]
+    }
+
+    #[test]
+    fn test_view_matches_const_generic_state() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let init_data = init_data(&headers);
+        let mut state = TestChainState::from_init_data(&init_data).unwrap();
+
+        let layout = QEDDogeChainStateLayout::new_for::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?;
+        let mut data = new_view_data(layout);
+        let mut view = QEDDogeChainStateView::<QSha256Hasher>::init_from_init_data(
+            &mut data,
+            layout,
+            &init_data.records,
+            &init_data.tracker_tree_siblings,
+            init_data.tip_block_number,
+        )?;
+        assert_eq!(view.to_chain_state::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?, state);
+
+        let mut snapshots = alloc::vec::Vec::new();
+        for x in &headers[CACHE_SIZE..CACHE_SIZE + 40] {
+            snapshots.push(state);
+            state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+            view.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+            assert_eq!(view.get_block_tree_root(), state.block_tree_tracker.get_root::<QSha256Hasher>());
+        }
+        assert_eq!(
+            view.append_block::<DogeMainNetConfig>(headers[CACHE_SIZE + 40].height, &headers[CACHE_SIZE].block_header, None),
+            Err(DogeBridgeError::InvalidParentBlockHash)
+        );
+        assert_eq!(view.to_chain_state::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?, state);
+
+        // rollback the last 3 blocks and re-insert 2 of them
+        let last_good_state = snapshots[snapshots.len() - 3];
+        let last_good_block_number = last_good_state.get_tip_block_number();
+        let tip_index = state.get_tip_block_number() as u64;
+        let mut changed_left_siblings = alloc::vec::Vec::new();
+        for (i, level) in last_good_state.block_tree_tracker.levels.iter().enumerate() {
+            let revert_index = last_good_block_number as u64 >> i;
+            if revert_index == tip_index >> i {
+                break;
+            }
+            if revert_index & 1 == 1 {
+                changed_left_siblings.push(level.left);
+            }
+        }
+        let reinserted = [
+            headers[CACHE_SIZE + 37].block_header.clone(),
+            headers[CACHE_SIZE + 38].block_header.clone(),
+        ];
+        state.rollback_insert_blocks::<DogeMainNetConfig>(last_good_block_number, &changed_left_siblings, &reinserted, &[None, None])?;
+        view.rollback_insert_blocks::<DogeMainNetConfig>(last_good_block_number, &changed_left_siblings, &reinserted, &[None, None])?;
+        let expected_state = snapshots[snapshots.len() - 1];
+        assert_eq!(state.get_tip_block_hash(), expected_state.get_tip_block_hash());
+        assert_eq!(
+            state.block_tree_tracker.get_root::<QSha256Hasher>(),
+            expected_state.block_tree_tracker.get_root::<QSha256Hasher>()
+        );
+        assert_eq!(view.to_chain_state::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?, state);
+        assert_eq!(&data[QDOGE_CHAIN_STATE_LAYOUT_SIZE..], state.as_bytes());
+
+        let mut data2 = new_view_data(layout);
+        let view2 = QEDDogeChainStateView::<QSha256Hasher>::init_from_chain_state(&mut data2, &state)?;
+        assert_eq!(view2.get_tip_block_hash(), state.get_tip_block_hash());
+        assert_eq!(data2, data);
+        assert_eq!(
+            QEDDogeChainStateView::<QSha256Hasher>::from_data_mut(&mut data2)?.to_chain_state::<16, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>(),
+            Err(DogeBridgeError::ChainStateLayoutMismatch)
+        );
+        assert_eq!(
+            QEDDogeChainStateView::<QSha256Hasher>::from_data_mut(&mut data2[..100]).err(),
+            Some(DogeBridgeError::ChainStateDataTooShort)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_layout() -> anyhow::Result<()> {
+        const NEW_CACHE_SIZE: usize = 16;
+        const NEW_TREE_HEIGHT: usize = 40;
+        let headers = load_mainnet_headers()?;
+        let mut state = TestChainState::from_init_data(&init_data(&headers)).unwrap();
+        for x in &headers[CACHE_SIZE..CACHE_SIZE + 10] {
+            state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+        let layout = QEDDogeChainStateLayout::new_for::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?;
+        let mut data = new_view_data(layout);
+        QEDDogeChainStateView::<QSha256Hasher>::init_from_chain_state(&mut data, &state)?;
+
+        let new_layout = QEDDogeChainStateLayout::new(NEW_CACHE_SIZE as u32, 6, NEW_TREE_HEIGHT as u32)?;
+        let mut new_data = new_view_data(new_layout);
+        migrate_chain_state_layout::<QSha256Hasher>(&data, &mut new_data, new_layout, &[], &[])?;
+        let mut new_view = QEDDogeChainStateView::<QSha256Hasher>::from_data_mut(&mut new_data)?;
+        assert_eq!(new_view.get_tip_block_hash(), state.get_tip_block_hash());
+        assert_eq!(new_view.get_finalized_block_number(), state.get_tip_block_number() - 6);
+        assert!(!new_view.contains_block(state.get_tip_block_number() - NEW_CACHE_SIZE as u32));
+
+        for x in &headers[CACHE_SIZE + 10..CACHE_SIZE + 20] {
+            new_view.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+        let mut expected_tree = FixedMerkleAppendTree::<QHash256, NEW_TREE_HEIGHT>::new_from_hasher::<QSha256Hasher>(
+            headers[0].height as u64 + 1,
+            get_empty_siblings::<NEW_TREE_HEIGHT>(),
+            headers[0].block_header.header.get_hash(),
+        );
+        for x in &headers[1..CACHE_SIZE + 20] {
+            expected_tree.append::<QSha256Hasher>(x.block_header.header.get_hash());
+        }
+        assert_eq!(new_view.get_block_tree_root(), expected_tree.get_root::<QSha256Hasher>());
+        let oldest_block_number = new_view.get_tip_block_number() + 1 - NEW_CACHE_SIZE as u32;
+        assert_eq!(
+            new_view.get_record(oldest_block_number)?.block_hash,
+            headers[CACHE_SIZE + 20 - NEW_CACHE_SIZE].block_header.header.get_hash()
+        );
+        let expanded = new_view.to_chain_state::<NEW_CACHE_SIZE, 6, NEW_TREE_HEIGHT>()?;
+        expanded.ensure_internal_consistency()?;
+
+        // growing the cache back needs the headers of the whole new cache and the block tree siblings of the oldest one
+        let grown_layout = QEDDogeChainStateLayout::new(CACHE_SIZE as u32, 4, NEW_TREE_HEIGHT as u32)?;
+        let mut grown_data = new_view_data(grown_layout);
+        assert_eq!(
+            migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, grown_layout, &[], &[]),
+            Err(DogeBridgeError::ChainStateMigrationHeadersMismatch)
+        );
+        let first = CACHE_SIZE + 20 - CACHE_SIZE;
+        let grown_headers: alloc::vec::Vec<QStandardBlockHeader> =
+            headers[first..CACHE_SIZE + 20].iter().map(|x| x.block_header.header).collect();
+        let mut expected_tree = FixedMerkleAppendTree::<QHash256, NEW_TREE_HEIGHT>::new_from_hasher::<QSha256Hasher>(
+            headers[0].height as u64 + 1,
+            get_empty_siblings::<NEW_TREE_HEIGHT>(),
+            headers[0].block_header.header.get_hash(),
+        );
+        for x in &headers[1..=first] {
+            expected_tree.append::<QSha256Hasher>(x.block_header.header.get_hash());
+        }
+        let siblings = expected_tree.get_partial_merkle_proof_for_current_index().siblings;
+
+        migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, grown_layout, &grown_headers, &siblings)?;
+        let grown_view = QEDDogeChainStateView::<QSha256Hasher>::from_data_mut(&mut grown_data)?;
+        for (i, x) in headers[first..CACHE_SIZE + 20].iter().enumerate() {
+            if i > 0 {
+                expected_tree.append::<QSha256Hasher>(x.block_header.header.get_hash());
+            }
+            let record = grown_view.get_record(x.height)?;
+            assert_eq!(record.block_hash, x.block_header.header.get_hash());
+            assert_eq!(record.block_hash_tree_root, expected_tree.get_root::<QSha256Hasher>());
+        }
+        assert_eq!(grown_view.get_tip_block_hash(), headers[CACHE_SIZE + 19].block_header.header.get_hash());
+
+        // the headers have to link up to the tip
+        let mut forged_headers = grown_headers.clone();
+        forged_headers[3].timestamp += 1;
+        assert_eq!(
+            migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, grown_layout, &forged_headers, &siblings),
+            Err(DogeBridgeError::ChainStateMigrationHeadersNotLinked)
+        );
+        assert_eq!(
+            migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, grown_layout, &grown_headers[1..], &siblings),
+            Err(DogeBridgeError::ChainStateMigrationHeadersMismatch)
+        );
+        // the recomputed block tree roots have to match the records still in the source cache
+        let forged_siblings = alloc::vec![[1u8; 32]; siblings.len()];
+        assert_eq!(
+            migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, grown_layout, &grown_headers, &forged_siblings),
+            Err(DogeBridgeError::ChainStateMigrationRecordMismatch)
+        );
+        assert_eq!(
+            migrate_chain_state_layout::<QSha256Hasher>(&new_data, &mut grown_data, layout, &[], &[]),
+            Err(DogeBridgeError::ChainStateMigrationTreeHeightDecrease)
+        );
+        Ok(())
+    }
+}
@@ -38,6 +38,17 @@ pub const DOGE_NETWORK_TYPE: DogeNetworkType = DogeNetworkType::MainNet;
 */
 pub const MERGED_MINING_HEADER: [u8; 4] = [0xfa, 0xbe, 0x6d, 0x6d];
 
+pub const DOGE_COIN: u64 = 100_000_000;
+pub const DOGE_MAX_MONEY: u64 = 10_000_000_000 * DOGE_COIN;
+// dogecoin core 1.14 fee policy: fees are charged per started kB, plus one fee_per_kb for every output below the soft dust limit
+pub const DOGE_RECOMMENDED_FEE_PER_KB: u64 = DOGE_COIN / 100;
+pub const DOGE_MIN_RELAY_FEE_PER_KB: u64 = DOGE_COIN / 1000;
+pub const DOGE_SOFT_DUST_LIMIT: u64 = DOGE_COIN / 100;
+pub const DOGE_HARD_DUST_LIMIT: u64 = DOGE_COIN / 1000;
+// dogecoin inherits bitcoin's pre-segwit limits (MAX_BLOCK_WEIGHT / 4 and MAX_BLOCK_SIGOPS_COST / 4)
+pub const DOGE_MAX_BLOCK_SERIALIZED_SIZE: usize = 1_000_000;
+pub const DOGE_MAX_BLOCK_SIGOPS: usize = 20_000;
+
 const DOGE_REGTEST_PARAMS: DogeNetworkParams = DogeNetworkParams {
     allow_min_difficulty_blocks: true,
     pow_target_timespan: 1,
@@ -46,6 +57,10 @@ const DOGE_REGTEST_PARAMS: DogeNetworkParams = DogeNetworkParams {
     strict_chain_id: true,
     aux_pow_chain_id: 0x0062,
     min_valid_height: 22,
+    // BIP34 has not activated on regtest
+    bip34_height: 100000000,
+    subsidy_halving_interval: 150,
+    simplified_rewards_height: 0,
 };
 const DOGE_TESTNET_PARAMS: DogeNetworkParams = DogeNetworkParams {
     allow_min_difficulty_blocks: true,
@@ -55,6 +70,9 @@ const DOGE_TESTNET_PARAMS: DogeNetworkParams = DogeNetworkParams {
     strict_chain_id: false,
     aux_pow_chain_id: 0x0062,
     min_valid_height: 158102,
+    bip34_height: 708658,
+    subsidy_halving_interval: 100000,
+    simplified_rewards_height: 145000,
 };
 const DOGE_MAINNET_PARAMS: DogeNetworkParams = DogeNetworkParams {
     allow_min_difficulty_blocks: false,
@@ -64,7 +82,9 @@ const DOGE_MAINNET_PARAMS: DogeNetworkParams = DogeNetworkParams {
     aux_pow_chain_id: 0x0062,
     strict_chain_id: true,
     min_valid_height: 371339,
-
+    bip34_height: 1034383,
+    subsidy_halving_interval: 100000,
+    simplified_rewards_height: 145000,
 };
 
 pub trait DogeNetworkConfig {
@@ -30,14 +30,16 @@ use borsh::{BorshSerialize, BorshDeserialize};
 use serde::{Serialize, Deserialize};
 
 
+use alloc::vec::Vec;
 use zerocopy_derive::{FromBytes, Immutable, IntoBytes};
 
 use crate::{
-    constants::{DogeNetworkConfig, MERGED_MINING_HEADER, VERSION_AUXPOW}, doge::transaction::BTCTransaction, error::{DogeBridgeError, QDogeResult}, hash::{
+    constants::{DogeNetworkConfig, MERGED_MINING_HEADER, VERSION_AUXPOW}, doge::{transaction::BTCTransaction, varuint::varuint_size}, error::{DogeBridgeError, QDogeResult}, hash::{
         scrypt_doge::scrypt_1024_1_1_256,
         sha256::QBTCHash256Hasher,
         traits::{BytesHasher, MerkleHasher},
-    }
+    },
+    logic::check_doge_block_transactions::{check_block_coinbase_value_err, check_block_transactions_err, compute_block_tx_merkle_root},
 };
 
 pub type QHash256 = [u8; 32];
@@ -103,12 +105,9 @@ impl QStandardBlockHeader {
         }
     }
 
-    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_bytes(data: &[u8]) -> QDogeResult<Self> {
         if data.len() < 80 {
-            anyhow::bail!(
-                "error deserializing QStandardBlockHeader: expected at least 80 bytes, got {}",
-                data.len()
-            );
+            return Err(DogeBridgeError::InvalidBlockHeaderLength);
         }
         let version = u32::from_le_bytes(data[0..4].try_into().unwrap());
         let previous_block_hash: [u8; 32] = data[4..36].try_into().unwrap();
@@ -149,6 +148,9 @@ pub struct QMerkleBranch {
 }
 
 impl QMerkleBranch {
+    pub fn get_serialized_size(&self) -> usize {
+        varuint_size(self.hashes.len() as u64) + self.hashes.len() * 32 + 4
+    }
     pub fn get_root<H: MerkleHasher<QHash256>>(&self, value: QHash256) -> QHash256 {
         let mut cur = value;
         let mut cur_index = self.side_mask;
@@ -196,6 +198,13 @@ pub fn get_expected_index(n_nonce: u32, n_chain_id: i32, h: u32) -> u32 {
 }
 
 impl QAuxPow {
+    pub fn get_serialized_size(&self) -> usize {
+        self.coinbase_transaction.byte_length(false)
+            + 32
+            + self.coinbase_branch.get_serialized_size()
+            + self.blockchain_branch.get_serialized_size()
+            + 80
+    }
     pub fn check<NC: DogeNetworkConfig>(&self, hash_aux_block: QHash256, chain_id: u32) -> bool {
         if self.coinbase_branch.side_mask == 0
             && self.blockchain_branch.hashes.len() <= 30
@@ -359,4 +368,23 @@ impl QDogeBlock {
             aux_pow: self.aux_pow.clone(),
         }
     }
+    /// Size of the block in the network serialization, including the aux pow.
+    pub fn get_serialized_size(&self) -> usize {
+        80 + self.aux_pow.as_ref().map_or(0, |aux_pow| aux_pow.get_serialized_size())
+            + varuint_size(self.transactions.len() as u64)
+            + self.transactions.iter().map(|tx| tx.byte_length(false)).sum::<usize>()
+    }
+    /// Computes the transaction merkle root and whether the transaction tree is mutated (CVE-2012-2459).
+    pub fn compute_tx_merkle_root(&self) -> (QHash256, bool) {
+        compute_block_tx_merkle_root(&self.transactions)
+    }
+    /// Checks the transactions of the block against its header, see [`check_block_transactions_err`].
+    pub fn validate(&self) -> QDogeResult<()> {
+        check_block_transactions_err(self)
+    }
+    /// Runs [`Self::validate`] and checks the coinbase value against the subsidy at `height` plus fees, see [`check_block_coinbase_value_err`].
+    pub fn validate_with_prevout_values<NC: DogeNetworkConfig>(&self, height: u32, prevout_values: &[Vec<u64>]) -> QDogeResult<()> {
+        self.validate()?;
+        check_block_coinbase_value_err::<NC>(self, height, prevout_values)
+    }
 }
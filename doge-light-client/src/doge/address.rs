@@ -24,12 +24,14 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
-use std::str::FromStr;
+use core::str::FromStr;
 
+use alloc::{string::String, vec::Vec};
 
+#[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
 
-use crate::{constants::{DogeMainNetConfig, DogeNetworkConfig, DogeRegTestConfig, DogeTestNetConfig}, core_data::QHash160, hash::{ripemd160::QBTCHash160Hasher, traits::BytesHasher}};
+use crate::{constants::{DogeMainNetConfig, DogeNetworkConfig, DogeRegTestConfig, DogeTestNetConfig}, core_data::QHash160, error::{DogeBridgeError, QDogeResult}, hash::{ripemd160::QBTCHash160Hasher, traits::BytesHasher}};
 
 use super::transaction::BTCTransactionOutput;
 
@@ -62,14 +64,16 @@ impl<'de> Deserialize<'de> for BTCAddressType {
         BTCAddressType::try_from(value).map_err(serde::de::Error::custom)
     }
 }
+#[cfg(feature = "borsh")]
 impl BorshSerialize for BTCAddressType {
-    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+    fn serialize<W: borsh::maybestd::io::Write>(&self, writer: &mut W) -> borsh::maybestd::io::Result<()> {
         BorshSerialize::serialize(&self.to_u8(), writer)
     }
 }
 
+#[cfg(feature = "borsh")]
 impl BorshDeserialize for BTCAddressType {
-    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+    fn deserialize_reader<R: borsh::maybestd::io::Read>(reader: &mut R) -> borsh::maybestd::io::Result<Self> {
         let mut res = [0u8; 1];
         reader.read_exact(&mut res)?;
        if res[0] == 0 {
@@ -77,8 +81,8 @@ impl BorshDeserialize for BTCAddressType {
         }else if res[0] == 1 {
             Ok(BTCAddressType::P2SH)
         }else{
-            Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
+            Err(borsh::maybestd::io::Error::new(
+                borsh::maybestd::io::ErrorKind::InvalidData,
                 "Invalid BTCAddressType type",
             ))
         }
@@ -95,7 +99,7 @@ impl BTCAddressType {
             BTCAddressType::P2SH => NC::P2SH_VERSION_BYTE,
         }
     }
-    pub fn try_from_version_byte(version_byte: u8) -> anyhow::Result<Self> {
+    pub fn try_from_version_byte(version_byte: u8) -> QDogeResult<Self> {
         match version_byte {
             DogeMainNetConfig::P2PKH_VERSION_BYTE => Ok(BTCAddressType::P2PKH),
             DogeMainNetConfig::P2SH_VERSION_BYTE => Ok(BTCAddressType::P2SH),
@@ -110,10 +114,7 @@ impl BTCAddressType {
             // regtest p2sh is the same as testnet
             // DogeRegTestConfig::P2SH_VERSION_BYTE => Ok(BTCAddressType::P2SH),
 
-            _ => Err(anyhow::format_err!(
-                "Invalid BTCAddressType version byte: {}",
-                version_byte
-            )),
+            _ => Err(DogeBridgeError::InvalidAddressVersionByte),
         }
     }
 }
@@ -123,16 +124,13 @@ impl From<BTCAddressType> for u8 {
     }
 }
 impl TryFrom<u8> for BTCAddressType {
-    type Error = anyhow::Error;
+    type Error = DogeBridgeError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(BTCAddressType::P2PKH),
             1 => Ok(BTCAddressType::P2SH),
-            _ => Err(anyhow::format_err!(
-                "Invalid BTCAddressType type: {}",
-                value
-            )),
+            _ => Err(DogeBridgeError::InvalidAddressType),
         }
     }
 }
@@ -158,13 +156,10 @@ pub struct BTCAddress160 {
 }
 
 impl BTCAddress160 {
-    pub fn try_from_string(str: &str) -> anyhow::Result<Self> {
-        let decoded = bs58::decode(str).with_check(None).into_vec().map_err(|e| anyhow::anyhow!("{:?}",e))?;
+    pub fn try_from_string(str: &str) -> QDogeResult<Self> {
+        let decoded = bs58::decode(str).with_check(None).into_vec().map_err(|_| DogeBridgeError::InvalidAddressEncoding)?;
         if decoded.len() != 21 {
-            return Err(anyhow::format_err!(
-                "Invalid BTC address length: {}",
-                decoded.len()
-            ));
+            return Err(DogeBridgeError::InvalidAddressLength);
         }
         let address_type = BTCAddressType::try_from_version_byte(decoded[0])?;
         let mut hash_160_bytes = [0u8; 20];
@@ -200,7 +195,7 @@ impl BTCAddress160 {
 }
 
 impl TryFrom<&str> for BTCAddress160 {
-    type Error = anyhow::Error;
+    type Error = DogeBridgeError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         BTCAddress160::try_from_string(value)
@@ -210,7 +205,7 @@ impl TryFrom<&str> for BTCAddress160 {
 #[cfg(feature = "bitcoin")]
 
 impl TryFrom<bitcoin::Address> for BTCAddress160 {
-    type Error = anyhow::Error;
+    type Error = DogeBridgeError;
 
     fn try_from(value: bitcoin::Address) -> Result<Self, Self::Error> {
         BTCAddress160::try_from_string(&value.to_string())
@@ -225,7 +220,7 @@ impl From<&BTCAddress160> for String {
 */
 
 impl FromStr for BTCAddress160 {
-    type Err = anyhow::Error;
+    type Err = DogeBridgeError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         BTCAddress160::try_from_string(s)
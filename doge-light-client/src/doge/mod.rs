@@ -26,4 +26,8 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 
 pub mod transaction;
 pub mod varuint;
-pub mod address;
\ No newline at end of file
+pub mod address;
+pub mod script;
+pub mod tx_builder;
+#[cfg(feature = "script-verify")]
+pub mod psbt;
@@ -0,0 +1,660 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Partially signed transactions, passed between the signers of a transaction until every input can be finalized.
+//!
+//! The container is borsh encoded behind a magic prefix (with a base64 text form), it is not compatible with BIP174.
+
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core_data::QHash256;
+use crate::error::{DogeBridgeError, QDogeResult};
+use crate::hash::ripemd160::QBTCHash160Hasher;
+use crate::hash::traits::BytesHasher;
+
+use super::address::gen_p2sh_script;
+use super::script::builder::ScriptBuilder;
+use super::script::instruction::Instruction;
+use super::script::interpreter::{is_p2sh_script, verify_ecdsa_signature, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS};
+use super::script::opcodes::Opcode;
+use super::script::{instructions, parse_multisig_script};
+use super::transaction::{BTCTransaction, BTCTransactionOutput, SIGHASH_ALL};
+use super::tx_builder::UnsignedBTCTransaction;
+
+pub const PSBT_MAGIC: [u8; 5] = *b"dpsbt";
+pub const PSBT_VERSION: u8 = 2;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct PartialSignature {
+    pub public_key: Vec<u8>,
+    /// DER signature followed by the sighash type.
+    pub signature: Vec<u8>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct PartiallySignedInput {
+    /// The output spent by this input.
+    pub prevout: BTCTransactionOutput,
+    /// The full transaction containing `prevout`, which lets a signer check the value it is signing for.
+    pub non_witness_utxo: Option<BTCTransaction>,
+    pub redeem_script: Option<Vec<u8>>,
+    pub sighash_type: u8,
+    pub partial_signatures: Vec<PartialSignature>,
+    pub final_script_sig: Option<Vec<u8>>,
+}
+
+impl PartiallySignedInput {
+    pub fn new(prevout: BTCTransactionOutput) -> Self {
+        Self {
+            prevout,
+            non_witness_utxo: None,
+            redeem_script: None,
+            sighash_type: SIGHASH_ALL,
+            partial_signatures: Vec::new(),
+            final_script_sig: None,
+        }
+    }
+    /// The script which is signed: the redeem script for P2SH outputs, otherwise the output script.
+    pub fn get_script_code(&self) -> QDogeResult<&[u8]> {
+        if is_p2sh_script(&self.prevout.script) {
+            self.redeem_script.as_deref().ok_or(DogeBridgeError::PsbtMissingRedeemScript)
+        } else {
+            Ok(&self.prevout.script)
+        }
+    }
+    pub fn get_signature(&self, public_key: &[u8]) -> Option<&[u8]> {
+        self.partial_signatures
+            .iter()
+            .find(|x| x.public_key == public_key)
+            .map(|x| x.signature.as_slice())
+    }
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some()
+    }
+    /// Checks the previous transaction and the redeem script of the input against the outpoint and the output it spends.
+    pub fn check_prevout(&self, outpoint_hash: &QHash256, outpoint_index: u32) -> QDogeResult<()> {
+        if let Some(previous_transaction) = &self.non_witness_utxo {
+            if previous_transaction.get_hash() != *outpoint_hash {
+                return Err(DogeBridgeError::PsbtNonWitnessUtxoTxidMismatch);
+            }
+            if previous_transaction.outputs.get(outpoint_index as usize) != Some(&self.prevout) {
+                return Err(DogeBridgeError::PsbtNonWitnessUtxoOutputMismatch);
+            }
+        }
+        if let Some(redeem_script) = &self.redeem_script {
+            if self.prevout.script != gen_p2sh_script(&QBTCHash160Hasher::hash_bytes(redeem_script)) {
+                return Err(DogeBridgeError::PsbtRedeemScriptMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the script sig from the partial signatures for multisig, pay to public key hash and pay to public key scripts.
+    ///
+    /// The same templates are accepted as P2SH redeem scripts, where any prefix of the redeem script before the
+    /// signature check (such as the `<data> OP_DROP` of the bridge deposit scripts) must not consume arguments.
+    fn build_script_sig(&self) -> QDogeResult<Vec<u8>> {
+        let script_code = self.get_script_code()?;
+        let mut builder = ScriptBuilder::new();
+        if let Some((required, public_keys)) = parse_multisig_script(script_code) {
+            let signatures: Vec<&[u8]> = public_keys.iter().filter_map(|x| self.get_signature(x)).take(required).collect();
+            if signatures.len() < required {
+                return Err(DogeBridgeError::PsbtMissingSignatures);
+            }
+            builder = builder.push_int(0);
+            for signature in signatures {
+                builder = builder.push_slice(signature);
+            }
+        } else {
+            let script: Vec<Instruction> = instructions(script_code)
+                .collect::<Result<_, _>>()
+                .map_err(|_| DogeBridgeError::PsbtUnsupportedScript)?;
+            match script.as_slice() {
+                [.., Instruction::Op(Opcode::OP_DUP), Instruction::Op(Opcode::OP_HASH160), Instruction::Push { data, .. }, Instruction::Op(Opcode::OP_EQUALVERIFY), Instruction::Op(Opcode::OP_CHECKSIG)]
+                    if data.len() == 20 =>
+                {
+                    let partial_signature = self
+                        .partial_signatures
+                        .iter()
+                        .find(|x| QBTCHash160Hasher::hash_bytes(&x.public_key) == **data)
+                        .ok_or(DogeBridgeError::PsbtMissingSignatures)?;
+                    builder = builder.push_slice(&partial_signature.signature).push_slice(&partial_signature.public_key);
+                }
+                [.., Instruction::Push { data, .. }, Instruction::Op(Opcode::OP_CHECKSIG)] => {
+                    let signature = self.get_signature(data).ok_or(DogeBridgeError::PsbtMissingSignatures)?;
+                    builder = builder.push_slice(signature);
+                }
+                _ => return Err(DogeBridgeError::PsbtUnsupportedScript),
+            }
+        }
+        if is_p2sh_script(&self.prevout.script) {
+            builder = builder.push_slice(script_code);
+        }
+        Ok(builder.into_bytes())
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct PartiallySignedBTCTransaction {
+    /// The unsigned transaction, finalized script sigs are only inserted by `extract`.
+    pub transaction: BTCTransaction,
+    pub inputs: Vec<PartiallySignedInput>,
+}
+
+impl PartiallySignedBTCTransaction {
+    pub fn new(transaction: BTCTransaction, prevouts: Vec<BTCTransactionOutput>) -> QDogeResult<Self> {
+        if transaction.inputs.len() != prevouts.len() {
+            return Err(DogeBridgeError::PsbtInputCountMismatch);
+        }
+        if transaction.inputs.iter().any(|x| !x.script.is_empty()) {
+            return Err(DogeBridgeError::PsbtTransactionNotUnsigned);
+        }
+        Ok(Self {
+            transaction,
+            inputs: prevouts.into_iter().map(PartiallySignedInput::new).collect(),
+        })
+    }
+    pub fn from_unsigned(unsigned: &UnsignedBTCTransaction) -> Self {
+        Self {
+            transaction: unsigned.transaction.clone(),
+            inputs: unsigned.spent_utxos.iter().map(|x| PartiallySignedInput::new(x.to_output())).collect(),
+        }
+    }
+
+    fn get_input_mut(&mut self, input_index: usize) -> QDogeResult<&mut PartiallySignedInput> {
+        self.inputs.get_mut(input_index).ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)
+    }
+
+    /// Checks every input against the outpoint it spends, see [`PartiallySignedInput::check_prevout`].
+    pub fn check_prevouts(&self) -> QDogeResult<()> {
+        if self.transaction.inputs.len() != self.inputs.len() {
+            return Err(DogeBridgeError::PsbtInputCountMismatch);
+        }
+        for (tx_input, input) in self.transaction.inputs.iter().zip(self.inputs.iter()) {
+            input.check_prevout(&tx_input.hash, tx_input.index)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_redeem_script(&mut self, input_index: usize, redeem_script: Vec<u8>) -> QDogeResult<()> {
+        let input = self.get_input_mut(input_index)?;
+        if input.prevout.script != gen_p2sh_script(&QBTCHash160Hasher::hash_bytes(&redeem_script)) {
+            return Err(DogeBridgeError::PsbtRedeemScriptMismatch);
+        }
+        input.redeem_script = Some(redeem_script);
+        Ok(())
+    }
+    /// Sets the full transaction containing the output spent by an input, after checking it against the outpoint and the stated output.
+    pub fn set_non_witness_utxo(&mut self, input_index: usize, previous_transaction: BTCTransaction) -> QDogeResult<()> {
+        let tx_input = self.transaction.inputs.get(input_index).ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)?;
+        let input = &self.inputs[input_index];
+        if previous_transaction.get_hash() != tx_input.hash {
+            return Err(DogeBridgeError::PsbtNonWitnessUtxoTxidMismatch);
+        }
+        if previous_transaction.outputs.get(tx_input.index as usize) != Some(&input.prevout) {
+            return Err(DogeBridgeError::PsbtNonWitnessUtxoOutputMismatch);
+        }
+        self.inputs[input_index].non_witness_utxo = Some(previous_transaction);
+        Ok(())
+    }
+    /// Changes the sighash type of an input, dropping the partial signatures made with the previous type.
+    pub fn set_sighash_type(&mut self, input_index: usize, sighash_type: u8) -> QDogeResult<()> {
+        let input = self.get_input_mut(input_index)?;
+        if input.sighash_type != sighash_type {
+            input.sighash_type = sighash_type;
+            input.partial_signatures.clear();
+        }
+        Ok(())
+    }
+
+    /// The message which the signers of an input sign.
+    pub fn get_signature_hash(&self, input_index: usize) -> QDogeResult<QHash256> {
+        let input = self.inputs.get(input_index).ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)?;
+        Ok(self.transaction.signature_hash(input_index, input.get_script_code()?, input.sighash_type as u32))
+    }
+
+    fn check_signature(&self, input_index: usize, public_key: &[u8], signature: &[u8]) -> QDogeResult<()> {
+        let sighash = self.get_signature_hash(input_index)?;
+        match signature.split_last() {
+            Some((sighash_type, der_signature))
+                if *sighash_type == self.inputs[input_index].sighash_type
+                    && verify_ecdsa_signature(der_signature, public_key, &sighash) =>
+            {
+                Ok(())
+            }
+            _ => Err(DogeBridgeError::PsbtInvalidSignature),
+        }
+    }
+
+    /// Adds a signature (DER followed by the sighash type) after checking it against the signature hash of the input.
+    pub fn add_signature(&mut self, input_index: usize, public_key: &[u8], signature: &[u8]) -> QDogeResult<()> {
+        self.check_signature(input_index, public_key, signature)?;
+        let input = &mut self.inputs[input_index];
+        input.partial_signatures.retain(|x| x.public_key != public_key);
+        input.partial_signatures.push(PartialSignature {
+            public_key: public_key.to_vec(),
+            signature: signature.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Signs an input with `sign`, which returns a DER signature of the signature hash for `public_key`.
+    pub fn sign_input<F: FnOnce(&QHash256) -> QDogeResult<Vec<u8>>>(
+        &mut self,
+        input_index: usize,
+        public_key: &[u8],
+        sign: F,
+    ) -> QDogeResult<()> {
+        let mut signature = sign(&self.get_signature_hash(input_index)?)?;
+        signature.push(self.inputs[input_index].sighash_type);
+        self.add_signature(input_index, public_key, &signature)
+    }
+
+    /// Merges the previous transactions, redeem scripts, partial signatures and finalized script sigs of another copy of the same transaction.
+    pub fn combine(&mut self, other: &Self) -> QDogeResult<()> {
+        if self.transaction != other.transaction || self.inputs.len() != other.inputs.len() {
+            return Err(DogeBridgeError::PsbtTransactionMismatch);
+        }
+        self.check_prevouts()?;
+        other.check_prevouts()?;
+        for (input_index, other_input) in other.inputs.iter().enumerate() {
+            let input = &self.inputs[input_index];
+            if input.prevout != other_input.prevout || input.sighash_type != other_input.sighash_type {
+                return Err(DogeBridgeError::PsbtTransactionMismatch);
+            }
+            match (&input.non_witness_utxo, &other_input.non_witness_utxo) {
+                (None, Some(previous_transaction)) => self.set_non_witness_utxo(input_index, previous_transaction.clone())?,
+                (Some(a), Some(b)) if a != b => return Err(DogeBridgeError::PsbtTransactionMismatch),
+                _ => {}
+            }
+            match (&self.inputs[input_index].redeem_script, &other_input.redeem_script) {
+                (None, Some(redeem_script)) => self.set_redeem_script(input_index, redeem_script.clone())?,
+                (Some(a), Some(b)) if a != b => return Err(DogeBridgeError::PsbtRedeemScriptMismatch),
+                _ => {}
+            }
+            for partial_signature in other_input.partial_signatures.iter() {
+                if self.inputs[input_index].get_signature(&partial_signature.public_key).is_none() {
+                    self.add_signature(input_index, &partial_signature.public_key, &partial_signature.signature)?;
+                }
+            }
+            if let (None, Some(script_sig)) = (&self.inputs[input_index].final_script_sig, &other_input.final_script_sig) {
+                self.finalize_input_with_script_sig(input_index, script_sig.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finalizes an input with a script sig built by the caller, for scripts which are not supported by `finalize`.
+    ///
+    /// The script sig is verified against the output being spent under the standard script flags.
+    pub fn finalize_input_with_script_sig(&mut self, input_index: usize, script_sig: Vec<u8>) -> QDogeResult<()> {
+        let prevout_script = &self.inputs.get(input_index).ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)?.prevout.script;
+        let mut transaction = self.transaction.clone();
+        transaction.inputs[input_index].script = script_sig;
+        transaction.verify_input_script(input_index, prevout_script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS)?;
+
+        let input = &mut self.inputs[input_index];
+        input.final_script_sig = Some(core::mem::take(&mut transaction.inputs[input_index].script));
+        input.partial_signatures.clear();
+        Ok(())
+    }
+
+    /// Finalizes every input which is not finalized yet from its partial signatures.
+    pub fn finalize(&mut self) -> QDogeResult<()> {
+        for input_index in 0..self.inputs.len() {
+            if !self.inputs[input_index].is_finalized() {
+                let script_sig = self.inputs[input_index].build_script_sig()?;
+                self.finalize_input_with_script_sig(input_index, script_sig)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(PartiallySignedInput::is_finalized)
+    }
+
+    /// Returns the signed transaction once every input is finalized.
+    pub fn extract(&self) -> QDogeResult<BTCTransaction> {
+        let mut transaction = self.transaction.clone();
+        for (tx_input, input) in transaction.inputs.iter_mut().zip(self.inputs.iter()) {
+            tx_input.script = input.final_script_sig.clone().ok_or(DogeBridgeError::PsbtNotFinalized)?;
+        }
+        Ok(transaction)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl PartiallySignedBTCTransaction {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&PSBT_MAGIC);
+        bytes.push(PSBT_VERSION);
+        BorshSerialize::serialize(self, &mut bytes).expect("writing to a vec does not fail");
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8]) -> QDogeResult<Self> {
+        let Some(body) = bytes.strip_prefix(&PSBT_MAGIC).and_then(|x| x.strip_prefix(&[PSBT_VERSION])) else {
+            return Err(DogeBridgeError::PsbtInvalidEncoding);
+        };
+        let psbt = Self::try_from_slice(body).map_err(|_| DogeBridgeError::PsbtInvalidEncoding)?;
+        psbt.check_prevouts()?;
+        Ok(psbt)
+    }
+    pub fn to_base64(&self) -> String {
+        encode_base64(&self.to_bytes())
+    }
+    pub fn from_base64(text: &str) -> QDogeResult<Self> {
+        Self::from_bytes(&decode_base64(text).ok_or(DogeBridgeError::PsbtInvalidEncoding)?)
+    }
+}
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding.
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let value = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64 with padding, returns None for any other input.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if text.len() % 4 != 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (chunk_index, chunk) in text.chunks(4).enumerate() {
+        let is_last = chunk_index == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|x| **x == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+        let mut value = 0u32;
+        for c in &chunk[..4 - padding] {
+            let digit = BASE64_CHARS.iter().position(|x| x == c)? as u32;
+            value = value << 6 | digit;
+        }
+        value <<= 6 * padding as u32;
+        // the unused bits before the padding have to be zero, otherwise several texts decode to the same bytes
+        if value & ((1u32 << (8 * padding)) - 1) != 0 {
+            return None;
+        }
+        let bytes = value.to_be_bytes();
+        out.extend_from_slice(&bytes[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::SecretKey;
+
+    use super::*;
+    use crate::constants::DOGE_COIN;
+    use crate::doge::address::BTCAddress160;
+    use crate::doge::tx_builder::{BTCTransactionBuilder, BTCUtxo, DogeFeePolicy, MAX_SIGNATURE_PUSH_SIZE};
+    use crate::test_fixtures::{get_pubkey, multisig_script, signer};
+
+    /// Same shape as the bridge deposit redeem script: `<32 bytes> OP_DROP` followed by a P2PKH script.
+    fn deposit_script(pubkey: &[u8]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_slice(&[0x42; 32])
+            .push_opcode(Opcode::OP_DROP)
+            .push_opcode(Opcode::OP_DUP)
+            .push_opcode(Opcode::OP_HASH160)
+            .push_slice(&QBTCHash160Hasher::hash_bytes(pubkey))
+            .push_opcode(Opcode::OP_EQUALVERIFY)
+            .push_opcode(Opcode::OP_CHECKSIG)
+            .into_bytes()
+    }
+
+    struct TestSetup {
+        keys: Vec<SecretKey>,
+        pubkeys: Vec<Vec<u8>>,
+        multisig: Vec<u8>,
+        deposit: Vec<u8>,
+        estimated_size: usize,
+        psbt: PartiallySignedBTCTransaction,
+    }
+
+    fn setup() -> TestSetup {
+        let keys: Vec<SecretKey> = (1u8..=4).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect();
+        let pubkeys: Vec<Vec<u8>> = keys.iter().map(get_pubkey).collect();
+        let multisig = multisig_script(2, &pubkeys[1..]);
+        let deposit = deposit_script(&pubkeys[0]);
+        let unsigned = BTCTransactionBuilder::new(BTCAddress160::new_p2sh([0x22; 20]), DogeFeePolicy::recommended())
+            .add_utxo(BTCUtxo::new_p2pkh([1; 32], 0, 3 * DOGE_COIN, &pubkeys[0]))
+            .add_utxo(BTCUtxo::new_p2sh_multisig([2; 32], 1, 3 * DOGE_COIN, &multisig, 2))
+            .add_utxo(BTCUtxo::new_p2sh([3; 32], 2, 3 * DOGE_COIN, &deposit, MAX_SIGNATURE_PUSH_SIZE + 34))
+            .add_recipient(&BTCAddress160::new_p2pkh([0x11; 20]), 8 * DOGE_COIN)
+            .build()
+            .unwrap();
+        let mut psbt = PartiallySignedBTCTransaction::from_unsigned(&unsigned);
+        psbt.set_redeem_script(1, multisig.clone()).unwrap();
+        psbt.set_redeem_script(2, deposit.clone()).unwrap();
+        TestSetup {
+            keys,
+            pubkeys,
+            multisig,
+            deposit,
+            estimated_size: unsigned.estimated_size,
+            psbt,
+        }
+    }
+
+    #[test]
+    fn test_sign_combine_finalize() {
+        let TestSetup {
+            keys,
+            pubkeys,
+            multisig,
+            deposit,
+            estimated_size,
+            psbt,
+        } = setup();
+
+        let mut first = psbt.clone();
+        first.sign_input(0, &pubkeys[0], signer(keys[0])).unwrap();
+        first.sign_input(1, &pubkeys[3], signer(keys[3])).unwrap();
+        first.sign_input(2, &pubkeys[0], signer(keys[0])).unwrap();
+        assert_eq!(first.clone().finalize(), Err(DogeBridgeError::PsbtMissingSignatures));
+        assert_eq!(first.extract(), Err(DogeBridgeError::PsbtNotFinalized));
+
+        let mut second = psbt.clone();
+        second.sign_input(1, &pubkeys[1], signer(keys[1])).unwrap();
+
+        first.combine(&second).unwrap();
+        first.finalize().unwrap();
+        assert!(first.is_finalized());
+        assert!(first.inputs.iter().all(|x| x.partial_signatures.is_empty()));
+        let signed = first.extract().unwrap();
+        for (i, input) in first.inputs.iter().enumerate() {
+            signed.verify_input_script(i, &input.prevout.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+        }
+        assert!(signed.to_bytes().len() <= estimated_size);
+
+        // multisig signatures are ordered by the public keys of the redeem script
+        let multisig_script_sig: Vec<Instruction> = instructions(&signed.inputs[1].script).map(Result::unwrap).collect();
+        assert_eq!(multisig_script_sig.len(), 4);
+        assert_eq!(multisig_script_sig[1].get_push_data(), psbt_signature(&second, 1, &pubkeys[1]).as_deref());
+        assert_eq!(multisig_script_sig[3].get_push_data(), Some(multisig.as_slice()));
+        let deposit_script_sig: Vec<Instruction> = instructions(&signed.inputs[2].script).map(Result::unwrap).collect();
+        assert_eq!(deposit_script_sig[1].get_push_data(), Some(pubkeys[0].as_slice()));
+        assert_eq!(deposit_script_sig[2].get_push_data(), Some(deposit.as_slice()));
+
+        // a finalized copy combines into an unsigned one
+        let mut third = psbt;
+        third.combine(&first).unwrap();
+        assert_eq!(third.extract(), Ok(signed));
+    }
+
+    fn psbt_signature(psbt: &PartiallySignedBTCTransaction, input_index: usize, public_key: &[u8]) -> Option<Vec<u8>> {
+        psbt.inputs[input_index].get_signature(public_key).map(|x| x.to_vec())
+    }
+
+    #[test]
+    fn test_errors() {
+        let TestSetup { keys, pubkeys, multisig, mut psbt, .. } = setup();
+
+        assert_eq!(psbt.set_redeem_script(2, multisig), Err(DogeBridgeError::PsbtRedeemScriptMismatch));
+        assert_eq!(psbt.sign_input(0, &pubkeys[0], signer(keys[1])), Err(DogeBridgeError::PsbtInvalidSignature));
+        assert_eq!(psbt.sign_input(5, &pubkeys[0], signer(keys[0])), Err(DogeBridgeError::ScriptInputIndexOutOfRange));
+
+        // signatures carry the sighash type of the input
+        psbt.sign_input(0, &pubkeys[0], signer(keys[0])).unwrap();
+        let mut signature = psbt_signature(&psbt, 0, &pubkeys[0]).unwrap();
+        *signature.last_mut().unwrap() = 0x02;
+        assert_eq!(psbt.add_signature(0, &pubkeys[0], &signature), Err(DogeBridgeError::PsbtInvalidSignature));
+        psbt.set_sighash_type(0, 0x02).unwrap();
+        assert!(psbt.inputs[0].partial_signatures.is_empty());
+
+        let mut without_redeem = PartiallySignedBTCTransaction::new(
+            psbt.transaction.clone(),
+            psbt.inputs.iter().map(|x| x.prevout.clone()).collect(),
+        )
+        .unwrap();
+        assert_eq!(without_redeem.get_signature_hash(1), Err(DogeBridgeError::PsbtMissingRedeemScript));
+        assert_eq!(without_redeem.finalize(), Err(DogeBridgeError::PsbtMissingSignatures));
+        assert_eq!(
+            PartiallySignedBTCTransaction::new(psbt.transaction.clone(), Vec::new()),
+            Err(DogeBridgeError::PsbtInputCountMismatch)
+        );
+
+        let mut other = psbt.clone();
+        other.transaction.locktime = 1;
+        assert_eq!(psbt.combine(&other), Err(DogeBridgeError::PsbtTransactionMismatch));
+
+        // scripts without a known template need an explicit script sig, which is still verified
+        let anyone_can_spend = [Opcode::OP_1 as u8];
+        let mut custom = PartiallySignedBTCTransaction::new(
+            psbt.transaction.clone(),
+            psbt.inputs
+                .iter()
+                .map(|x| BTCTransactionOutput {
+                    value: x.prevout.value,
+                    script: anyone_can_spend.to_vec(),
+                })
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(custom.finalize(), Err(DogeBridgeError::PsbtUnsupportedScript));
+        assert_eq!(custom.finalize_input_with_script_sig(0, vec![Opcode::OP_0 as u8]), Err(DogeBridgeError::ScriptCleanStack));
+        custom.finalize_input_with_script_sig(0, Vec::new()).unwrap();
+        assert!(custom.inputs[0].is_finalized());
+    }
+
+    #[test]
+    fn test_non_witness_utxo() {
+        let TestSetup { multisig, deposit, psbt, .. } = setup();
+        let previous = BTCTransaction {
+            version: 1,
+            inputs: Vec::new(),
+            outputs: vec![psbt.inputs[1].prevout.clone(), psbt.inputs[0].prevout.clone()],
+            locktime: 0,
+        };
+        let mut transaction = psbt.transaction.clone();
+        transaction.inputs[0].hash = previous.get_hash();
+        transaction.inputs[0].index = 1;
+        let mut psbt = PartiallySignedBTCTransaction::new(transaction, psbt.inputs.iter().map(|x| x.prevout.clone()).collect()).unwrap();
+        psbt.set_redeem_script(1, multisig).unwrap();
+
+        let mut other_txid = previous.clone();
+        other_txid.locktime = 1;
+        assert_eq!(psbt.set_non_witness_utxo(0, other_txid), Err(DogeBridgeError::PsbtNonWitnessUtxoTxidMismatch));
+        assert_eq!(psbt.set_non_witness_utxo(1, previous.clone()), Err(DogeBridgeError::PsbtNonWitnessUtxoTxidMismatch));
+        let mut wrong_output = psbt.clone();
+        wrong_output.transaction.inputs[0].index = 0;
+        assert_eq!(wrong_output.set_non_witness_utxo(0, previous.clone()), Err(DogeBridgeError::PsbtNonWitnessUtxoOutputMismatch));
+        let mut wrong_prevout = psbt.clone();
+        wrong_prevout.inputs[0].prevout.value += 1;
+        assert_eq!(wrong_prevout.set_non_witness_utxo(0, previous.clone()), Err(DogeBridgeError::PsbtNonWitnessUtxoOutputMismatch));
+
+        let mut with_previous = psbt.clone();
+        with_previous.set_non_witness_utxo(0, previous.clone()).unwrap();
+        let mut combined = psbt.clone();
+        combined.combine(&with_previous).unwrap();
+        assert_eq!(combined.inputs[0].non_witness_utxo.as_ref(), Some(&previous));
+
+        // inputs are re-checked against their prevouts when combining
+        let mut tampered = with_previous.clone();
+        tampered.inputs[0].prevout.value += 1;
+        tampered.inputs[0].non_witness_utxo.as_mut().unwrap().outputs[1].value += 1;
+        assert_eq!(psbt.clone().combine(&tampered), Err(DogeBridgeError::PsbtNonWitnessUtxoTxidMismatch));
+        let mut tampered = psbt.clone();
+        tampered.inputs[2].redeem_script = Some(deposit.clone());
+        tampered.inputs[1].redeem_script = Some(deposit);
+        assert_eq!(psbt.clone().combine(&tampered), Err(DogeBridgeError::PsbtRedeemScriptMismatch));
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_encoding() {
+        let TestSetup { keys, pubkeys, mut psbt, .. } = setup();
+        psbt.sign_input(0, &pubkeys[0], signer(keys[0])).unwrap();
+        let text = psbt.to_base64();
+        assert!(text.starts_with("ZHBzYnQC"));
+        assert_eq!(PartiallySignedBTCTransaction::from_base64(&text), Ok(psbt.clone()));
+
+        let mut tampered = psbt.clone();
+        tampered.inputs[1].redeem_script = tampered.inputs[2].redeem_script.clone();
+        assert_eq!(PartiallySignedBTCTransaction::from_bytes(&tampered.to_bytes()), Err(DogeBridgeError::PsbtRedeemScriptMismatch));
+
+        let bytes = psbt.to_bytes();
+        assert_eq!(PartiallySignedBTCTransaction::from_bytes(&bytes[1..]), Err(DogeBridgeError::PsbtInvalidEncoding));
+        assert_eq!(PartiallySignedBTCTransaction::from_bytes(&bytes[..bytes.len() - 1]), Err(DogeBridgeError::PsbtInvalidEncoding));
+        assert_eq!(PartiallySignedBTCTransaction::from_base64(&text[1..]), Err(DogeBridgeError::PsbtInvalidEncoding));
+
+        for (data, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(encode_base64(data.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded).as_deref(), Some(data.as_bytes()));
+        }
+        for invalid in ["Zg=", "Z===", "Zg==Zg==", "Zm9*", "Zh==", "Zm9=", "Zm8/Zh=="] {
+            assert_eq!(decode_base64(invalid), None);
+        }
+    }
+}
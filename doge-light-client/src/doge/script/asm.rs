@@ -0,0 +1,127 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Assembly representation of scripts.
+//!
+//! Opcodes are written with their dogecoin core names (`OP_DUP`, `OP_0`, `OP_16`, ...). A push using the smallest push
+//! opcode for its length is written as bare hex, any other push as the push opcode followed by `0x` prefixed hex
+//! (`OP_PUSHDATA1 0x0102`), so parsing the output of [`script_to_asm`] gives back the exact same bytes.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::error::{DogeBridgeError, QDogeResult};
+
+use super::{
+    builder::ScriptBuilder,
+    instruction::{get_push_size_opcode, Instruction, Instructions},
+    opcodes::Opcode,
+};
+
+const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
+
+fn push_hex(out: &mut String, data: &[u8]) {
+    for b in data {
+        out.push(HEX_CHARS[(b >> 4) as usize] as char);
+        out.push(HEX_CHARS[(b & 0x0f) as usize] as char);
+    }
+}
+
+fn decode_hex(hex: &str) -> QDogeResult<Vec<u8>> {
+    let hex = hex.as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(DogeBridgeError::InvalidScriptAsm);
+    }
+    let nibble = |c: u8| match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(DogeBridgeError::InvalidScriptAsm),
+    };
+    hex.chunks(2).map(|pair| Ok((nibble(pair[0])? << 4) | nibble(pair[1])?)).collect()
+}
+
+/// Writes the assembly of a script, a push running past the end of the script is written as `[error]`.
+pub fn script_to_asm(script: &[u8]) -> String {
+    let mut out = String::new();
+    for instruction in Instructions::new(script) {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        match instruction {
+            Ok(Instruction::Push { opcode, data }) => {
+                if opcode != get_push_size_opcode(data.len()) {
+                    // only OP_PUSHDATA1/2/4 can be larger than needed
+                    out.push_str(Opcode::from_u8(opcode).map_or("OP_PUSHDATA4", |op| op.name()));
+                    out.push_str(" 0x");
+                }
+                push_hex(&mut out, data);
+            }
+            Ok(Instruction::Op(op)) => out.push_str(op.name()),
+            Ok(Instruction::Unknown(op)) => out.push_str(&format!("OP_UNKNOWN_0x{:02x}", op)),
+            Err(_) => out.push_str("[error]"),
+        }
+    }
+    out
+}
+
+/// Parses the assembly written by [`script_to_asm`].
+pub fn script_from_asm(asm: &str) -> QDogeResult<Vec<u8>> {
+    let mut builder = ScriptBuilder::new();
+    let mut tokens = asm.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if let Some(op) = Opcode::from_name(token) {
+            if matches!(op, Opcode::OP_PUSHDATA1 | Opcode::OP_PUSHDATA2 | Opcode::OP_PUSHDATA4) {
+                let data = tokens
+                    .next()
+                    .and_then(|data| data.strip_prefix("0x"))
+                    .ok_or(DogeBridgeError::InvalidScriptAsm)
+                    .and_then(decode_hex)?;
+                let len_size = match op {
+                    Opcode::OP_PUSHDATA1 => 1,
+                    Opcode::OP_PUSHDATA2 => 2,
+                    _ => 4,
+                };
+                let len_bytes = (data.len() as u64).to_le_bytes();
+                if len_bytes[len_size..].iter().any(|b| *b != 0) {
+                    return Err(DogeBridgeError::InvalidScriptAsm);
+                }
+                builder = builder.push_opcode(op).append(&len_bytes[..len_size]).append(&data);
+            } else {
+                builder = builder.push_opcode(op);
+            }
+        } else if let Some(op) = token.strip_prefix("OP_UNKNOWN_0x") {
+            match decode_hex(op)?.as_slice() {
+                [op] if Opcode::from_u8(*op).is_none() && *op > Opcode::OP_PUSHDATA4 as u8 => {
+                    builder = builder.append(&[*op]);
+                }
+                _ => return Err(DogeBridgeError::InvalidScriptAsm),
+            }
+        } else {
+            builder = builder.push_data_raw(&decode_hex(token)?);
+        }
+    }
+    Ok(builder.into_bytes())
+}
@@ -0,0 +1,103 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use alloc::vec::Vec;
+
+use super::{
+    instruction::{get_minimal_push_opcode, get_push_size_opcode},
+    num::encode_script_num,
+    opcodes::Opcode,
+};
+
+/// Builds scripts from opcodes and minimally encoded pushes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptBuilder {
+    bytes: Vec<u8>,
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+    pub fn push_opcode(mut self, opcode: Opcode) -> Self {
+        self.bytes.push(opcode as u8);
+        self
+    }
+    /// Pushes `data` with the smallest encoding, using OP_0, OP_1..=OP_16 and OP_1NEGATE where possible.
+    pub fn push_slice(mut self, data: &[u8]) -> Self {
+        match data {
+            [] | [1..=16] | [0x81] => {
+                self.bytes.push(get_minimal_push_opcode(data));
+                self
+            }
+            _ => self.push_data_raw(data),
+        }
+    }
+    /// Pushes `data` with the smallest push opcode for its length, without replacing small values with OP_N.
+    pub fn push_data_raw(mut self, data: &[u8]) -> Self {
+        let opcode = get_push_size_opcode(data.len());
+        self.bytes.push(opcode);
+        if opcode == Opcode::OP_PUSHDATA1 as u8 {
+            self.bytes.push(data.len() as u8);
+        } else if opcode == Opcode::OP_PUSHDATA2 as u8 {
+            self.bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        } else if opcode == Opcode::OP_PUSHDATA4 as u8 {
+            self.bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        }
+        self.bytes.extend_from_slice(data);
+        self
+    }
+    /// Pushes a number the same way as `CScript() << value` in dogecoin core.
+    pub fn push_int(mut self, value: i64) -> Self {
+        if value == -1 || (0..=16).contains(&value) {
+            self.bytes.push(match value {
+                -1 => Opcode::OP_1NEGATE as u8,
+                0 => Opcode::OP_0 as u8,
+                _ => Opcode::OP_1 as u8 + value as u8 - 1,
+            });
+            self
+        } else {
+            self.push_data_raw(&encode_script_num(value))
+        }
+    }
+    /// Appends raw script bytes.
+    pub fn append(mut self, script: &[u8]) -> Self {
+        self.bytes.extend_from_slice(script);
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
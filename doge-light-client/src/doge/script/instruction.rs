@@ -0,0 +1,174 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use crate::error::{DogeBridgeError, QDogeResult};
+
+use super::opcodes::{Opcode, MAX_DIRECT_PUSH_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    /// A data push, `opcode` is the push opcode used (0x01..=0x4b or OP_PUSHDATA1/2/4), `OP_0` is decoded as an opcode
+    Push { opcode: u8, data: &'a [u8] },
+    Op(Opcode),
+    /// A byte which is not a defined opcode (0xba..=0xfe)
+    Unknown(u8),
+}
+
+impl<'a> Instruction<'a> {
+    pub fn get_push_data(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Push { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+    /// The push opcode and data for pushes, treating `OP_0` as an empty push, equivalent to `GetOp` in dogecoin core.
+    pub fn get_opcode(&self) -> u8 {
+        match self {
+            Self::Push { opcode, .. } => *opcode,
+            Self::Op(op) => *op as u8,
+            Self::Unknown(op) => *op,
+        }
+    }
+    /// Whether the opcode is at most OP_16, as checked by `IsPushOnly` in dogecoin core (which also counts OP_RESERVED).
+    pub fn is_push(&self) -> bool {
+        match self {
+            Self::Push { .. } => true,
+            Self::Op(op) => *op as u8 <= Opcode::OP_16 as u8,
+            Self::Unknown(_) => false,
+        }
+    }
+    /// Whether the push uses the smallest possible encoding, equivalent to `CheckMinimalPush` in dogecoin core.
+    ///
+    /// Non push instructions are considered minimal.
+    pub fn is_minimal_push(&self) -> bool {
+        match self {
+            Self::Push { opcode, data } => get_minimal_push_opcode(data) == *opcode,
+            _ => true,
+        }
+    }
+}
+
+/// The opcode `CScript() << data` would use to push `data`.
+///
+/// Empty pushes and single bytes 1..=16 and 0x81 are pushed with OP_0, OP_1..=OP_16 and OP_1NEGATE.
+pub fn get_minimal_push_opcode(data: &[u8]) -> u8 {
+    match data {
+        [] => Opcode::OP_0 as u8,
+        [value @ 1..=16] => Opcode::OP_1 as u8 + value - 1,
+        [0x81] => Opcode::OP_1NEGATE as u8,
+        _ => get_push_size_opcode(data.len()),
+    }
+}
+
+/// The smallest push opcode which can hold `len` bytes of data.
+pub fn get_push_size_opcode(len: usize) -> u8 {
+    if len <= MAX_DIRECT_PUSH_SIZE {
+        len as u8
+    } else if len <= 0xff {
+        Opcode::OP_PUSHDATA1 as u8
+    } else if len <= 0xffff {
+        Opcode::OP_PUSHDATA2 as u8
+    } else {
+        Opcode::OP_PUSHDATA4 as u8
+    }
+}
+
+/// The number of script bytes used by a push of `len` bytes of data with the smallest push opcode.
+pub fn get_push_encoded_size(len: usize) -> usize {
+    let length_bytes = if len <= MAX_DIRECT_PUSH_SIZE {
+        0
+    } else if len <= 0xff {
+        1
+    } else if len <= 0xffff {
+        2
+    } else {
+        4
+    };
+    1 + length_bytes + len
+}
+
+/// Iterates over the instructions of a script, yields an error and stops at the first push which runs past the end of the script.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(script: &'a [u8]) -> Self {
+        Self { script, offset: 0 }
+    }
+    /// Offset in the script of the next instruction.
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+    fn read_push_len(&mut self, len_size: usize) -> QDogeResult<usize> {
+        if self.script.len() - self.offset < len_size {
+            return Err(DogeBridgeError::ScriptPushOutOfBounds);
+        }
+        let mut len_bytes = [0u8; 4];
+        len_bytes[..len_size].copy_from_slice(&self.script[self.offset..self.offset + len_size]);
+        self.offset += len_size;
+        Ok(u32::from_le_bytes(len_bytes) as usize)
+    }
+    fn next_instruction(&mut self) -> QDogeResult<Instruction<'a>> {
+        let opcode = self.script[self.offset];
+        self.offset += 1;
+        let push_len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            0x4c => self.read_push_len(1)?,
+            0x4d => self.read_push_len(2)?,
+            0x4e => self.read_push_len(4)?,
+            _ => {
+                return Ok(match Opcode::from_u8(opcode) {
+                    Some(op) => Instruction::Op(op),
+                    None => Instruction::Unknown(opcode),
+                })
+            }
+        };
+        if self.script.len() - self.offset < push_len {
+            return Err(DogeBridgeError::ScriptPushOutOfBounds);
+        }
+        let data = &self.script[self.offset..self.offset + push_len];
+        self.offset += push_len;
+        Ok(Instruction::Push { opcode, data })
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = QDogeResult<Instruction<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.script.len() {
+            return None;
+        }
+        let result = self.next_instruction();
+        if result.is_err() {
+            self.offset = self.script.len();
+        }
+        Some(result)
+    }
+}
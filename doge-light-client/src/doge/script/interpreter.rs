@@ -0,0 +1,1226 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Legacy (pre-segwit) script interpreter, a port of `EvalScript` and `VerifyScript` from dogecoin core 1.14.
+
+use alloc::{vec, vec::Vec};
+
+use libsecp256k1::{Message, PublicKey, Signature};
+
+use crate::core_data::QHash256;
+use crate::doge::transaction::{BTCTransaction, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_SINGLE};
+use crate::error::{DogeBridgeError, QDogeResult};
+use crate::hash::ripemd160_impl::hash_impl_ripemd160_bytes;
+use crate::hash::sha1::hash_impl_sha1_bytes;
+use crate::hash::sha256_impl::hash_impl_sha256_bytes;
+
+use super::builder::ScriptBuilder;
+use super::instruction::{Instruction, Instructions};
+use super::num::{decode_script_num, encode_script_num, DEFAULT_MAX_SCRIPT_NUM_SIZE};
+use super::opcodes::Opcode;
+use super::{is_push_only, LOCKTIME_THRESHOLD, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE};
+
+pub const SCRIPT_VERIFY_NONE: u32 = 0;
+/// Evaluate P2SH subscripts (BIP16).
+pub const SCRIPT_VERIFY_P2SH: u32 = 1 << 0;
+/// Require signatures and public keys to use a strict encoding and a defined sighash type.
+pub const SCRIPT_VERIFY_STRICTENC: u32 = 1 << 1;
+/// Require strict DER signatures (BIP66).
+pub const SCRIPT_VERIFY_DERSIG: u32 = 1 << 2;
+/// Require the S value of signatures to be in the lower half of the curve order (policy only).
+pub const SCRIPT_VERIFY_LOW_S: u32 = 1 << 3;
+/// Require the extra CHECKMULTISIG stack element to be empty.
+pub const SCRIPT_VERIFY_NULLDUMMY: u32 = 1 << 4;
+/// Require the script sig to only contain pushes.
+pub const SCRIPT_VERIFY_SIGPUSHONLY: u32 = 1 << 5;
+/// Require pushes and number operands to use their minimal encoding.
+pub const SCRIPT_VERIFY_MINIMALDATA: u32 = 1 << 6;
+/// Fail on the NOPs reserved for soft forks.
+pub const SCRIPT_VERIFY_DISCOURAGE_UPGRADABLE_NOPS: u32 = 1 << 7;
+/// Require exactly one stack element to remain after evaluation, only valid together with P2SH.
+pub const SCRIPT_VERIFY_CLEANSTACK: u32 = 1 << 8;
+/// Execute OP_CHECKLOCKTIMEVERIFY instead of treating it as OP_NOP2 (BIP65).
+pub const SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY: u32 = 1 << 9;
+/// Require failed signature checks to use empty signatures.
+pub const SCRIPT_VERIFY_NULLFAIL: u32 = 1 << 14;
+
+/// Flags enforced by consensus on dogecoin mainnet since the BIP65/BIP66 soft forks.
+pub const DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS: u32 =
+    SCRIPT_VERIFY_P2SH | SCRIPT_VERIFY_DERSIG | SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY;
+/// Flags enforced by the mempool policy of dogecoin core, transactions must pass these to be relayed.
+pub const DOGE_STANDARD_SCRIPT_VERIFY_FLAGS: u32 = DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS
+    | SCRIPT_VERIFY_STRICTENC
+    | SCRIPT_VERIFY_MINIMALDATA
+    | SCRIPT_VERIFY_NULLDUMMY
+    | SCRIPT_VERIFY_DISCOURAGE_UPGRADABLE_NOPS
+    | SCRIPT_VERIFY_CLEANSTACK
+    | SCRIPT_VERIFY_NULLFAIL
+    | SCRIPT_VERIFY_LOW_S;
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_STACK_SIZE: usize = 1000;
+
+const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+const MAX_LOCKTIME_NUM_SIZE: usize = 5;
+
+/// Checks the transaction dependent parts of a script (signatures and lock times).
+pub trait SignatureChecker {
+    /// Checks a signature (including its trailing sighash type byte) against a public key and script code.
+    fn check_sig(&self, signature: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool;
+    fn check_lock_time(&self, lock_time: i64) -> bool;
+}
+
+/// A checker which fails every signature and lock time check, for scripts which don't depend on a transaction.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoSignatureChecker;
+
+impl SignatureChecker for NoSignatureChecker {
+    fn check_sig(&self, _signature: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+        false
+    }
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+}
+
+/// Checks signatures against the legacy sighash of an input of a transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionSignatureChecker<'a> {
+    pub transaction: &'a BTCTransaction,
+    pub input_index: usize,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    pub fn new(transaction: &'a BTCTransaction, input_index: usize) -> Self {
+        Self { transaction, input_index }
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn check_sig(&self, signature: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool {
+        let Some((hash_type, der_signature)) = signature.split_last() else {
+            return false;
+        };
+        let sighash = self.transaction.signature_hash(self.input_index, script_code, *hash_type as u32);
+        verify_ecdsa_signature(der_signature, pubkey, &sighash)
+    }
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx_lock_time = self.transaction.locktime as i64;
+        // the lock time and the transaction lock time must both be heights or both be timestamps
+        if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) || lock_time > tx_lock_time {
+            return false;
+        }
+        // a final input disables the transaction lock time, which would allow bypassing the check
+        self.transaction
+            .inputs
+            .get(self.input_index)
+            .is_some_and(|input| input.sequence != SEQUENCE_FINAL)
+    }
+}
+
+/// Verifies a DER signature (without the sighash type byte) over a 32 byte message, equivalent to `CPubKey::Verify`.
+///
+/// The signature is parsed laxly and normalized to low S before verification, as in dogecoin core.
+pub fn verify_ecdsa_signature(der_signature: &[u8], pubkey: &[u8], message: &QHash256) -> bool {
+    let expected_len = match pubkey.first() {
+        Some(0x02 | 0x03) => 33,
+        Some(0x04 | 0x06 | 0x07) => 65,
+        _ => return false,
+    };
+    if pubkey.len() != expected_len {
+        return false;
+    }
+    let Ok(pubkey) = PublicKey::parse_slice(pubkey, None) else {
+        return false;
+    };
+    let Ok(mut signature) = Signature::parse_der_lax(der_signature) else {
+        return false;
+    };
+    signature.normalize_s();
+    libsecp256k1::verify(&Message::parse(message), &signature, &pubkey)
+}
+
+/// Removes every occurrence of `pattern` which starts at an instruction boundary of the script (`FindAndDelete`).
+fn find_and_delete(script: &[u8], pattern: &[u8]) -> Vec<u8> {
+    if pattern.is_empty() {
+        return script.to_vec();
+    }
+    let mut result = Vec::with_capacity(script.len());
+    let mut offset = 0;
+    loop {
+        while script[offset..].starts_with(pattern) {
+            offset += pattern.len();
+        }
+        let mut instructions = Instructions::new(&script[offset..]);
+        let start = offset;
+        match instructions.next() {
+            Some(Ok(_)) => offset += instructions.get_offset(),
+            _ => break,
+        }
+        result.extend_from_slice(&script[start..offset]);
+    }
+    result.extend_from_slice(&script[offset..]);
+    result
+}
+
+/// Interprets a stack element as a boolean, any non zero value other than negative zero is true.
+pub fn cast_to_bool(data: &[u8]) -> bool {
+    match data.iter().position(|b| *b != 0) {
+        Some(i) => !(i == data.len() - 1 && data[i] == 0x80),
+        None => false,
+    }
+}
+
+/// Whether the script is a P2SH output script (`OP_HASH160 <20 bytes> OP_EQUAL`).
+pub fn is_p2sh_script(script: &[u8]) -> bool {
+    script.len() == 23
+        && script[0] == Opcode::OP_HASH160 as u8
+        && script[1] == 0x14
+        && script[22] == Opcode::OP_EQUAL as u8
+}
+
+/// Strict DER encoding check from BIP66, the signature includes the trailing sighash type byte.
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 || (len_r > 1 && sig[4] == 0 && sig[5] & 0x80 == 0) {
+        return false;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    !(len_s > 1 && sig[len_r + 6] == 0 && sig[len_r + 7] & 0x80 == 0)
+}
+
+fn is_low_s_signature(sig: &[u8]) -> bool {
+    match Signature::parse_der_lax(&sig[..sig.len() - 1]) {
+        Ok(signature) => !signature.s.is_high(),
+        Err(_) => false,
+    }
+}
+
+fn is_defined_hash_type_signature(sig: &[u8]) -> bool {
+    match sig.last() {
+        Some(hash_type) => (SIGHASH_ALL..=SIGHASH_SINGLE).contains(&(hash_type & !SIGHASH_ANYONECANPAY)),
+        None => false,
+    }
+}
+
+fn check_signature_encoding(sig: &[u8], flags: u32) -> QDogeResult<()> {
+    // empty signatures are allowed as a compact way to provide an invalid signature
+    if sig.is_empty() {
+        return Ok(());
+    }
+    if flags & (SCRIPT_VERIFY_DERSIG | SCRIPT_VERIFY_LOW_S | SCRIPT_VERIFY_STRICTENC) != 0
+        && !is_valid_signature_encoding(sig)
+    {
+        Err(DogeBridgeError::ScriptSigDer)
+    } else if flags & SCRIPT_VERIFY_LOW_S != 0 && !is_low_s_signature(sig) {
+        Err(DogeBridgeError::ScriptSigHighS)
+    } else if flags & SCRIPT_VERIFY_STRICTENC != 0 && !is_defined_hash_type_signature(sig) {
+        Err(DogeBridgeError::ScriptSigHashType)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_pubkey_encoding(pubkey: &[u8], flags: u32) -> QDogeResult<()> {
+    let is_compressed_or_uncompressed = match pubkey.first() {
+        Some(0x04) => pubkey.len() == 65,
+        Some(0x02 | 0x03) => pubkey.len() == 33,
+        _ => false,
+    };
+    if flags & SCRIPT_VERIFY_STRICTENC != 0 && !is_compressed_or_uncompressed {
+        Err(DogeBridgeError::ScriptPubkeyType)
+    } else {
+        Ok(())
+    }
+}
+
+fn stack_top(stack: &[Vec<u8>], depth: usize) -> QDogeResult<&Vec<u8>> {
+    if depth == 0 || stack.len() < depth {
+        Err(DogeBridgeError::ScriptInvalidStackOperation)
+    } else {
+        Ok(&stack[stack.len() - depth])
+    }
+}
+
+fn require_stack(stack: &[Vec<u8>], size: usize) -> QDogeResult<()> {
+    if stack.len() < size {
+        Err(DogeBridgeError::ScriptInvalidStackOperation)
+    } else {
+        Ok(())
+    }
+}
+
+fn pop_stack(stack: &mut Vec<Vec<u8>>) -> QDogeResult<Vec<u8>> {
+    stack.pop().ok_or(DogeBridgeError::ScriptInvalidStackOperation)
+}
+
+fn pop_num(stack: &mut Vec<Vec<u8>>, require_minimal: bool) -> QDogeResult<i64> {
+    let value = decode_script_num(stack_top(stack, 1)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+    stack.pop();
+    Ok(value)
+}
+
+fn push_bool(stack: &mut Vec<Vec<u8>>, value: bool) {
+    stack.push(if value { vec![1] } else { Vec::new() });
+}
+
+/// Evaluates a script on top of an existing stack (`EvalScript` with `SIGVERSION_BASE`).
+pub fn eval_script<C: SignatureChecker>(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    flags: u32,
+    checker: &C,
+) -> QDogeResult<()> {
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(DogeBridgeError::ScriptSizeTooLarge);
+    }
+    let require_minimal = flags & SCRIPT_VERIFY_MINIMALDATA != 0;
+    let mut instructions = Instructions::new(script);
+    let mut code_hash_start = 0;
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut alt_stack: Vec<Vec<u8>> = Vec::new();
+    let mut op_count = 0usize;
+
+    while let Some(instruction) = instructions.next() {
+        let executing = !exec_stack.contains(&false);
+        let instruction = instruction.map_err(|_| DogeBridgeError::ScriptBadOpcode)?;
+        if instruction.get_push_data().is_some_and(|data| data.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+            return Err(DogeBridgeError::ScriptPushSizeTooLarge);
+        }
+        // OP_RESERVED does not count towards the opcode limit
+        if instruction.get_opcode() > Opcode::OP_16 as u8 {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(DogeBridgeError::ScriptOpCountTooLarge);
+            }
+        }
+
+        let op = match instruction {
+            Instruction::Push { data, .. } => {
+                if executing {
+                    if require_minimal && !instruction.is_minimal_push() {
+                        return Err(DogeBridgeError::ScriptMinimalData);
+                    }
+                    stack.push(data.to_vec());
+                }
+                None
+            }
+            Instruction::Op(op) if op.is_disabled() => return Err(DogeBridgeError::ScriptDisabledOpcode),
+            Instruction::Op(Opcode::OP_0) => {
+                if executing {
+                    stack.push(Vec::new());
+                }
+                None
+            }
+            Instruction::Op(op) => Some(op),
+            Instruction::Unknown(_) => {
+                if executing {
+                    return Err(DogeBridgeError::ScriptBadOpcode);
+                }
+                None
+            }
+        };
+        let Some(op) = op else {
+            if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+                return Err(DogeBridgeError::ScriptStackSizeTooLarge);
+            }
+            continue;
+        };
+        let is_conditional = (Opcode::OP_IF as u8..=Opcode::OP_ENDIF as u8).contains(&(op as u8));
+        if !executing && !is_conditional {
+            continue;
+        }
+
+        match op {
+            Opcode::OP_1NEGATE => stack.push(encode_script_num(-1)),
+            _ if op.to_small_int().is_some() => stack.push(encode_script_num(op.to_small_int().unwrap_or_default() as i64)),
+
+            Opcode::OP_NOP => {}
+            Opcode::OP_CHECKLOCKTIMEVERIFY if flags & SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY != 0 => {
+                // the lock time is 5 bytes, since a 4 byte number would overflow in 2038
+                let lock_time = decode_script_num(stack_top(stack, 1)?, require_minimal, MAX_LOCKTIME_NUM_SIZE)?;
+                if lock_time < 0 {
+                    return Err(DogeBridgeError::ScriptNegativeLocktime);
+                }
+                if !checker.check_lock_time(lock_time) {
+                    return Err(DogeBridgeError::ScriptUnsatisfiedLocktime);
+                }
+            }
+            Opcode::OP_NOP1
+            | Opcode::OP_CHECKLOCKTIMEVERIFY
+            | Opcode::OP_CHECKSEQUENCEVERIFY
+            | Opcode::OP_NOP4
+            | Opcode::OP_NOP5
+            | Opcode::OP_NOP6
+            | Opcode::OP_NOP7
+            | Opcode::OP_NOP8
+            | Opcode::OP_NOP9
+            | Opcode::OP_NOP10 => {
+                if flags & SCRIPT_VERIFY_DISCOURAGE_UPGRADABLE_NOPS != 0 {
+                    return Err(DogeBridgeError::ScriptDiscourageUpgradableNops);
+                }
+            }
+
+            Opcode::OP_IF | Opcode::OP_NOTIF => {
+                let mut value = false;
+                if executing {
+                    let top = stack.pop().ok_or(DogeBridgeError::ScriptUnbalancedConditional)?;
+                    value = cast_to_bool(&top) != (op == Opcode::OP_NOTIF);
+                }
+                exec_stack.push(value);
+            }
+            Opcode::OP_ELSE => {
+                let last = exec_stack.last_mut().ok_or(DogeBridgeError::ScriptUnbalancedConditional)?;
+                *last = !*last;
+            }
+            Opcode::OP_ENDIF => {
+                exec_stack.pop().ok_or(DogeBridgeError::ScriptUnbalancedConditional)?;
+            }
+            Opcode::OP_VERIFY => {
+                if !cast_to_bool(stack_top(stack, 1)?) {
+                    return Err(DogeBridgeError::ScriptVerifyFailed);
+                }
+                stack.pop();
+            }
+            Opcode::OP_RETURN => return Err(DogeBridgeError::ScriptOpReturn),
+
+            Opcode::OP_TOALTSTACK => alt_stack.push(pop_stack(stack)?),
+            Opcode::OP_FROMALTSTACK => {
+                stack.push(alt_stack.pop().ok_or(DogeBridgeError::ScriptInvalidAltstackOperation)?)
+            }
+            Opcode::OP_2DROP => {
+                require_stack(stack, 2)?;
+                stack.truncate(stack.len() - 2);
+            }
+            Opcode::OP_2DUP => {
+                require_stack(stack, 2)?;
+                stack.extend_from_within(stack.len() - 2..);
+            }
+            Opcode::OP_3DUP => {
+                require_stack(stack, 3)?;
+                stack.extend_from_within(stack.len() - 3..);
+            }
+            Opcode::OP_2OVER => {
+                require_stack(stack, 4)?;
+                stack.extend_from_within(stack.len() - 4..stack.len() - 2);
+            }
+            Opcode::OP_2ROT => {
+                require_stack(stack, 6)?;
+                let start = stack.len() - 6;
+                stack[start..].rotate_left(2);
+            }
+            Opcode::OP_2SWAP => {
+                require_stack(stack, 4)?;
+                let start = stack.len() - 4;
+                stack[start..].rotate_left(2);
+            }
+            Opcode::OP_IFDUP => {
+                let top = stack_top(stack, 1)?;
+                if cast_to_bool(top) {
+                    stack.push(top.clone());
+                }
+            }
+            Opcode::OP_DEPTH => stack.push(encode_script_num(stack.len() as i64)),
+            Opcode::OP_DROP => {
+                pop_stack(stack)?;
+            }
+            Opcode::OP_DUP => stack.push(stack_top(stack, 1)?.clone()),
+            Opcode::OP_NIP => {
+                require_stack(stack, 2)?;
+                stack.remove(stack.len() - 2);
+            }
+            Opcode::OP_OVER => stack.push(stack_top(stack, 2)?.clone()),
+            Opcode::OP_PICK | Opcode::OP_ROLL => {
+                require_stack(stack, 2)?;
+                let n = pop_num(stack, require_minimal)?;
+                if n < 0 || n >= stack.len() as i64 {
+                    return Err(DogeBridgeError::ScriptInvalidStackOperation);
+                }
+                let index = stack.len() - 1 - n as usize;
+                let value = if op == Opcode::OP_ROLL { stack.remove(index) } else { stack[index].clone() };
+                stack.push(value);
+            }
+            Opcode::OP_ROT => {
+                require_stack(stack, 3)?;
+                let start = stack.len() - 3;
+                stack[start..].rotate_left(1);
+            }
+            Opcode::OP_SWAP => {
+                require_stack(stack, 2)?;
+                let len = stack.len();
+                stack.swap(len - 2, len - 1);
+            }
+            Opcode::OP_TUCK => {
+                require_stack(stack, 2)?;
+                let top = stack[stack.len() - 1].clone();
+                stack.insert(stack.len() - 2, top);
+            }
+            Opcode::OP_SIZE => stack.push(encode_script_num(stack_top(stack, 1)?.len() as i64)),
+
+            Opcode::OP_EQUAL | Opcode::OP_EQUALVERIFY => {
+                require_stack(stack, 2)?;
+                let b = pop_stack(stack)?;
+                let a = pop_stack(stack)?;
+                if op == Opcode::OP_EQUALVERIFY {
+                    if a != b {
+                        return Err(DogeBridgeError::ScriptEqualVerifyFailed);
+                    }
+                } else {
+                    push_bool(stack, a == b);
+                }
+            }
+
+            Opcode::OP_1ADD
+            | Opcode::OP_1SUB
+            | Opcode::OP_NEGATE
+            | Opcode::OP_ABS
+            | Opcode::OP_NOT
+            | Opcode::OP_0NOTEQUAL => {
+                require_stack(stack, 1)?;
+                let a = pop_num(stack, require_minimal)?;
+                let result = match op {
+                    Opcode::OP_1ADD => a + 1,
+                    Opcode::OP_1SUB => a - 1,
+                    Opcode::OP_NEGATE => -a,
+                    Opcode::OP_ABS => a.abs(),
+                    Opcode::OP_NOT => (a == 0) as i64,
+                    _ => (a != 0) as i64,
+                };
+                stack.push(encode_script_num(result));
+            }
+            Opcode::OP_ADD
+            | Opcode::OP_SUB
+            | Opcode::OP_BOOLAND
+            | Opcode::OP_BOOLOR
+            | Opcode::OP_NUMEQUAL
+            | Opcode::OP_NUMEQUALVERIFY
+            | Opcode::OP_NUMNOTEQUAL
+            | Opcode::OP_LESSTHAN
+            | Opcode::OP_GREATERTHAN
+            | Opcode::OP_LESSTHANOREQUAL
+            | Opcode::OP_GREATERTHANOREQUAL
+            | Opcode::OP_MIN
+            | Opcode::OP_MAX => {
+                require_stack(stack, 2)?;
+                let a = decode_script_num(stack_top(stack, 2)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                let b = decode_script_num(stack_top(stack, 1)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                stack.truncate(stack.len() - 2);
+                let result = match op {
+                    Opcode::OP_ADD => a + b,
+                    Opcode::OP_SUB => a - b,
+                    Opcode::OP_BOOLAND => (a != 0 && b != 0) as i64,
+                    Opcode::OP_BOOLOR => (a != 0 || b != 0) as i64,
+                    Opcode::OP_NUMEQUAL | Opcode::OP_NUMEQUALVERIFY => (a == b) as i64,
+                    Opcode::OP_NUMNOTEQUAL => (a != b) as i64,
+                    Opcode::OP_LESSTHAN => (a < b) as i64,
+                    Opcode::OP_GREATERTHAN => (a > b) as i64,
+                    Opcode::OP_LESSTHANOREQUAL => (a <= b) as i64,
+                    Opcode::OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                    Opcode::OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                if op == Opcode::OP_NUMEQUALVERIFY {
+                    if result == 0 {
+                        return Err(DogeBridgeError::ScriptNumEqualVerifyFailed);
+                    }
+                } else {
+                    stack.push(encode_script_num(result));
+                }
+            }
+            Opcode::OP_WITHIN => {
+                require_stack(stack, 3)?;
+                let x = decode_script_num(stack_top(stack, 3)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                let min = decode_script_num(stack_top(stack, 2)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                let max = decode_script_num(stack_top(stack, 1)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                stack.truncate(stack.len() - 3);
+                push_bool(stack, min <= x && x < max);
+            }
+
+            Opcode::OP_RIPEMD160 => {
+                let data = pop_stack(stack)?;
+                stack.push(hash_impl_ripemd160_bytes(&data).to_vec());
+            }
+            Opcode::OP_SHA1 => {
+                let data = pop_stack(stack)?;
+                stack.push(hash_impl_sha1_bytes(&data).to_vec());
+            }
+            Opcode::OP_SHA256 => {
+                let data = pop_stack(stack)?;
+                stack.push(hash_impl_sha256_bytes(&data).to_vec());
+            }
+            Opcode::OP_HASH160 => {
+                let data = pop_stack(stack)?;
+                stack.push(hash_impl_ripemd160_bytes(&hash_impl_sha256_bytes(&data)).to_vec());
+            }
+            Opcode::OP_HASH256 => {
+                let data = pop_stack(stack)?;
+                stack.push(hash_impl_sha256_bytes(&hash_impl_sha256_bytes(&data)).to_vec());
+            }
+            Opcode::OP_CODESEPARATOR => code_hash_start = instructions.get_offset(),
+
+            Opcode::OP_CHECKSIG | Opcode::OP_CHECKSIGVERIFY => {
+                require_stack(stack, 2)?;
+                let pubkey = pop_stack(stack)?;
+                let sig = pop_stack(stack)?;
+                // a signature can't sign itself, so it is removed from the signed script code
+                let script_code = find_and_delete(&script[code_hash_start..], ScriptBuilder::new().push_data_raw(&sig).as_bytes());
+                check_signature_encoding(&sig, flags)?;
+                check_pubkey_encoding(&pubkey, flags)?;
+                let success = checker.check_sig(&sig, &pubkey, &script_code);
+                if !success && flags & SCRIPT_VERIFY_NULLFAIL != 0 && !sig.is_empty() {
+                    return Err(DogeBridgeError::ScriptSigNullFail);
+                }
+                if op == Opcode::OP_CHECKSIGVERIFY {
+                    if !success {
+                        return Err(DogeBridgeError::ScriptCheckSigVerifyFailed);
+                    }
+                } else {
+                    push_bool(stack, success);
+                }
+            }
+            Opcode::OP_CHECKMULTISIG | Opcode::OP_CHECKMULTISIGVERIFY => {
+                let key_count = decode_script_num(stack_top(stack, 1)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                if key_count < 0 || key_count > MAX_PUBKEYS_PER_MULTISIG as i64 {
+                    return Err(DogeBridgeError::ScriptPubkeyCountOutOfRange);
+                }
+                let key_count = key_count as usize;
+                op_count += key_count;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    return Err(DogeBridgeError::ScriptOpCountTooLarge);
+                }
+                // depths from the top of the stack of the first key and the signature count
+                let first_key_depth = 2;
+                let sig_count_depth = first_key_depth + key_count;
+                let sig_count = decode_script_num(stack_top(stack, sig_count_depth)?, require_minimal, DEFAULT_MAX_SCRIPT_NUM_SIZE)?;
+                if sig_count < 0 || sig_count > key_count as i64 {
+                    return Err(DogeBridgeError::ScriptSigCountOutOfRange);
+                }
+                let sig_count = sig_count as usize;
+                let first_sig_depth = sig_count_depth + 1;
+                let arg_count = sig_count_depth + sig_count;
+                // the extra dummy element must also be present
+                require_stack(stack, arg_count + 1)?;
+
+                let mut script_code = script[code_hash_start..].to_vec();
+                for k in 0..sig_count {
+                    script_code = find_and_delete(&script_code, ScriptBuilder::new().push_data_raw(stack_top(stack, first_sig_depth + k)?).as_bytes());
+                }
+
+                let mut success = true;
+                let (mut sig_depth, mut key_depth) = (first_sig_depth, first_key_depth);
+                let (mut sigs_left, mut keys_left) = (sig_count, key_count);
+                while success && sigs_left > 0 {
+                    let sig = stack_top(stack, sig_depth)?;
+                    let pubkey = stack_top(stack, key_depth)?;
+                    check_signature_encoding(sig, flags)?;
+                    check_pubkey_encoding(pubkey, flags)?;
+                    // signatures must be in the same order as their keys
+                    if checker.check_sig(sig, pubkey, &script_code) {
+                        sig_depth += 1;
+                        sigs_left -= 1;
+                    }
+                    key_depth += 1;
+                    keys_left -= 1;
+                    if sigs_left > keys_left {
+                        success = false;
+                    }
+                }
+
+                // with NULLFAIL, every signature of a failed check must be empty
+                if !success
+                    && flags & SCRIPT_VERIFY_NULLFAIL != 0
+                    && stack[stack.len() - arg_count..stack.len() - sig_count_depth].iter().any(|sig| !sig.is_empty())
+                {
+                    return Err(DogeBridgeError::ScriptSigNullFail);
+                }
+                stack.truncate(stack.len() - arg_count);
+                // an off by one bug in the original implementation consumes an extra stack element
+                let dummy = pop_stack(stack)?;
+                if flags & SCRIPT_VERIFY_NULLDUMMY != 0 && !dummy.is_empty() {
+                    return Err(DogeBridgeError::ScriptSigNullDummy);
+                }
+                if op == Opcode::OP_CHECKMULTISIGVERIFY {
+                    if !success {
+                        return Err(DogeBridgeError::ScriptCheckMultisigVerifyFailed);
+                    }
+                } else {
+                    push_bool(stack, success);
+                }
+            }
+
+            _ => return Err(DogeBridgeError::ScriptBadOpcode),
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(DogeBridgeError::ScriptStackSizeTooLarge);
+        }
+    }
+
+    if !exec_stack.is_empty() {
+        return Err(DogeBridgeError::ScriptUnbalancedConditional);
+    }
+    Ok(())
+}
+
+/// Verifies a script sig against the output script it spends (`VerifyScript` without witness support).
+pub fn verify_script<C: SignatureChecker>(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    flags: u32,
+    checker: &C,
+) -> QDogeResult<()> {
+    if flags & SCRIPT_VERIFY_SIGPUSHONLY != 0 && !is_push_only(script_sig) {
+        return Err(DogeBridgeError::ScriptSigPushOnly);
+    }
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, checker)?;
+    let p2sh_stack = if flags & SCRIPT_VERIFY_P2SH != 0 { Some(stack.clone()) } else { None };
+    eval_script(&mut stack, script_pubkey, flags, checker)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return Err(DogeBridgeError::ScriptEvalFalse);
+    }
+
+    if let Some(p2sh_stack) = p2sh_stack.filter(|_| is_p2sh_script(script_pubkey)) {
+        if !is_push_only(script_sig) {
+            return Err(DogeBridgeError::ScriptSigPushOnly);
+        }
+        stack = p2sh_stack;
+        // the stack can't be empty, since the hash of the top element matched the P2SH output
+        let redeem_script = pop_stack(&mut stack)?;
+        eval_script(&mut stack, &redeem_script, flags, checker)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(DogeBridgeError::ScriptEvalFalse);
+        }
+    }
+
+    // only checked after P2SH evaluation, since the outer evaluation of a P2SH spend leaves the redeem arguments on the stack
+    if flags & SCRIPT_VERIFY_CLEANSTACK != 0 {
+        debug_assert!(flags & SCRIPT_VERIFY_P2SH != 0, "CLEANSTACK requires P2SH");
+        if stack.len() != 1 {
+            return Err(DogeBridgeError::ScriptCleanStack);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Message as BtcMessage, PublicKey as BtcPublicKey, Secp256k1, SecretKey};
+
+    use crate::doge::script::{builder::ScriptBuilder, opcodes::Opcode};
+    use crate::doge::transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput, SIGHASH_NONE};
+    use crate::error::{DogeBridgeError, QDogeResult};
+    use crate::hash::ripemd160_impl::hash_impl_ripemd160_bytes;
+    use crate::hash::sha256_impl::hash_impl_sha256_bytes;
+    use crate::logic::check_doge_block_transactions::check_transaction_err;
+    use crate::test_fixtures::{get_pubkey, multisig_script};
+
+    use super::*;
+
+    /// Parses the asm dialect of the bitcoin core test vectors (`ParseScript`).
+    fn parse_core_asm(asm: &str) -> Vec<u8> {
+        let mut builder = ScriptBuilder::new();
+        for token in asm.split_whitespace() {
+            builder = if let Ok(value) = token.parse::<i64>() {
+                builder.push_int(value)
+            } else if let Some(hex) = token.strip_prefix("0x") {
+                builder.append(&hex::decode(hex).unwrap())
+            } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+                builder.push_data_raw(&token.as_bytes()[1..token.len() - 1])
+            } else {
+                let name = if token.starts_with("OP_") { String::from(token) } else { alloc::format!("OP_{}", token) };
+                builder.push_opcode(Opcode::from_name(&name).unwrap_or_else(|| panic!("unknown opcode {}", token)))
+            };
+        }
+        builder.into_bytes()
+    }
+
+    /// Flags of the core vectors which are not implemented, vectors using them are skipped.
+    const SKIPPED_FLAGS: &[(&str, &str)] = &[
+        ("WITNESS", "segwit is not active on dogecoin"),
+        ("WITNESS_PUBKEYTYPE", "segwit is not active on dogecoin"),
+        ("DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM", "segwit is not active on dogecoin"),
+        ("MINIMALIF", "only applies to witness scripts"),
+        ("CHECKSEQUENCEVERIFY", "BIP112 is not implemented"),
+    ];
+
+    /// Parses the flags of a core vector, returning the first skipped flag instead if there is one.
+    fn parse_flags(flags: &str) -> Result<u32, &'static str> {
+        if let Some((_, reason)) = SKIPPED_FLAGS.iter().find(|(name, _)| flags.split(',').any(|flag| flag == *name)) {
+            return Err(reason);
+        }
+        Ok(flags.split(',').filter(|flag| !flag.is_empty()).fold(SCRIPT_VERIFY_NONE, |result, flag| {
+            result
+                | match flag {
+                    "NONE" => SCRIPT_VERIFY_NONE,
+                    "P2SH" => SCRIPT_VERIFY_P2SH,
+                    "STRICTENC" => SCRIPT_VERIFY_STRICTENC,
+                    "DERSIG" => SCRIPT_VERIFY_DERSIG,
+                    "LOW_S" => SCRIPT_VERIFY_LOW_S,
+                    "NULLDUMMY" => SCRIPT_VERIFY_NULLDUMMY,
+                    "SIGPUSHONLY" => SCRIPT_VERIFY_SIGPUSHONLY,
+                    "MINIMALDATA" => SCRIPT_VERIFY_MINIMALDATA,
+                    "DISCOURAGE_UPGRADABLE_NOPS" => SCRIPT_VERIFY_DISCOURAGE_UPGRADABLE_NOPS,
+                    "CLEANSTACK" => SCRIPT_VERIFY_CLEANSTACK,
+                    "CHECKLOCKTIMEVERIFY" => SCRIPT_VERIFY_CHECKLOCKTIMEVERIFY,
+                    "NULLFAIL" => SCRIPT_VERIFY_NULLFAIL,
+                    _ => panic!("unknown flag {}", flag),
+                }
+        }))
+    }
+
+    fn get_core_error_name(result: QDogeResult<()>) -> &'static str {
+        match result {
+            Ok(()) => "OK",
+            Err(DogeBridgeError::ScriptEvalFalse) => "EVAL_FALSE",
+            Err(DogeBridgeError::ScriptOpReturn) => "OP_RETURN",
+            Err(DogeBridgeError::ScriptSizeTooLarge) => "SCRIPT_SIZE",
+            Err(DogeBridgeError::ScriptPushSizeTooLarge) => "PUSH_SIZE",
+            Err(DogeBridgeError::ScriptOpCountTooLarge) => "OP_COUNT",
+            Err(DogeBridgeError::ScriptStackSizeTooLarge) => "STACK_SIZE",
+            Err(DogeBridgeError::ScriptSigCountOutOfRange) => "SIG_COUNT",
+            Err(DogeBridgeError::ScriptPubkeyCountOutOfRange) => "PUBKEY_COUNT",
+            Err(DogeBridgeError::ScriptVerifyFailed) => "VERIFY",
+            Err(DogeBridgeError::ScriptEqualVerifyFailed) => "EQUALVERIFY",
+            Err(DogeBridgeError::ScriptCheckMultisigVerifyFailed) => "CHECKMULTISIGVERIFY",
+            Err(DogeBridgeError::ScriptCheckSigVerifyFailed) => "CHECKSIGVERIFY",
+            Err(DogeBridgeError::ScriptNumEqualVerifyFailed) => "NUMEQUALVERIFY",
+            Err(DogeBridgeError::ScriptBadOpcode) => "BAD_OPCODE",
+            Err(DogeBridgeError::ScriptDisabledOpcode) => "DISABLED_OPCODE",
+            Err(DogeBridgeError::ScriptInvalidStackOperation) => "INVALID_STACK_OPERATION",
+            Err(DogeBridgeError::ScriptInvalidAltstackOperation) => "INVALID_ALTSTACK_OPERATION",
+            Err(DogeBridgeError::ScriptUnbalancedConditional) => "UNBALANCED_CONDITIONAL",
+            Err(DogeBridgeError::ScriptNegativeLocktime) => "NEGATIVE_LOCKTIME",
+            Err(DogeBridgeError::ScriptUnsatisfiedLocktime) => "UNSATISFIED_LOCKTIME",
+            Err(DogeBridgeError::ScriptSigHashType) => "SIG_HASHTYPE",
+            Err(DogeBridgeError::ScriptSigDer) => "SIG_DER",
+            Err(DogeBridgeError::ScriptMinimalData) => "MINIMALDATA",
+            Err(DogeBridgeError::ScriptSigPushOnly) => "SIG_PUSHONLY",
+            Err(DogeBridgeError::ScriptSigHighS) => "SIG_HIGH_S",
+            Err(DogeBridgeError::ScriptSigNullDummy) => "SIG_NULLDUMMY",
+            Err(DogeBridgeError::ScriptPubkeyType) => "PUBKEYTYPE",
+            Err(DogeBridgeError::ScriptCleanStack) => "CLEANSTACK",
+            Err(DogeBridgeError::ScriptSigNullFail) => "NULLFAIL",
+            Err(DogeBridgeError::ScriptDiscourageUpgradableNops) => "DISCOURAGE_UPGRADABLE_NOPS",
+            // number decoding throws scriptnum_error in dogecoin core, which is reported as an unknown error
+            Err(DogeBridgeError::ScriptNumOverflow | DogeBridgeError::ScriptNumNotMinimal) => "UNKNOWN_ERROR",
+            Err(error) => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    /// The spending transaction of the core test framework, which spends a single output paying to `script_pubkey`.
+    fn build_spending_tx(script_sig: &[u8], script_pubkey: &[u8]) -> BTCTransaction {
+        let credit_tx = BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput { hash: [0u8; 32], index: 0xffff_ffff, script: vec![0x00, 0x00], sequence: 0xffff_ffff }],
+            outputs: vec![BTCTransactionOutput { value: 0, script: script_pubkey.to_vec() }],
+            locktime: 0,
+        };
+        BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput { hash: credit_tx.get_hash(), index: 0, script: script_sig.to_vec(), sequence: 0xffff_ffff }],
+            outputs: vec![BTCTransactionOutput { value: 0, script: Vec::new() }],
+            locktime: 0,
+        }
+    }
+
+    fn read_core_vectors(name: &str) -> Vec<serde_json::Value> {
+        let path = alloc::format!("{}/../test_data/{}", env!("CARGO_MANIFEST_DIR"), name);
+        // the vectors are vendored unmodified from src/test/data of dogecoin core 1.14
+        let data = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
+        serde_json::from_str(&data).unwrap()
+    }
+
+    /// Runs the `script_tests.json` vectors of bitcoin/dogecoin core, in the upstream format:
+    /// `[scriptSig, scriptPubKey, flags, expected_scripterror, comment...]`, or with a leading `[witness..., amount]`.
+    #[test]
+    fn test_core_script_vectors() {
+        let (mut run, mut skipped) = (0, 0);
+        for vector in read_core_vectors("script_tests.json") {
+            let vector = vector.as_array().unwrap();
+            if vector.len() == 1 {
+                continue;
+            }
+            if vector[0].is_array() {
+                // witness vectors, segwit is not active on dogecoin
+                skipped += 1;
+                continue;
+            }
+            let fields: Vec<&str> = vector.iter().map(|x| x.as_str().unwrap()).collect();
+            let Ok(flags) = parse_flags(fields[2]) else {
+                skipped += 1;
+                continue;
+            };
+            let script_sig = parse_core_asm(fields[0]);
+            let script_pubkey = parse_core_asm(fields[1]);
+            let tx = build_spending_tx(&script_sig, &script_pubkey);
+            let result = tx.verify_input_script(0, &script_pubkey, flags);
+            assert_eq!(get_core_error_name(result), fields[3], "{:?}", fields);
+            run += 1;
+        }
+        assert!(run > 200, "ran {} script vectors, skipped {}", run, skipped);
+    }
+
+    /// Parses the prevouts of a `tx_valid.json`/`tx_invalid.json` vector: `[[prevout_hash, prevout_index, prevout_scriptPubKey(, amount)]...]`.
+    fn parse_core_prevouts(prevouts: &serde_json::Value) -> Vec<([u8; 32], u32, Vec<u8>)> {
+        prevouts
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|prevout| {
+                let prevout = prevout.as_array().unwrap();
+                // the hashes are in display (reversed) order
+                let mut hash: [u8; 32] = hex::decode(prevout[0].as_str().unwrap()).unwrap().try_into().unwrap();
+                hash.reverse();
+                let index = prevout[1].as_i64().unwrap() as u32;
+                (hash, index, parse_core_asm(prevout[2].as_str().unwrap()))
+            })
+            .collect()
+    }
+
+    /// Checks a `tx_valid.json`/`tx_invalid.json` vector, returning `None` if it is skipped for its flags or witness data.
+    fn check_core_tx_vector(vector: &[serde_json::Value]) -> Option<QDogeResult<()>> {
+        let prevouts = parse_core_prevouts(&vector[0]);
+        let tx_bytes = hex::decode(vector[1].as_str().unwrap()).unwrap();
+        let flags = parse_flags(vector[2].as_str().unwrap()).ok()?;
+        // a zero input count followed by the flag byte is the witness serialization
+        if tx_bytes.get(4) == Some(&0) && tx_bytes.get(5) == Some(&1) {
+            return None;
+        }
+        let tx = match BTCTransaction::from_bytes(&tx_bytes) {
+            Ok(tx) => tx,
+            Err(error) => return Some(Err(error)),
+        };
+        Some(check_transaction_err(&tx).and_then(|_| {
+            (0..tx.inputs.len()).try_for_each(|input_index| {
+                let input = &tx.inputs[input_index];
+                let prevout_script = prevouts
+                    .iter()
+                    .find(|(hash, index, _)| *hash == input.hash && *index == input.index)
+                    .map(|(_, _, script)| script)
+                    .ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)?;
+                tx.verify_input_script(input_index, prevout_script, flags)
+            })
+        }))
+    }
+
+    fn run_core_tx_vectors(name: &str, expect_valid: bool) {
+        let (mut run, mut skipped) = (0, 0);
+        for vector in read_core_vectors(name) {
+            let vector = vector.as_array().unwrap();
+            if vector.len() == 1 {
+                continue;
+            }
+            match check_core_tx_vector(vector) {
+                Some(result) => {
+                    assert_eq!(result.is_ok(), expect_valid, "{:?}: {:?}", vector, result);
+                    run += 1;
+                }
+                None => skipped += 1,
+            }
+        }
+        assert!(run > 0, "ran {} tx vectors, skipped {}", run, skipped);
+    }
+
+    #[test]
+    fn test_core_tx_valid_vectors() {
+        run_core_tx_vectors("tx_valid.json", true);
+    }
+
+    #[test]
+    fn test_core_tx_invalid_vectors() {
+        run_core_tx_vectors("tx_invalid.json", false);
+    }
+
+    /// Signs an input using the legacy sighash of the `bitcoin` crate.
+    fn sign_input(tx: &BTCTransaction, input_index: usize, script_code: &[u8], secret: &SecretKey, hash_type: u8) -> Vec<u8> {
+        let btc_tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&tx.to_bytes()).unwrap();
+        let sighash = bitcoin::sighash::SighashCache::new(&btc_tx)
+            .legacy_signature_hash(input_index, bitcoin::Script::from_bytes(script_code), hash_type as u32)
+            .unwrap();
+        let signature = Secp256k1::signing_only().sign_ecdsa(&BtcMessage::from_digest(sighash.to_byte_array()), secret);
+        let mut result = signature.serialize_der().to_vec();
+        result.push(hash_type);
+        result
+    }
+
+    fn p2pkh_script(pubkey: &[u8]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_opcode(Opcode::OP_DUP)
+            .push_opcode(Opcode::OP_HASH160)
+            .push_slice(&hash_impl_ripemd160_bytes(&hash_impl_sha256_bytes(pubkey)))
+            .push_opcode(Opcode::OP_EQUALVERIFY)
+            .push_opcode(Opcode::OP_CHECKSIG)
+            .into_bytes()
+    }
+
+    fn p2sh_script(redeem_script: &[u8]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_opcode(Opcode::OP_HASH160)
+            .push_slice(&hash_impl_ripemd160_bytes(&hash_impl_sha256_bytes(redeem_script)))
+            .push_opcode(Opcode::OP_EQUAL)
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_verify_signed_spends() {
+        let keys: Vec<SecretKey> = (1u8..=4).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect();
+        let pubkeys: Vec<Vec<u8>> = keys.iter().map(get_pubkey).collect();
+        let uncompressed_pubkey = BtcPublicKey::from_secret_key(&Secp256k1::signing_only(), &keys[3]).serialize_uncompressed();
+        let redeem_script = multisig_script(2, &pubkeys[..3]);
+        let prevout_scripts = [p2pkh_script(&pubkeys[0]), p2sh_script(&redeem_script), p2pkh_script(&uncompressed_pubkey)];
+
+        let mut tx = BTCTransaction {
+            version: 1,
+            inputs: (0..3u8)
+                .map(|i| BTCTransactionInput { hash: [i + 1; 32], index: i as u32, script: Vec::new(), sequence: 0xffff_ffff })
+                .collect(),
+            outputs: (0..3u64).map(|i| BTCTransactionOutput { value: 1000 * (i + 1), script: p2pkh_script(&pubkeys[i as usize]) }).collect(),
+            locktime: 0,
+        };
+        let sig_0 = sign_input(&tx, 0, &prevout_scripts[0], &keys[0], SIGHASH_ALL);
+        let sig_1a = sign_input(&tx, 1, &redeem_script, &keys[0], SIGHASH_ALL | SIGHASH_ANYONECANPAY);
+        let sig_1b = sign_input(&tx, 1, &redeem_script, &keys[2], SIGHASH_SINGLE);
+        let sig_2 = sign_input(&tx, 2, &prevout_scripts[2], &keys[3], SIGHASH_NONE);
+        tx.inputs[0].script = ScriptBuilder::new().push_slice(&sig_0).push_slice(&pubkeys[0]).into_bytes();
+        tx.inputs[1].script = ScriptBuilder::new()
+            .push_int(0)
+            .push_slice(&sig_1a)
+            .push_slice(&sig_1b)
+            .push_slice(&redeem_script)
+            .into_bytes();
+        tx.inputs[2].script = ScriptBuilder::new().push_slice(&sig_2).push_slice(&uncompressed_pubkey).into_bytes();
+
+        for (i, prevout_script) in prevout_scripts.iter().enumerate() {
+            assert_eq!(tx.verify_input_script(i, prevout_script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Ok(()));
+            assert_eq!(tx.verify_input_script(i, prevout_script, DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS), Ok(()));
+        }
+        assert_eq!(
+            tx.verify_input_script(3, &prevout_scripts[0], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptInputIndexOutOfRange)
+        );
+        assert_eq!(
+            tx.verify_input_script(0, &prevout_scripts[2], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptEqualVerifyFailed)
+        );
+
+        // SIGHASH_ALL commits to every output, SIGHASH_NONE to none of them
+        let mut tampered = tx.clone();
+        tampered.outputs[2].value += 1;
+        assert_eq!(
+            tampered.verify_input_script(0, &prevout_scripts[0], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigNullFail)
+        );
+        assert_eq!(
+            tampered.verify_input_script(0, &prevout_scripts[0], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptEvalFalse)
+        );
+        assert!(tampered.verify_input_script(1, &prevout_scripts[1], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS).is_err());
+        assert_eq!(tampered.verify_input_script(2, &prevout_scripts[2], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Ok(()));
+
+        // multisig signatures must be in the order of their keys
+        let mut swapped = tx.clone();
+        swapped.inputs[1].script = ScriptBuilder::new()
+            .push_int(0)
+            .push_slice(&sig_1b)
+            .push_slice(&sig_1a)
+            .push_slice(&redeem_script)
+            .into_bytes();
+        assert_eq!(
+            swapped.verify_input_script(1, &prevout_scripts[1], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigNullFail)
+        );
+        assert_eq!(
+            swapped.verify_input_script(1, &prevout_scripts[1], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptEvalFalse)
+        );
+        // the redeem script is only evaluated with P2SH
+        assert_eq!(swapped.verify_input_script(1, &prevout_scripts[1], SCRIPT_VERIFY_NONE), Ok(()));
+
+        let mut non_null_dummy = tx.clone();
+        non_null_dummy.inputs[1].script[0] = Opcode::OP_1 as u8;
+        assert_eq!(
+            non_null_dummy.verify_input_script(1, &prevout_scripts[1], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigNullDummy)
+        );
+        assert_eq!(non_null_dummy.verify_input_script(1, &prevout_scripts[1], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS), Ok(()));
+
+        // LOW_S is only enforced by policy
+        let mut high_s = Signature::parse_der(&sig_0[..sig_0.len() - 1]).unwrap();
+        high_s.s = -high_s.s;
+        let mut high_s_sig = high_s.serialize_der().as_ref().to_vec();
+        high_s_sig.push(SIGHASH_ALL);
+        let mut high_s_tx = tx.clone();
+        high_s_tx.inputs[0].script = ScriptBuilder::new().push_slice(&high_s_sig).push_slice(&pubkeys[0]).into_bytes();
+        assert_eq!(
+            high_s_tx.verify_input_script(0, &prevout_scripts[0], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigHighS)
+        );
+        assert_eq!(high_s_tx.verify_input_script(0, &prevout_scripts[0], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS), Ok(()));
+
+        // BIP66 rejects the padded R value, which is accepted by the lax parser without DERSIG
+        let len_r = sig_0[3] as usize;
+        let mut padded_sig = vec![0x30, sig_0[1] + 1, 0x02, sig_0[3] + 1, 0x00];
+        padded_sig.extend_from_slice(&sig_0[4..]);
+        assert_eq!(padded_sig.len(), sig_0.len() + 1);
+        assert_eq!(padded_sig[5 + len_r], 0x02);
+        let mut padded_tx = tx.clone();
+        padded_tx.inputs[0].script = ScriptBuilder::new().push_slice(&padded_sig).push_slice(&pubkeys[0]).into_bytes();
+        assert_eq!(
+            padded_tx.verify_input_script(0, &prevout_scripts[0], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigDer)
+        );
+        assert_eq!(padded_tx.verify_input_script(0, &prevout_scripts[0], SCRIPT_VERIFY_P2SH), Ok(()));
+
+        // undefined sighash types sign like SIGHASH_ALL but are non standard
+        let mut undefined_type_tx = tx.clone();
+        let undefined_type_sig = sign_input(&tx, 0, &prevout_scripts[0], &keys[0], 0x04);
+        undefined_type_tx.inputs[0].script = ScriptBuilder::new().push_slice(&undefined_type_sig).push_slice(&pubkeys[0]).into_bytes();
+        assert_eq!(
+            undefined_type_tx.verify_input_script(0, &prevout_scripts[0], DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptSigHashType)
+        );
+        assert_eq!(undefined_type_tx.verify_input_script(0, &prevout_scripts[0], DOGE_MANDATORY_SCRIPT_VERIFY_FLAGS), Ok(()));
+    }
+
+    #[test]
+    fn test_check_lock_time_verify() {
+        let cltv_script = |lock_time: i64| {
+            ScriptBuilder::new()
+                .push_int(lock_time)
+                .push_opcode(Opcode::OP_CHECKLOCKTIMEVERIFY)
+                .push_opcode(Opcode::OP_DROP)
+                .push_int(1)
+                .into_bytes()
+        };
+        let mut tx = build_spending_tx(&[], &[]);
+        tx.locktime = 1000;
+        tx.inputs[0].sequence = 0xffff_fffe;
+        let verify = |tx: &BTCTransaction, lock_time: i64, flags: u32| tx.verify_input_script(0, &cltv_script(lock_time), flags);
+
+        assert_eq!(verify(&tx, 999, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Ok(()));
+        assert_eq!(verify(&tx, 1000, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Ok(()));
+        assert_eq!(verify(&tx, 1001, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Err(DogeBridgeError::ScriptUnsatisfiedLocktime));
+        assert_eq!(
+            verify(&tx, LOCKTIME_THRESHOLD, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptUnsatisfiedLocktime)
+        );
+        assert_eq!(verify(&tx, 1001, SCRIPT_VERIFY_P2SH), Ok(()));
+
+        tx.locktime = LOCKTIME_THRESHOLD as u32 + 100;
+        assert_eq!(verify(&tx, LOCKTIME_THRESHOLD + 100, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Ok(()));
+        assert_eq!(verify(&tx, 100, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), Err(DogeBridgeError::ScriptUnsatisfiedLocktime));
+
+        tx.inputs[0].sequence = 0xffff_ffff;
+        assert_eq!(
+            verify(&tx, LOCKTIME_THRESHOLD + 100, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS),
+            Err(DogeBridgeError::ScriptUnsatisfiedLocktime)
+        );
+    }
+
+    #[test]
+    fn test_script_limits() {
+        let eval = |script: &[u8]| {
+            let mut stack = Vec::new();
+            eval_script(&mut stack, script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS, &NoSignatureChecker).map(|_| stack.len())
+        };
+        let push = |len: usize| ScriptBuilder::new().push_data_raw(&vec![0x42; len]).into_bytes();
+        assert_eq!(eval(&push(MAX_SCRIPT_ELEMENT_SIZE)), Ok(1));
+        assert_eq!(eval(&push(MAX_SCRIPT_ELEMENT_SIZE + 1)), Err(DogeBridgeError::ScriptPushSizeTooLarge));
+
+        assert_eq!(eval(&vec![Opcode::OP_NOP as u8; MAX_OPS_PER_SCRIPT]), Ok(0));
+        assert_eq!(eval(&vec![Opcode::OP_NOP as u8; MAX_OPS_PER_SCRIPT + 1]), Err(DogeBridgeError::ScriptOpCountTooLarge));
+        // the keys of a CHECKMULTISIG count towards the opcode limit
+        let multisig_with_nops = |nop_count: usize| {
+            let mut script = vec![Opcode::OP_NOP as u8; nop_count];
+            script.extend_from_slice(&[Opcode::OP_0 as u8; 22]);
+            script.extend_from_slice(&ScriptBuilder::new().push_int(20).push_opcode(Opcode::OP_CHECKMULTISIG).into_bytes());
+            script
+        };
+        assert_eq!(eval(&multisig_with_nops(MAX_OPS_PER_SCRIPT - 21)), Ok(1));
+        assert_eq!(eval(&multisig_with_nops(MAX_OPS_PER_SCRIPT - 20)), Err(DogeBridgeError::ScriptOpCountTooLarge));
+
+        assert_eq!(eval(&vec![Opcode::OP_1 as u8; MAX_STACK_SIZE]), Ok(MAX_STACK_SIZE));
+        assert_eq!(eval(&vec![Opcode::OP_1 as u8; MAX_STACK_SIZE + 1]), Err(DogeBridgeError::ScriptStackSizeTooLarge));
+        let mut altstack_script = ScriptBuilder::new().push_int(1).push_opcode(Opcode::OP_TOALTSTACK).into_bytes();
+        altstack_script.extend_from_slice(&vec![Opcode::OP_1 as u8; MAX_STACK_SIZE]);
+        assert_eq!(eval(&altstack_script), Err(DogeBridgeError::ScriptStackSizeTooLarge));
+
+        let mut max_size_script = push(500).repeat(19);
+        max_size_script.extend_from_slice(&push(MAX_SCRIPT_SIZE - max_size_script.len() - 3));
+        assert_eq!(max_size_script.len(), MAX_SCRIPT_SIZE);
+        assert_eq!(eval(&max_size_script), Ok(20));
+        max_size_script.push(Opcode::OP_NOP as u8);
+        assert_eq!(eval(&max_size_script), Err(DogeBridgeError::ScriptSizeTooLarge));
+    }
+
+    #[test]
+    fn test_find_and_delete() {
+        // cases from script_FindAndDelete in bitcoin core
+        let cases: [(&str, &str, &str); 13] = [
+            ("0302ff03", "0302ff03", ""),
+            ("0302ff030302ff03", "0302ff03", ""),
+            ("0302ff030302ff03", "02", "0302ff030302ff03"),
+            ("0302ff030302ff03", "ff", "0302ff030302ff03"),
+            // the pattern is only matched at instruction boundaries, which moves after each match
+            ("0302ff030302ff03", "03", "02ff0302ff03"),
+            ("02feed5169", "feed51", "02feed5169"),
+            ("02feed5169", "02feed51", "69"),
+            ("516902feed5169", "feed51", "516902feed5169"),
+            ("516902feed5169", "02feed51", "516969"),
+            ("00005151", "0051", "0051"),
+            ("000051005151", "0051", "0051"),
+            // a truncated push at the end is kept
+            ("0003feed", "03feed", "00"),
+            ("0003feed", "00", "03feed"),
+        ];
+        for (script, pattern, expected) in cases {
+            assert_eq!(
+                hex::encode(find_and_delete(&hex::decode(script).unwrap(), &hex::decode(pattern).unwrap())),
+                expected,
+                "FindAndDelete({}, {})",
+                script,
+                pattern
+            );
+        }
+    }
+}
@@ -0,0 +1,176 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+pub mod opcodes;
+pub mod instruction;
+pub mod num;
+pub mod builder;
+pub mod asm;
+#[cfg(feature = "script-verify")]
+pub mod interpreter;
+
+use alloc::vec::Vec;
+
+use instruction::{Instruction, Instructions};
+use opcodes::Opcode;
+
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Lock times below this value are block heights, lock times at or above it are unix timestamps.
+pub const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+
+pub fn instructions(script: &[u8]) -> Instructions<'_> {
+    Instructions::new(script)
+}
+
+/// Whether the script only contains pushes (data pushes and opcodes up to OP_16), as required for P2SH script sigs.
+pub fn is_push_only(script: &[u8]) -> bool {
+    instructions(script).all(|instruction| instruction.is_ok_and(|instruction| instruction.is_push()))
+}
+
+/// Parses an m-of-n multisig script (`OP_m <pubkey_1> ... <pubkey_n> OP_n OP_CHECKMULTISIG`), returning m and the public keys.
+pub fn parse_multisig_script(script: &[u8]) -> Option<(usize, Vec<&[u8]>)> {
+    let instructions: Vec<Instruction> = instructions(script).collect::<Result<_, _>>().ok()?;
+    let [Instruction::Op(required), keys @ .., Instruction::Op(total), Instruction::Op(Opcode::OP_CHECKMULTISIG)] = instructions.as_slice() else {
+        return None;
+    };
+    let required = required.to_small_int()? as usize;
+    let total = total.to_small_int()? as usize;
+    let pubkeys: Vec<&[u8]> = keys.iter().map(|x| x.get_push_data()).collect::<Option<_>>()?;
+    if required == 0 || required > total || pubkeys.len() != total {
+        return None;
+    }
+    Some((required, pubkeys))
+}
+
+/// Whether every push in the script uses its minimal encoding.
+pub fn has_only_minimal_pushes(script: &[u8]) -> bool {
+    instructions(script).all(|instruction| instruction.is_ok_and(|instruction| instruction.is_minimal_push()))
+}
+
+/// Counts the signature operations of a script, equivalent to `GetSigOpCount(accurate)` in dogecoin core.
+///
+/// With `accurate`, a CHECKMULTISIG preceded by OP_1..=OP_16 counts that many keys instead of 20.
+/// Counting stops at the first malformed push, matching the reference implementation.
+pub fn get_script_sig_op_count(script: &[u8], accurate: bool) -> usize {
+    let mut count = 0usize;
+    let mut last_op = None;
+    for instruction in instructions(script) {
+        let Ok(instruction) = instruction else {
+            break;
+        };
+        if let Instruction::Op(op) = instruction {
+            match op {
+                Opcode::OP_CHECKSIG | Opcode::OP_CHECKSIGVERIFY => count += 1,
+                Opcode::OP_CHECKMULTISIG | Opcode::OP_CHECKMULTISIGVERIFY => {
+                    count += match last_op.and_then(|last: Opcode| last.to_small_int()) {
+                        Some(keys) if accurate && keys > 0 => keys as usize,
+                        _ => MAX_PUBKEYS_PER_MULTISIG,
+                    }
+                }
+                _ => {}
+            }
+            last_op = Some(op);
+        } else {
+            last_op = None;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::error::DogeBridgeError;
+
+    use super::{
+        asm::{script_from_asm, script_to_asm},
+        builder::ScriptBuilder,
+        get_script_sig_op_count, has_only_minimal_pushes,
+        instruction::Instruction,
+        instructions, is_push_only,
+        num::{decode_script_num, encode_script_num},
+        opcodes::Opcode,
+    };
+
+    #[test]
+    fn test_builder_matches_bitcoin_builder() {
+        for value in [-129i64, -128, -1, 0, 1, 16, 17, 127, 128, 255, 256, 0x7fffffff, -0x7fffffff, 0x8000000000] {
+            let expected = bitcoin::script::Builder::new().push_int(value).into_bytes();
+            assert_eq!(ScriptBuilder::new().push_int(value).into_bytes(), expected, "push_int({})", value);
+            assert_eq!(decode_script_num(&encode_script_num(value), true, 8), Ok(value));
+        }
+        for len in [0usize, 1, 75, 76, 255, 256, 65535, 65536] {
+            let data = vec![0xaau8; len];
+            let push_bytes: &bitcoin::script::PushBytes = data.as_slice().try_into().unwrap();
+            let expected = bitcoin::script::Builder::new().push_slice(push_bytes).into_bytes();
+            assert_eq!(ScriptBuilder::new().push_data_raw(&data).into_bytes(), expected, "push of {} bytes", len);
+        }
+        assert_eq!(ScriptBuilder::new().push_slice(&[]).push_slice(&[5]).push_slice(&[0x81]).into_bytes(), vec![0x00, 0x55, 0x4f]);
+    }
+
+    #[test]
+    fn test_instructions_and_asm() {
+        let pubkey_hash = [0x11u8; 20];
+        let p2pkh = ScriptBuilder::new()
+            .push_opcode(Opcode::OP_DUP)
+            .push_opcode(Opcode::OP_HASH160)
+            .push_slice(&pubkey_hash)
+            .push_opcode(Opcode::OP_EQUALVERIFY)
+            .push_opcode(Opcode::OP_CHECKSIG)
+            .into_bytes();
+        assert_eq!(p2pkh, crate::doge::address::gen_p2pkh_script(&pubkey_hash).to_vec());
+        let asm = script_to_asm(&p2pkh);
+        assert_eq!(asm, "OP_DUP OP_HASH160 1111111111111111111111111111111111111111 OP_EQUALVERIFY OP_CHECKSIG");
+        assert_eq!(script_from_asm(&asm), Ok(p2pkh.clone()));
+        assert_eq!(get_script_sig_op_count(&p2pkh, false), 1);
+
+        // non minimal pushes and unknown opcodes round trip exactly
+        let script = vec![0x4c, 0x01, 0x05, 0x01, 0x05, 0x4d, 0x00, 0x00, 0x52, 0x53, 0xae, 0xba, 0x00];
+        let asm = script_to_asm(&script);
+        assert_eq!(asm, "OP_PUSHDATA1 0x05 05 OP_PUSHDATA2 0x OP_2 OP_3 OP_CHECKMULTISIG OP_UNKNOWN_0xba OP_0");
+        assert_eq!(script_from_asm(&asm), Ok(script.clone()));
+        assert_eq!(get_script_sig_op_count(&script, false), 20);
+        assert_eq!(get_script_sig_op_count(&script, true), 3);
+        assert!(!has_only_minimal_pushes(&script));
+        assert!(!is_push_only(&script));
+        assert!(is_push_only(&script[..10]));
+
+        let ins = instructions(&script).collect::<Result<vec::Vec<_>, _>>().unwrap();
+        assert_eq!(ins[0], Instruction::Push { opcode: 0x4c, data: &[0x05] });
+        assert!(!ins[0].is_minimal_push() && !ins[1].is_minimal_push());
+        assert_eq!(ins[3], Instruction::Op(Opcode::OP_2));
+
+        // truncated push
+        let truncated = [0x76, 0x4c, 0x05, 0x01];
+        assert_eq!(instructions(&truncated).nth(1), Some(Err(DogeBridgeError::ScriptPushOutOfBounds)));
+        assert_eq!(instructions(&truncated).count(), 2);
+        assert_eq!(script_to_asm(&truncated), "OP_DUP [error]");
+        assert_eq!(script_from_asm("OP_DUP zz"), Err(DogeBridgeError::InvalidScriptAsm));
+        assert_eq!(script_from_asm("OP_FALSE OP_NOP2"), Ok(vec![0x00, 0xb1]));
+    }
+}
@@ -0,0 +1,86 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use alloc::vec::Vec;
+
+use crate::error::{DogeBridgeError, QDogeResult};
+
+/// Default maximum size of a number operand, larger numbers may be produced but not consumed by arithmetic opcodes.
+pub const DEFAULT_MAX_SCRIPT_NUM_SIZE: usize = 4;
+
+/// Encodes a number as a little endian, sign magnitude, minimal byte vector (`CScriptNum::serialize`).
+pub fn encode_script_num(value: i64) -> Vec<u8> {
+    if value == 0 {
+        return Vec::new();
+    }
+    let negative = value < 0;
+    let mut abs = value.unsigned_abs();
+    let mut result = Vec::with_capacity(9);
+    while abs != 0 {
+        result.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    let last = result.len() - 1;
+    if result[last] & 0x80 != 0 {
+        result.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        result[last] |= 0x80;
+    }
+    result
+}
+
+/// Whether a number is encoded without unnecessary trailing zero bytes.
+pub fn is_minimal_script_num(data: &[u8]) -> bool {
+    match data {
+        [] => true,
+        [.., last] if last & 0x7f != 0 => true,
+        [_] => false,
+        [.., second_last, _] => second_last & 0x80 != 0,
+    }
+}
+
+/// Decodes a number operand of at most `max_size` bytes (`CScriptNum(vch, fRequireMinimal, nMaxNumSize)`).
+pub fn decode_script_num(data: &[u8], require_minimal: bool, max_size: usize) -> QDogeResult<i64> {
+    if data.len() > max_size.min(8) {
+        return Err(DogeBridgeError::ScriptNumOverflow);
+    }
+    if require_minimal && !is_minimal_script_num(data) {
+        return Err(DogeBridgeError::ScriptNumNotMinimal);
+    }
+    if data.is_empty() {
+        return Ok(0);
+    }
+    let mut result = 0i64;
+    for (i, b) in data.iter().enumerate() {
+        result |= (*b as i64) << (8 * i);
+    }
+    let sign_bit = 0x80i64 << (8 * (data.len() - 1));
+    if result & sign_bit != 0 {
+        Ok(-(result & !sign_bit))
+    } else {
+        Ok(result)
+    }
+}
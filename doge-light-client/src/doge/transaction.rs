@@ -24,6 +24,7 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+#[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
 
 #[cfg(feature = "serde")]
@@ -31,13 +32,57 @@ use serde::{Deserialize, Serialize};
 //use bitcoin::consensus::{deserialize_partial, serialize};
 //use bitcoin::VarInt;
 
+use alloc::{vec, vec::Vec};
+
+use crate::constants::DogeNetworkConfig;
 use crate::core_data::QHash256;
+use crate::error::{DogeBridgeError, QDogeResult};
 use crate::hash::sha256::QBTCHash256Hasher;
 use crate::hash::traits::BytesHasher;
 
 use super::address::{AddressToBTCScript, BTCAddress160};
+use super::script::instruction::Instruction;
+use super::script::instructions;
+#[cfg(feature = "script-verify")]
+use super::script::interpreter::{verify_script, TransactionSignatureChecker};
+use super::script::opcodes::Opcode;
 use super::varuint::{decode_varuint_partial, encode_varuint, varuint_size};
 
+pub const SIGHASH_ALL: u8 = 1;
+pub const SIGHASH_NONE: u8 = 2;
+pub const SIGHASH_SINGLE: u8 = 3;
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Decodes the block height pushed at the start of a coinbase script.
+///
+/// Only the encoding produced by `CScript() << nHeight` is accepted (OP_0, OP_1..OP_16 or a minimal positive CScriptNum),
+/// since consensus checks that the script starts with exactly those bytes.
+pub fn decode_bip34_height(coinbase_script: &[u8]) -> QDogeResult<u32> {
+    match coinbase_script.first() {
+        None => Err(DogeBridgeError::CoinbaseHeightMissing),
+        Some(0x00) => Ok(0),
+        Some(&op) if (0x51..=0x60).contains(&op) => Ok((op - 0x50) as u32),
+        Some(&push_len) if (0x01..=0x05).contains(&push_len) => {
+            let push_len = push_len as usize;
+            if coinbase_script.len() < 1 + push_len {
+                return Err(DogeBridgeError::CoinbaseHeightMissing);
+            }
+            let num_bytes = &coinbase_script[1..1 + push_len];
+            let last = num_bytes[push_len - 1];
+            // the sign bit must be clear and the most significant byte may only be zero if it holds the sign bit
+            if last & 0x80 != 0 || (last == 0 && (push_len == 1 || num_bytes[push_len - 2] & 0x80 == 0)) {
+                return Err(DogeBridgeError::InvalidCoinbaseHeightEncoding);
+            }
+            let height = num_bytes.iter().rev().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            if height > u32::MAX as u64 || (push_len == 1 && height <= 16) {
+                return Err(DogeBridgeError::InvalidCoinbaseHeightEncoding);
+            }
+            Ok(height as u32)
+        }
+        Some(_) => Err(DogeBridgeError::CoinbaseHeightMissing),
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[derive(PartialEq, Clone, Debug, Eq, Ord, PartialOrd)]
@@ -132,6 +177,31 @@ impl BTCTransaction {
     pub fn is_dummy(&self) -> bool {
         self.inputs.len() == 0 && self.outputs.len() == 0
     }
+    /// A coinbase transaction has exactly one input, which spends the null outpoint.
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].is_null_outpoint()
+    }
+    pub fn get_coinbase_script(&self) -> Option<&[u8]> {
+        if self.is_coinbase() {
+            Some(&self.inputs[0].script)
+        } else {
+            None
+        }
+    }
+    /// Decodes the block height serialized at the start of the coinbase script (BIP34).
+    pub fn get_bip34_height(&self) -> QDogeResult<u32> {
+        decode_bip34_height(self.get_coinbase_script().ok_or(DogeBridgeError::TransactionNotCoinbase)?)
+    }
+    /// Checks the coinbase commits to `height` if BIP34 is active at `height` on the network.
+    pub fn check_bip34_height<NC: DogeNetworkConfig>(&self, height: u32) -> QDogeResult<()> {
+        if !self.is_coinbase() {
+            return Err(DogeBridgeError::TransactionNotCoinbase);
+        }
+        if height >= NC::NETWORK_PARAMS.bip34_height && self.get_bip34_height()? != height {
+            return Err(DogeBridgeError::MismatchedCoinbaseHeight);
+        }
+        Ok(())
+    }
     pub fn get_vouts_for_address(&self, address: &BTCAddress160) -> Vec<u32> {
         let address_script = address.to_btc_script();
         self.outputs
@@ -220,9 +290,9 @@ impl BTCTransaction {
         bytes: &[u8],
         start_offset: usize,
         output_index: usize,
-    ) -> anyhow::Result<(u32, u32, BTCTransactionOutput)> {
+    ) -> QDogeResult<(u32, u32, BTCTransactionOutput)> {
         if bytes.len() - start_offset < (32 + 4 + 4 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length, too small"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = start_offset;
 
@@ -230,7 +300,7 @@ impl BTCTransaction {
         read_index += 4;
 
         let inputs_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += inputs_len.1;
 
         let inputs_size = inputs_len.0 as usize;
@@ -242,12 +312,12 @@ impl BTCTransaction {
         }
 
         let outputs_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
 
         read_index += outputs_len.1;
         let outputs_size = outputs_len.0 as usize;
         if output_index >= outputs_size {
-            return Err(anyhow::anyhow!("Invalid output index"));
+            return Err(DogeBridgeError::TransactionOutputIndexOutOfRange);
         }
 
         for _ in 0..output_index {
@@ -264,11 +334,7 @@ impl BTCTransaction {
         read_index += 4;
 
         if read_index - start_offset != bytes.len() {
-            return Err(anyhow::anyhow!(
-                "Invalid bytes length, too large, {}, {}",
-                read_index - start_offset,
-                bytes.len()
-            ));
+            return Err(DogeBridgeError::TransactionDataTooLong);
         }
 
         Ok((version, locktime, output))
@@ -289,9 +355,9 @@ impl BTCTransaction {
         bytes.extend(self.locktime.to_le_bytes());
         bytes
     }
-    pub fn from_bytes_offset(bytes: &[u8], offset: usize) -> anyhow::Result<(Self, usize)> {
+    pub fn from_bytes_offset(bytes: &[u8], offset: usize) -> QDogeResult<(Self, usize)> {
         if bytes.len() - offset < (32 + 4 + 4 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = offset;
 
@@ -299,7 +365,7 @@ impl BTCTransaction {
         read_index += 4;
 
         let inputs_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += inputs_len.1;
 
         let inputs_size = inputs_len.0 as usize;
@@ -311,7 +377,7 @@ impl BTCTransaction {
         }
 
         let outputs_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
 
         read_index += outputs_len.1;
 
@@ -334,15 +400,87 @@ impl BTCTransaction {
             read_index + 4,
         ))
     }
-    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_bytes(bytes: &[u8]) -> QDogeResult<Self> {
         let (tx, _) = Self::from_bytes_offset(bytes, 0)?;
         Ok(tx)
     }
     pub fn get_hash(&self) -> QHash256 {
         QBTCHash256Hasher::hash_bytes(&self.to_bytes())
     }
+    /// Legacy signature hash of an input, equivalent to `SignatureHash` in dogecoin core.
+    ///
+    /// The OP_CODESEPARATORs of `script_code` are removed before hashing. An out of range `input_index`, or
+    /// SIGHASH_SINGLE without an output at `input_index`, returns the number one instead of failing, as in
+    /// the reference implementation (signatures over it are valid).
+    pub fn signature_hash(&self, input_index: usize, script_code: &[u8], sighash_type: u32) -> QHash256 {
+        let base_type = (sighash_type & 0x1f) as u8;
+        if input_index >= self.inputs.len() || (base_type == SIGHASH_SINGLE && input_index >= self.outputs.len()) {
+            let mut one = [0u8; 32];
+            one[0] = 1;
+            return one;
+        }
+        let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY as u32 != 0;
+
+        let mut inputs = Vec::with_capacity(if anyone_can_pay { 1 } else { self.inputs.len() });
+        for (i, input) in self.inputs.iter().enumerate() {
+            if i == input_index {
+                inputs.push(BTCTransactionInput {
+                    script: remove_code_separators(script_code),
+                    ..input.clone()
+                });
+            } else if !anyone_can_pay {
+                let zero_sequence = base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE;
+                inputs.push(BTCTransactionInput {
+                    hash: input.hash,
+                    index: input.index,
+                    script: Vec::new(),
+                    sequence: if zero_sequence { 0 } else { input.sequence },
+                });
+            }
+        }
+        let outputs = match base_type {
+            SIGHASH_NONE => Vec::new(),
+            SIGHASH_SINGLE => {
+                // outputs before the signed one are replaced by null outputs (value -1 and an empty script)
+                let mut outputs = vec![BTCTransactionOutput { value: u64::MAX, script: Vec::new() }; input_index];
+                outputs.push(self.outputs[input_index].clone());
+                outputs
+            }
+            _ => self.outputs.clone(),
+        };
+
+        let mut preimage = Self { version: self.version, inputs, outputs, locktime: self.locktime }.to_bytes();
+        preimage.extend_from_slice(&sighash_type.to_le_bytes());
+        QBTCHash256Hasher::hash_bytes(&preimage)
+    }
+    /// Verifies the script sig of an input against the script of the output it spends, see [`verify_script`].
+    #[cfg(feature = "script-verify")]
+    pub fn verify_input_script(&self, input_index: usize, prevout_script: &[u8], flags: u32) -> QDogeResult<()> {
+        let input = self.inputs.get(input_index).ok_or(DogeBridgeError::ScriptInputIndexOutOfRange)?;
+        verify_script(&input.script, prevout_script, flags, &TransactionSignatureChecker::new(self, input_index))
+    }
+}
+
+/// Removes the OP_CODESEPARATORs of a script code before it is signed.
+///
+/// A malformed push at the end of the script is kept as is.
+pub fn remove_code_separators(script: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(script.len());
+    let mut instructions = instructions(script);
+    let mut start = 0;
+    while let Some(Ok(instruction)) = instructions.next() {
+        if instruction == Instruction::Op(Opcode::OP_CODESEPARATOR) {
+            result.extend_from_slice(&script[start..instructions.get_offset() - 1]);
+            start = instructions.get_offset();
+        }
+    }
+    result.extend_from_slice(&script[start..]);
+    result
 }
 impl BTCTransactionInput {
+    pub fn is_null_outpoint(&self) -> bool {
+        self.index == u32::MAX && self.hash == [0u8; 32]
+    }
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = vec![];
         bytes.extend(&self.hash);
@@ -353,21 +491,21 @@ impl BTCTransactionInput {
         bytes.extend(self.sequence.to_le_bytes());
         bytes
     }
-    pub fn from_bytes(bytes: &[u8], offset: usize) -> anyhow::Result<(Self, usize)> {
+    pub fn from_bytes(bytes: &[u8], offset: usize) -> QDogeResult<(Self, usize)> {
         if bytes.len() - offset < (32 + 4 + 4 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = offset;
 
         let hash_bytes: [u8; 32] = bytes[read_index..(read_index + 32)]
             .try_into()
-            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            .map_err(|_| DogeBridgeError::TransactionDataTooShort)?;
         let hash = hash_bytes;
         read_index += 32;
         let index = u32::from_le_bytes(bytes[read_index..(read_index + 4)].try_into().unwrap());
         read_index += 4;
         let script_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += script_len.1;
         let script_size = script_len.0 as usize;
 
@@ -386,9 +524,9 @@ impl BTCTransactionInput {
             read_index,
         ))
     }
-    pub fn skip_decode(bytes: &[u8], offset: usize) -> anyhow::Result<usize> {
+    pub fn skip_decode(bytes: &[u8], offset: usize) -> QDogeResult<usize> {
         if bytes.len() - offset < (32 + 4 + 4 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = offset;
 
@@ -398,7 +536,7 @@ impl BTCTransactionInput {
         //let index = u32::from_le_bytes(bytes[read_index..(read_index + 4)].try_into().unwrap());
         read_index += 4;
         let script_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += script_len.1;
         let script_size = script_len.0 as usize;
 
@@ -430,16 +568,16 @@ impl BTCTransactionOutput {
         bytes.extend(&self.script);
         bytes
     }
-    pub fn from_bytes(bytes: &[u8], offset: usize) -> anyhow::Result<(Self, usize)> {
+    pub fn from_bytes(bytes: &[u8], offset: usize) -> QDogeResult<(Self, usize)> {
         if bytes.len() - offset < (8 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = offset;
 
         let value = u64::from_le_bytes(bytes[read_index..(read_index + 8)].try_into().unwrap());
         read_index += 8;
         let script_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += script_len.1;
         let script_size = script_len.0 as usize;
 
@@ -448,16 +586,16 @@ impl BTCTransactionOutput {
 
         Ok((Self { value, script }, read_index))
     }
-    pub fn skip_decode(bytes: &[u8], offset: usize) -> anyhow::Result<usize> {
+    pub fn skip_decode(bytes: &[u8], offset: usize) -> QDogeResult<usize> {
         if bytes.len() - offset < (8 + 1) {
-            return Err(anyhow::anyhow!("Invalid bytes length"));
+            return Err(DogeBridgeError::TransactionDataTooShort);
         }
         let mut read_index = offset;
 
         //let value = u64::from_le_bytes(bytes[read_index..(read_index + 8)].try_into().unwrap());
         read_index += 8;
         let script_len: (u64, usize) =
-            decode_varuint_partial(&bytes[read_index..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            decode_varuint_partial(&bytes[read_index..])?;
         read_index += script_len.1;
         let script_size = script_len.0 as usize;
 
@@ -487,7 +625,7 @@ impl BTCTransactionOutput {
             && self.script[1] == 0x14
             && self.script[22] == 0x87
     }
-    pub fn get_output_address(&self) -> anyhow::Result<BTCAddress160> {
+    pub fn get_output_address(&self) -> QDogeResult<BTCAddress160> {
         if self.is_p2pkh_output() {
             Ok(BTCAddress160::new_p2pkh(
                 self.script[3..23].try_into().unwrap(),
@@ -497,7 +635,7 @@ impl BTCTransactionOutput {
                 self.script[2..22].try_into().unwrap(),
             ))
         } else {
-            Err(anyhow::anyhow!("could not find address in output script"))
+            Err(DogeBridgeError::OutputScriptHasNoAddress)
         }
     }
 }
@@ -516,7 +654,15 @@ mod tests {
         Transaction,
     };
 
-    use super::BTCTransaction;
+    use bitcoin::{hashes::Hash, sighash::SighashCache, Script};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::error::DogeBridgeError;
+
+    use super::{
+        decode_bip34_height, BTCTransaction, BTCTransactionInput, BTCTransactionOutput, SIGHASH_ALL, SIGHASH_ANYONECANPAY,
+        SIGHASH_NONE, SIGHASH_SINGLE,
+    };
 
     fn get_example_raw_txs() -> Vec<Vec<u8>> {
         vec![
@@ -545,4 +691,98 @@ mod tests {
             assert_eq!(btc_tx_bytes, rtx);
         }
     }
+    #[test]
+    fn test_decode_bip34_height() {
+        for height in [0u32, 1, 16, 17, 127, 128, 255, 256, 32767, 32768, 1034383, 5_000_000, 0x7fffffff, u32::MAX] {
+            let mut script = bitcoin::script::Builder::new().push_int(height as i64).into_bytes();
+            script.extend_from_slice(b"/extra nonce/");
+            assert_eq!(decode_bip34_height(&script), Ok(height), "height {}", height);
+        }
+        // non minimal encodings never match the serialized height checked by consensus
+        assert_eq!(decode_bip34_height(&[0x01, 0x05]), Err(DogeBridgeError::InvalidCoinbaseHeightEncoding));
+        assert_eq!(decode_bip34_height(&[0x02, 0x80, 0x00]), Ok(128));
+        assert_eq!(decode_bip34_height(&[0x02, 0x7f, 0x00]), Err(DogeBridgeError::InvalidCoinbaseHeightEncoding));
+        assert_eq!(decode_bip34_height(&[0x01, 0x81]), Err(DogeBridgeError::InvalidCoinbaseHeightEncoding));
+        assert_eq!(decode_bip34_height(&[0x03, 0x01, 0x02]), Err(DogeBridgeError::CoinbaseHeightMissing));
+        assert_eq!(decode_bip34_height(&[0x6a]), Err(DogeBridgeError::CoinbaseHeightMissing));
+    }
+
+    fn random_script(rng: &mut StdRng) -> Vec<u8> {
+        // OP_CODESEPARATOR is excluded since the bitcoin crate signs script codes as given
+        (0..rng.gen_range(0..40)).map(|_| rng.gen_range(0..=0xaa)).collect()
+    }
+
+    #[test]
+    fn test_signature_hash_matches_bitcoin() {
+        let mut rng = StdRng::seed_from_u64(0x5167_4a54);
+        for _ in 0..100 {
+            let tx = BTCTransaction {
+                version: rng.gen_range(1..=2),
+                inputs: (0..rng.gen_range(1..5))
+                    .map(|_| BTCTransactionInput {
+                        hash: rng.gen(),
+                        index: rng.gen_range(0..4),
+                        script: random_script(&mut rng),
+                        sequence: rng.gen(),
+                    })
+                    .collect(),
+                outputs: (0..rng.gen_range(1..5))
+                    .map(|_| BTCTransactionOutput { value: rng.gen_range(0..1 << 50), script: random_script(&mut rng) })
+                    .collect(),
+                locktime: rng.gen(),
+            };
+            let btc_tx: Transaction = deserialize(&tx.to_bytes()).unwrap();
+            let cache = SighashCache::new(&btc_tx);
+            for input_index in 0..tx.inputs.len() {
+                let script_code = random_script(&mut rng);
+                let base_types = [SIGHASH_ALL, SIGHASH_NONE, SIGHASH_SINGLE, 0, 4];
+                let sighash_types = base_types
+                    .iter()
+                    .flat_map(|base_type| [*base_type as u32, (*base_type | SIGHASH_ANYONECANPAY) as u32])
+                    .chain([0x41, rng.gen()]);
+                for sighash_type in sighash_types {
+                    // the bitcoin crate only applies the SIGHASH_SINGLE bug without ANYONECANPAY, unlike dogecoin core
+                    if sighash_type & 0x9f == (SIGHASH_SINGLE | SIGHASH_ANYONECANPAY) as u32 && input_index >= tx.outputs.len() {
+                        continue;
+                    }
+                    let expected = cache.legacy_signature_hash(input_index, Script::from_bytes(&script_code), sighash_type).unwrap();
+                    assert_eq!(
+                        tx.signature_hash(input_index, &script_code, sighash_type),
+                        expected.to_byte_array(),
+                        "input {} of {}, sighash type {:#x}",
+                        input_index,
+                        tx.inputs.len(),
+                        sighash_type
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_signature_hash_edge_cases() {
+        let tx = BTCTransaction {
+            version: 1,
+            inputs: (0..3u8).map(|i| BTCTransactionInput { hash: [i; 32], index: i as u32, script: vec![], sequence: 0xffff_fffe }).collect(),
+            outputs: vec![BTCTransactionOutput { value: 1000, script: vec![0x51] }],
+            locktime: 0,
+        };
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        // SIGHASH_SINGLE without a matching output and out of range inputs sign the number one
+        assert_eq!(tx.signature_hash(1, &[0x51], SIGHASH_SINGLE as u32), one);
+        assert_eq!(tx.signature_hash(2, &[0x51], (SIGHASH_SINGLE | SIGHASH_ANYONECANPAY) as u32), one);
+        assert_ne!(tx.signature_hash(0, &[0x51], SIGHASH_SINGLE as u32), one);
+        assert_eq!(tx.signature_hash(3, &[0x51], SIGHASH_ALL as u32), one);
+
+        // OP_CODESEPARATORs are removed, except inside pushes
+        assert_eq!(
+            tx.signature_hash(0, &[0xab, 0x51, 0xab, 0xab, 0x52, 0xab], SIGHASH_ALL as u32),
+            tx.signature_hash(0, &[0x51, 0x52], SIGHASH_ALL as u32)
+        );
+        assert_ne!(
+            tx.signature_hash(0, &[0x01, 0xab], SIGHASH_ALL as u32),
+            tx.signature_hash(0, &[0x01], SIGHASH_ALL as u32)
+        );
+    }
 }
@@ -0,0 +1,523 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Builds unsigned transactions from a set of utxos, with coin selection, a change output, the dogecoin fee policy and input/output ordering.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    DOGE_HARD_DUST_LIMIT, DOGE_MAX_MONEY, DOGE_MIN_RELAY_FEE_PER_KB, DOGE_RECOMMENDED_FEE_PER_KB, DOGE_SOFT_DUST_LIMIT,
+};
+use crate::core_data::QHash256;
+use crate::error::{DogeBridgeError, QDogeResult};
+use crate::hash::ripemd160::QBTCHash160Hasher;
+use crate::hash::traits::BytesHasher;
+
+use super::address::{gen_p2pkh_script, gen_p2sh_script, AddressToBTCScript, BTCAddress160};
+use super::script::instruction::get_push_encoded_size;
+use super::script::opcodes::Opcode;
+use super::transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput};
+use super::varuint::varuint_size;
+
+/// A pushed low-S DER signature with its sighash type: at most 72 bytes of data plus the push opcode.
+pub const MAX_SIGNATURE_PUSH_SIZE: usize = 73;
+
+// outpoint (32 + 4) and sequence (4)
+const INPUT_BASE_SIZE: usize = 40;
+// value
+const OUTPUT_BASE_SIZE: usize = 8;
+// version and locktime
+const TRANSACTION_BASE_SIZE: usize = 8;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct DogeFeePolicy {
+    pub fee_per_kb: u64,
+    /// Every output below this value adds `soft_dust_limit` to the fee.
+    pub soft_dust_limit: u64,
+    /// Outputs below this value are non-standard and are never created by the builder.
+    pub hard_dust_limit: u64,
+}
+
+impl DogeFeePolicy {
+    pub fn new(fee_per_kb: u64, soft_dust_limit: u64, hard_dust_limit: u64) -> Self {
+        Self {
+            fee_per_kb,
+            soft_dust_limit,
+            hard_dust_limit,
+        }
+    }
+    /// The default fee rate of the dogecoin core wallet (0.01 DOGE per kB).
+    pub fn recommended() -> Self {
+        Self::new(DOGE_RECOMMENDED_FEE_PER_KB, DOGE_SOFT_DUST_LIMIT, DOGE_HARD_DUST_LIMIT)
+    }
+    /// The lowest fee rate relayed by dogecoin core nodes (0.001 DOGE per kB).
+    pub fn min_relay() -> Self {
+        Self::new(DOGE_MIN_RELAY_FEE_PER_KB, DOGE_SOFT_DUST_LIMIT, DOGE_HARD_DUST_LIMIT)
+    }
+    pub fn is_dust(&self, value: u64) -> bool {
+        value < self.soft_dust_limit
+    }
+    /// The fee of a transaction of `size` bytes paying `outputs`, rounding the size up to the next kB like dogecoin core.
+    pub fn get_fee(&self, size: usize, outputs: &[BTCTransactionOutput]) -> u64 {
+        let size_kb = size.div_ceil(1000) as u64;
+        let dust_outputs = outputs.iter().filter(|x| self.is_dust(x.value)).count() as u64;
+        size_kb * self.fee_per_kb + dust_outputs * self.soft_dust_limit
+    }
+}
+
+impl Default for DogeFeePolicy {
+    fn default() -> Self {
+        Self::recommended()
+    }
+}
+
+/// An unspent output, together with an upper bound on the size of the script sig which will spend it.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct BTCUtxo {
+    pub hash: QHash256,
+    pub index: u32,
+    pub value: u64,
+    pub script: Vec<u8>,
+    pub max_script_sig_size: usize,
+}
+
+impl BTCUtxo {
+    pub fn new(hash: QHash256, index: u32, value: u64, script: Vec<u8>, max_script_sig_size: usize) -> Self {
+        Self {
+            hash,
+            index,
+            value,
+            script,
+            max_script_sig_size,
+        }
+    }
+    /// A P2PKH output spent with `<sig> <public_key>`, the public key can be compressed or uncompressed.
+    pub fn new_p2pkh(hash: QHash256, index: u32, value: u64, public_key: &[u8]) -> Self {
+        let script = gen_p2pkh_script(&QBTCHash160Hasher::hash_bytes(public_key)).to_vec();
+        Self::new(hash, index, value, script, MAX_SIGNATURE_PUSH_SIZE + get_push_encoded_size(public_key.len()))
+    }
+    /// A P2SH output spent with `<arguments> <redeem_script>`, where `max_arguments_size` is the size of the serialized pushes before the redeem script.
+    pub fn new_p2sh(hash: QHash256, index: u32, value: u64, redeem_script: &[u8], max_arguments_size: usize) -> Self {
+        let script = gen_p2sh_script(&QBTCHash160Hasher::hash_bytes(redeem_script)).to_vec();
+        Self::new(hash, index, value, script, max_arguments_size + get_push_encoded_size(redeem_script.len()))
+    }
+    /// A P2SH output with an m-of-n CHECKMULTISIG redeem script, spent with `OP_0 <sig_1> ... <sig_m> <redeem_script>`.
+    pub fn new_p2sh_multisig(hash: QHash256, index: u32, value: u64, redeem_script: &[u8], required_signatures: usize) -> Self {
+        Self::new_p2sh(hash, index, value, redeem_script, 1 + required_signatures * MAX_SIGNATURE_PUSH_SIZE)
+    }
+    pub fn get_input_size(&self) -> usize {
+        INPUT_BASE_SIZE + varuint_size(self.max_script_sig_size as u64) + self.max_script_sig_size
+    }
+    pub fn to_output(&self) -> BTCTransactionOutput {
+        BTCTransactionOutput {
+            value: self.value,
+            script: self.script.clone(),
+        }
+    }
+}
+
+pub fn get_output_size(output: &BTCTransactionOutput) -> usize {
+    OUTPUT_BASE_SIZE + varuint_size(output.script.len() as u64) + output.script.len()
+}
+
+fn get_transaction_size(input_count: usize, inputs_size: usize, outputs: &[BTCTransactionOutput]) -> usize {
+    TRANSACTION_BASE_SIZE
+        + varuint_size(input_count as u64)
+        + inputs_size
+        + varuint_size(outputs.len() as u64)
+        + outputs.iter().map(get_output_size).sum::<usize>()
+}
+
+/// Upper bound of the serialized size of a transaction spending `utxos` into `outputs` once all inputs are signed.
+pub fn estimate_signed_size(utxos: &[BTCUtxo], outputs: &[BTCTransactionOutput]) -> usize {
+    get_transaction_size(utxos.len(), utxos.iter().map(BTCUtxo::get_input_size).sum(), outputs)
+}
+
+fn is_op_return_output(output: &BTCTransactionOutput) -> bool {
+    output.script.first() == Some(&(Opcode::OP_RETURN as u8))
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+pub enum BTCTransactionOrdering {
+    /// Inputs in the order the utxos were added, outputs in the order they were added followed by the change output.
+    #[default]
+    Preserve,
+    /// BIP69: inputs sorted by txid (in display order) and index, outputs sorted by value and script.
+    Bip69,
+}
+
+/// An unsigned transaction created by [`BTCTransactionBuilder`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct UnsignedBTCTransaction {
+    pub transaction: BTCTransaction,
+    /// The utxo spent by each input, in input order.
+    pub spent_utxos: Vec<BTCUtxo>,
+    pub fee: u64,
+    pub change_output_index: Option<usize>,
+    /// The size the fee was computed for, an upper bound of the size of the signed transaction.
+    pub estimated_size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct BTCTransactionBuilder {
+    utxos: Vec<BTCUtxo>,
+    outputs: Vec<BTCTransactionOutput>,
+    change_address: BTCAddress160,
+    fee_policy: DogeFeePolicy,
+    ordering: BTCTransactionOrdering,
+    locktime: u32,
+}
+
+impl BTCTransactionBuilder {
+    pub fn new(change_address: BTCAddress160, fee_policy: DogeFeePolicy) -> Self {
+        Self {
+            utxos: Vec::new(),
+            outputs: Vec::new(),
+            change_address,
+            fee_policy,
+            ordering: BTCTransactionOrdering::Preserve,
+            locktime: 0,
+        }
+    }
+    pub fn add_utxo(mut self, utxo: BTCUtxo) -> Self {
+        self.utxos.push(utxo);
+        self
+    }
+    pub fn add_utxos(mut self, utxos: &[BTCUtxo]) -> Self {
+        self.utxos.extend_from_slice(utxos);
+        self
+    }
+    pub fn add_recipient(self, address: &BTCAddress160, amount: u64) -> Self {
+        self.add_output(address.to_btc_output(amount))
+    }
+    /// Adds an output with an arbitrary script, OP_RETURN outputs are exempt from the hard dust limit.
+    pub fn add_output(mut self, output: BTCTransactionOutput) -> Self {
+        self.outputs.push(output);
+        self
+    }
+    pub fn with_ordering(mut self, ordering: BTCTransactionOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+    /// Sets the locktime, inputs get a non-final sequence so that the locktime is enforced.
+    pub fn with_locktime(mut self, locktime: u32) -> Self {
+        self.locktime = locktime;
+        self
+    }
+
+    /// Selects utxos largest first until they pay for the outputs and the fee.
+    /// A change output is only added if it would not be dust, otherwise the remainder goes to the fee.
+    pub fn build(&self) -> QDogeResult<UnsignedBTCTransaction> {
+        if self.outputs.is_empty() {
+            return Err(DogeBridgeError::TxBuilderNoRecipients);
+        }
+        let mut outputs_total = 0u64;
+        for output in self.outputs.iter() {
+            if output.value < self.fee_policy.hard_dust_limit && !is_op_return_output(output) {
+                return Err(DogeBridgeError::TxBuilderDustOutput);
+            }
+            outputs_total = checked_add_money(outputs_total, output.value)?;
+        }
+
+        let mut outputs_with_change = self.outputs.clone();
+        outputs_with_change.push(self.change_address.to_btc_output(0));
+
+        let mut candidates: Vec<usize> = (0..self.utxos.len()).collect();
+        candidates.sort_by(|a, b| self.utxos[*b].value.cmp(&self.utxos[*a].value));
+
+        let mut selected = Vec::new();
+        let mut inputs_total = 0u64;
+        let mut inputs_size = 0usize;
+        for utxo_index in candidates {
+            let utxo = &self.utxos[utxo_index];
+            selected.push(utxo_index);
+            inputs_total = checked_add_money(inputs_total, utxo.value)?;
+            inputs_size += utxo.get_input_size();
+
+            let size = get_transaction_size(selected.len(), inputs_size, &self.outputs);
+            let fee = self.fee_policy.get_fee(size, &self.outputs);
+            if inputs_total < outputs_total + fee {
+                continue;
+            }
+
+            // the change output is never dust, so it only adds to the size of the transaction
+            let size_with_change = get_transaction_size(selected.len(), inputs_size, &outputs_with_change);
+            let fee_with_change = self.fee_policy.get_fee(size_with_change, &self.outputs);
+            let change = (inputs_total - outputs_total).saturating_sub(fee_with_change);
+            return if self.fee_policy.is_dust(change) {
+                Ok(self.assemble(selected, None, inputs_total - outputs_total, size))
+            } else {
+                Ok(self.assemble(selected, Some(change), fee_with_change, size_with_change))
+            };
+        }
+        Err(DogeBridgeError::TxBuilderInsufficientFunds)
+    }
+
+    fn assemble(&self, mut selected: Vec<usize>, change: Option<u64>, fee: u64, estimated_size: usize) -> UnsignedBTCTransaction {
+        match self.ordering {
+            BTCTransactionOrdering::Preserve => selected.sort(),
+            BTCTransactionOrdering::Bip69 => selected.sort_by(|a, b| {
+                let (a, b) = (&self.utxos[*a], &self.utxos[*b]);
+                a.hash.iter().rev().cmp(b.hash.iter().rev()).then(a.index.cmp(&b.index))
+            }),
+        }
+        let spent_utxos: Vec<BTCUtxo> = selected.into_iter().map(|i| self.utxos[i].clone()).collect();
+
+        let mut outputs: Vec<(BTCTransactionOutput, bool)> = self.outputs.iter().map(|x| (x.clone(), false)).collect();
+        if let Some(change) = change {
+            outputs.push((self.change_address.to_btc_output(change), true));
+        }
+        if self.ordering == BTCTransactionOrdering::Bip69 {
+            outputs.sort_by(|(a, _), (b, _)| a.value.cmp(&b.value).then_with(|| a.script.cmp(&b.script)));
+        }
+        let change_output_index = outputs.iter().position(|(_, is_change)| *is_change);
+
+        let sequence = if self.locktime == 0 { 0xffffffff } else { 0xfffffffe };
+        let inputs = spent_utxos
+            .iter()
+            .map(|x| BTCTransactionInput {
+                hash: x.hash,
+                index: x.index,
+                script: Vec::new(),
+                sequence,
+            })
+            .collect();
+        let mut transaction = BTCTransaction::from_io(inputs, outputs.into_iter().map(|(x, _)| x).collect());
+        transaction.locktime = self.locktime;
+
+        UnsignedBTCTransaction {
+            transaction,
+            spent_utxos,
+            fee,
+            change_output_index,
+            estimated_size,
+        }
+    }
+}
+
+fn checked_add_money(a: u64, b: u64) -> QDogeResult<u64> {
+    match a.checked_add(b) {
+        Some(sum) if sum <= DOGE_MAX_MONEY => Ok(sum),
+        _ => Err(DogeBridgeError::TxBuilderAmountOverflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+    use super::*;
+    use crate::constants::DOGE_COIN;
+    use crate::doge::script::builder::ScriptBuilder;
+    #[cfg(feature = "script-verify")]
+    use crate::doge::script::interpreter::DOGE_STANDARD_SCRIPT_VERIFY_FLAGS;
+    use crate::doge::transaction::SIGHASH_ALL;
+    use crate::test_fixtures::{get_pubkey, multisig_script};
+
+    fn sign(tx: &BTCTransaction, input_index: usize, script_code: &[u8], secret: &SecretKey) -> Vec<u8> {
+        let sighash = tx.signature_hash(input_index, script_code, SIGHASH_ALL as u32);
+        let signature = Secp256k1::signing_only().sign_ecdsa(&Message::from_digest(sighash), secret);
+        let mut result = signature.serialize_der().to_vec();
+        result.push(SIGHASH_ALL);
+        result
+    }
+
+    fn recipient() -> BTCAddress160 {
+        BTCAddress160::new_p2pkh([0x11; 20])
+    }
+
+    fn change_address() -> BTCAddress160 {
+        BTCAddress160::new_p2sh([0x22; 20])
+    }
+
+    #[test]
+    fn test_fee_policy() {
+        let policy = DogeFeePolicy::recommended();
+        assert_eq!(policy.get_fee(1, &[]), DOGE_COIN / 100);
+        assert_eq!(policy.get_fee(1000, &[]), DOGE_COIN / 100);
+        assert_eq!(policy.get_fee(1001, &[]), DOGE_COIN / 50);
+        let outputs = [recipient().to_btc_output(DOGE_COIN), recipient().to_btc_output(DOGE_COIN / 200)];
+        assert_eq!(policy.get_fee(226, &outputs), DOGE_COIN / 50);
+        assert_eq!(DogeFeePolicy::min_relay().get_fee(226, &outputs[..1]), DOGE_COIN / 1000);
+    }
+
+    #[test]
+    fn test_input_sizes() {
+        let compressed = get_pubkey(&SecretKey::from_slice(&[1; 32]).unwrap());
+        let uncompressed = PublicKey::from_slice(&compressed).unwrap().serialize_uncompressed();
+        assert_eq!(BTCUtxo::new_p2pkh([0; 32], 0, DOGE_COIN, &compressed).get_input_size(), 148);
+        assert_eq!(BTCUtxo::new_p2pkh([0; 32], 0, DOGE_COIN, &uncompressed).get_input_size(), 180);
+
+        // 105 byte redeem script pushed with OP_PUSHDATA1, the 254 byte script sig needs a 3 byte length
+        let redeem_script = multisig_script(2, &[compressed.clone(), compressed.clone(), compressed]);
+        assert_eq!(redeem_script.len(), 105);
+        assert_eq!(BTCUtxo::new_p2sh_multisig([0; 32], 0, DOGE_COIN, &redeem_script, 2).get_input_size(), 297);
+
+        // one input and two outputs, the usual 226 byte P2PKH payment
+        let utxo = BTCUtxo::new_p2pkh([0; 32], 0, DOGE_COIN, &get_pubkey(&SecretKey::from_slice(&[1; 32]).unwrap()));
+        let outputs = [recipient().to_btc_output(1), BTCAddress160::new_p2pkh([0x33; 20]).to_btc_output(1)];
+        assert_eq!(estimate_signed_size(&[utxo], &outputs), 226);
+    }
+
+    #[test]
+    fn test_build_and_sign() {
+        let keys: Vec<SecretKey> = (1u8..=4).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect();
+        let pubkeys: Vec<Vec<u8>> = keys.iter().map(get_pubkey).collect();
+        let redeem_script = multisig_script(2, &pubkeys[1..]);
+
+        let utxos = [
+            BTCUtxo::new_p2pkh([1; 32], 0, 5 * DOGE_COIN, &pubkeys[0]),
+            BTCUtxo::new_p2pkh([2; 32], 1, 3 * DOGE_COIN, &pubkeys[0]),
+            BTCUtxo::new_p2pkh([3; 32], 2, DOGE_COIN, &pubkeys[0]),
+            BTCUtxo::new_p2sh_multisig([4; 32], 3, 4 * DOGE_COIN, &redeem_script, 2),
+        ];
+        let unsigned = BTCTransactionBuilder::new(change_address(), DogeFeePolicy::recommended())
+            .add_utxos(&utxos)
+            .add_recipient(&recipient(), 8 * DOGE_COIN + DOGE_COIN / 2)
+            .build()
+            .unwrap();
+
+        assert_eq!(unsigned.spent_utxos, [utxos[0].clone(), utxos[3].clone()]);
+        assert_eq!(unsigned.change_output_index, Some(1));
+        let tx = &unsigned.transaction;
+        assert_eq!(tx.outputs[0], recipient().to_btc_output(8 * DOGE_COIN + DOGE_COIN / 2));
+        assert_eq!(tx.outputs[1].script, change_address().to_btc_script());
+        let outputs_total: u64 = tx.outputs.iter().map(|x| x.value).sum();
+        assert_eq!(9 * DOGE_COIN - outputs_total, unsigned.fee);
+        assert_eq!(unsigned.fee, DogeFeePolicy::recommended().get_fee(unsigned.estimated_size, &tx.outputs));
+        assert_eq!(unsigned.estimated_size, estimate_signed_size(&unsigned.spent_utxos, &tx.outputs));
+
+        let mut signed = tx.clone();
+        signed.inputs[0].script = ScriptBuilder::new()
+            .push_slice(&sign(tx, 0, &utxos[0].script, &keys[0]))
+            .push_slice(&pubkeys[0])
+            .into_bytes();
+        signed.inputs[1].script = ScriptBuilder::new()
+            .push_int(0)
+            .push_slice(&sign(tx, 1, &redeem_script, &keys[1]))
+            .push_slice(&sign(tx, 1, &redeem_script, &keys[3]))
+            .push_slice(&redeem_script)
+            .into_bytes();
+        #[cfg(feature = "script-verify")]
+        for (i, utxo) in unsigned.spent_utxos.iter().enumerate() {
+            signed.verify_input_script(i, &utxo.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+        }
+        // signatures are at most one byte shorter than the estimate in practice
+        let size = signed.to_bytes().len();
+        assert!(size <= unsigned.estimated_size);
+        assert!(size + 3 * 2 >= unsigned.estimated_size);
+    }
+
+    #[test]
+    fn test_change_and_errors() {
+        let pubkey = get_pubkey(&SecretKey::from_slice(&[1; 32]).unwrap());
+        let policy = DogeFeePolicy::recommended();
+        let amount = 10 * DOGE_COIN;
+        let fee = policy.get_fee(192, &[]);
+
+        // a remainder below the soft dust limit is added to the fee instead of creating change
+        let utxo = BTCUtxo::new_p2pkh([1; 32], 0, amount + fee + DOGE_COIN / 200, &pubkey);
+        let unsigned = BTCTransactionBuilder::new(change_address(), policy)
+            .add_utxo(utxo.clone())
+            .add_recipient(&recipient(), amount)
+            .build()
+            .unwrap();
+        assert_eq!(unsigned.estimated_size, 192);
+        assert_eq!(unsigned.change_output_index, None);
+        assert_eq!(unsigned.transaction.outputs.len(), 1);
+        assert_eq!(unsigned.fee, fee + DOGE_COIN / 200);
+
+        let builder = BTCTransactionBuilder::new(change_address(), policy).add_utxo(utxo);
+        assert_eq!(builder.clone().build(), Err(DogeBridgeError::TxBuilderNoRecipients));
+        assert_eq!(
+            builder.clone().add_recipient(&recipient(), amount + DOGE_COIN).build(),
+            Err(DogeBridgeError::TxBuilderInsufficientFunds)
+        );
+        assert_eq!(
+            builder.clone().add_recipient(&recipient(), DOGE_HARD_DUST_LIMIT - 1).build(),
+            Err(DogeBridgeError::TxBuilderDustOutput)
+        );
+        assert_eq!(
+            builder.clone().add_recipient(&recipient(), DOGE_MAX_MONEY).add_recipient(&recipient(), DOGE_COIN).build(),
+            Err(DogeBridgeError::TxBuilderAmountOverflow)
+        );
+
+        // OP_RETURN outputs can carry no value, but still pay the dust fee
+        let op_return = BTCTransactionOutput {
+            value: 0,
+            script: ScriptBuilder::new().push_opcode(Opcode::OP_RETURN).push_slice(b"qed").into_bytes(),
+        };
+        let unsigned = builder.add_recipient(&recipient(), DOGE_COIN).add_output(op_return).with_locktime(100).build().unwrap();
+        assert_eq!(unsigned.fee, policy.get_fee(unsigned.estimated_size, &[]) + policy.soft_dust_limit);
+        assert_eq!(unsigned.transaction.locktime, 100);
+        assert_eq!(unsigned.transaction.inputs[0].sequence, 0xfffffffe);
+    }
+
+    #[test]
+    fn test_bip69_ordering() {
+        let pubkey = get_pubkey(&SecretKey::from_slice(&[1; 32]).unwrap());
+        let mut hash_a = [0u8; 32];
+        hash_a[0] = 1;
+        hash_a[31] = 2;
+        let mut hash_b = [0u8; 32];
+        hash_b[0] = 2;
+        hash_b[31] = 1;
+        let utxos = [
+            BTCUtxo::new_p2pkh(hash_a, 0, 5 * DOGE_COIN, &pubkey),
+            BTCUtxo::new_p2pkh(hash_b, 1, 5 * DOGE_COIN, &pubkey),
+            BTCUtxo::new_p2pkh(hash_b, 0, 5 * DOGE_COIN, &pubkey),
+        ];
+        let builder = BTCTransactionBuilder::new(change_address(), DogeFeePolicy::recommended())
+            .add_utxos(&utxos)
+            .add_recipient(&recipient(), 7 * DOGE_COIN)
+            .add_recipient(&BTCAddress160::new_p2pkh([0x33; 20]), 5 * DOGE_COIN);
+
+        let preserved = builder.clone().build().unwrap();
+        assert_eq!(preserved.spent_utxos, utxos);
+        assert_eq!(preserved.transaction.outputs[0].value, 7 * DOGE_COIN);
+        assert_eq!(preserved.change_output_index, Some(2));
+
+        let sorted = builder.with_ordering(BTCTransactionOrdering::Bip69).build().unwrap();
+        assert_eq!(sorted.spent_utxos, [utxos[2].clone(), utxos[1].clone(), utxos[0].clone()]);
+        let values: Vec<u64> = sorted.transaction.outputs.iter().map(|x| x.value).collect();
+        assert!(values.windows(2).all(|x| x[0] <= x[1]));
+        assert_eq!(sorted.change_output_index, Some(0));
+        assert_eq!(sorted.fee, preserved.fee);
+    }
+}
@@ -24,6 +24,10 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+use alloc::{vec, vec::Vec};
+
+use crate::error::DogeBridgeError;
+
 pub fn varuint_size(value: u64) -> usize {
     if value < 0xfd {
         1
@@ -62,6 +66,12 @@ impl core::fmt::Display for VaruintDecodingError {
     }
 }
 
+impl From<VaruintDecodingError> for DogeBridgeError {
+    fn from(_: VaruintDecodingError) -> Self {
+        DogeBridgeError::InvalidVaruint
+    }
+}
+
 pub fn decode_varuint(data: &[u8]) -> Result<u64, VaruintDecodingError> {
     if data.is_empty() {
         return Err(VaruintDecodingError);
@@ -164,12 +164,281 @@ pub enum DogeBridgeError {
     RevertIndexNotPrefix = 726,
     #[error("Too many changed left siblings provided")]
     TooManyChangedLeftSiblings = 727,
+
+
+    // start merkle mountain range errors
+    #[error("Cannot roll back a merkle mountain range to a size greater than or equal to its current size")]
+    MmrRollbackSizeTooHigh = 730,
+    #[error("Not enough restored peaks provided for merkle mountain range rollback")]
+    MmrNotEnoughRestoredPeaks = 731,
+    #[error("Too many restored peaks provided for merkle mountain range rollback")]
+    MmrTooManyRestoredPeaks = 732,
+    #[error("The last leaf does not match the lowest peak of the merkle mountain range")]
+    MmrLastLeafMismatch = 733,
+    #[error("Cannot append to a full merkle mountain range")]
+    MmrFull = 734,
+
+
+    // start flyclient errors
+    #[error("FlyClient proof block range is invalid or shorter than the block hash cache")]
+    FlyClientInvalidRange = 740,
+    #[error("FlyClient proof suffix headers do not form a chain")]
+    FlyClientSuffixNotLinked = 741,
+    #[error("FlyClient proof suffix does not match the claimed block tree root")]
+    FlyClientBlockTreeRootMismatch = 742,
+    #[error("FlyClient sampled block is not included in the claimed block tree root")]
+    FlyClientInvalidBlockTreeProof = 743,
+    #[error("FlyClient sampled block has an invalid cumulative work proof")]
+    FlyClientInvalidWorkProof = 744,
+    #[error("FlyClient proof has the wrong number of samples")]
+    FlyClientSampleCountMismatch = 745,
+    #[error("FlyClient sampled block does not contain the sampled work")]
+    FlyClientSampleMismatch = 746,
+    #[error("FlyClient proof does not start at the trusted checkpoint")]
+    FlyClientCheckpointMismatch = 747,
+    #[error("FlyClient proof has less total work than required")]
+    FlyClientInsufficientWork = 748,
+    #[error("FlyClient header does not link to the parent and grandparent headers used to check its difficulty bits")]
+    FlyClientAncestorsNotLinked = 749,
+
+
+    // start chain state layout errors
+    #[error("Invalid chain state layout (cache size, required confirmations or block tree height out of range)")]
+    ChainStateLayoutInvalid = 750,
+    #[error("Chain state data is shorter than its layout requires")]
+    ChainStateDataTooShort = 751,
+    #[error("Chain state layout does not match the expected sizes")]
+    ChainStateLayoutMismatch = 752,
+    #[error("Chain state migration cannot decrease the block tree height")]
+    ChainStateMigrationTreeHeightDecrease = 753,
+    #[error("Chain state migration was given the wrong number of block headers or block tree siblings")]
+    ChainStateMigrationHeadersMismatch = 754,
+    #[error("Chain state migration headers do not link up to the current tip")]
+    ChainStateMigrationHeadersNotLinked = 755,
+    #[error("Block record recomputed during a chain state migration does not match the current chain state")]
+    ChainStateMigrationRecordMismatch = 756,
+
+
+    // start state envelope errors
+    #[error("State data does not start with the state envelope magic")]
+    StateEnvelopeMagicMismatch = 760,
+    #[error("Unknown state envelope version")]
+    StateEnvelopeUnknownVersion = 761,
+    #[error("State envelope was written for a different block tree tracker")]
+    StateEnvelopeBlockTreeTrackerMismatch = 762,
+    #[error("State envelope was written for a different block tree hasher")]
+    StateEnvelopeBlockTreeHasherMismatch = 763,
+
+
+    // start state diff errors
+    #[error("State diff was computed for a state of a different size")]
+    StateDiffSizeMismatch = 770,
+    #[error("State diff base hash does not match the hash of the state it is applied to")]
+    StateDiffBaseHashMismatch = 771,
+    #[error("State diff chunk is out of bounds or not in ascending order")]
+    StateDiffChunkOutOfBounds = 772,
+    #[error("State after applying the diff does not match the expected hash")]
+    StateDiffResultHashMismatch = 773,
+
+
+    // start state commitment errors
+    #[error("State commitment opening does not hash to the state commitment")]
+    StateCommitmentMismatch = 780,
+    #[error("State commitment field proof does not match the opened part of the state")]
+    StateCommitmentInvalidFieldProof = 781,
+    #[error("State commitment field index is out of range")]
+    StateCommitmentFieldIndexOutOfRange = 782,
+
+
+    // start decoding errors
+    #[error("Block header data is shorter than 80 bytes")]
+    InvalidBlockHeaderLength = 800,
+    #[error("Transaction data is too short")]
+    TransactionDataTooShort = 801,
+    #[error("Transaction data has unexpected trailing bytes")]
+    TransactionDataTooLong = 802,
+    #[error("Tried to decode malformed bytes into varuint")]
+    InvalidVaruint = 803,
+    #[error("Transaction output index out of range")]
+    TransactionOutputIndexOutOfRange = 804,
+    #[error("Invalid base58check address encoding")]
+    InvalidAddressEncoding = 805,
+    #[error("Invalid address length, expected 21 bytes (version byte + hash160)")]
+    InvalidAddressLength = 806,
+    #[error("Invalid address version byte")]
+    InvalidAddressVersionByte = 807,
+    #[error("Invalid address type")]
+    InvalidAddressType = 808,
+    #[error("Could not find address in output script")]
+    OutputScriptHasNoAddress = 809,
+
+
+    // start block transaction errors
+    #[error("Block has no transactions")]
+    BlockHasNoTransactions = 820,
+    #[error("Transactions do not hash to the merkle root in the block header")]
+    BlockTxMerkleRootMismatch = 821,
+    #[error("Block transaction merkle tree is mutated (duplicate transactions, CVE-2012-2459)")]
+    BlockTxMerkleTreeMutated = 822,
+    #[error("Block size exceeds the maximum block size")]
+    BlockSizeTooLarge = 823,
+    #[error("First transaction of the block is not a coinbase")]
+    BlockFirstTransactionNotCoinbase = 824,
+    #[error("Block contains more than one coinbase transaction")]
+    BlockMultipleCoinbases = 825,
+    #[error("Block exceeds the maximum number of signature operations")]
+    BlockTooManySigops = 826,
+    #[error("Block contains duplicate transaction ids")]
+    BlockDuplicateTransaction = 827,
+    #[error("Transaction has no inputs")]
+    TransactionNoInputs = 828,
+    #[error("Transaction has no outputs")]
+    TransactionNoOutputs = 829,
+    #[error("Transaction output value or total output value is out of range")]
+    TransactionOutputValueOutOfRange = 830,
+    #[error("Transaction spends the same outpoint twice")]
+    TransactionDuplicateInputs = 831,
+    #[error("Coinbase script size is out of range")]
+    CoinbaseScriptSizeOutOfRange = 832,
+    #[error("Non-coinbase transaction spends the null outpoint")]
+    TransactionNullPrevout = 833,
+    #[error("Number of prevout values does not match the number of transaction inputs")]
+    MismatchedPrevoutValuesCount = 834,
+    #[error("Transaction input value or total input value is out of range")]
+    TransactionInputValueOutOfRange = 835,
+    #[error("Transaction outputs exceed its inputs")]
+    TransactionOutputsExceedInputs = 836,
+    #[error("Coinbase outputs exceed the block subsidy plus fees")]
+    CoinbaseValueTooLarge = 837,
+
+
+    // start coinbase errors
+    #[error("Transaction is not a coinbase transaction")]
+    TransactionNotCoinbase = 840,
+    #[error("Coinbase script does not start with a serialized block height (BIP34)")]
+    CoinbaseHeightMissing = 841,
+    #[error("Coinbase block height is not minimally encoded or out of range (BIP34)")]
+    InvalidCoinbaseHeightEncoding = 842,
+    #[error("Coinbase block height does not match the height of the block (BIP34)")]
+    MismatchedCoinbaseHeight = 843,
+
+
+    // start script errors
+    #[error("Script push runs past the end of the script")]
+    ScriptPushOutOfBounds = 850,
+    #[error("Invalid script assembly")]
+    InvalidScriptAsm = 851,
+    #[error("Script number is larger than the maximum number size")]
+    ScriptNumOverflow = 852,
+    #[error("Script number is not minimally encoded")]
+    ScriptNumNotMinimal = 853,
+    #[error("Script evaluated without error but finished with a false/empty top stack element")]
+    ScriptEvalFalse = 854,
+    #[error("OP_RETURN was encountered")]
+    ScriptOpReturn = 855,
+    #[error("Script is too big")]
+    ScriptSizeTooLarge = 856,
+    #[error("Push value size limit exceeded")]
+    ScriptPushSizeTooLarge = 857,
+    #[error("Operation limit exceeded")]
+    ScriptOpCountTooLarge = 858,
+    #[error("Stack size limit exceeded")]
+    ScriptStackSizeTooLarge = 859,
+    #[error("Signature count negative or greater than pubkey count")]
+    ScriptSigCountOutOfRange = 860,
+    #[error("Pubkey count negative or limit exceeded")]
+    ScriptPubkeyCountOutOfRange = 861,
+    #[error("Script failed an OP_VERIFY operation")]
+    ScriptVerifyFailed = 862,
+    #[error("Script failed an OP_EQUALVERIFY operation")]
+    ScriptEqualVerifyFailed = 863,
+    #[error("Script failed an OP_CHECKMULTISIGVERIFY operation")]
+    ScriptCheckMultisigVerifyFailed = 864,
+    #[error("Script failed an OP_CHECKSIGVERIFY operation")]
+    ScriptCheckSigVerifyFailed = 865,
+    #[error("Script failed an OP_NUMEQUALVERIFY operation")]
+    ScriptNumEqualVerifyFailed = 866,
+    #[error("Opcode missing or not understood")]
+    ScriptBadOpcode = 867,
+    #[error("Attempted to use a disabled opcode")]
+    ScriptDisabledOpcode = 868,
+    #[error("Operation not valid with the current stack size")]
+    ScriptInvalidStackOperation = 869,
+    #[error("Operation not valid with the current altstack size")]
+    ScriptInvalidAltstackOperation = 870,
+    #[error("Invalid OP_IF construction")]
+    ScriptUnbalancedConditional = 871,
+    #[error("Negative locktime")]
+    ScriptNegativeLocktime = 872,
+    #[error("Locktime requirement not satisfied")]
+    ScriptUnsatisfiedLocktime = 873,
+    #[error("Signature hash type missing or not understood")]
+    ScriptSigHashType = 874,
+    #[error("Non-canonical DER signature")]
+    ScriptSigDer = 875,
+    #[error("Data push larger than necessary")]
+    ScriptMinimalData = 876,
+    #[error("Only non-push operators allowed in signatures")]
+    ScriptSigPushOnly = 877,
+    #[error("Non-canonical signature: S value is unnecessarily high")]
+    ScriptSigHighS = 878,
+    #[error("Dummy CHECKMULTISIG argument must be zero")]
+    ScriptSigNullDummy = 879,
+    #[error("Public key is neither compressed or uncompressed")]
+    ScriptPubkeyType = 880,
+    #[error("Extra items left on stack after execution")]
+    ScriptCleanStack = 881,
+    #[error("Signature must be zero for failed CHECK(MULTI)SIG operation")]
+    ScriptSigNullFail = 882,
+    #[error("NOPx reserved for soft-fork upgrades")]
+    ScriptDiscourageUpgradableNops = 883,
+    #[error("Input index is out of range")]
+    ScriptInputIndexOutOfRange = 884,
+
+
+    // start transaction builder errors
+    #[error("Transaction builder has no recipients")]
+    TxBuilderNoRecipients = 885,
+    #[error("Recipient amount is below the hard dust limit")]
+    TxBuilderDustOutput = 886,
+    #[error("Not enough funds in the available utxos to pay the recipients and the fee")]
+    TxBuilderInsufficientFunds = 887,
+    #[error("Transaction amounts exceed the maximum money supply")]
+    TxBuilderAmountOverflow = 888,
+
+
+    // start partially signed transaction errors
+    #[error("Number of partially signed inputs does not match the transaction inputs")]
+    PsbtInputCountMismatch = 889,
+    #[error("Partially signed transaction must have empty script sigs")]
+    PsbtTransactionNotUnsigned = 890,
+    #[error("Partially signed transactions spend different inputs or outputs and cannot be combined")]
+    PsbtTransactionMismatch = 891,
+    #[error("Redeem script does not match the P2SH output being spent")]
+    PsbtRedeemScriptMismatch = 892,
+    #[error("Input spends a P2SH output but has no redeem script")]
+    PsbtMissingRedeemScript = 893,
+    #[error("Partial signature is invalid or uses a different sighash type than the input")]
+    PsbtInvalidSignature = 894,
+    #[error("Input script is not a supported template and needs an explicit script sig")]
+    PsbtUnsupportedScript = 895,
+    #[error("Input does not have enough partial signatures to be finalized")]
+    PsbtMissingSignatures = 896,
+    #[error("Partially signed transaction has inputs which are not finalized")]
+    PsbtNotFinalized = 897,
+    #[error("Invalid partially signed transaction encoding")]
+    PsbtInvalidEncoding = 898,
+    #[error("Previous transaction of an input does not hash to the txid of the outpoint being spent")]
+    PsbtNonWitnessUtxoTxidMismatch = 899,
+    #[error("Previous transaction of an input does not contain the stated output being spent")]
+    PsbtNonWitnessUtxoOutputMismatch = 900,
 }
 
 
 #[cfg(feature = "solprogram")]
 impl solana_program::program_error::PrintProgramError for DogeBridgeError {
     fn print<E>(&self) {
+        use alloc::string::ToString;
         solana_program::msg!(&self.to_string());
     }
 }
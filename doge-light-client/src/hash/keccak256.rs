@@ -0,0 +1,81 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use sha3::{Digest, Keccak256};
+
+use crate::core_data::QHash256;
+
+use super::traits::{iterate_merkle_hasher, BytesHasher, MerkleHasher, MerkleZeroHasher};
+
+fn keccak256_bytes(data: &[u8]) -> QHash256 {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Keccak-256 hasher, `two_to_one` matches `keccak256(abi.encodePacked(left, right))` in solidity.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct QKeccak256Hasher;
+
+impl BytesHasher<QHash256> for QKeccak256Hasher {
+    fn hash_bytes(data: &[u8]) -> QHash256 {
+        keccak256_bytes(data)
+    }
+}
+impl MerkleHasher<QHash256> for QKeccak256Hasher {
+    fn two_to_one(left: &QHash256, right: &QHash256) -> QHash256 {
+        let mut bytes = [0u8; 64];
+        bytes[0..32].copy_from_slice(left);
+        bytes[32..64].copy_from_slice(right);
+        keccak256_bytes(&bytes)
+    }
+}
+impl MerkleZeroHasher<QHash256> for QKeccak256Hasher {
+    fn get_zero_hash(reverse_level: usize) -> QHash256 {
+        iterate_merkle_hasher::<QHash256, Self>([0u8; 32], reverse_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use crate::hash::traits::{BytesHasher, MerkleHasher};
+
+    use super::QKeccak256Hasher;
+
+    #[test]
+    fn test_keccak256_known_vectors() {
+        assert_eq!(
+            QKeccak256Hasher::hash_bytes(b""),
+            hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+        assert_eq!(
+            QKeccak256Hasher::two_to_one(&[0u8; 32], &[0u8; 32]),
+            QKeccak256Hasher::hash_bytes(&[0u8; 64])
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use crate::{error::QDogeResult, hash::traits::{MerkleHasher, MerkleZeroHasher, ZeroableHash}};
+
+use super::{fixed_append_tree::FixedMerkleAppendTree, mmr::MerkleMountainRange};
+
+/// Append-only accumulator used by the chain state to commit to every block hash it has processed.
+///
+/// `SIBLINGS` is the size of the array used to restore the accumulator from init data: the merkle path of
+/// the last leaf for [`FixedMerkleAppendTree`], or the peaks preceding the last leaf for [`MerkleMountainRange`].
+pub trait MerkleAppendAccumulator<Hash: PartialEq + Copy, const SIBLINGS: usize>: Copy {
+    fn new_from_siblings<H: MerkleZeroHasher<Hash>>(next_index: u64, siblings: [Hash; SIBLINGS], value: Hash) -> QDogeResult<Self>;
+    fn get_next_index(&self) -> u64;
+    fn get_value(&self) -> Hash;
+    fn get_root<H: MerkleHasher<Hash>>(&self) -> Hash;
+    fn append<H: MerkleHasher<Hash>>(&mut self, value: Hash) -> QDogeResult<()>;
+    /// Reverts to the state where `index` was the last leaf appended, see the implementations for what `restore_data` holds.
+    fn revert_to_index<H: MerkleHasher<Hash>>(&mut self, index: u64, restore_data: &[Hash], value: Hash) -> QDogeResult<()>;
+}
+
+impl<Hash: PartialEq + Copy + ZeroableHash, const HEIGHT: usize> MerkleAppendAccumulator<Hash, HEIGHT> for FixedMerkleAppendTree<Hash, HEIGHT> {
+    fn new_from_siblings<H: MerkleZeroHasher<Hash>>(next_index: u64, siblings: [Hash; HEIGHT], value: Hash) -> QDogeResult<Self> {
+        Ok(Self::new_from_hasher::<H>(next_index, siblings, value))
+    }
+    fn get_next_index(&self) -> u64 {
+        FixedMerkleAppendTree::get_next_index(self)
+    }
+    fn get_value(&self) -> Hash {
+        FixedMerkleAppendTree::get_value(self)
+    }
+    fn get_root<H: MerkleHasher<Hash>>(&self) -> Hash {
+        FixedMerkleAppendTree::get_root::<H>(self)
+    }
+    fn append<H: MerkleHasher<Hash>>(&mut self, value: Hash) -> QDogeResult<()> {
+        FixedMerkleAppendTree::append::<H>(self, value);
+        Ok(())
+    }
+    fn revert_to_index<H: MerkleHasher<Hash>>(&mut self, index: u64, restore_data: &[Hash], value: Hash) -> QDogeResult<()> {
+        FixedMerkleAppendTree::revert_to_index::<H>(self, index, restore_data, value)
+    }
+}
+
+impl<Hash: PartialEq + Copy + ZeroableHash, const MAX_PEAKS: usize> MerkleAppendAccumulator<Hash, MAX_PEAKS> for MerkleMountainRange<Hash, MAX_PEAKS> {
+    fn new_from_siblings<H: MerkleZeroHasher<Hash>>(next_index: u64, siblings: [Hash; MAX_PEAKS], value: Hash) -> QDogeResult<Self> {
+        if next_index == 0 {
+            Ok(Self::new_empty())
+        } else {
+            Self::new_from_peaks::<H>(next_index - 1, siblings, value)
+        }
+    }
+    fn get_next_index(&self) -> u64 {
+        self.get_leaf_count()
+    }
+    fn get_value(&self) -> Hash {
+        MerkleMountainRange::get_value(self)
+    }
+    fn get_root<H: MerkleHasher<Hash>>(&self) -> Hash {
+        MerkleMountainRange::get_root::<H>(self)
+    }
+    fn append<H: MerkleHasher<Hash>>(&mut self, value: Hash) -> QDogeResult<()> {
+        MerkleMountainRange::append::<H>(self, value)
+    }
+    fn revert_to_index<H: MerkleHasher<Hash>>(&mut self, index: u64, restore_data: &[Hash], value: Hash) -> QDogeResult<()> {
+        self.rollback_to_size(index + 1, restore_data, value)
+    }
+}
@@ -0,0 +1,180 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use alloc::vec::Vec;
+use crate::hash::traits::{MerkleHasher, MerkleZeroHasher};
+
+/// Proves that the append tree with root `new_root` and `new_size` leaves starts with the
+/// `old_size` leaves of the tree with root `old_root` (Certificate Transparency style).
+///
+/// `siblings` is the merkle path of leaf `old_size - 1` in the new tree. Left siblings only cover old
+/// leaves, so they are shared by both trees, while the right siblings are zero subtrees in the old tree.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MerkleConsistencyProofCore<Hash: PartialEq + Copy> {
+    pub old_root: Hash,
+    pub new_root: Hash,
+
+    pub old_size: u64,
+    pub new_size: u64,
+
+    pub old_last_leaf: Hash,
+    pub siblings: Vec<Hash>,
+}
+
+impl<Hash: PartialEq + Copy> MerkleConsistencyProofCore<Hash> {
+    pub fn get_height(&self) -> usize {
+        self.siblings.len()
+    }
+    pub fn verify<H: MerkleZeroHasher<Hash>>(&self) -> bool {
+        let height = self.siblings.len();
+        if self.old_size > self.new_size || (height < 64 && self.new_size > (1u64 << height)) {
+            return false;
+        }
+        if self.old_size == 0 {
+            // every tree is an extension of the empty tree
+            return self.old_root == H::get_zero_hash(height);
+        }
+
+        let mut old_current = self.old_last_leaf;
+        let mut new_current = self.old_last_leaf;
+        let mut zero_hash = H::get_zero_hash(0);
+        let mut index = self.old_size - 1;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            if (index & 1) == 1 {
+                old_current = H::two_to_one(sibling, &old_current);
+                new_current = H::two_to_one(sibling, &new_current);
+            } else {
+                // a right subtree starting at or after new_size must still be empty in the new tree
+                let first_leaf = ((index as u128) + 1) << level;
+                if first_leaf >= self.new_size as u128 && *sibling != zero_hash {
+                    return false;
+                }
+                old_current = H::two_to_one(&old_current, &zero_hash);
+                new_current = H::two_to_one(&new_current, sibling);
+            }
+            zero_hash = H::two_to_one(&zero_hash, &zero_hash);
+            index >>= 1;
+        }
+        old_current == self.old_root && new_current == self.new_root
+    }
+}
+
+pub(crate) fn compute_zero_padded_subtree_root<Hash: PartialEq + Copy, H: MerkleHasher<Hash>>(
+    leaves: &[Hash],
+    height: usize,
+    zero_hashes: &[Hash],
+) -> Hash {
+    if leaves.is_empty() {
+        zero_hashes[height]
+    } else if height == 0 {
+        leaves[0]
+    } else {
+        let half = 1usize << (height - 1);
+        let (left, right) = leaves.split_at(leaves.len().min(half));
+        H::two_to_one(
+            &compute_zero_padded_subtree_root::<Hash, H>(left, height - 1, zero_hashes),
+            &compute_zero_padded_subtree_root::<Hash, H>(right, height - 1, zero_hashes),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use crate::{core_data::QHash256, hash::{merkle::fixed_append_tree::FixedMerkleAppendTree, sha256::QSha256Hasher}};
+
+    type TestTree = FixedMerkleAppendTree<QHash256, 8>;
+
+    fn random_leaves(count: usize) -> alloc::vec::Vec<QHash256> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        (0..count).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_consistency_proofs() {
+        let leaves = random_leaves(40);
+        for old_size in 0..leaves.len() {
+            let mut old_tree = TestTree::new_empty::<QSha256Hasher>();
+            for leaf in &leaves[..old_size] {
+                old_tree.append::<QSha256Hasher>(*leaf);
+            }
+            for new_size in old_size..=leaves.len() {
+                let mut new_tree = old_tree;
+                for leaf in &leaves[old_size..new_size] {
+                    new_tree.append::<QSha256Hasher>(*leaf);
+                }
+                let proof = old_tree.get_consistency_proof::<QSha256Hasher>(&leaves[old_size..new_size]);
+                assert_eq!(proof.old_root, old_tree.get_root::<QSha256Hasher>());
+                assert_eq!(proof.new_root, new_tree.get_root::<QSha256Hasher>());
+                assert!(proof.verify::<QSha256Hasher>(), "old_size: {}, new_size: {}", old_size, new_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_consistency_proof_rejects_rewritten_history() {
+        let leaves = random_leaves(21);
+        let mut old_tree = TestTree::new_empty::<QSha256Hasher>();
+        for leaf in &leaves[..13] {
+            old_tree.append::<QSha256Hasher>(*leaf);
+        }
+        let proof = old_tree.get_consistency_proof::<QSha256Hasher>(&leaves[13..]);
+        assert!(proof.verify::<QSha256Hasher>());
+
+        // a new tree which forked at leaf 10
+        let mut forked_tree = TestTree::new_empty::<QSha256Hasher>();
+        for leaf in &leaves[..10] {
+            forked_tree.append::<QSha256Hasher>(*leaf);
+        }
+        for leaf in &leaves[11..] {
+            forked_tree.append::<QSha256Hasher>(*leaf);
+        }
+        let mut forged = proof.clone();
+        forged.new_root = forked_tree.get_root::<QSha256Hasher>();
+        assert!(!forged.verify::<QSha256Hasher>());
+
+        let mut forged = proof.clone();
+        forged.siblings[1][0] ^= 1;
+        assert!(!forged.verify::<QSha256Hasher>());
+
+        // claiming fewer leaves than were appended
+        let mut forged = proof.clone();
+        forged.new_size = 14;
+        assert!(!forged.verify::<QSha256Hasher>());
+
+        let mut forged = proof;
+        forged.old_size = 22;
+        assert!(!forged.verify::<QSha256Hasher>());
+    }
+}
@@ -29,6 +29,7 @@ use borsh::{BorshSerialize, BorshDeserialize};
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+use alloc::vec::Vec;
 use crate::hash::traits::MerkleHasher;
 
 use super::utils::compute_root_merkle_proof_generic;
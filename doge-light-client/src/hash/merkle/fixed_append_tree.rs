@@ -35,12 +35,13 @@ use serde_with::serde_as;
 
 
 
+use alloc::vec::Vec;
 use zerocopy::little_endian::U64;
 use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
 
 use crate::{error::{DogeBridgeError, QDogeResult}, hash::traits::{get_zero_hashes_sized, MerkleHasher, MerkleZeroHasher, ZeroableHash}};
 
-use super::{append_tree::MerkleAppendTreeLevel, delta_merkle_proof::DeltaMerkleProofCore, merkle_proof::{MerkleProofCore, MerkleProofCorePartial}};
+use super::{append_tree::MerkleAppendTreeLevel, consistency_proof::{compute_zero_padded_subtree_root, MerkleConsistencyProofCore}, delta_merkle_proof::DeltaMerkleProofCore, merkle_proof::{MerkleProofCore, MerkleProofCorePartial}};
 
 
 /* 
@@ -170,27 +171,7 @@ impl<Hash: PartialEq + Copy, const HEIGHT: usize> FixedMerkleAppendTree<Hash, HE
                 right: zero_hashes[i],
                 zero_hash: zero_hashes[i],
             });
-
-            let mut current = value;
-            let mut current_index = next_index-1;
-            for i in 0..HEIGHT {
-                let sibling = siblings[i];
-                let swap = (current_index & 1) == 1;
-                let new_v = Hasher::two_to_one_swap(swap, &current, &sibling);
-
-                if swap {
-                    levels[i].left = sibling;
-                    levels[i].right = current;
-                    
-                }else{
-                    levels[i].left = current;
-                    levels[i].right = sibling;
-                }
-
-                current = new_v;
-                
-                current_index >>= 1;
-            }
+            set_levels_from_siblings::<Hash, Hasher>(&mut levels, next_index, &siblings, value);
             Self {
                 next_index: next_index.into(),
                 levels,
@@ -205,33 +186,14 @@ impl<Hash: PartialEq + Copy, const HEIGHT: usize> FixedMerkleAppendTree<Hash, HE
         HEIGHT as u8
     }
     pub fn get_value(&self) -> Hash {
-        let is_next_right = (self.next_index&1) == 1;
-        if is_next_right{
-            self.levels[0].left
-        }else{
-            self.levels[0].right
-        }
+        get_levels_value(self.get_next_index(), &self.levels)
     }
     pub fn get_root<Hasher: MerkleHasher<Hash>>(&self) -> Hash {
         self.levels.last().unwrap().get_hash::<Hasher>()
     }
 
     pub fn append<H: MerkleHasher<Hash>>(&mut self, new_value: Hash) {
-        let mut current = new_value;
-        let mut current_index = self.next_index;
-
-        for level in self.levels.iter_mut() {
-            let is_right_child = (current_index & 1) == 1;
-            if is_right_child {
-                level.right = current;
-            }else{
-                level.left = current;
-                level.right = level.zero_hash;
-            }
-            current = level.get_hash::<H>();
-            current_index >>= 1;
-        }
-        self.next_index += 1;
+        append_to_levels::<Hash, H>(&mut self.next_index, &mut self.levels, new_value)
     }
 
     pub fn append_delta_merkle_proof<H: MerkleHasher<Hash>>(&mut self, new_value: Hash) -> DeltaMerkleProofCore<Hash> {
@@ -308,64 +270,229 @@ impl<Hash: PartialEq + Copy, const HEIGHT: usize> FixedMerkleAppendTree<Hash, HE
         }
     }
 
-    pub fn revert_to_index<Hasher: MerkleHasher<Hash>>(&mut self, index: u64, changed_left_siblings: &[Hash], value: Hash) -> QDogeResult<()> {
-        if self.next_index == 0 || index >= (self.get_next_index()-1) {
-            return Err(DogeBridgeError::RevertIndexTooHigh);
-            //anyhow::bail!("Cannot revert to index greater than or equal to current index");
-        }
-
-        let mut current_index = (self.next_index)-1;
-        let mut revert_index = index;
-
-        let mut next_changed_left_sibling_index = 0;
+    /// Generates a proof that the tree obtained by appending `new_values` to `self` extends `self`.
+    pub fn get_consistency_proof<H: MerkleZeroHasher<Hash>>(&self, new_values: &[Hash]) -> MerkleConsistencyProofCore<Hash> {
+        let old_size = self.get_next_index();
+        let new_size = old_size + new_values.len() as u64;
 
-        let mut current_hash = value;
-        let mut i = 0;
+        let mut new_tree = *self;
+        for value in new_values {
+            new_tree.append::<H>(*value);
+        }
 
-        while i < HEIGHT && current_index != revert_index {
-            let is_right_child = (revert_index & 1) == 1;
-            if is_right_child {
-                if next_changed_left_sibling_index == changed_left_siblings.len() {
-                    return Err(DogeBridgeError::NotEnoughChangedLeftSiblings);
+        let zero_hashes = self.levels.iter().map(|x| x.zero_hash).collect::<Vec<_>>();
+        let (old_last_leaf, siblings) = if old_size == 0 {
+            (self.levels[0].zero_hash, zero_hashes.clone())
+        } else {
+            let mut siblings = Vec::with_capacity(HEIGHT);
+            let mut current_index = old_size - 1;
+            for (i, level) in self.levels.iter().enumerate() {
+                if (current_index & 1) == 1 {
+                    siblings.push(level.left);
+                } else {
+                    // leaves of the right subtree are all appended after old_size - 1
+                    let first_leaf = ((current_index as u128 + 1) << i) - old_size as u128;
+                    let start = (first_leaf.min(new_values.len() as u128)) as usize;
+                    let end = (first_leaf + (1u128 << i)).min(new_values.len() as u128) as usize;
+                    siblings.push(compute_zero_padded_subtree_root::<Hash, H>(&new_values[start..end], i, &zero_hashes));
                 }
-                self.levels[i].left = changed_left_siblings[next_changed_left_sibling_index];
-                self.levels[i].right = current_hash;
-                next_changed_left_sibling_index += 1;
-            }else{
-                self.levels[i].left = current_hash;
-                self.levels[i].right = self.levels[i].zero_hash;
-                //self.levels[i].left = self.levels[i].zero_hash;
+                current_index >>= 1;
             }
-            current_hash = self.levels[i].get_hash::<Hasher>();
-            current_index >>= 1;
-            revert_index >>= 1;
-            i += 1;
+            (self.get_value(), siblings)
+        };
+
+        MerkleConsistencyProofCore {
+            old_root: self.get_root::<H>(),
+            new_root: new_tree.get_root::<H>(),
+            old_size,
+            new_size,
+            old_last_leaf,
+            siblings,
         }
+    }
+
+    pub fn revert_to_index<Hasher: MerkleHasher<Hash>>(&mut self, index: u64, changed_left_siblings: &[Hash], value: Hash) -> QDogeResult<()> {
+        revert_levels_to_index::<Hash, Hasher>(&mut self.next_index, &mut self.levels, index, changed_left_siblings, value)
+    }
+
+}
 
-        if current_index != revert_index {
-            return Err(DogeBridgeError::RevertIndexNotPrefix);
+// The tree logic on a slice of levels, shared with chain states whose block tree height is only known at runtime.
+
+/// Fills in the path of the leaf `next_index - 1`, the levels must hold their zero hashes.
+///
+/// Levels above the given siblings are treated as having zero hash siblings, which extends a tree to a larger height.
+pub(crate) fn set_levels_from_siblings<Hash: PartialEq + Copy, Hasher: MerkleHasher<Hash>>(
+    levels: &mut [MerkleAppendTreeLevel<Hash>],
+    next_index: u64,
+    siblings: &[Hash],
+    value: Hash,
+) {
+    if next_index == 0 {
+        return;
+    }
+    let mut current = value;
+    let mut current_index = next_index-1;
+    for (i, level) in levels.iter_mut().enumerate() {
+        let sibling = siblings.get(i).copied().unwrap_or(level.zero_hash);
+        let swap = (current_index & 1) == 1;
+        let new_v = Hasher::two_to_one_swap(swap, &current, &sibling);
+
+        if swap {
+            level.left = sibling;
+            level.right = current;
+        }else{
+            level.left = current;
+            level.right = sibling;
         }
-        if next_changed_left_sibling_index != changed_left_siblings.len() {
-            return Err(DogeBridgeError::TooManyChangedLeftSiblings);
+
+        current = new_v;
+        current_index >>= 1;
+    }
+}
+
+pub(crate) fn get_levels_value<Hash: PartialEq + Copy>(next_index: u64, levels: &[MerkleAppendTreeLevel<Hash>]) -> Hash {
+    let is_next_right = (next_index&1) == 1;
+    if is_next_right{
+        levels[0].left
+    }else{
+        levels[0].right
+    }
+}
+
+pub(crate) fn append_to_levels<Hash: PartialEq + Copy, H: MerkleHasher<Hash>>(
+    next_index: &mut U64,
+    levels: &mut [MerkleAppendTreeLevel<Hash>],
+    new_value: Hash,
+) {
+    let mut current = new_value;
+    let mut current_index = *next_index;
+
+    for level in levels.iter_mut() {
+        let is_right_child = (current_index & 1) == 1;
+        if is_right_child {
+            level.right = current;
+        }else{
+            level.left = current;
+            level.right = level.zero_hash;
         }
+        current = level.get_hash::<H>();
+        current_index >>= 1;
+    }
+    *next_index += 1;
+}
 
-        while i < HEIGHT {
-            let is_right_child = (revert_index & 1) == 1;
-            if is_right_child {
-                self.levels[i].right = current_hash;
-            }else{
-                self.levels[i].left = current_hash;
-                self.levels[i].right = self.levels[i].zero_hash;
+pub(crate) fn revert_levels_to_index<Hash: PartialEq + Copy, Hasher: MerkleHasher<Hash>>(
+    next_index: &mut U64,
+    levels: &mut [MerkleAppendTreeLevel<Hash>],
+    index: u64,
+    changed_left_siblings: &[Hash],
+    value: Hash,
+) -> QDogeResult<()> {
+    let height = levels.len();
+    if *next_index == 0 || index >= (next_index.get()-1) {
+        return Err(DogeBridgeError::RevertIndexTooHigh);
+        //anyhow::bail!("Cannot revert to index greater than or equal to current index");
+    }
+
+    let mut current_index = next_index.get()-1;
+    let mut revert_index = index;
+
+    let mut next_changed_left_sibling_index = 0;
+
+    let mut current_hash = value;
+    let mut i = 0;
+
+    while i < height && current_index != revert_index {
+        let is_right_child = (revert_index & 1) == 1;
+        if is_right_child {
+            if next_changed_left_sibling_index == changed_left_siblings.len() {
+                return Err(DogeBridgeError::NotEnoughChangedLeftSiblings);
             }
-            current_hash = self.levels[i].get_hash::<Hasher>();
-            revert_index >>= 1;
-            i += 1;
+            levels[i].left = changed_left_siblings[next_changed_left_sibling_index];
+            levels[i].right = current_hash;
+            next_changed_left_sibling_index += 1;
+        }else{
+            levels[i].left = current_hash;
+            levels[i].right = levels[i].zero_hash;
+            //levels[i].left = levels[i].zero_hash;
         }
+        current_hash = levels[i].get_hash::<Hasher>();
+        current_index >>= 1;
+        revert_index >>= 1;
+        i += 1;
+    }
+
+    if current_index != revert_index {
+        return Err(DogeBridgeError::RevertIndexNotPrefix);
+    }
+    if next_changed_left_sibling_index != changed_left_siblings.len() {
+        return Err(DogeBridgeError::TooManyChangedLeftSiblings);
+    }
+
+    while i < height {
+        let is_right_child = (revert_index & 1) == 1;
+        if is_right_child {
+            levels[i].right = current_hash;
+        }else{
+            levels[i].left = current_hash;
+            levels[i].right = levels[i].zero_hash;
+        }
+        current_hash = levels[i].get_hash::<Hasher>();
+        revert_index >>= 1;
+        i += 1;
+    }
+    *next_index = (index + 1).into();
 
+    Ok(())
+}
 
+#[cfg(test)]
+mod tests {
+    use crate::{core_data::QHash256, hash::sha256::QSha256Hasher};
 
-        Ok(())
-        
+    use super::FixedMerkleAppendTree;
+
+    type TestTree = FixedMerkleAppendTree<QHash256, 8>;
+
+    fn tree_with_leaves(count: u8) -> TestTree {
+        let mut tree = TestTree::new_empty::<QSha256Hasher>();
+        for i in 0..count {
+            tree.append::<QSha256Hasher>([i; 32]);
+        }
+        tree
     }
 
+    #[test]
+    fn test_revert_to_index_then_append() {
+        let size = 23u8;
+        for index in 0..(size as u64 - 1) {
+            let expected = tree_with_leaves(index as u8 + 1);
+
+            // the left siblings of the reverted leaf on the levels where its path leaves the path of the old last leaf
+            let mut changed_left_siblings = alloc::vec::Vec::new();
+            let (mut current_index, mut revert_index) = (size as u64 - 1, index);
+            let mut level = 0;
+            while current_index != revert_index {
+                if revert_index & 1 == 1 {
+                    changed_left_siblings.push(expected.levels[level].left);
+                }
+                current_index >>= 1;
+                revert_index >>= 1;
+                level += 1;
+            }
+
+            let mut tree = tree_with_leaves(size);
+            tree.revert_to_index::<QSha256Hasher>(index, &changed_left_siblings, [index as u8; 32]).unwrap();
+            assert_eq!(tree.get_next_index(), index + 1);
+            assert_eq!(tree.get_root::<QSha256Hasher>(), expected.get_root::<QSha256Hasher>());
+            assert_eq!(tree.get_value(), [index as u8; 32]);
+
+            let mut expected = expected;
+            tree.append::<QSha256Hasher>([0xff; 32]);
+            expected.append::<QSha256Hasher>([0xff; 32]);
+            assert_eq!(tree.get_next_index(), index + 2);
+            assert_eq!(tree.get_root::<QSha256Hasher>(), expected.get_root::<QSha256Hasher>());
+        }
+    }
 }
\ No newline at end of file
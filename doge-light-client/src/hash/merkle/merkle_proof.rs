@@ -29,9 +29,10 @@ use borsh::{BorshSerialize, BorshDeserialize};
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+use alloc::vec::Vec;
 use crate::hash::traits::MerkleHasher;
 
-use super::{delta_merkle_proof::DeltaMerkleProofCore, utils::compute_root_merkle_proof_generic};
+use super::{delta_merkle_proof::DeltaMerkleProofCore, utils::{compute_root_merkle_proof_generic, get_btc_tx_tree_depth}};
 
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -106,6 +107,30 @@ impl<Hash: PartialEq + Copy> MerkleProofCore<Hash> {
         }
         current == self.root
     }
+    /// Verifies a transaction inclusion proof against a block with a known number of transactions.
+    ///
+    /// On top of [`Self::verify_btc_block_tx_tree`], the index and the number of siblings must agree with
+    /// `tx_count`, so an inner node can not be passed off as a transaction (or a 64 byte transaction as an
+    /// inner node), and a sibling may only equal the current node where the last node of an odd level is
+    /// duplicated (CVE-2012-2459).
+    pub fn verify_btc_block_tx_tree_with_tx_count<Hasher: MerkleHasher<Hash>>(&self, tx_count: u32) -> bool {
+        if self.index >= tx_count as u64 || self.siblings.len() != get_btc_tx_tree_depth(tx_count) {
+            return false;
+        }
+        let mut current = self.value;
+        let mut index_tracker = self.index;
+        let mut level_size = tx_count as u64;
+        for sibling in self.siblings.iter() {
+            let is_duplicated_last = (index_tracker & 1) == 0 && index_tracker + 1 == level_size;
+            if is_duplicated_last != sibling.eq(&current) {
+                return false;
+            }
+            current = Hasher::two_to_one_swap((index_tracker & 1) == 1, &current, sibling);
+            index_tracker >>= 1;
+            level_size = level_size.div_ceil(2);
+        }
+        current == self.root
+    }
     pub fn into_delta_merkle_proof(self) -> DeltaMerkleProofCore<Hash> {
         DeltaMerkleProofCore {
             old_root: self.root,
@@ -190,4 +215,47 @@ impl<Hash: PartialEq + Copy> From<&MerkleProofCore<Hash>> for MerkleProofCorePar
             siblings: value.siblings.clone(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::{core_data::QHash256, hash::{merkle::utils::{compute_btc_merkle_root_from_leaves, get_btc_merkle_proof_siblings_from_leaves}, sha256::QBTCHash256Hasher, traits::MerkleHasher}};
+
+    use super::MerkleProofCore;
+
+    #[test]
+    fn test_btc_tx_tree_rejects_mutation_and_inner_nodes() {
+        let txids: [QHash256; 5] = core::array::from_fn(|i| [i as u8 + 1; 32]);
+        let (root, mutated) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+        assert!(!mutated);
+        for (i, txid) in txids.iter().enumerate() {
+            let siblings = get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, i);
+            let proof = MerkleProofCore { root, value: *txid, index: i as u64, siblings };
+            assert!(proof.verify_btc_block_tx_tree::<QBTCHash256Hasher>());
+            assert!(proof.verify_btc_block_tx_tree_with_tx_count::<QBTCHash256Hasher>(5));
+            // only the right edge of the tree depends on the transaction count
+            assert_eq!(proof.verify_btc_block_tx_tree_with_tx_count::<QBTCHash256Hasher>(6), i < 4);
+            assert!(!proof.verify_btc_block_tx_tree_with_tx_count::<QBTCHash256Hasher>(4));
+        }
+
+        // [a, b, c, d, e, e] has the same root as [a, b, c, d, e]
+        let mut mutated_txids = txids.to_vec();
+        mutated_txids.push(txids[4]);
+        assert_eq!(compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&mutated_txids), (root, true));
+        let siblings = get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&mutated_txids, 5);
+        let duplicate_proof = MerkleProofCore { root, value: txids[4], index: 5, siblings };
+        assert!(duplicate_proof.verify::<QBTCHash256Hasher>());
+        assert!(!duplicate_proof.verify_btc_block_tx_tree::<QBTCHash256Hasher>());
+        assert!(!duplicate_proof.verify_btc_block_tx_tree_with_tx_count::<QBTCHash256Hasher>(6));
+
+        // an inner node (the 64 byte concatenation of two txids) passed off as a transaction
+        let full_siblings = get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 0);
+        let inner_node_proof = MerkleProofCore {
+            root,
+            value: QBTCHash256Hasher::two_to_one(&txids[0], &txids[1]),
+            index: 0,
+            siblings: full_siblings[1..].to_vec(),
+        };
+        assert!(inner_node_proof.verify_btc_block_tx_tree::<QBTCHash256Hasher>());
+        assert!(!inner_node_proof.verify_btc_block_tx_tree_with_tx_count::<QBTCHash256Hasher>(5));
+    }
+}
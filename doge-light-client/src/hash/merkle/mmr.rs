@@ -0,0 +1,402 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+#[cfg(feature = "serde")]
+use serde_with::serde_as;
+
+use alloc::vec::Vec;
+use zerocopy::little_endian::U64;
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{error::{DogeBridgeError, QDogeResult}, hash::traits::{MerkleHasher, ZeroableHash}};
+
+use super::utils::{compute_partial_merkle_root_from_leaves, compute_root_merkle_proof_generic};
+
+/// Merkle Mountain Range accumulator.
+///
+/// `peaks[h]` holds the root of the perfect subtree with `2^h` leaves when bit `h` of `leaf_count` is set,
+/// and the zero value otherwise. The root is obtained by bagging the peaks from the lowest to the highest.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromBytes, KnownLayout, IntoBytes, Unaligned, Immutable)]
+#[repr(C)]
+pub struct MerkleMountainRange<Hash: PartialEq + Copy, const MAX_PEAKS: usize> {
+    pub leaf_count: U64,
+    pub last_leaf: Hash,
+    pub peaks: [Hash; MAX_PEAKS],
+}
+
+#[cfg(feature = "serde")]
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "for<'de2> Hash: Deserialize<'de2>")]
+struct SerMerkleMountainRange<Hash: PartialEq + Copy + Serialize, const MAX_PEAKS: usize> {
+    pub leaf_count: U64,
+    pub last_leaf: Hash,
+    #[serde_as(as = "[_; MAX_PEAKS]")]
+    pub peaks: [Hash; MAX_PEAKS],
+}
+
+#[cfg(feature = "serde")]
+impl<Hash: PartialEq + Copy, const MAX_PEAKS: usize> serde::Serialize for MerkleMountainRange<Hash, MAX_PEAKS> where Hash: serde::Serialize + serde::de::DeserializeOwned {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        serde::Serialize::serialize(&SerMerkleMountainRange {
+            leaf_count: self.leaf_count,
+            last_leaf: self.last_leaf,
+            peaks: self.peaks,
+        }, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Hash: PartialEq + Copy, const MAX_PEAKS: usize> serde::Deserialize<'de> for MerkleMountainRange<Hash, MAX_PEAKS> where Hash: serde::Serialize + serde::de::DeserializeOwned {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de> {
+        let SerMerkleMountainRange { leaf_count, last_leaf, peaks } = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            leaf_count,
+            last_leaf,
+            peaks,
+        })
+    }
+}
+
+/// Proof that `value` is the leaf at `index` of the mountain range with `leaf_count` leaves and root `root`.
+///
+/// The root does not commit to the leaf count, so `leaf_count` must come from the same trusted source as the root
+/// (for the chain state, the block number of the record holding the root plus one).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MerkleMountainRangeProofCore<Hash: PartialEq + Copy> {
+    pub root: Hash,
+    pub value: Hash,
+
+    pub index: u64,
+    pub leaf_count: u64,
+
+    /// merkle path from the leaf to the peak containing it
+    pub siblings: Vec<Hash>,
+    /// the remaining peaks, highest first
+    pub peaks: Vec<Hash>,
+}
+
+/// Returns (peak height, position of the peak when ordered highest first, index of the leaf inside the peak).
+fn find_peak_for_index(leaf_count: u64, index: u64) -> Option<(usize, usize, u64)> {
+    if index >= leaf_count {
+        return None;
+    }
+    let mut start = 0u64;
+    let mut position = 0usize;
+    for height in (0..64).rev() {
+        if (leaf_count >> height) & 1 == 1 {
+            let size = 1u64 << height;
+            if index < start + size {
+                return Some((height, position, index - start));
+            }
+            start += size;
+            position += 1;
+        }
+    }
+    None
+}
+
+fn bag_peaks_highest_first<Hash: PartialEq + Copy, H: MerkleHasher<Hash>>(peaks: &[Hash]) -> Option<Hash> {
+    let (lowest, rest) = peaks.split_last()?;
+    Some(rest.iter().rev().fold(*lowest, |acc, peak| H::two_to_one(peak, &acc)))
+}
+
+impl<Hash: PartialEq + Copy> MerkleMountainRangeProofCore<Hash> {
+    pub fn verify<H: MerkleHasher<Hash>>(&self) -> bool {
+        let (height, position, local_index) = match find_peak_for_index(self.leaf_count, self.index) {
+            Some(x) => x,
+            None => return false,
+        };
+        if self.siblings.len() != height || self.peaks.len() + 1 != self.leaf_count.count_ones() as usize {
+            return false;
+        }
+        let peak = compute_root_merkle_proof_generic::<Hash, H>(self.value, local_index, &self.siblings);
+        let mut peaks = self.peaks.clone();
+        peaks.insert(position, peak);
+        bag_peaks_highest_first::<Hash, H>(&peaks) == Some(self.root)
+    }
+}
+
+impl<Hash: PartialEq + Copy + ZeroableHash, const MAX_PEAKS: usize> MerkleMountainRange<Hash, MAX_PEAKS> {
+    pub fn new_empty() -> Self {
+        Self {
+            leaf_count: 0.into(),
+            last_leaf: Hash::get_zero_value(),
+            peaks: [Hash::get_zero_value(); MAX_PEAKS],
+        }
+    }
+    /// Creates a range with `leaf_count + 1` leaves from the peaks of the first `leaf_count` leaves and the last leaf.
+    pub fn new_from_peaks<H: MerkleHasher<Hash>>(leaf_count: u64, peaks: [Hash; MAX_PEAKS], value: Hash) -> QDogeResult<Self> {
+        let mut range = Self {
+            leaf_count: leaf_count.into(),
+            last_leaf: Hash::get_zero_value(),
+            peaks: core::array::from_fn(|i| {
+                if i < 64 && (leaf_count >> i) & 1 == 1 {
+                    peaks[i]
+                } else {
+                    Hash::get_zero_value()
+                }
+            }),
+        };
+        range.append::<H>(value)?;
+        Ok(range)
+    }
+    pub fn from_leaves<H: MerkleHasher<Hash>>(leaves: &[Hash]) -> QDogeResult<Self> {
+        let mut range = Self::new_empty();
+        for leaf in leaves {
+            range.append::<H>(*leaf)?;
+        }
+        Ok(range)
+    }
+
+    /// Appends a leaf, failing with [`DogeBridgeError::MmrFull`] once the range holds `2^MAX_PEAKS - 1` leaves.
+    pub fn append<H: MerkleHasher<Hash>>(&mut self, value: Hash) -> QDogeResult<()> {
+        let leaf_count = self.get_leaf_count();
+        if MAX_PEAKS < 64 && leaf_count >= (1u64 << MAX_PEAKS) - 1 {
+            return Err(DogeBridgeError::MmrFull);
+        }
+        let mut current = value;
+        let mut height = 0;
+        while (leaf_count >> height) & 1 == 1 {
+            current = H::two_to_one(&self.peaks[height], &current);
+            self.peaks[height] = Hash::get_zero_value();
+            height += 1;
+        }
+        self.peaks[height] = current;
+        self.leaf_count = (leaf_count + 1).into();
+        self.last_leaf = value;
+        Ok(())
+    }
+
+    /// Rolls the range back to its first `leaf_count` leaves.
+    ///
+    /// Peaks of the smaller range which were merged into higher peaks since cannot be recomputed, so they
+    /// are supplied in `restored_peaks` (lowest first, see [`Self::get_rollback_peaks`]) and the caller
+    /// must check the resulting root against a root it already trusts.
+    pub fn rollback_to_size(&mut self, leaf_count: u64, restored_peaks: &[Hash], last_leaf: Hash) -> QDogeResult<()> {
+        let current_leaf_count = self.get_leaf_count();
+        if leaf_count >= current_leaf_count {
+            return Err(DogeBridgeError::MmrRollbackSizeTooHigh);
+        }
+        let mut next_restored_peak_index = 0;
+        let mut peaks = [Hash::get_zero_value(); MAX_PEAKS];
+        for (height, peak) in peaks.iter_mut().enumerate().take(64) {
+            if (leaf_count >> height) & 1 == 0 {
+                continue;
+            }
+            if (leaf_count >> height) == (current_leaf_count >> height) {
+                *peak = self.peaks[height];
+            } else {
+                if next_restored_peak_index == restored_peaks.len() {
+                    return Err(DogeBridgeError::MmrNotEnoughRestoredPeaks);
+                }
+                *peak = restored_peaks[next_restored_peak_index];
+                next_restored_peak_index += 1;
+            }
+        }
+        if next_restored_peak_index != restored_peaks.len() {
+            return Err(DogeBridgeError::MmrTooManyRestoredPeaks);
+        }
+        if leaf_count & 1 == 1 && peaks[0] != last_leaf {
+            return Err(DogeBridgeError::MmrLastLeafMismatch);
+        }
+
+        self.leaf_count = leaf_count.into();
+        self.peaks = peaks;
+        self.last_leaf = if leaf_count == 0 { Hash::get_zero_value() } else { last_leaf };
+        Ok(())
+    }
+
+    pub fn get_root<H: MerkleHasher<Hash>>(&self) -> Hash {
+        bag_peaks_highest_first::<Hash, H>(&self.get_peaks()).unwrap_or(Hash::get_zero_value())
+    }
+
+    /// Returns the peaks `rollback_to_size(leaf_count, ..)` needs to roll a range over `leaves` back to `leaf_count` leaves.
+    pub fn get_rollback_peaks<H: MerkleHasher<Hash>>(leaves: &[Hash], leaf_count: u64) -> QDogeResult<Vec<Hash>> {
+        let current_leaf_count = leaves.len() as u64;
+        let target = Self::from_leaves::<H>(&leaves[..leaf_count as usize])?;
+        Ok((0..MAX_PEAKS.min(64))
+            .filter(|height| {
+                (leaf_count >> height) & 1 == 1 && (leaf_count >> height) != (current_leaf_count >> height)
+            })
+            .map(|height| target.peaks[height])
+            .collect())
+    }
+
+    /// Generates an inclusion proof for the leaf at `index` of the range built from `leaves`.
+    pub fn get_inclusion_proof<H: MerkleHasher<Hash>>(leaves: &[Hash], index: u64) -> Option<MerkleMountainRangeProofCore<Hash>> {
+        let leaf_count = leaves.len() as u64;
+        let (height, position, local_index) = find_peak_for_index(leaf_count, index)?;
+
+        let peak_start = (index - local_index) as usize;
+        let mut level = leaves[peak_start..(peak_start + (1usize << height))].to_vec();
+        let mut siblings = Vec::with_capacity(height);
+        let mut current_index = local_index as usize;
+        while level.len() > 1 {
+            siblings.push(level[current_index ^ 1]);
+            level = level.chunks(2).map(|pair| H::two_to_one(&pair[0], &pair[1])).collect();
+            current_index >>= 1;
+        }
+
+        let mut peaks = Vec::with_capacity(leaf_count.count_ones() as usize);
+        let mut start = 0usize;
+        for peak_height in (0..64).rev() {
+            if (leaf_count >> peak_height) & 1 == 1 {
+                let size = 1usize << peak_height;
+                peaks.push(compute_partial_merkle_root_from_leaves::<Hash, H>(&leaves[start..start + size]));
+                start += size;
+            }
+        }
+        let root = bag_peaks_highest_first::<Hash, H>(&peaks)?;
+        peaks.remove(position);
+
+        Some(MerkleMountainRangeProofCore {
+            root,
+            value: leaves[index as usize],
+            index,
+            leaf_count,
+            siblings,
+            peaks,
+        })
+    }
+}
+
+impl<Hash: PartialEq + Copy, const MAX_PEAKS: usize> MerkleMountainRange<Hash, MAX_PEAKS> {
+    pub fn get_leaf_count(&self) -> u64 {
+        self.leaf_count.into()
+    }
+    pub fn get_value(&self) -> Hash {
+        self.last_leaf
+    }
+    /// Returns the peaks in bagging order, highest first.
+    pub fn get_peaks(&self) -> Vec<Hash> {
+        let leaf_count = self.get_leaf_count();
+        (0..MAX_PEAKS.min(64))
+            .rev()
+            .filter(|height| (leaf_count >> height) & 1 == 1)
+            .map(|height| self.peaks[height])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use crate::{core_data::QHash256, error::DogeBridgeError, hash::sha256::QSha256Hasher};
+
+    use super::MerkleMountainRange;
+
+    type TestRange = MerkleMountainRange<QHash256, 32>;
+
+    fn random_leaves(count: usize) -> alloc::vec::Vec<QHash256> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x6d6d72);
+        (0..count).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        let leaves = random_leaves(45);
+        for leaf_count in 1..=leaves.len() {
+            let range = TestRange::from_leaves::<QSha256Hasher>(&leaves[..leaf_count]).unwrap();
+            assert_eq!(range.get_value(), leaves[leaf_count - 1]);
+            for index in 0..leaf_count {
+                let proof = TestRange::get_inclusion_proof::<QSha256Hasher>(&leaves[..leaf_count], index as u64).unwrap();
+                assert_eq!(proof.root, range.get_root::<QSha256Hasher>());
+                assert!(proof.verify::<QSha256Hasher>());
+
+                let mut bad_proof = proof.clone();
+                bad_proof.value[0] ^= 1;
+                assert!(!bad_proof.verify::<QSha256Hasher>());
+                let mut bad_proof = proof;
+                bad_proof.index ^= 1;
+                if bad_proof.index < leaf_count as u64 {
+                    assert!(!bad_proof.verify::<QSha256Hasher>());
+                }
+            }
+        }
+        // the most recent leaf sits in the lowest peak
+        let proof = TestRange::get_inclusion_proof::<QSha256Hasher>(&leaves, 44).unwrap();
+        assert!(proof.siblings.is_empty());
+        let proof = TestRange::get_inclusion_proof::<QSha256Hasher>(&leaves, 0).unwrap();
+        assert_eq!(proof.siblings.len(), 5);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let leaves = random_leaves(37);
+        for leaf_count in 0..leaves.len() {
+            for current_leaf_count in (leaf_count + 1)..=leaves.len() {
+                let expected = TestRange::from_leaves::<QSha256Hasher>(&leaves[..leaf_count]).unwrap();
+                let mut range = TestRange::from_leaves::<QSha256Hasher>(&leaves[..current_leaf_count]).unwrap();
+                let restored_peaks = TestRange::get_rollback_peaks::<QSha256Hasher>(&leaves[..current_leaf_count], leaf_count as u64).unwrap();
+                let last_leaf = if leaf_count == 0 { [0u8; 32] } else { leaves[leaf_count - 1] };
+                range.rollback_to_size(leaf_count as u64, &restored_peaks, last_leaf).unwrap();
+                assert_eq!(range, expected);
+            }
+        }
+
+        let mut range = TestRange::from_leaves::<QSha256Hasher>(&leaves[..12]).unwrap();
+        assert_eq!(range.rollback_to_size(12, &[], leaves[11]), Err(DogeBridgeError::MmrRollbackSizeTooHigh));
+        assert_eq!(range.rollback_to_size(7, &[], leaves[6]), Err(DogeBridgeError::MmrNotEnoughRestoredPeaks));
+        let restored_peaks = TestRange::get_rollback_peaks::<QSha256Hasher>(&leaves[..12], 7).unwrap();
+        assert_eq!(range.rollback_to_size(7, &restored_peaks, leaves[5]), Err(DogeBridgeError::MmrLastLeafMismatch));
+    }
+
+    #[test]
+    fn test_new_from_peaks() {
+        let leaves = random_leaves(23);
+        let prefix = TestRange::from_leaves::<QSha256Hasher>(&leaves[..22]).unwrap();
+        let range = TestRange::new_from_peaks::<QSha256Hasher>(22, prefix.peaks, leaves[22]).unwrap();
+        assert_eq!(range, TestRange::from_leaves::<QSha256Hasher>(&leaves).unwrap());
+    }
+
+    #[test]
+    fn test_append_full() {
+        let leaves = random_leaves(8);
+        let mut range = MerkleMountainRange::<QHash256, 3>::from_leaves::<QSha256Hasher>(&leaves[..7]).unwrap();
+        let full = range;
+        assert_eq!(range.append::<QSha256Hasher>(leaves[7]), Err(DogeBridgeError::MmrFull));
+        assert_eq!(range, full);
+        assert_eq!(
+            MerkleMountainRange::<QHash256, 3>::new_from_peaks::<QSha256Hasher>(7, full.peaks, leaves[7]),
+            Err(DogeBridgeError::MmrFull)
+        );
+    }
+}
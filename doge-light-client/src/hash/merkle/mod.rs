@@ -29,3 +29,6 @@ pub mod merkle_proof;
 pub mod delta_merkle_proof;
 pub mod append_tree;
 pub mod fixed_append_tree;
+pub mod consistency_proof;
+pub mod mmr;
+pub mod accumulator;
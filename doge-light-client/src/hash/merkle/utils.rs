@@ -24,7 +24,8 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
-use crate::hash::traits::MerkleHasher;
+use alloc::{vec, vec::Vec};
+use crate::hash::traits::{MerkleHasher, ZeroableHash};
 
 
 pub fn compute_root_merkle_proof_generic<Hash: PartialEq + Copy, H: MerkleHasher<Hash>>(
@@ -60,4 +61,54 @@ pub fn compute_partial_merkle_root_from_leaves<
         current = next;
     }
     current[0]
-}
\ No newline at end of file
+}
+/// Number of levels of the BTC style transaction merkle tree of a block with `tx_count` transactions.
+pub fn get_btc_tx_tree_depth(tx_count: u32) -> usize {
+    (tx_count.max(1) as u64).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Computes the BTC style merkle root of `leaves`, duplicating the last node of odd sized levels.
+///
+/// Also returns whether the tree is mutated (CVE-2012-2459): two equal siblings which are not the result of
+/// duplicating the last node, in which case a different list of leaves has the same root.
+pub fn compute_btc_merkle_root_from_leaves<Hash: PartialEq + Copy + ZeroableHash, Hasher: MerkleHasher<Hash>>(
+    leaves: &[Hash],
+) -> (Hash, bool) {
+    if leaves.is_empty() {
+        return (Hash::get_zero_value(), false);
+    }
+    let mut mutated = false;
+    let mut current = leaves.to_vec();
+    while current.len() > 1 {
+        let mut next = Vec::with_capacity(current.len().div_ceil(2));
+        for pair in current.chunks(2) {
+            if pair.len() == 2 {
+                mutated |= pair[0] == pair[1];
+                next.push(Hasher::two_to_one(&pair[0], &pair[1]));
+            } else {
+                next.push(Hasher::two_to_one(&pair[0], &pair[0]));
+            }
+        }
+        current = next;
+    }
+    (current[0], mutated)
+}
+
+/// Returns the siblings of the leaf at `index` in the BTC style merkle tree of `leaves`.
+pub fn get_btc_merkle_proof_siblings_from_leaves<Hash: PartialEq + Copy, Hasher: MerkleHasher<Hash>>(
+    leaves: &[Hash],
+    index: usize,
+) -> Vec<Hash> {
+    let mut siblings = Vec::new();
+    let mut current = leaves.to_vec();
+    let mut index = index;
+    while current.len() > 1 {
+        siblings.push(*current.get(index ^ 1).unwrap_or(&current[index]));
+        current = current
+            .chunks(2)
+            .map(|pair| Hasher::two_to_one(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+        index >>= 1;
+    }
+    siblings
+}
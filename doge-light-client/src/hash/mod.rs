@@ -26,8 +26,14 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 
 pub mod sha256;
 pub mod sha256_impl;
+pub mod sha256_portable;
 pub mod ripemd160_impl;
 pub mod traits;
 pub mod ripemd160;
+pub mod sha1;
 pub mod scrypt_doge;
+#[cfg(feature = "keccak")]
+pub mod keccak256;
+pub mod poseidon_bn254;
+mod poseidon_bn254_constants;
 pub mod merkle;
\ No newline at end of file
@@ -0,0 +1,243 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Poseidon hasher over the BN254 scalar field, compatible with circomlib's `Poseidon(2)`.
+//!
+//! Hashes are 32-byte big-endian field elements. Inputs which are not canonical field elements (>= the modulus)
+//! are reduced modulo the field size, so circuits verifying trees over raw block hashes must reduce their leaves too.
+
+use crate::core_data::QHash256;
+
+use super::{
+    poseidon_bn254_constants::{POSEIDON_BN254_T3_MDS, POSEIDON_BN254_T3_ROUND_CONSTANTS},
+    traits::{iterate_merkle_hasher, BytesHasher, MerkleHasher, MerkleZeroHasher},
+};
+
+const POSEIDON_T3_FULL_ROUNDS: usize = 8;
+const POSEIDON_T3_PARTIAL_ROUNDS: usize = 57;
+
+/// bytes absorbed per field element in [`QPoseidonBN254Hasher::hash_bytes`], small enough to never exceed the modulus
+const POSEIDON_BYTES_PER_ELEMENT: usize = 31;
+
+const MODULUS: [u64; 4] = [0x43e1f593f0000001, 0x2833e84879b97091, 0xb85045b68181585d, 0x30644e72e131a029];
+// -MODULUS^-1 mod 2^64
+const INV: u64 = 0xc2e1f593efffffff;
+// 2^512 mod MODULUS
+const R2: [u64; 4] = [0x1bb8e645ae216da7, 0x53fe3ab1e35c59e3, 0x8c49833d53bb8085, 0x0216d0b17f4e44a5];
+
+/// element of the BN254 scalar field in Montgomery form, little-endian limbs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct BN254Fr([u64; 4]);
+
+fn limbs_geq(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    for i in (0..4).rev() {
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    true
+}
+
+fn limbs_sub(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut borrow = false;
+    core::array::from_fn(|i| {
+        let (v, b1) = a[i].overflowing_sub(b[i]);
+        let (v, b2) = v.overflowing_sub(borrow as u64);
+        borrow = b1 || b2;
+        v
+    })
+}
+
+impl BN254Fr {
+    const ZERO: Self = Self([0; 4]);
+
+    fn from_raw_limbs(mut limbs: [u64; 4]) -> Self {
+        while limbs_geq(&limbs, &MODULUS) {
+            limbs = limbs_sub(&limbs, &MODULUS);
+        }
+        Self(limbs).mul(&Self(R2))
+    }
+    fn from_u64(value: u64) -> Self {
+        Self::from_raw_limbs([value, 0, 0, 0])
+    }
+    fn from_be_bytes_reduced(bytes: &[u8; 32]) -> Self {
+        Self::from_raw_limbs(core::array::from_fn(|i| {
+            u64::from_be_bytes(bytes[(24 - i * 8)..(32 - i * 8)].try_into().unwrap())
+        }))
+    }
+    fn to_be_bytes(self) -> [u8; 32] {
+        let limbs = self.mul(&Self([1, 0, 0, 0])).0;
+        let mut bytes = [0u8; 32];
+        for (i, limb) in limbs.iter().enumerate() {
+            bytes[(24 - i * 8)..(32 - i * 8)].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+    fn add(&self, other: &Self) -> Self {
+        let mut carry = false;
+        let sum: [u64; 4] = core::array::from_fn(|i| {
+            let (v, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (v, c2) = v.overflowing_add(carry as u64);
+            carry = c1 || c2;
+            v
+        });
+        // the modulus is below 2^254 so the sum of two reduced elements never overflows 256 bits
+        if limbs_geq(&sum, &MODULUS) {
+            Self(limbs_sub(&sum, &MODULUS))
+        } else {
+            Self(sum)
+        }
+    }
+    /// Montgomery multiplication (CIOS)
+    fn mul(&self, other: &Self) -> Self {
+        let mut t = [0u64; 6];
+        for b in other.0 {
+            let mut carry = 0u64;
+            for (t_j, a_j) in t.iter_mut().zip(self.0) {
+                let v = *t_j as u128 + (a_j as u128) * (b as u128) + carry as u128;
+                *t_j = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[4] as u128 + carry as u128;
+            t[4] = v as u64;
+            t[5] = (v >> 64) as u64;
+
+            let m = t[0].wrapping_mul(INV);
+            let mut carry = ((t[0] as u128 + (m as u128) * (MODULUS[0] as u128)) >> 64) as u64;
+            for j in 1..4 {
+                let v = t[j] as u128 + (m as u128) * (MODULUS[j] as u128) + carry as u128;
+                t[j - 1] = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[4] as u128 + carry as u128;
+            t[3] = v as u64;
+            t[4] = t[5] + (v >> 64) as u64;
+        }
+        let result = [t[0], t[1], t[2], t[3]];
+        if t[4] != 0 || limbs_geq(&result, &MODULUS) {
+            Self(limbs_sub(&result, &MODULUS))
+        } else {
+            Self(result)
+        }
+    }
+    fn pow5(&self) -> Self {
+        let x2 = self.mul(self);
+        x2.mul(&x2).mul(self)
+    }
+}
+
+fn poseidon_bn254_t3_permute(state: &mut [BN254Fr; 3]) {
+    let half_full_rounds = POSEIDON_T3_FULL_ROUNDS / 2;
+    for round in 0..(POSEIDON_T3_FULL_ROUNDS + POSEIDON_T3_PARTIAL_ROUNDS) {
+        for (i, x) in state.iter_mut().enumerate() {
+            *x = x.add(&BN254Fr(POSEIDON_BN254_T3_ROUND_CONSTANTS[round * 3 + i]));
+        }
+        if round < half_full_rounds || round >= half_full_rounds + POSEIDON_T3_PARTIAL_ROUNDS {
+            for x in state.iter_mut() {
+                *x = x.pow5();
+            }
+        } else {
+            state[0] = state[0].pow5();
+        }
+        *state = core::array::from_fn(|i| {
+            state
+                .iter()
+                .zip(POSEIDON_BN254_T3_MDS[i])
+                .fold(BN254Fr::ZERO, |acc, (x, m)| acc.add(&x.mul(&BN254Fr(m))))
+        });
+    }
+}
+
+fn poseidon_bn254_hash_two(left: BN254Fr, right: BN254Fr) -> BN254Fr {
+    let mut state = [BN254Fr::ZERO, left, right];
+    poseidon_bn254_t3_permute(&mut state);
+    state[0]
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct QPoseidonBN254Hasher;
+
+impl BytesHasher<QHash256> for QPoseidonBN254Hasher {
+    /// Absorbs `data` in 31-byte big-endian chunks, `h = poseidon(h, chunk)` starting from zero, and finishes with `poseidon(h, data.len())`.
+    fn hash_bytes(data: &[u8]) -> QHash256 {
+        let mut current = BN254Fr::ZERO;
+        for chunk in data.chunks(POSEIDON_BYTES_PER_ELEMENT) {
+            let mut bytes = [0u8; 32];
+            bytes[(32 - chunk.len())..].copy_from_slice(chunk);
+            current = poseidon_bn254_hash_two(current, BN254Fr::from_be_bytes_reduced(&bytes));
+        }
+        poseidon_bn254_hash_two(current, BN254Fr::from_u64(data.len() as u64)).to_be_bytes()
+    }
+}
+impl MerkleHasher<QHash256> for QPoseidonBN254Hasher {
+    fn two_to_one(left: &QHash256, right: &QHash256) -> QHash256 {
+        poseidon_bn254_hash_two(
+            BN254Fr::from_be_bytes_reduced(left),
+            BN254Fr::from_be_bytes_reduced(right),
+        )
+        .to_be_bytes()
+    }
+}
+impl MerkleZeroHasher<QHash256> for QPoseidonBN254Hasher {
+    fn get_zero_hash(reverse_level: usize) -> QHash256 {
+        iterate_merkle_hasher::<QHash256, Self>([0u8; 32], reverse_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use crate::hash::traits::MerkleHasher;
+
+    use super::{BN254Fr, QPoseidonBN254Hasher, MODULUS};
+
+    fn be_u64(value: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[24..32].copy_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_poseidon_bn254_circom_vector() {
+        // circomlibjs: poseidon([1, 2])
+        assert_eq!(
+            QPoseidonBN254Hasher::two_to_one(&be_u64(1), &be_u64(2)),
+            hex!("115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a")
+        );
+    }
+
+    #[test]
+    fn test_poseidon_bn254_reduces_inputs() {
+        let mut modulus_bytes = [0u8; 32];
+        for (i, limb) in MODULUS.iter().enumerate() {
+            modulus_bytes[(24 - i * 8)..(32 - i * 8)].copy_from_slice(&limb.to_be_bytes());
+        }
+        assert_eq!(BN254Fr::from_be_bytes_reduced(&modulus_bytes), BN254Fr::ZERO);
+        assert_eq!(BN254Fr::from_be_bytes_reduced(&[0xff; 32]).to_be_bytes(), hex!("0e0a77c19a07df2f666ea36f7879462e36fc76959f60cd29ac96341c4ffffffa"));
+        assert_eq!(BN254Fr::from_u64(7).to_be_bytes(), be_u64(7));
+    }
+}
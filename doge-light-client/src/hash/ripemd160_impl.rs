@@ -26,10 +26,10 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 
 use ripemd::{Digest, Ripemd160};
 
-
+// solana has no RIPEMD-160 syscall, so the pure-rust ripemd crate is used by every build
 pub fn hash_impl_ripemd160_bytes(bytes: &[u8]) -> [u8; 20] {
     let mut hasher = Ripemd160::new();
     hasher.update(bytes);
     let result = hasher.finalize();
     result.into()
-}
\ No newline at end of file
+}
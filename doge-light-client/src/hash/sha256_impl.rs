@@ -24,18 +24,110 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+// SHA-256 backends, the default is picked by feature flags:
+// solprogram => solana sha256 syscall, sha2 => sha2 crate, otherwise => portable pure-rust implementation
+
 #[cfg(all(not(feature = "solprogram"),feature = "sha2"))]
 use sha2::{Digest, Sha256};
 
+use super::sha256_portable::portable_sha256_bytes;
+
+pub trait Sha256Backend {
+    fn sha256_bytes(bytes: &[u8]) -> [u8; 32];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct PortableSha256Backend;
+
+impl Sha256Backend for PortableSha256Backend {
+    fn sha256_bytes(bytes: &[u8]) -> [u8; 32] {
+        portable_sha256_bytes(bytes)
+    }
+}
+
 #[cfg(all(not(feature = "solprogram"),feature = "sha2"))]
-pub fn hash_impl_sha256_bytes(bytes: &[u8]) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(bytes);
-    let result = hasher.finalize();
-    result.into()
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Sha2CrateSha256Backend;
+
+#[cfg(all(not(feature = "solprogram"),feature = "sha2"))]
+impl Sha256Backend for Sha2CrateSha256Backend {
+    fn sha256_bytes(bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        let result = hasher.finalize();
+        result.into()
+    }
+}
+
+#[cfg(feature = "solprogram")]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct SolanaSha256Backend;
+
+#[cfg(feature = "solprogram")]
+impl Sha256Backend for SolanaSha256Backend {
+    fn sha256_bytes(bytes: &[u8]) -> [u8; 32] {
+        solana_program::hash::hash(bytes).to_bytes()
+    }
 }
 
 #[cfg(feature = "solprogram")]
+pub type DefaultSha256Backend = SolanaSha256Backend;
+
+#[cfg(all(not(feature = "solprogram"),feature = "sha2"))]
+pub type DefaultSha256Backend = Sha2CrateSha256Backend;
+
+#[cfg(all(not(feature = "solprogram"),not(feature = "sha2")))]
+pub type DefaultSha256Backend = PortableSha256Backend;
+
 pub fn hash_impl_sha256_bytes(bytes: &[u8]) -> [u8; 32] {
-    solana_program::hash::hash(bytes).to_bytes()
-}
\ No newline at end of file
+    DefaultSha256Backend::sha256_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, RngCore};
+    use sha2::{Digest, Sha256};
+
+    use super::{hash_impl_sha256_bytes, PortableSha256Backend, Sha256Backend};
+
+    fn sha2_crate_sha256(bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        hasher.finalize().into()
+    }
+
+    #[test]
+    fn test_portable_sha256_known_vectors() {
+        assert_eq!(
+            PortableSha256Backend::sha256_bytes(b""),
+            hex_literal::hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            PortableSha256Backend::sha256_bytes(b"abc"),
+            hex_literal::hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            PortableSha256Backend::sha256_bytes(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            hex_literal::hex!("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")
+        );
+        assert_eq!(
+            PortableSha256Backend::sha256_bytes(&[b'a'; 1_000_000]),
+            hex_literal::hex!("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")
+        );
+    }
+
+    #[test]
+    fn test_backends_match() {
+        let mut rng = thread_rng();
+        // cover every padding boundary (55/56/63/64 bytes) a few blocks deep
+        for len in 0..300 {
+            let mut data = vec![0u8; len];
+            rng.fill_bytes(&mut data);
+            let expected = sha2_crate_sha256(&data);
+            assert_eq!(PortableSha256Backend::sha256_bytes(&data), expected, "portable mismatch for len {}", len);
+            assert_eq!(hash_impl_sha256_bytes(&data), expected, "default backend mismatch for len {}", len);
+            #[cfg(feature = "solprogram")]
+            assert_eq!(super::SolanaSha256Backend::sha256_bytes(&data), expected, "solana mismatch for len {}", len);
+        }
+    }
+}
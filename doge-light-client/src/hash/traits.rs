@@ -24,6 +24,8 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+use alloc::vec::Vec;
+
 pub trait ZeroableHash: Sized + Copy + Clone {
     fn get_zero_value() -> Self;
 }
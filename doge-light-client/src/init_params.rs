@@ -29,12 +29,14 @@ use borsh::{BorshDeserialize, BorshSerialize};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use core::marker::PhantomData;
+
 use crate::{
     block_data_tracker::BlockDataRecord,
     core_data::{QDogeBlock, QDogeBlockHeader, QHash256},
     hash::{
         sha256::QSha256Hasher,
-        traits::MerkleHasher,
+        traits::{MerkleHasher, ZeroableHash},
     },
 };
 
@@ -98,19 +100,22 @@ pub struct InitBlockDataIBC<const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize, con
 pub struct InitBlockDataIBC<
     const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
     const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeHasher = QSha256Hasher,
 > {
     pub records: [InitBlockDataRecord; QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE],
     pub tracker_tree_siblings: [QHash256; QDOGE_BRIDGE_BLOCK_TREE_HEIGHT],
     pub tip_block_number: u32,
+    _block_tree_hasher: PhantomData<BlockTreeHasher>,
 }
 
-type QBlockTreeTrackerHasher = QSha256Hasher;
-
 pub fn get_empty_siblings<const N: usize>() -> [QHash256; N] {
-    let mut siblings = [QHash256::default(); N];
-    let mut current = QHash256::default();
+    get_empty_siblings_with_hasher::<QSha256Hasher, N>()
+}
+pub fn get_empty_siblings_with_hasher<BlockTreeHasher: MerkleHasher<QHash256>, const N: usize>() -> [QHash256; N] {
+    let mut siblings = [QHash256::get_zero_value(); N];
+    let mut current = QHash256::get_zero_value();
     for i in 1..N {
-        current = QBlockTreeTrackerHasher::two_to_one(&current, &current);
+        current = BlockTreeHasher::two_to_one(&current, &current);
         siblings[i] = current;
     }
     siblings
@@ -118,7 +123,8 @@ pub fn get_empty_siblings<const N: usize>() -> [QHash256; N] {
 impl<
         const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
         const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
-    > InitBlockDataIBC<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>
+        BlockTreeHasher: MerkleHasher<QHash256>,
+    > InitBlockDataIBC<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT, BlockTreeHasher>
 {
     pub fn new_from_blocks(
         blocks: &[QDogeBlock; QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE],
@@ -134,6 +140,7 @@ impl<
             }),
             tracker_tree_siblings,
             tip_block_number,
+            _block_tree_hasher: PhantomData,
         }
     }
     pub fn new_from_block_headers(
@@ -150,6 +157,7 @@ impl<
             }),
             tracker_tree_siblings,
             tip_block_number,
+            _block_tree_hasher: PhantomData,
         }
     }
     pub fn new_from_blocks_empty_tree(
@@ -158,7 +166,7 @@ impl<
     ) -> Self {
         Self::new_from_blocks(
             blocks,
-            get_empty_siblings::<QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>(),
+            get_empty_siblings_with_hasher::<BlockTreeHasher, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>(),
             tip_block_number,
         )
     }
@@ -168,7 +176,7 @@ impl<
     ) -> Self {
         Self::new_from_block_headers(
             headers,
-            get_empty_siblings::<QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>(),
+            get_empty_siblings_with_hasher::<BlockTreeHasher, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>(),
             tip_block_number,
         )
     }
@@ -24,6 +24,10 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod logic;
 pub mod doge;
 pub mod core_data;
@@ -34,4 +38,11 @@ pub mod network_params;
 pub mod error;
 pub mod block_data_tracker;
 pub mod init_params;
-pub mod chain_state;
\ No newline at end of file
+pub mod chain_state;
+pub mod chain_state_view;
+pub mod state_envelope;
+pub mod state_diff;
+pub mod state_commitment;
+
+#[cfg(test)]
+mod test_fixtures;
\ No newline at end of file
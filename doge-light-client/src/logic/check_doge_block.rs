@@ -38,14 +38,7 @@ pub fn check_block_header_err<NC: DogeNetworkConfig>(
     first_block_time: u32,
     known_pow_block_hash: Option<QHash256>,
 ) -> QDogeResult<()> {
-    if block_header.header.is_aux_pow() != block_header.aux_pow.is_some() {
-        return Err(DogeBridgeError::AuxPowVersionBitsMismatch);
-    }
-    if NC::NETWORK_PARAMS.strict_chain_id
-        && NC::NETWORK_PARAMS.aux_pow_chain_id != block_header.header.get_chain_id()
-    {
-        return Err(DogeBridgeError::AuxPowChainIdMismatch);
-    }
+    check_block_header_version_err::<NC>(block_header)?;
     let expected_difficulty_bits = get_next_work_required::<NC>(
         last_height,
         last_block_time as i64,
@@ -56,6 +49,34 @@ pub fn check_block_header_err<NC: DogeNetworkConfig>(
     if expected_difficulty_bits != block_header.header.bits {
         return Err(DogeBridgeError::DifficutlyBitsMismatch);
     }
+    check_block_header_pow_only_err::<NC>(block_header, known_pow_block_hash)
+}
+
+/// Checks the proof of work (and aux pow) of a header against its own difficulty bits, without checking that the bits follow from the previous blocks.
+pub fn check_block_header_pow_err<NC: DogeNetworkConfig>(
+    block_header: &QDogeBlockHeader,
+    known_pow_block_hash: Option<QHash256>,
+) -> QDogeResult<()> {
+    check_block_header_version_err::<NC>(block_header)?;
+    check_block_header_pow_only_err::<NC>(block_header, known_pow_block_hash)
+}
+
+fn check_block_header_version_err<NC: DogeNetworkConfig>(block_header: &QDogeBlockHeader) -> QDogeResult<()> {
+    if block_header.header.is_aux_pow() != block_header.aux_pow.is_some() {
+        return Err(DogeBridgeError::AuxPowVersionBitsMismatch);
+    }
+    if NC::NETWORK_PARAMS.strict_chain_id
+        && NC::NETWORK_PARAMS.aux_pow_chain_id != block_header.header.get_chain_id()
+    {
+        return Err(DogeBridgeError::AuxPowChainIdMismatch);
+    }
+    Ok(())
+}
+
+fn check_block_header_pow_only_err<NC: DogeNetworkConfig>(
+    block_header: &QDogeBlockHeader,
+    known_pow_block_hash: Option<QHash256>,
+) -> QDogeResult<()> {
     if block_header.aux_pow.is_none() {
         if !check_proof_of_work::<NC>(if known_pow_block_hash.is_some() {
             known_pow_block_hash.unwrap()
@@ -87,3 +108,43 @@ pub fn check_block_header_err<NC: DogeNetworkConfig>(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::DogeMainNetConfig,
+        core_data::{QDogeBlockHeader, QStandardBlockHeader},
+        error::DogeBridgeError,
+    };
+
+    use super::check_block_header_err;
+
+    #[test]
+    fn test_aux_pow_version_checked_before_difficulty() {
+        // claims aux pow without carrying one and has the wrong difficulty bits, the aux pow error wins
+        let header = QDogeBlockHeader {
+            header: QStandardBlockHeader {
+                version: 0x00620104,
+                bits: 0x1e0fffff,
+                ..Default::default()
+            },
+            aux_pow: None,
+        };
+        assert_eq!(
+            check_block_header_err::<DogeMainNetConfig>(5610330, &header, 1_700_000_000, 0x1a01fdb9, 1_700_000_000 - 60, None),
+            Err(DogeBridgeError::AuxPowVersionBitsMismatch)
+        );
+        let header = QDogeBlockHeader {
+            header: QStandardBlockHeader {
+                version: 0x00010004,
+                bits: 0x1e0fffff,
+                ..Default::default()
+            },
+            aux_pow: None,
+        };
+        assert_eq!(
+            check_block_header_err::<DogeMainNetConfig>(5610330, &header, 1_700_000_000, 0x1a01fdb9, 1_700_000_000 - 60, None),
+            Err(DogeBridgeError::AuxPowChainIdMismatch)
+        );
+    }
+}
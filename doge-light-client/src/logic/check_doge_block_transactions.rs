@@ -0,0 +1,309 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use alloc::vec::Vec;
+
+use crate::{
+    constants::{DogeNetworkConfig, DOGE_MAX_BLOCK_SERIALIZED_SIZE, DOGE_MAX_BLOCK_SIGOPS, DOGE_MAX_MONEY},
+    core_data::{QDogeBlock, QHash256},
+    doge::{script::get_script_sig_op_count, transaction::BTCTransaction},
+    error::{DogeBridgeError, QDogeResult},
+    hash::{merkle::utils::compute_btc_merkle_root_from_leaves, sha256::QBTCHash256Hasher},
+};
+
+use super::subsidy::get_block_subsidy;
+
+/// Computes the BTC style transaction merkle root of a block and whether the transaction tree is mutated.
+pub fn compute_block_tx_merkle_root(transactions: &[BTCTransaction]) -> (QHash256, bool) {
+    let txids = transactions.iter().map(|tx| tx.get_hash()).collect::<Vec<_>>();
+    compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids)
+}
+
+/// Counts the legacy signature operations of all the input and output scripts of a transaction.
+pub fn get_legacy_transaction_sig_op_count(transaction: &BTCTransaction) -> usize {
+    transaction.inputs.iter().map(|input| get_script_sig_op_count(&input.script, false)).sum::<usize>()
+        + transaction.outputs.iter().map(|output| get_script_sig_op_count(&output.script, false)).sum::<usize>()
+}
+
+/// Context free transaction checks, equivalent to `CheckTransaction` in dogecoin core.
+pub fn check_transaction_err(transaction: &BTCTransaction) -> QDogeResult<()> {
+    if transaction.inputs.is_empty() {
+        return Err(DogeBridgeError::TransactionNoInputs);
+    }
+    if transaction.outputs.is_empty() {
+        return Err(DogeBridgeError::TransactionNoOutputs);
+    }
+    get_total_output_value(transaction)?;
+    let mut outpoints = transaction.inputs.iter().map(|input| (input.hash, input.index)).collect::<Vec<_>>();
+    outpoints.sort_unstable();
+    if outpoints.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(DogeBridgeError::TransactionDuplicateInputs);
+    }
+    if transaction.is_coinbase() {
+        let script_len = transaction.inputs[0].script.len();
+        if !(2..=100).contains(&script_len) {
+            return Err(DogeBridgeError::CoinbaseScriptSizeOutOfRange);
+        }
+    } else if transaction.inputs.iter().any(|input| input.is_null_outpoint()) {
+        return Err(DogeBridgeError::TransactionNullPrevout);
+    }
+    Ok(())
+}
+
+/// Checks the transactions of a block against its header, equivalent to the transaction checks of `CheckBlock` in dogecoin core:
+/// the transactions hash to the header's merkle root without a mutated tree, the block is within the size and sigop limits,
+/// the first (and only the first) transaction is a coinbase, every transaction passes [`check_transaction_err`] and there are no duplicate txids.
+pub fn check_block_transactions_err(block: &QDogeBlock) -> QDogeResult<()> {
+    let transactions = &block.transactions;
+    if transactions.is_empty() {
+        return Err(DogeBridgeError::BlockHasNoTransactions);
+    }
+    let mut txids = transactions.iter().map(|tx| tx.get_hash()).collect::<Vec<_>>();
+    let (merkle_root, mutated) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+    if merkle_root != block.header.merkle_root {
+        return Err(DogeBridgeError::BlockTxMerkleRootMismatch);
+    }
+    if mutated {
+        return Err(DogeBridgeError::BlockTxMerkleTreeMutated);
+    }
+
+    if block.get_serialized_size() > DOGE_MAX_BLOCK_SERIALIZED_SIZE {
+        return Err(DogeBridgeError::BlockSizeTooLarge);
+    }
+
+    if !transactions[0].is_coinbase() {
+        return Err(DogeBridgeError::BlockFirstTransactionNotCoinbase);
+    }
+    if transactions[1..].iter().any(|tx| tx.is_coinbase()) {
+        return Err(DogeBridgeError::BlockMultipleCoinbases);
+    }
+    for tx in transactions.iter() {
+        check_transaction_err(tx)?;
+    }
+
+    // the merkle tree mutation check does not catch duplicates which are not siblings
+    txids.sort_unstable();
+    if txids.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(DogeBridgeError::BlockDuplicateTransaction);
+    }
+
+    let sig_op_count = transactions.iter().map(get_legacy_transaction_sig_op_count).sum::<usize>();
+    if sig_op_count > DOGE_MAX_BLOCK_SIGOPS {
+        return Err(DogeBridgeError::BlockTooManySigops);
+    }
+    Ok(())
+}
+
+/// Sums the output values of a transaction, failing if a value or the running sum is above `DOGE_MAX_MONEY`.
+fn get_total_output_value(transaction: &BTCTransaction) -> QDogeResult<u64> {
+    let mut total_value = 0u64;
+    for output in transaction.outputs.iter() {
+        if output.value > DOGE_MAX_MONEY {
+            return Err(DogeBridgeError::TransactionOutputValueOutOfRange);
+        }
+        total_value += output.value;
+        if total_value > DOGE_MAX_MONEY {
+            return Err(DogeBridgeError::TransactionOutputValueOutOfRange);
+        }
+    }
+    Ok(total_value)
+}
+
+/// Returns the fee of a transaction given the values of the outputs spent by each of its inputs, equivalent to `CheckTxInputs` in dogecoin core.
+pub fn get_transaction_fee_err(transaction: &BTCTransaction, prevout_values: &[u64]) -> QDogeResult<u64> {
+    if prevout_values.len() != transaction.inputs.len() {
+        return Err(DogeBridgeError::MismatchedPrevoutValuesCount);
+    }
+    let mut total_input_value = 0u64;
+    for value in prevout_values.iter() {
+        if *value > DOGE_MAX_MONEY {
+            return Err(DogeBridgeError::TransactionInputValueOutOfRange);
+        }
+        total_input_value += *value;
+        if total_input_value > DOGE_MAX_MONEY {
+            return Err(DogeBridgeError::TransactionInputValueOutOfRange);
+        }
+    }
+    total_input_value
+        .checked_sub(get_total_output_value(transaction)?)
+        .ok_or(DogeBridgeError::TransactionOutputsExceedInputs)
+}
+
+/// Checks the coinbase of a block at `height` does not claim more than the block subsidy plus the fees of the other transactions.
+///
+/// `prevout_values[i]` holds the values of the outputs spent by the inputs of `block.transactions[i + 1]`, the coinbase has no prevouts.
+pub fn check_block_coinbase_value_err<NC: DogeNetworkConfig>(
+    block: &QDogeBlock,
+    height: u32,
+    prevout_values: &[Vec<u64>],
+) -> QDogeResult<()> {
+    if block.transactions.is_empty() {
+        return Err(DogeBridgeError::BlockHasNoTransactions);
+    }
+    if prevout_values.len() + 1 != block.transactions.len() {
+        return Err(DogeBridgeError::MismatchedPrevoutValuesCount);
+    }
+    let mut total_fees = 0u64;
+    for (transaction, values) in block.transactions[1..].iter().zip(prevout_values.iter()) {
+        total_fees += get_transaction_fee_err(transaction, values)?;
+        if total_fees > DOGE_MAX_MONEY {
+            return Err(DogeBridgeError::TransactionInputValueOutOfRange);
+        }
+    }
+    let subsidy = get_block_subsidy::<NC>(height, &block.header.previous_block_hash);
+    if get_total_output_value(&block.transactions[0])? > subsidy + total_fees {
+        return Err(DogeBridgeError::CoinbaseValueTooLarge);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        constants::{DogeMainNetConfig, DOGE_COIN},
+        core_data::{QDogeBlock, QStandardBlockHeader},
+        doge::transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput},
+        error::DogeBridgeError,
+    };
+
+    use super::check_block_coinbase_value_err;
+
+    fn make_tx(prev_hash: [u8; 32], prev_index: u32, script: Vec<u8>) -> BTCTransaction {
+        BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput {
+                hash: prev_hash,
+                index: prev_index,
+                script,
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![BTCTransactionOutput {
+                value: 50_000,
+                script: vec![0x76, 0xa9, 0x14, 0u8, 0u8, 0x88, 0xac],
+            }],
+            locktime: 0,
+        }
+    }
+
+    fn make_block(transactions: Vec<BTCTransaction>) -> QDogeBlock {
+        let mut block = QDogeBlock {
+            header: QStandardBlockHeader {
+                version: 1,
+                previous_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 0,
+                bits: 0x1e0ffff0,
+                nonce: 0,
+            },
+            aux_pow: None,
+            transactions,
+        };
+        block.header.merkle_root = block.compute_tx_merkle_root().0;
+        block
+    }
+
+    #[test]
+    fn test_validate_block_transactions() {
+        let coinbase = make_tx([0u8; 32], u32::MAX, vec![0x51, 0x51]);
+        let spends = (1u8..=4).map(|i| make_tx([i; 32], 0, vec![])).collect::<Vec<_>>();
+
+        let mut transactions = vec![coinbase.clone()];
+        transactions.extend(spends.iter().cloned());
+        assert_eq!(make_block(transactions.clone()).validate(), Ok(()));
+
+        // wrong merkle root
+        let mut block = make_block(transactions.clone());
+        block.header.merkle_root[0] ^= 1;
+        assert_eq!(block.validate(), Err(DogeBridgeError::BlockTxMerkleRootMismatch));
+
+        // [c, a, b, d, d] has the same merkle root as [c, a, b, d, d, d] with 5 leaves duplicated to 6
+        let mut mutated = vec![coinbase.clone(), spends[0].clone(), spends[1].clone(), spends[2].clone(), spends[2].clone()];
+        let mut block = make_block(mutated.clone());
+        mutated.push(spends[2].clone());
+        block.transactions = mutated;
+        assert_eq!(block.validate(), Err(DogeBridgeError::BlockTxMerkleTreeMutated));
+
+        // duplicates which are not siblings are not caught by the mutation check
+        let block = make_block(vec![coinbase.clone(), spends[0].clone(), spends[1].clone(), spends[0].clone()]);
+        assert_eq!(block.validate(), Err(DogeBridgeError::BlockDuplicateTransaction));
+
+        let block = make_block(spends.clone());
+        assert_eq!(block.validate(), Err(DogeBridgeError::BlockFirstTransactionNotCoinbase));
+
+        let block = make_block(vec![coinbase.clone(), make_tx([0u8; 32], u32::MAX, vec![0x52, 0x52])]);
+        assert_eq!(block.validate(), Err(DogeBridgeError::BlockMultipleCoinbases));
+
+        let block = make_block(vec![make_tx([0u8; 32], u32::MAX, vec![0x51])]);
+        assert_eq!(block.validate(), Err(DogeBridgeError::CoinbaseScriptSizeOutOfRange));
+
+        // 20 OP_CHECKMULTISIG per tx * 1001 txs > 20_000 sigops
+        let mut transactions = vec![coinbase.clone()];
+        transactions.extend((0..1001u32).map(|i| {
+            let mut prev_hash = [1u8; 32];
+            prev_hash[..4].copy_from_slice(&i.to_le_bytes());
+            make_tx(prev_hash, 0, vec![0xae])
+        }));
+        assert_eq!(make_block(transactions).validate(), Err(DogeBridgeError::BlockTooManySigops));
+    }
+
+    #[test]
+    fn test_validate_coinbase_value() {
+        // 10,000 DOGE subsidy plus 2 * 50_000 koinu of fees
+        let mut coinbase = make_tx([0u8; 32], u32::MAX, vec![0x51, 0x51]);
+        coinbase.outputs[0].value = 10_000 * DOGE_COIN + 100_000;
+        let spends = (1u8..=2).map(|i| make_tx([i; 32], 0, vec![])).collect::<Vec<_>>();
+        let mut transactions = vec![coinbase];
+        transactions.extend(spends);
+        let block = make_block(transactions);
+
+        let prevout_values = vec![vec![100_000], vec![100_000]];
+        assert_eq!(block.validate_with_prevout_values::<DogeMainNetConfig>(600_000, &prevout_values), Ok(()));
+        assert_eq!(
+            block.validate_with_prevout_values::<DogeMainNetConfig>(600_000, &[vec![100_000], vec![99_999]]),
+            Err(DogeBridgeError::CoinbaseValueTooLarge)
+        );
+        assert_eq!(
+            block.validate_with_prevout_values::<DogeMainNetConfig>(600_000, &[vec![100_000], vec![49_999]]),
+            Err(DogeBridgeError::TransactionOutputsExceedInputs)
+        );
+        assert_eq!(
+            block.validate_with_prevout_values::<DogeMainNetConfig>(600_000, &prevout_values[..1]),
+            Err(DogeBridgeError::MismatchedPrevoutValuesCount)
+        );
+        assert_eq!(block.validate_with_prevout_values::<DogeMainNetConfig>(599_999, &prevout_values), Ok(()));
+
+        // the coinbase outputs are summed with the same bounds as check_transaction_err, without the other block checks
+        let mut overflowing = block.clone();
+        overflowing.transactions[0].outputs = vec![overflowing.transactions[0].outputs[0].clone(); 2];
+        overflowing.transactions[0].outputs[0].value = u64::MAX;
+        assert_eq!(
+            check_block_coinbase_value_err::<DogeMainNetConfig>(&overflowing, 600_000, &prevout_values),
+            Err(DogeBridgeError::TransactionOutputValueOutOfRange)
+        );
+    }
+}
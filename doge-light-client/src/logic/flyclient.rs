@@ -0,0 +1,726 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! FlyClient style succinct proofs that a block tree root commits to a heavy Dogecoin chain.
+//!
+//! Next to the block tree, the prover commits to a "work tree" whose leaf `i` is
+//! `sha256(block_hash || cumulative_work)` for the `i`-th block after the proof's start block.
+//! Samples are drawn from a seed over the proof, the suffix headers and a verifier supplied nonce, weighted by
+//! cumulative work with a density that grows towards the tip, and every sampled header is checked for proof of work, difficulty bits following from its
+//! parent and grandparent, inclusion in the block tree and for adding exactly its own block proof to the
+//! cumulative work of its parent.
+//! The proof has to start at a trusted `FlyClientCheckpoint` and prove at least a caller supplied amount of work.
+//! The last `QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE` headers are provided in full and become the verified anchor.
+//!
+//! Difficulty bits are checked with the DigiShield rules used by the rest of the crate, so the checkpoint has to be
+//! after DigiShield activated on the network.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    chain_state::QEDDogeChainStateCore, constants::DogeNetworkConfig, core_data::{QDogeBlockHeader, QHash256, QStandardBlockHeader}, error::{DogeBridgeError, QDogeResult}, hash::{merkle::utils::compute_root_merkle_proof_generic, sha256::QSha256Hasher, traits::{get_zero_hashes, BytesHasher, MerkleHasher}}, init_params::InitBlockDataIBC, math::chain_work::QChainWork
+};
+
+use super::{check_doge_block::check_block_header_pow_err, check_doge_block_seq::get_next_work_required};
+
+const FLYCLIENT_TRANSCRIPT_DOMAIN: &[u8] = b"QED_DOGE_FLYCLIENT_V1";
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlyClientSampledBlock {
+    pub block_number: u32,
+    pub header: QDogeBlockHeader,
+    pub block_tree_siblings: Vec<QHash256>,
+
+    pub cumulative_work: QHash256,
+    pub work_tree_siblings: Vec<QHash256>,
+    /// cumulative work of the parent, zero when the sampled block is the start block
+    pub previous_cumulative_work: QHash256,
+    pub previous_work_tree_siblings: Vec<QHash256>,
+    /// `[parent, grandparent]` of the sampled block to check its difficulty bits, empty when the sampled block is the start block
+    pub ancestor_headers: Vec<QStandardBlockHeader>,
+}
+
+/// A trusted block the proof has to start from, e.g. the checkpoint a deployment is configured with.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlyClientCheckpoint {
+    pub block_number: u32,
+    pub block_hash: QHash256,
+    /// block tree siblings of the checkpoint at the time it was appended, which commit to the block tree before it
+    pub block_tree_siblings: Vec<QHash256>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlyClientChainProof {
+    pub start_block_number: u32,
+    pub tip_block_number: u32,
+    pub block_tree_root: QHash256,
+    pub work_tree_root: QHash256,
+    /// cumulative work from the start block to the tip (inclusive), big-endian
+    pub total_work: QHash256,
+
+    /// block tree siblings of the first suffix header at the time it was appended
+    pub anchor_block_tree_siblings: Vec<QHash256>,
+    /// `[grandparent, parent]` of the first suffix header
+    pub suffix_ancestor_headers: Vec<QStandardBlockHeader>,
+    pub suffix_headers: Vec<QDogeBlockHeader>,
+
+    pub start: FlyClientSampledBlock,
+    pub tip: FlyClientSampledBlock,
+    pub samples: Vec<FlyClientSampledBlock>,
+}
+
+pub fn get_flyclient_work_leaf(block_hash: &QHash256, cumulative_work: &QHash256) -> QHash256 {
+    let mut data = [0u8; 64];
+    data[0..32].copy_from_slice(block_hash);
+    data[32..64].copy_from_slice(cumulative_work);
+    QSha256Hasher::hash_bytes(&data)
+}
+
+// the seed covers the full 80 byte suffix headers (the tip included) whose proof of work is checked, next to the
+// commitments of the prover and the nonce of the verifier, see verify_flyclient_chain_proof for the bound it gives
+fn get_flyclient_seed(proof: &FlyClientChainProof, verifier_nonce: &QHash256) -> QHash256 {
+    let mut data = Vec::with_capacity(FLYCLIENT_TRANSCRIPT_DOMAIN.len() + 136 + 80 * proof.suffix_headers.len());
+    data.extend_from_slice(FLYCLIENT_TRANSCRIPT_DOMAIN);
+    data.extend_from_slice(verifier_nonce);
+    data.extend_from_slice(&proof.start_block_number.to_le_bytes());
+    data.extend_from_slice(&proof.tip_block_number.to_le_bytes());
+    data.extend_from_slice(&proof.block_tree_root);
+    data.extend_from_slice(&proof.work_tree_root);
+    data.extend_from_slice(&proof.total_work);
+    for header in proof.suffix_headers.iter() {
+        data.extend_from_slice(&header.header.to_bytes_fixed());
+    }
+    QSha256Hasher::hash_bytes(&data)
+}
+
+/// Draws the cumulative work value targeted by sample `sample_index`.
+///
+/// The work range is split into buckets `[W - W/2^j, W - W/2^(j+1))` which are picked uniformly, so the sampling
+/// density is proportional to `1 / (W - x)` as in FlyClient, without needing floating point arithmetic.
+pub fn get_flyclient_sample_work(seed: &QHash256, sample_index: u32, total_work: &QChainWork, block_count: u32) -> QChainWork {
+    let mut data = [0u8; 36];
+    data[0..32].copy_from_slice(seed);
+    data[32..36].copy_from_slice(&sample_index.to_le_bytes());
+    let randomness = QSha256Hasher::hash_bytes(&data);
+    let bucket_randomness = u64::from_le_bytes(randomness[0..8].try_into().unwrap());
+    let offset_randomness = u64::from_le_bytes(randomness[8..16].try_into().unwrap());
+
+    let bucket_count = (32 - block_count.leading_zeros()).max(1);
+    let bucket = (bucket_randomness % bucket_count as u64) as u32;
+
+    let remaining = total_work.shr(bucket);
+    let bucket_start = total_work.checked_sub(&remaining).unwrap();
+    let bucket_width = if bucket + 1 == bucket_count {
+        remaining
+    } else {
+        remaining.checked_sub(&total_work.shr(bucket + 1)).unwrap()
+    };
+    bucket_start
+        .checked_add(&bucket_width.mul_fraction_u64(offset_randomness))
+        .unwrap()
+}
+
+/// Checks that `header` links to `parent` and `grandparent` and that its difficulty bits follow from them.
+fn check_header_difficulty_bits<NC: DogeNetworkConfig>(
+    block_number: u32,
+    header: &QStandardBlockHeader,
+    parent: &QStandardBlockHeader,
+    grandparent: &QStandardBlockHeader,
+) -> QDogeResult<()> {
+    if block_number < 2
+        || header.previous_block_hash != parent.get_hash()
+        || parent.previous_block_hash != grandparent.get_hash()
+    {
+        return Err(DogeBridgeError::FlyClientAncestorsNotLinked);
+    }
+    let expected_bits = get_next_work_required::<NC>(
+        block_number - 1,
+        parent.timestamp as i64,
+        parent.bits,
+        grandparent.timestamp as i64,
+        header.timestamp as i64,
+    );
+    if expected_bits != header.bits {
+        return Err(DogeBridgeError::DifficutlyBitsMismatch);
+    }
+    Ok(())
+}
+
+impl FlyClientSampledBlock {
+    /// Returns the cumulative work of the parent and of the sampled block after checking the sample.
+    pub fn verify<NC: DogeNetworkConfig>(
+        &self,
+        start_block_number: u32,
+        block_tree_root: &QHash256,
+        work_tree_root: &QHash256,
+        block_tree_height: usize,
+        work_tree_height: usize,
+    ) -> QDogeResult<(QChainWork, QChainWork)> {
+        if self.block_number < start_block_number {
+            return Err(DogeBridgeError::FlyClientInvalidRange);
+        }
+        let block_hash = self.header.header.get_hash();
+        if self.block_tree_siblings.len() != block_tree_height
+            || compute_root_merkle_proof_generic::<QHash256, QSha256Hasher>(
+                block_hash,
+                self.block_number as u64,
+                &self.block_tree_siblings,
+            ) != *block_tree_root
+        {
+            return Err(DogeBridgeError::FlyClientInvalidBlockTreeProof);
+        }
+        check_block_header_pow_err::<NC>(&self.header, None)?;
+
+        let work_index = (self.block_number - start_block_number) as u64;
+        if self.work_tree_siblings.len() != work_tree_height
+            || compute_root_merkle_proof_generic::<QHash256, QSha256Hasher>(
+                get_flyclient_work_leaf(&block_hash, &self.cumulative_work),
+                work_index,
+                &self.work_tree_siblings,
+            ) != *work_tree_root
+        {
+            return Err(DogeBridgeError::FlyClientInvalidWorkProof);
+        }
+        if work_index == 0 {
+            if self.previous_cumulative_work != [0u8; 32] || !self.previous_work_tree_siblings.is_empty() {
+                return Err(DogeBridgeError::FlyClientInvalidWorkProof);
+            }
+        } else if self.previous_work_tree_siblings.len() != work_tree_height
+            || compute_root_merkle_proof_generic::<QHash256, QSha256Hasher>(
+                get_flyclient_work_leaf(&self.header.header.previous_block_hash, &self.previous_cumulative_work),
+                work_index - 1,
+                &self.previous_work_tree_siblings,
+            ) != *work_tree_root
+        {
+            return Err(DogeBridgeError::FlyClientInvalidWorkProof);
+        }
+
+        if work_index == 0 {
+            if !self.ancestor_headers.is_empty() {
+                return Err(DogeBridgeError::FlyClientAncestorsNotLinked);
+            }
+        } else {
+            let [parent, grandparent] = self.ancestor_headers[..] else {
+                return Err(DogeBridgeError::FlyClientAncestorsNotLinked);
+            };
+            check_header_difficulty_bits::<NC>(self.block_number, &self.header.header, &parent, &grandparent)?;
+        }
+
+        let previous_cumulative_work = QChainWork::from_be_bytes(self.previous_cumulative_work);
+        let cumulative_work = QChainWork::from_be_bytes(self.cumulative_work);
+        if previous_cumulative_work.checked_add(&QChainWork::get_block_proof(self.header.header.bits))
+            != Some(cumulative_work)
+        {
+            return Err(DogeBridgeError::FlyClientInvalidWorkProof);
+        }
+        Ok((previous_cumulative_work, cumulative_work))
+    }
+}
+
+/// Verifies a FlyClient proof with `sample_count` samples which starts at `checkpoint` and proves at least
+/// `min_total_work` (counted from the checkpoint to the tip, inclusive), and returns the chain state anchored at its tip.
+///
+/// DigiShield lets a fork lower its difficulty within a few dozen blocks, so the difficulty checks alone do not stop a
+/// cheap fork of the checkpoint, `min_total_work` has to come from the work the real chain is known to have added.
+///
+/// Each sample lands in a set of blocks holding a fraction `q` of the claimed work with probability at least `2q / B`,
+/// `B` being the bit length of the number of blocks, so a proof whose failing blocks hold a fraction `q` of the work
+/// is accepted with probability at most `(1 - 2q / B)^sample_count`. This only holds if `verifier_nonce` is unknown to
+/// the prover until the roots, total work and suffix of the proof are fixed, e.g. a slot hash taken after the prover
+/// posted them. Dogecoin headers do not commit to the block tree, so with a predictable nonce the prover can rebuild the
+/// failing part of its chain at no proof of work cost and retry, and after `N` attempts the bound is
+/// `N * (1 - 2q / B)^sample_count`. The suffix headers in the seed only make retrying with another tip cost the proof
+/// of work of a new suffix.
+pub fn verify_flyclient_chain_proof<
+    NC: DogeNetworkConfig,
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+>(
+    proof: &FlyClientChainProof,
+    checkpoint: &FlyClientCheckpoint,
+    min_total_work: &QChainWork,
+    sample_count: usize,
+    verifier_nonce: &QHash256,
+) -> QDogeResult<
+    QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+    >,
+> {
+    let block_count = proof
+        .tip_block_number
+        .checked_sub(proof.start_block_number)
+        .and_then(|x| x.checked_add(1))
+        .ok_or(DogeBridgeError::FlyClientInvalidRange)?;
+    let work_tree_height = proof.tip.work_tree_siblings.len();
+    if block_count < QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32
+        || proof.suffix_headers.len() != QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE
+        || proof.suffix_ancestor_headers.len() != 2
+        || proof.anchor_block_tree_siblings.len() != QDOGE_BRIDGE_BLOCK_TREE_HEIGHT
+        || work_tree_height > 32
+        || (block_count as u64) > (1u64 << work_tree_height)
+    {
+        return Err(DogeBridgeError::FlyClientInvalidRange);
+    }
+    if proof.samples.len() != sample_count {
+        return Err(DogeBridgeError::FlyClientSampleCountMismatch);
+    }
+
+    // the proof starts at the checkpoint and shares the block tree history before it
+    if proof.start_block_number != checkpoint.block_number
+        || proof.start.block_number != checkpoint.block_number
+        || proof.start.header.header.get_hash() != checkpoint.block_hash
+        || checkpoint.block_tree_siblings.len() != QDOGE_BRIDGE_BLOCK_TREE_HEIGHT
+        || proof.start.block_tree_siblings.len() != QDOGE_BRIDGE_BLOCK_TREE_HEIGHT
+        || (0..QDOGE_BRIDGE_BLOCK_TREE_HEIGHT).any(|level| {
+            (checkpoint.block_number >> level) & 1 == 1
+                && proof.start.block_tree_siblings[level] != checkpoint.block_tree_siblings[level]
+        })
+    {
+        return Err(DogeBridgeError::FlyClientCheckpointMismatch);
+    }
+    proof.start.verify::<NC>(
+        proof.start_block_number,
+        &proof.block_tree_root,
+        &proof.work_tree_root,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        work_tree_height,
+    )?;
+
+    // the suffix becomes the anchor of the new chain state
+    let suffix_start_block_number = proof.tip_block_number + 1 - QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE as u32;
+    for (i, header) in proof.suffix_headers.iter().enumerate() {
+        let (parent, grandparent) = match i {
+            0 => (&proof.suffix_ancestor_headers[1], &proof.suffix_ancestor_headers[0]),
+            1 => (&proof.suffix_headers[0].header, &proof.suffix_ancestor_headers[1]),
+            _ => (&proof.suffix_headers[i - 1].header, &proof.suffix_headers[i - 2].header),
+        };
+        if header.header.previous_block_hash != parent.get_hash() {
+            return Err(DogeBridgeError::FlyClientSuffixNotLinked);
+        }
+        let block_number = suffix_start_block_number + i as u32;
+        if block_number != proof.start_block_number {
+            check_header_difficulty_bits::<NC>(block_number, &header.header, parent, grandparent)?;
+        }
+        check_block_header_pow_err::<NC>(header, None)?;
+    }
+    let init_data = InitBlockDataIBC::<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>::new_from_block_headers(
+        &core::array::from_fn(|i| proof.suffix_headers[i].clone()),
+        core::array::from_fn(|i| proof.anchor_block_tree_siblings[i]),
+        proof.tip_block_number,
+    );
+    let state = QEDDogeChainStateCore::<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+    >::from_init_data(&init_data)?;
+    if state.block_tree_tracker.get_root::<QSha256Hasher>() != proof.block_tree_root {
+        return Err(DogeBridgeError::FlyClientBlockTreeRootMismatch);
+    }
+
+    // the tip commits to the claimed total work
+    if proof.tip.block_number != proof.tip_block_number
+        || Some(&proof.tip.header) != proof.suffix_headers.last()
+    {
+        return Err(DogeBridgeError::FlyClientSampleMismatch);
+    }
+    let (_, tip_cumulative_work) = proof.tip.verify::<NC>(
+        proof.start_block_number,
+        &proof.block_tree_root,
+        &proof.work_tree_root,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        work_tree_height,
+    )?;
+    let total_work = QChainWork::from_be_bytes(proof.total_work);
+    if tip_cumulative_work != total_work {
+        return Err(DogeBridgeError::FlyClientInvalidWorkProof);
+    }
+    if total_work < *min_total_work {
+        return Err(DogeBridgeError::FlyClientInsufficientWork);
+    }
+
+    let seed = get_flyclient_seed(proof, verifier_nonce);
+    for (i, sample) in proof.samples.iter().enumerate() {
+        if sample.block_number > proof.tip_block_number {
+            return Err(DogeBridgeError::FlyClientSampleMismatch);
+        }
+        let (previous_cumulative_work, cumulative_work) = sample.verify::<NC>(
+            proof.start_block_number,
+            &proof.block_tree_root,
+            &proof.work_tree_root,
+            QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+            work_tree_height,
+        )?;
+        let sample_work = get_flyclient_sample_work(&seed, i as u32, &total_work, block_count);
+        if sample_work < previous_cumulative_work || sample_work >= cumulative_work {
+            return Err(DogeBridgeError::FlyClientSampleMismatch);
+        }
+    }
+
+    Ok(state)
+}
+
+/// Merkle tree levels over a contiguous range of leaves starting at `first_index`, where every node left of the
+/// range is a left sibling of the path of `first_index` and every node right of the range is empty.
+struct PartialMerkleLevels {
+    first_index: u64,
+    levels: Vec<Vec<QHash256>>,
+    left_siblings: Vec<QHash256>,
+    zero_hashes: Vec<QHash256>,
+}
+
+impl PartialMerkleLevels {
+    fn new(first_index: u64, leaves: Vec<QHash256>, left_siblings: Vec<QHash256>) -> Self {
+        let height = left_siblings.len();
+        let mut tree = Self {
+            first_index,
+            levels: vec![leaves],
+            left_siblings,
+            zero_hashes: get_zero_hashes::<QHash256, QSha256Hasher>(height + 1),
+        };
+        for level in 0..height {
+            let level_first_index = first_index >> level;
+            let level_last_index = level_first_index + tree.levels[level].len() as u64 - 1;
+            let next_level = ((level_first_index >> 1)..=(level_last_index >> 1))
+                .map(|index| {
+                    QSha256Hasher::two_to_one(
+                        &tree.get_node(level, index * 2),
+                        &tree.get_node(level, index * 2 + 1),
+                    )
+                })
+                .collect();
+            tree.levels.push(next_level);
+        }
+        tree
+    }
+    fn get_node(&self, level: usize, index: u64) -> QHash256 {
+        let level_first_index = self.first_index >> level;
+        if index < level_first_index {
+            self.left_siblings[level]
+        } else if index - level_first_index >= self.levels[level].len() as u64 {
+            self.zero_hashes[level]
+        } else {
+            self.levels[level][(index - level_first_index) as usize]
+        }
+    }
+    fn get_siblings(&self, index: u64) -> Vec<QHash256> {
+        (0..self.left_siblings.len())
+            .map(|level| self.get_node(level, (index >> level) ^ 1))
+            .collect()
+    }
+    /// siblings of `index` in the tree where `index` is the last appended leaf
+    fn get_append_siblings(&self, index: u64) -> Vec<QHash256> {
+        (0..self.left_siblings.len())
+            .map(|level| {
+                if (index >> level) & 1 == 1 {
+                    self.get_node(level, (index >> level) ^ 1)
+                } else {
+                    self.zero_hashes[level]
+                }
+            })
+            .collect()
+    }
+    fn get_root(&self) -> QHash256 {
+        self.levels[self.left_siblings.len()][0]
+    }
+}
+
+/// Generates a FlyClient proof for `headers`, the blocks `start_block_number..=tip` committed in a block tree,
+/// where the start block is the block of `checkpoint`, with the samples drawn for `verifier_nonce`.
+///
+/// `start_grandparent_header` is the header before the parent of the start block (the parent is the start header's
+/// `previous_block_hash`), `start_parent_header` the parent itself. They are needed to check the difficulty bits of
+/// the blocks right after the start block.
+pub fn generate_flyclient_chain_proof<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+>(
+    checkpoint: &FlyClientCheckpoint,
+    start_grandparent_header: &QStandardBlockHeader,
+    start_parent_header: &QStandardBlockHeader,
+    headers: &[QDogeBlockHeader],
+    sample_count: usize,
+    verifier_nonce: &QHash256,
+) -> QDogeResult<FlyClientChainProof> {
+    let start_block_number = checkpoint.block_number;
+    if checkpoint.block_tree_siblings.len() != QDOGE_BRIDGE_BLOCK_TREE_HEIGHT
+        || headers.first().map(|x| x.header.get_hash()) != Some(checkpoint.block_hash)
+    {
+        return Err(DogeBridgeError::FlyClientCheckpointMismatch);
+    }
+    if headers.len() < QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE || headers.len() > u32::MAX as usize {
+        return Err(DogeBridgeError::FlyClientInvalidRange);
+    }
+    let block_count = headers.len() as u32;
+    let tip_block_number = start_block_number
+        .checked_add(block_count - 1)
+        .ok_or(DogeBridgeError::FlyClientInvalidRange)?;
+
+    let block_hashes = headers.iter().map(|x| x.header.get_hash()).collect::<Vec<_>>();
+    let mut cumulative_works = Vec::with_capacity(headers.len());
+    let mut total_work = QChainWork::ZERO;
+    for header in headers {
+        total_work = total_work
+            .checked_add(&QChainWork::get_block_proof(header.header.bits))
+            .ok_or(DogeBridgeError::NumericalOverflow)?;
+        cumulative_works.push(total_work);
+    }
+
+    let block_tree = PartialMerkleLevels::new(start_block_number as u64, block_hashes.clone(), checkpoint.block_tree_siblings.clone());
+    let work_tree_height = (32 - (block_count - 1).leading_zeros()) as usize;
+    let work_tree = PartialMerkleLevels::new(
+        0,
+        block_hashes
+            .iter()
+            .zip(cumulative_works.iter())
+            .map(|(hash, work)| get_flyclient_work_leaf(hash, &work.to_be_bytes()))
+            .collect(),
+        vec![[0u8; 32]; work_tree_height],
+    );
+
+    // headers[offset - 2..offset] with the ancestors of the start block in front
+    let get_ancestor = |offset: usize| match offset {
+        0 => *start_grandparent_header,
+        1 => *start_parent_header,
+        _ => headers[offset - 2].header,
+    };
+    let get_sample = |offset: usize| FlyClientSampledBlock {
+        block_number: start_block_number + offset as u32,
+        header: headers[offset].clone(),
+        block_tree_siblings: block_tree.get_siblings(start_block_number as u64 + offset as u64),
+        cumulative_work: cumulative_works[offset].to_be_bytes(),
+        work_tree_siblings: work_tree.get_siblings(offset as u64),
+        previous_cumulative_work: if offset == 0 { [0u8; 32] } else { cumulative_works[offset - 1].to_be_bytes() },
+        previous_work_tree_siblings: if offset == 0 { Vec::new() } else { work_tree.get_siblings(offset as u64 - 1) },
+        ancestor_headers: if offset == 0 { Vec::new() } else { vec![get_ancestor(offset + 1), get_ancestor(offset)] },
+    };
+
+    let suffix_start = headers.len() - QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE;
+    let mut proof = FlyClientChainProof {
+        start_block_number,
+        tip_block_number,
+        block_tree_root: block_tree.get_root(),
+        work_tree_root: work_tree.get_root(),
+        total_work: total_work.to_be_bytes(),
+        anchor_block_tree_siblings: block_tree.get_append_siblings(start_block_number as u64 + suffix_start as u64),
+        suffix_ancestor_headers: vec![get_ancestor(suffix_start), get_ancestor(suffix_start + 1)],
+        suffix_headers: headers[suffix_start..].to_vec(),
+        start: get_sample(0),
+        tip: get_sample(headers.len() - 1),
+        samples: Vec::with_capacity(sample_count),
+    };
+
+    let seed = get_flyclient_seed(&proof, verifier_nonce);
+    for i in 0..sample_count {
+        let sample_work = get_flyclient_sample_work(&seed, i as u32, &total_work, block_count);
+        let offset = cumulative_works.partition_point(|x| *x <= sample_work);
+        proof.samples.push(get_sample(offset));
+    }
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_fixtures::{load_mainnet_headers, BlockHeaderWithIndex},
+        block_data_tracker::BlockDataStorage, chain_state::QEDDogeChainStateCore, constants::DogeMainNetConfig, core_data::QDogeBlockHeader, error::DogeBridgeError, hash::sha256::QSha256Hasher, init_params::{get_empty_siblings, InitBlockDataIBC}, math::chain_work::QChainWork
+    };
+
+    use super::{check_header_difficulty_bits, generate_flyclient_chain_proof, verify_flyclient_chain_proof, FlyClientChainProof, FlyClientCheckpoint};
+
+    const CACHE_SIZE: usize = 32;
+    const REQUIRED_CONFIRMATIONS: usize = 4;
+    const TREE_HEIGHT: usize = 32;
+    const SAMPLE_COUNT: usize = 8;
+    const NONCE: [u8; 32] = [0x4e; 32];
+
+    // the first two headers are only the ancestors of the checkpoint, which is headers[2] in an otherwise empty block tree
+    fn get_checkpoint(headers: &[BlockHeaderWithIndex]) -> FlyClientCheckpoint {
+        FlyClientCheckpoint {
+            block_number: headers[2].height,
+            block_hash: headers[2].block_header.header.get_hash(),
+            block_tree_siblings: get_empty_siblings::<TREE_HEIGHT>().to_vec(),
+        }
+    }
+
+    fn generate_proof(headers: &[BlockHeaderWithIndex], nonce: &[u8; 32]) -> anyhow::Result<FlyClientChainProof> {
+        let block_headers = headers[2..].iter().map(|x| x.block_header.clone()).collect::<alloc::vec::Vec<_>>();
+        Ok(generate_flyclient_chain_proof::<CACHE_SIZE, TREE_HEIGHT>(
+            &get_checkpoint(headers),
+            &headers[0].block_header.header,
+            &headers[1].block_header.header,
+            &block_headers,
+            SAMPLE_COUNT,
+            nonce,
+        )?)
+    }
+
+    #[test]
+    fn test_flyclient_proof_matches_chain_state() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let headers = &headers[..202];
+
+        // the state a bridge deployed at the checkpoint + CACHE_SIZE - 1 would have after syncing all headers
+        let base: [QDogeBlockHeader; CACHE_SIZE] = core::array::from_fn(|i| headers[i + 2].block_header.clone());
+        let init_data = InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE + 1].height,
+        );
+        let mut synced_state = QEDDogeChainStateCore::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>::from_init_data(&init_data).unwrap();
+        for x in &headers[CACHE_SIZE + 2..] {
+            synced_state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+
+        let proof = generate_proof(headers, &NONCE)?;
+        assert_eq!(proof.block_tree_root, synced_state.block_tree_tracker.get_root::<QSha256Hasher>());
+
+        let anchored_state = verify_flyclient_chain_proof::<DogeMainNetConfig, CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>(
+            &proof,
+            &get_checkpoint(headers),
+            &QChainWork::from_be_bytes(proof.total_work),
+            SAMPLE_COUNT,
+            &NONCE,
+        )?;
+        assert_eq!(anchored_state.get_tip_block_number(), headers.last().unwrap().height);
+        assert_eq!(anchored_state.get_tip_block_hash(), synced_state.get_tip_block_hash());
+        assert_eq!(anchored_state.block_tree_tracker.get_root::<QSha256Hasher>(), proof.block_tree_root);
+        for block_number in (anchored_state.get_tip_block_number() + 1 - CACHE_SIZE as u32)..=anchored_state.get_tip_block_number() {
+            assert_eq!(
+                anchored_state.block_data_tracker.get_record(block_number)?,
+                synced_state.block_data_tracker.get_record(block_number)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flyclient_proof_rejects_tampering() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let headers = &headers[..102];
+        let proof = generate_proof(headers, &NONCE)?;
+        let checkpoint = get_checkpoint(headers);
+        let total_work = QChainWork::from_be_bytes(proof.total_work);
+        let verify_with_nonce = |proof, checkpoint, min_total_work, nonce| {
+            verify_flyclient_chain_proof::<DogeMainNetConfig, CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>(
+                proof,
+                checkpoint,
+                min_total_work,
+                SAMPLE_COUNT,
+                nonce,
+            )
+            .map(|_| ())
+        };
+        let verify = |proof, checkpoint, min_total_work| verify_with_nonce(proof, checkpoint, min_total_work, &NONCE);
+        assert_eq!(verify(&proof, &checkpoint, &total_work), Ok(()));
+
+        // the samples are drawn for the nonce of the verifier
+        let other_nonce_proof = generate_proof(headers, &[0x4f; 32])?;
+        assert_ne!(other_nonce_proof.samples, proof.samples);
+        assert_eq!(verify_with_nonce(&other_nonce_proof, &checkpoint, &total_work, &[0x4f; 32]), Ok(()));
+        assert_eq!(verify(&other_nonce_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientSampleMismatch));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.samples.pop();
+        assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientSampleCountMismatch));
+
+        // claiming more work changes the transcript and the tip no longer matches the claim
+        let mut bad_proof = proof.clone();
+        bad_proof.total_work[31] ^= 1;
+        assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientInvalidWorkProof));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.samples[0] = bad_proof.samples[1].clone();
+        bad_proof.samples[1] = proof.samples[0].clone();
+        if bad_proof.samples[0] != proof.samples[0] {
+            assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientSampleMismatch));
+        }
+
+        let mut bad_proof = proof.clone();
+        bad_proof.samples[2].cumulative_work[31] ^= 1;
+        assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientInvalidWorkProof));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.suffix_headers.swap(3, 4);
+        assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientSuffixNotLinked));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.samples[3].header.header.nonce ^= 1;
+        assert!(verify(&bad_proof, &checkpoint, &total_work).is_err());
+
+        // the ancestors used for the difficulty check have to be the real parent and grandparent
+        let mut bad_proof = proof.clone();
+        bad_proof.samples[3].ancestor_headers[1].timestamp += 1;
+        assert_eq!(verify(&bad_proof, &checkpoint, &total_work), Err(DogeBridgeError::FlyClientAncestorsNotLinked));
+
+        // not enough work for the caller
+        let more_work = total_work.checked_add(&QChainWork::from_u64(1)).unwrap();
+        assert_eq!(verify(&proof, &checkpoint, &more_work), Err(DogeBridgeError::FlyClientInsufficientWork));
+
+        // a proof for the same chain from a different start block is not anchored at the checkpoint
+        let shifted_proof = generate_proof(&headers[1..], &NONCE)?;
+        assert_eq!(verify(&shifted_proof, &checkpoint, &QChainWork::ZERO), Err(DogeBridgeError::FlyClientCheckpointMismatch));
+        let mut bad_checkpoint = checkpoint.clone();
+        bad_checkpoint.block_tree_siblings[0] = [1u8; 32];
+        if checkpoint.block_number & 1 == 1 {
+            assert_eq!(verify(&proof, &bad_checkpoint, &total_work), Err(DogeBridgeError::FlyClientCheckpointMismatch));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flyclient_difficulty_bits() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        for x in headers[..100].windows(3) {
+            check_header_difficulty_bits::<DogeMainNetConfig>(x[2].height, &x[2].block_header.header, &x[1].block_header.header, &x[0].block_header.header)?;
+        }
+
+        // a block claiming the lowest difficulty right after real blocks
+        let mut header = headers[2].block_header.header;
+        header.bits = 0x1e0fffff;
+        assert_eq!(
+            check_header_difficulty_bits::<DogeMainNetConfig>(headers[2].height, &header, &headers[1].block_header.header, &headers[0].block_header.header),
+            Err(DogeBridgeError::DifficutlyBitsMismatch)
+        );
+        assert_eq!(
+            check_header_difficulty_bits::<DogeMainNetConfig>(headers[3].height, &headers[3].block_header.header, &headers[2].block_header.header, &headers[0].block_header.header),
+            Err(DogeBridgeError::FlyClientAncestorsNotLinked)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use zerocopy::little_endian::U32;
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{
+    block_data_tracker::BlockDataStorage, chain_state::QEDDogeChainStateCore, constants::DogeNetworkConfig, core_data::{QDogeBlockHeader, QHash256}, error::{DogeBridgeError, QDogeResult}, hash::{merkle::accumulator::MerkleAppendAccumulator, traits::MerkleZeroHasher}, math::chain_work::QChainWork
+};
+
+/// Public output of a header chain segment verification, committed as raw bytes by a zkVM guest.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned, Default)]
+#[repr(C)]
+pub struct HeaderChainSegmentOutput {
+    pub old_tip_block_number: U32,
+    pub old_tip_hash: QHash256,
+    pub new_tip_hash: QHash256,
+    pub old_block_tree_root: QHash256,
+    pub new_block_tree_root: QHash256,
+    pub count: U32,
+    /// sum of the block proofs of the appended headers, big-endian
+    pub total_work: QHash256,
+}
+
+impl HeaderChainSegmentOutput {
+    pub fn get_new_tip_block_number(&self) -> u32 {
+        self.old_tip_block_number.get() + self.count.get()
+    }
+    pub fn get_total_work(&self) -> QChainWork {
+        QChainWork::from_be_bytes(self.total_work)
+    }
+}
+
+/// Appends `headers` on top of the tip of `state` and returns the transition as a public output.
+///
+/// The headers are checked exactly like `append_block` checks them, but without trusting any
+/// precomputed aux pow hashes, so a segment which verifies here verifies on chain as well.
+/// On error `state` may have been partially updated and should be discarded.
+pub fn apply_header_chain_segment<
+    NC: DogeNetworkConfig,
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeTracker: MerkleAppendAccumulator<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+    BlockTreeHasher: MerkleZeroHasher<QHash256> + Copy,
+>(
+    state: &mut QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >,
+    headers: &[QDogeBlockHeader],
+) -> QDogeResult<HeaderChainSegmentOutput> {
+    state.ensure_internal_consistency()?;
+    let count: u32 = headers
+        .len()
+        .try_into()
+        .map_err(|_| DogeBridgeError::NumericalOverflow)?;
+
+    let old_tip_block_number = state.get_tip_block_number();
+    old_tip_block_number
+        .checked_add(count)
+        .ok_or(DogeBridgeError::NumericalOverflow)?;
+    let old_tip_record = state.block_data_tracker.get_record(old_tip_block_number)?;
+
+    let mut total_work = QChainWork::ZERO;
+    for (i, header) in headers.iter().enumerate() {
+        state.append_block::<NC>(old_tip_block_number + i as u32 + 1, header, None)?;
+        total_work = total_work
+            .checked_add(&QChainWork::get_block_proof(header.header.bits))
+            .ok_or(DogeBridgeError::NumericalOverflow)?;
+    }
+
+    let new_tip_record = state.block_data_tracker.get_record(state.get_tip_block_number())?;
+
+    Ok(HeaderChainSegmentOutput {
+        old_tip_block_number: old_tip_block_number.into(),
+        old_tip_hash: old_tip_record.block_hash,
+        new_tip_hash: new_tip_record.block_hash,
+        old_block_tree_root: old_tip_record.block_hash_tree_root,
+        new_block_tree_root: new_tip_record.block_hash_tree_root,
+        count: count.into(),
+        total_work: total_work.to_be_bytes(),
+    })
+}
+
+/// Pure variant of [`apply_header_chain_segment`] which leaves the starting snapshot untouched.
+pub fn verify_header_chain_segment<
+    NC: DogeNetworkConfig,
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeTracker: MerkleAppendAccumulator<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+    BlockTreeHasher: MerkleZeroHasher<QHash256> + Copy,
+>(
+    state: &QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        BlockTreeTracker,
+        BlockTreeHasher,
+    >,
+    headers: &[QDogeBlockHeader],
+) -> QDogeResult<HeaderChainSegmentOutput> {
+    let mut working_state = *state;
+    apply_header_chain_segment::<NC, _, _, _, _, _>(&mut working_state, headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_fixtures::{load_mainnet_headers, BlockHeaderWithIndex},
+        block_data_tracker::BlockDataStorage, chain_state::{QEDDogeChainStateCore, QEDDogeChainStateCoreMMR}, constants::DogeMainNetConfig, core_data::QDogeBlockHeader, error::DogeBridgeError, hash::{merkle::fixed_append_tree::FixedMerkleAppendTree, poseidon_bn254::QPoseidonBN254Hasher, sha256::QSha256Hasher}, init_params::{get_empty_siblings_with_hasher, InitBlockDataIBC}, math::chain_work::QChainWork
+    };
+
+    use super::verify_header_chain_segment;
+
+    const CACHE_SIZE: usize = 32;
+    const REQUIRED_CONFIRMATIONS: usize = 4;
+    const TREE_HEIGHT: usize = 32;
+    type TestChainState = QEDDogeChainStateCore<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>;
+    type TestChainStateMMR = QEDDogeChainStateCoreMMR<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>;
+    type TestChainStatePoseidon = QEDDogeChainStateCore<
+        CACHE_SIZE,
+        REQUIRED_CONFIRMATIONS,
+        TREE_HEIGHT,
+        FixedMerkleAppendTree<[u8; 32], TREE_HEIGHT>,
+        QPoseidonBN254Hasher,
+    >;
+
+    fn initial_state(headers: &[BlockHeaderWithIndex]) -> TestChainState {
+        let base: [QDogeBlockHeader; CACHE_SIZE] =
+            core::array::from_fn(|i| headers[i].block_header.clone());
+        let init_data = InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE - 1].height,
+        );
+        TestChainState::from_init_data(&init_data).unwrap()
+    }
+
+    #[test]
+    fn test_verify_mainnet_segment() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let state = initial_state(&headers);
+        let segment = &headers[CACHE_SIZE..CACHE_SIZE + 40];
+        let segment_headers = segment.iter().map(|x| x.block_header.clone()).collect::<alloc::vec::Vec<_>>();
+
+        let state_before = state;
+        let output = verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&state, &segment_headers)?;
+        assert_eq!(state, state_before);
+
+        let mut expected_state = state;
+        let mut expected_work = QChainWork::ZERO;
+        for x in segment {
+            expected_state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+            expected_work = expected_work
+                .checked_add(&QChainWork::get_block_proof(x.block_header.header.bits))
+                .unwrap();
+        }
+
+        assert_eq!(output.old_tip_block_number.get(), headers[CACHE_SIZE - 1].height);
+        assert_eq!(output.get_new_tip_block_number(), segment.last().unwrap().height);
+        assert_eq!(output.old_tip_hash, state.get_tip_block_hash());
+        assert_eq!(output.new_tip_hash, segment.last().unwrap().block_header.header.get_hash());
+        assert_eq!(output.new_tip_hash, expected_state.get_tip_block_hash());
+        assert_eq!(
+            output.old_block_tree_root,
+            state.block_data_tracker.get_record(state.get_tip_block_number())?.block_hash_tree_root
+        );
+        assert_eq!(
+            output.new_block_tree_root,
+            expected_state.block_data_tracker.get_record(expected_state.get_tip_block_number())?.block_hash_tree_root
+        );
+        assert_eq!(output.count.get(), segment.len() as u32);
+        assert_eq!(output.get_total_work(), expected_work);
+        assert!(!expected_work.is_zero());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_mainnet_segment_mmr() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let base: [QDogeBlockHeader; CACHE_SIZE] =
+            core::array::from_fn(|i| headers[i].block_header.clone());
+        let init_data = InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE - 1].height,
+        );
+        let state = TestChainStateMMR::from_init_data(&init_data)?;
+        state.ensure_internal_consistency()?;
+
+        let segment = &headers[CACHE_SIZE..CACHE_SIZE + 10];
+        let segment_headers = segment.iter().map(|x| x.block_header.clone()).collect::<alloc::vec::Vec<_>>();
+        let output = verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&state, &segment_headers)?;
+
+        let mut expected_state = state;
+        for x in segment {
+            expected_state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+        assert_eq!(output.new_tip_hash, expected_state.get_tip_block_hash());
+        assert_eq!(output.new_block_tree_root, expected_state.block_tree_tracker.get_root::<QSha256Hasher>());
+        assert_eq!(expected_state.block_tree_tracker.get_leaf_count(), segment.last().unwrap().height as u64 + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_mainnet_segment_poseidon() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let base: [QDogeBlockHeader; CACHE_SIZE] =
+            core::array::from_fn(|i| headers[i].block_header.clone());
+        let init_data = InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT, QPoseidonBN254Hasher>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE - 1].height,
+        );
+        let state = TestChainStatePoseidon::from_init_data(&init_data)?;
+        state.ensure_internal_consistency()?;
+
+        let segment = &headers[CACHE_SIZE..CACHE_SIZE + 10];
+        let segment_headers = segment.iter().map(|x| x.block_header.clone()).collect::<alloc::vec::Vec<_>>();
+        let output = verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&state, &segment_headers)?;
+        let sha256_output = verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&initial_state(&headers), &segment_headers)?;
+
+        let mut expected_tree = FixedMerkleAppendTree::<[u8; 32], TREE_HEIGHT>::new_from_hasher::<QPoseidonBN254Hasher>(
+            headers[0].height as u64 + 1,
+            get_empty_siblings_with_hasher::<QPoseidonBN254Hasher, TREE_HEIGHT>(),
+            headers[0].block_header.header.get_hash(),
+        );
+        for x in &headers[1..CACHE_SIZE + 10] {
+            expected_tree.append::<QPoseidonBN254Hasher>(x.block_header.header.get_hash());
+        }
+        assert_eq!(output.new_tip_hash, sha256_output.new_tip_hash);
+        assert_eq!(output.new_block_tree_root, expected_tree.get_root::<QPoseidonBN254Hasher>());
+        assert_ne!(output.new_block_tree_root, sha256_output.new_block_tree_root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_segment_rejects_gap() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let state = initial_state(&headers);
+        let segment_headers = [
+            headers[CACHE_SIZE].block_header.clone(),
+            headers[CACHE_SIZE + 2].block_header.clone(),
+        ];
+        assert_eq!(
+            verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&state, &segment_headers),
+            Err(DogeBridgeError::InvalidParentBlockHash)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_empty_segment() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let state = initial_state(&headers);
+        let output = verify_header_chain_segment::<DogeMainNetConfig, _, _, _, _, _>(&state, &[])?;
+        assert_eq!(output.old_tip_hash, output.new_tip_hash);
+        assert_eq!(output.old_block_tree_root, output.new_block_tree_root);
+        assert_eq!(output.count.get(), 0);
+        assert!(output.get_total_work().is_zero());
+        Ok(())
+    }
+}
@@ -25,4 +25,8 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
 pub mod check_doge_block_seq;
-pub mod check_doge_block;
\ No newline at end of file
+pub mod check_doge_block;
+pub mod header_chain_segment;
+pub mod flyclient;
+pub mod check_doge_block_transactions;
+pub mod subsidy;
@@ -0,0 +1,182 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use crate::{
+    constants::{DogeNetworkConfig, DOGE_COIN},
+    core_data::QHash256,
+};
+
+const MT19937_N: usize = 624;
+const MT19937_M: usize = 397;
+
+/// 32 bit Mersenne Twister, matching `boost::mt19937`.
+pub struct MT19937 {
+    state: [u32; MT19937_N],
+    index: usize,
+}
+
+impl MT19937 {
+    pub fn new(seed: u32) -> Self {
+        let mut state = [0u32; MT19937_N];
+        state[0] = seed;
+        for i in 1..MT19937_N {
+            state[i] = 1812433253u32
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Self { state, index: MT19937_N }
+    }
+    fn twist(&mut self) {
+        for i in 0..MT19937_N {
+            let y = (self.state[i] & 0x80000000) | (self.state[(i + 1) % MT19937_N] & 0x7fffffff);
+            let mut next = self.state[(i + MT19937_M) % MT19937_N] ^ (y >> 1);
+            if y & 1 != 0 {
+                next ^= 0x9908b0df;
+            }
+            self.state[i] = next;
+        }
+        self.index = 0;
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        if self.index >= MT19937_N {
+            self.twist();
+        }
+        let mut y = self.state[self.index];
+        self.index += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c5680;
+        y ^= (y << 15) & 0xefc60000;
+        y ^ (y >> 18)
+    }
+    /// Uniform integer in `[min, max]`, matching `boost::uniform_int<>(min, max)(gen)` for ranges smaller than 2^32.
+    pub fn gen_uniform_int(&mut self, min: u32, max: u32) -> u32 {
+        let range = max - min;
+        if range == u32::MAX {
+            return self.next_u32();
+        }
+        // the engine range is 2^32 - 1, so boost divides the output into buckets and rejects the remainder
+        let mut bucket_size = u32::MAX / (range + 1);
+        if u32::MAX % (range + 1) == range {
+            bucket_size += 1;
+        }
+        loop {
+            let result = self.next_u32() / bucket_size;
+            if result <= range {
+                return result + min;
+            }
+        }
+    }
+}
+
+/// Seed of the random reward era: the 7 hex digits at offset 7 of the previous block hash in display (reversed) order.
+pub fn get_random_reward_seed(previous_block_hash: &QHash256) -> u32 {
+    // display character 7 is the low nibble of display byte 3, characters 8..14 are display bytes 4..=6
+    let display_byte = |i: usize| previous_block_hash[31 - i] as u32;
+    ((display_byte(3) & 0x0f) << 24) | (display_byte(4) << 16) | (display_byte(5) << 8) | display_byte(6)
+}
+
+/// Block subsidy in koinu, equivalent to `GetDogecoinBlockSubsidy` in dogecoin core.
+///
+/// Before `simplified_rewards_height` the reward is a random number of whole coins seeded by the previous block hash, then the
+/// reward is 500,000 DOGE halved every `subsidy_halving_interval` blocks until the sixth interval, after which it is fixed at 10,000 DOGE.
+pub fn get_block_subsidy<NC: DogeNetworkConfig>(height: u32, previous_block_hash: &QHash256) -> u64 {
+    let halving_interval = NC::NETWORK_PARAMS.subsidy_halving_interval;
+    let halvings = height / halving_interval;
+    if height < NC::NETWORK_PARAMS.simplified_rewards_height {
+        let max_reward = (1_000_000u32 >> halvings.min(31)).saturating_sub(1);
+        if max_reward == 0 {
+            return DOGE_COIN;
+        }
+        let mut gen = MT19937::new(get_random_reward_seed(previous_block_hash));
+        (1 + gen.gen_uniform_int(1, max_reward) as u64) * DOGE_COIN
+    } else if halvings < 6 {
+        (500_000 * DOGE_COIN) >> halvings
+    } else {
+        10_000 * DOGE_COIN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{DogeMainNetConfig, DogeRegTestConfig, DOGE_COIN};
+
+    use super::{get_block_subsidy, get_random_reward_seed, MT19937};
+
+    #[test]
+    fn test_mt19937() {
+        // required by the C++ standard for std::mt19937 with the default seed
+        let mut gen = MT19937::new(5489);
+        let mut value = 0;
+        for _ in 0..10000 {
+            value = gen.next_u32();
+        }
+        assert_eq!(value, 4123659995);
+        let mut gen = MT19937::new(5489);
+        assert_eq!(gen.next_u32(), 3499211612);
+    }
+
+    #[test]
+    fn test_block_subsidy() {
+        // display hex "1a69d3c5e4b7c2d9..." -> seed 0x5e4b7c2
+        let mut previous_block_hash = [0u8; 32];
+        for (i, b) in [0x1a, 0x69, 0xd3, 0xc5, 0xe4, 0xb7, 0xc2, 0xd9].into_iter().enumerate() {
+            previous_block_hash[31 - i] = b;
+        }
+        assert_eq!(get_random_reward_seed(&previous_block_hash), 0x5e4b7c2);
+
+        // the reward only depends on the seed and the halving of the maximum reward
+        for (height, reward) in [(1u32, 163_305), (50_000, 163_305), (99_999, 163_305), (100_000, 81_644), (144_999, 81_644)] {
+            assert_eq!(get_block_subsidy::<DogeMainNetConfig>(height, &previous_block_hash), reward * DOGE_COIN);
+        }
+        assert_eq!(get_block_subsidy::<DogeMainNetConfig>(145_000, &previous_block_hash), 250_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeMainNetConfig>(200_000, &previous_block_hash), 125_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeMainNetConfig>(599_999, &previous_block_hash), 15_625 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeMainNetConfig>(600_000, &previous_block_hash), 10_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeMainNetConfig>(5_610_330, &previous_block_hash), 10_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeRegTestConfig>(0, &previous_block_hash), 500_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeRegTestConfig>(150, &previous_block_hash), 250_000 * DOGE_COIN);
+        assert_eq!(get_block_subsidy::<DogeRegTestConfig>(900, &previous_block_hash), 10_000 * DOGE_COIN);
+    }
+
+    // sums the rewards of <heights>, using the sum so far as the previous block hash, same as subsidy_tests.cpp in dogecoin core
+    fn get_subsidy_sum(heights: core::ops::RangeInclusive<u32>) -> u64 {
+        let mut sum = 0u64;
+        let mut previous_block_hash = [0u8; 32];
+        for height in heights {
+            let subsidy = get_block_subsidy::<DogeMainNetConfig>(height, &previous_block_hash);
+            assert!(subsidy <= 1_000_000 * DOGE_COIN);
+            sum += subsidy;
+            previous_block_hash[0..8].copy_from_slice(&sum.to_le_bytes());
+        }
+        sum
+    }
+
+    #[test]
+    fn test_block_subsidy_random_era_sum() {
+        assert_eq!(get_subsidy_sum(0..=100_000), 54_894_174_438 * DOGE_COIN);
+        assert_eq!(get_subsidy_sum(100_000..=145_000), 12_349_960_000 * DOGE_COIN);
+    }
+}
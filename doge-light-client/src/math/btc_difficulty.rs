@@ -24,6 +24,8 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+use alloc::{format, string::String};
+
 use crate::core_data::QHash256;
 
 const DIFFICULTY_NEGATIVE_FLAG: u32 = 0x00800000;
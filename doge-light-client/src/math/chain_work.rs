@@ -0,0 +1,235 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+/// 256-bit unsigned chain work, stored as big-endian u64 limbs so the derived `Ord` matches numeric order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QChainWork([u64; 4]);
+
+impl QChainWork {
+    pub const ZERO: Self = Self([0; 4]);
+
+    pub fn from_u64(value: u64) -> Self {
+        Self([0, 0, 0, value])
+    }
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        Self(core::array::from_fn(|i| {
+            u64::from_be_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap())
+        }))
+    }
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for i in 0..4 {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&self.0[i].to_be_bytes());
+        }
+        bytes
+    }
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    // decodes nBits the same way as arith_uint256::SetCompact, returning None for negative or overflowing targets
+    pub fn target_from_compact_bits(bits: u32) -> Option<Self> {
+        let exponent = bits >> 24;
+        let mut mantissa = bits & 0x007fffff;
+        let negative = mantissa != 0 && (bits & 0x00800000) != 0;
+        let overflow = mantissa != 0
+            && (exponent > 34
+                || (mantissa > 0xff && exponent > 33)
+                || (mantissa > 0xffff && exponent > 32));
+        if negative || overflow {
+            return None;
+        }
+        if exponent <= 3 {
+            mantissa >>= 8 * (3 - exponent);
+            Some(Self::from_u64(mantissa as u64))
+        } else {
+            Some(Self::from_u64(mantissa as u64).shl(8 * (exponent - 3)))
+        }
+    }
+
+    // equivalent to GetBlockProof in Dogecoin Core: 2**256 / (target + 1), or zero for an invalid target
+    pub fn get_block_proof(bits: u32) -> Self {
+        match Self::target_from_compact_bits(bits) {
+            Some(target) if !target.is_zero() => {
+                // 2**256 does not fit, so compute (~target / (target + 1)) + 1 instead
+                let (target_plus_one, _) = target.overflowing_add(&Self::from_u64(1));
+                let (result, _) = target
+                    .not()
+                    .div(&target_plus_one)
+                    .overflowing_add(&Self::from_u64(1));
+                result
+            }
+            _ => Self::ZERO,
+        }
+    }
+
+    pub fn overflowing_add(&self, other: &Self) -> (Self, bool) {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        for i in (0..4).rev() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            result[i] = sum;
+            carry = c1 || c2;
+        }
+        (Self(result), carry)
+    }
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        match self.overflowing_add(other) {
+            (result, false) => Some(result),
+            _ => None,
+        }
+    }
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        match self.overflowing_sub(other) {
+            (result, false) => Some(result),
+            _ => None,
+        }
+    }
+    pub fn shr(&self, shift: u32) -> Self {
+        if shift >= 256 {
+            return Self::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        Self(core::array::from_fn(|i| {
+            if i < limb_shift {
+                0
+            } else if bit_shift != 0 && i > limb_shift {
+                (self.0[i - limb_shift] >> bit_shift) | (self.0[i - limb_shift - 1] << (64 - bit_shift))
+            } else {
+                self.0[i - limb_shift] >> bit_shift
+            }
+        }))
+    }
+    /// Returns `floor(self * value / 2**64)`, i.e. scales `self` by the fraction `value / 2**64`.
+    pub fn mul_fraction_u64(&self, value: u64) -> Self {
+        let mut result = [0u64; 4];
+        let mut carry = 0u128;
+        // the lowest limb of the 320-bit product is dropped by the division
+        for i in (0..4).rev() {
+            let product = (self.0[i] as u128) * (value as u128) + carry;
+            if i < 3 {
+                result[i + 1] = product as u64;
+            }
+            carry = product >> 64;
+        }
+        result[0] = carry as u64;
+        Self(result)
+    }
+    fn overflowing_sub(&self, other: &Self) -> (Self, bool) {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        for i in (0..4).rev() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            result[i] = diff;
+            borrow = b1 || b2;
+        }
+        (Self(result), borrow)
+    }
+    fn not(&self) -> Self {
+        Self(self.0.map(|x| !x))
+    }
+    fn shl(&self, shift: u32) -> Self {
+        if shift >= 256 {
+            return Self::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        Self(core::array::from_fn(|i| {
+            let src = i + limb_shift;
+            if src >= 4 {
+                0
+            } else if bit_shift != 0 && src + 1 < 4 {
+                (self.0[src] << bit_shift) | (self.0[src + 1] >> (64 - bit_shift))
+            } else {
+                self.0[src] << bit_shift
+            }
+        }))
+    }
+    fn get_bit(&self, index: usize) -> bool {
+        (self.0[3 - index / 64] >> (index % 64)) & 1 == 1
+    }
+    fn set_bit(&mut self, index: usize) {
+        self.0[3 - index / 64] |= 1u64 << (index % 64);
+    }
+    // binary long division with a fixed number of iterations, the divisor must be non-zero
+    fn div(&self, divisor: &Self) -> Self {
+        let mut quotient = Self::ZERO;
+        let mut remainder = Self::ZERO;
+        for i in (0..256).rev() {
+            let carry_out = remainder.get_bit(255);
+            remainder = remainder.shl(1);
+            if self.get_bit(i) {
+                remainder.0[3] |= 1;
+            }
+            if carry_out || remainder >= *divisor {
+                remainder = remainder.overflowing_sub(divisor).0;
+                quotient.set_bit(i);
+            }
+        }
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_proof() {
+        // bitcoin genesis difficulty
+        assert_eq!(QChainWork::get_block_proof(0x1d00ffff), QChainWork::from_u64(0x100010001));
+        // dogecoin pow limit
+        assert_eq!(QChainWork::get_block_proof(0x1e0fffff), QChainWork::from_u64(0x100001));
+        // regtest pow limit is 2**255 - 1 ish, so every block is worth 2
+        assert_eq!(QChainWork::get_block_proof(0x207fffff), QChainWork::from_u64(2));
+        assert_eq!(QChainWork::get_block_proof(0x01803456), QChainWork::ZERO);
+        assert_eq!(QChainWork::get_block_proof(0xff123456), QChainWork::ZERO);
+        assert_eq!(QChainWork::get_block_proof(0), QChainWork::ZERO);
+    }
+
+    #[test]
+    fn test_shr_and_fraction() {
+        let a = QChainWork::from_be_bytes(hex_literal::hex!("8000000000000000000000000000000000000000000000000000000000000001"));
+        assert_eq!(a.shr(255), QChainWork::from_u64(1));
+        assert_eq!(a.shr(192), QChainWork::from_u64(0x8000000000000000));
+        assert_eq!(a.shr(1).to_be_bytes(), hex_literal::hex!("4000000000000000000000000000000000000000000000000000000000000000"));
+        assert_eq!(QChainWork::from_u64(1000).mul_fraction_u64(1 << 63), QChainWork::from_u64(500));
+        assert_eq!(a.mul_fraction_u64(1 << 62), a.shr(2));
+        assert_eq!(QChainWork::from_u64(7).checked_sub(&QChainWork::from_u64(8)), None);
+    }
+
+    #[test]
+    fn test_add_and_bytes() {
+        let a = QChainWork::from_be_bytes(hex_literal::hex!("00000000000000000000000000000000ffffffffffffffffffffffffffffffff"));
+        let b = a.checked_add(&QChainWork::from_u64(1)).unwrap();
+        assert_eq!(b.to_be_bytes(), hex_literal::hex!("0000000000000000000000000000000100000000000000000000000000000000"));
+        assert!(b > a);
+        assert_eq!(QChainWork::from_be_bytes([0xff; 32]).checked_add(&QChainWork::from_u64(1)), None);
+    }
+}
@@ -24,4 +24,5 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
-pub mod btc_difficulty;
\ No newline at end of file
+pub mod btc_difficulty;
+pub mod chain_work;
@@ -53,6 +53,11 @@ pub struct DogeNetworkParams {
     pub strict_chain_id: bool,
     pub aux_pow_chain_id: u32,
     pub min_valid_height: u32,
+    // coinbase transactions of blocks at or above this height must start with the serialized block height
+    pub bip34_height: u32,
+    pub subsidy_halving_interval: u32,
+    // blocks below this height have a random reward seeded by the previous block hash
+    pub simplified_rewards_height: u32,
 }
 
 impl DogeNetworkParams {
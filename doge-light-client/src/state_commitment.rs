@@ -0,0 +1,329 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! A single hash committing to the whole chain state, with proofs opening individual fields.
+//!
+//! ```text
+//! commitment = H(H(summary_leaf, records_root), H(levels_root, 0))
+//! ```
+//!
+//! - `summary_leaf` hashes the [`QEDDogeStateSummary`] (tip, finalized height, ring position, sizes and block tree root)
+//! - `records_root` is the merkle root of the record ring in storage order, padded to a power of two with zero leaves
+//! - `levels_root` is the merkle root of the block tree levels from the bottom up, padded the same way
+//!
+//! Record leaves hash the bytes of a [`BlockDataRecord`] and level leaves the bytes of a [`MerkleAppendTreeLevel`],
+//! so the commitment can be recomputed from the raw account data.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use alloc::vec::Vec;
+use zerocopy::{little_endian::{U16, U32, U64}, IntoBytes};
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{
+    block_data_tracker::BlockDataRecord,
+    core_data::QHash256,
+    error::{DogeBridgeError, QDogeResult},
+    hash::{
+        merkle::{append_tree::MerkleAppendTreeLevel, utils::compute_root_merkle_proof_generic},
+        traits::{BytesHasher, MerkleHasher, ZeroableHash},
+    },
+};
+
+/// The chain state fields which are not part of the record ring or the block tree levels.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct QEDDogeStateSummary {
+    pub tip_block_number: U32,
+    pub finalized_block_number: U32,
+    pub tip_internal_index: U16,
+    pub block_hash_cache_size: U32,
+    pub block_tree_height: U32,
+    pub block_tree_next_index: U64,
+    pub block_tree_root: QHash256,
+}
+
+impl QEDDogeStateSummary {
+    pub fn contains_block(&self, block_number: u32) -> bool {
+        let tip_block_number = self.tip_block_number.get();
+        block_number <= tip_block_number
+            && tip_block_number - block_number < self.block_hash_cache_size.get()
+    }
+    /// Storage slot of the record for `block_number` in the record ring.
+    pub fn get_record_slot(&self, block_number: u32) -> QDogeResult<usize> {
+        if !self.contains_block(block_number) {
+            return Err(DogeBridgeError::BlockNotInCache);
+        }
+        let cache_size = self.block_hash_cache_size.get() as usize;
+        let offset = (self.tip_block_number.get() - block_number) as usize;
+        Ok((cache_size + self.tip_internal_index.get() as usize - offset) % cache_size)
+    }
+}
+
+/// Opens the summary of a state commitment, also the base of every field opening.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QEDDogeStateCommitmentOpening {
+    pub summary: QEDDogeStateSummary,
+    pub records_root: QHash256,
+    pub levels_root: QHash256,
+}
+
+impl QEDDogeStateCommitmentOpening {
+    pub fn get_commitment<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self) -> QHash256 {
+        Hasher::two_to_one(
+            &Hasher::two_to_one(&Hasher::hash_bytes(self.summary.as_bytes()), &self.records_root),
+            &Hasher::two_to_one(&self.levels_root, &QHash256::get_zero_value()),
+        )
+    }
+    pub fn verify<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self, commitment: QHash256) -> QDogeResult<()> {
+        if self.get_commitment::<Hasher>() != commitment {
+            return Err(DogeBridgeError::StateCommitmentMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Opens the record of one block in the cache, e.g. to prove the tx tree root of block N.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QEDDogeStateRecordOpening {
+    pub state: QEDDogeStateCommitmentOpening,
+    pub block_number: u32,
+    pub record: BlockDataRecord,
+    pub siblings: Vec<QHash256>,
+}
+
+impl QEDDogeStateRecordOpening {
+    pub fn verify<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self, commitment: QHash256) -> QDogeResult<()> {
+        self.state.verify::<Hasher>(commitment)?;
+        let slot = self.state.summary.get_record_slot(self.block_number)?;
+        verify_leaf_proof::<Hasher>(
+            Hasher::hash_bytes(self.record.as_bytes()),
+            slot,
+            self.state.summary.block_hash_cache_size.get() as usize,
+            &self.siblings,
+            self.state.records_root,
+        )
+    }
+}
+
+/// Opens one level of the block tree, counted from the leaves.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QEDDogeStateLevelOpening {
+    pub state: QEDDogeStateCommitmentOpening,
+    pub level_index: u32,
+    pub level: MerkleAppendTreeLevel<QHash256>,
+    pub siblings: Vec<QHash256>,
+}
+
+impl QEDDogeStateLevelOpening {
+    pub fn verify<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self, commitment: QHash256) -> QDogeResult<()> {
+        self.state.verify::<Hasher>(commitment)?;
+        let block_tree_height = self.state.summary.block_tree_height.get() as usize;
+        if self.level_index as usize >= block_tree_height {
+            return Err(DogeBridgeError::StateCommitmentFieldIndexOutOfRange);
+        }
+        verify_leaf_proof::<Hasher>(
+            Hasher::hash_bytes(self.level.as_bytes()),
+            self.level_index as usize,
+            block_tree_height,
+            &self.siblings,
+            self.state.levels_root,
+        )
+    }
+}
+
+fn get_leaf_tree_depth(leaf_count: usize) -> usize {
+    leaf_count.next_power_of_two().trailing_zeros() as usize
+}
+
+fn verify_leaf_proof<Hasher: MerkleHasher<QHash256>>(
+    leaf: QHash256,
+    index: usize,
+    leaf_count: usize,
+    siblings: &[QHash256],
+    root: QHash256,
+) -> QDogeResult<()> {
+    if siblings.len() != get_leaf_tree_depth(leaf_count)
+        || compute_root_merkle_proof_generic::<QHash256, Hasher>(leaf, index as u64, siblings) != root
+    {
+        return Err(DogeBridgeError::StateCommitmentInvalidFieldProof);
+    }
+    Ok(())
+}
+
+// returns the root of the zero padded tree over `leaves` and the siblings of `index`
+fn get_leaf_tree_root_and_siblings<Hasher: MerkleHasher<QHash256>>(
+    mut leaves: Vec<QHash256>,
+    mut index: usize,
+) -> (QHash256, Vec<QHash256>) {
+    leaves.resize(leaves.len().next_power_of_two(), QHash256::get_zero_value());
+    let mut siblings = Vec::with_capacity(get_leaf_tree_depth(leaves.len()));
+    while leaves.len() > 1 {
+        siblings.push(leaves[index ^ 1]);
+        leaves = leaves
+            .chunks_exact(2)
+            .map(|pair| Hasher::two_to_one(&pair[0], &pair[1]))
+            .collect();
+        index >>= 1;
+    }
+    (leaves[0], siblings)
+}
+
+/// Computes the commitment and field openings over borrowed chain state parts.
+pub struct QEDDogeStateCommitmentBuilder<'a> {
+    pub summary: QEDDogeStateSummary,
+    pub records: &'a [BlockDataRecord],
+    pub levels: &'a [MerkleAppendTreeLevel<QHash256>],
+}
+
+impl<'a> QEDDogeStateCommitmentBuilder<'a> {
+    fn get_record_leaves<Hasher: BytesHasher<QHash256>>(&self) -> Vec<QHash256> {
+        self.records.iter().map(|record| Hasher::hash_bytes(record.as_bytes())).collect()
+    }
+    fn get_level_leaves<Hasher: BytesHasher<QHash256>>(&self) -> Vec<QHash256> {
+        self.levels.iter().map(|level| Hasher::hash_bytes(level.as_bytes())).collect()
+    }
+
+    pub fn get_opening<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self) -> QEDDogeStateCommitmentOpening {
+        QEDDogeStateCommitmentOpening {
+            summary: self.summary,
+            records_root: get_leaf_tree_root_and_siblings::<Hasher>(self.get_record_leaves::<Hasher>(), 0).0,
+            levels_root: get_leaf_tree_root_and_siblings::<Hasher>(self.get_level_leaves::<Hasher>(), 0).0,
+        }
+    }
+    pub fn get_commitment<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(&self) -> QHash256 {
+        self.get_opening::<Hasher>().get_commitment::<Hasher>()
+    }
+    pub fn get_record_opening<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(
+        &self,
+        block_number: u32,
+    ) -> QDogeResult<QEDDogeStateRecordOpening> {
+        let slot = self.summary.get_record_slot(block_number)?;
+        let (_, siblings) = get_leaf_tree_root_and_siblings::<Hasher>(self.get_record_leaves::<Hasher>(), slot);
+        Ok(QEDDogeStateRecordOpening {
+            state: self.get_opening::<Hasher>(),
+            block_number,
+            record: self.records[slot],
+            siblings,
+        })
+    }
+    pub fn get_level_opening<Hasher: MerkleHasher<QHash256> + BytesHasher<QHash256>>(
+        &self,
+        level_index: u32,
+    ) -> QDogeResult<QEDDogeStateLevelOpening> {
+        let level = *self
+            .levels
+            .get(level_index as usize)
+            .ok_or(DogeBridgeError::StateCommitmentFieldIndexOutOfRange)?;
+        let (_, siblings) = get_leaf_tree_root_and_siblings::<Hasher>(self.get_level_leaves::<Hasher>(), level_index as usize);
+        Ok(QEDDogeStateLevelOpening {
+            state: self.get_opening::<Hasher>(),
+            level_index,
+            level,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_fixtures::load_mainnet_headers,
+        chain_state::QEDDogeChainStateCore, chain_state_view::{QEDDogeChainStateLayout, QEDDogeChainStateView}, constants::DogeMainNetConfig, core_data::QDogeBlockHeader, error::DogeBridgeError, hash::sha256::QSha256Hasher, init_params::InitBlockDataIBC
+    };
+
+    const CACHE_SIZE: usize = 24;
+    const TREE_HEIGHT: usize = 32;
+    type TestChainState = QEDDogeChainStateCore<CACHE_SIZE, 4, TREE_HEIGHT>;
+
+    #[test]
+    fn test_state_commitment_openings() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let base: [QDogeBlockHeader; CACHE_SIZE] = core::array::from_fn(|i| headers[i].block_header.clone());
+        let mut state = TestChainState::from_init_data(&InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE - 1].height,
+        )).unwrap();
+        for x in &headers[CACHE_SIZE..CACHE_SIZE + 30] {
+            state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+
+        let commitment = state.get_state_commitment::<QSha256Hasher>();
+        let layout = QEDDogeChainStateLayout::new_for::<CACHE_SIZE, 4, TREE_HEIGHT>()?;
+        let mut view_data = alloc::vec![0u8; layout.get_data_size()];
+        let view = QEDDogeChainStateView::<QSha256Hasher>::init_from_chain_state(&mut view_data, &state)?;
+        assert_eq!(view.get_state_commitment::<QSha256Hasher>(), commitment);
+
+        let builder = state.get_state_commitment_builder();
+        let opening = builder.get_opening::<QSha256Hasher>();
+        opening.verify::<QSha256Hasher>(commitment)?;
+        assert_eq!(opening.summary.tip_block_number.get(), state.get_tip_block_number());
+        assert_eq!(opening.summary.finalized_block_number.get(), state.get_finalized_block_number());
+
+        // the record for block N has tx root X
+        let block = &headers[CACHE_SIZE + 20];
+        let record_opening = builder.get_record_opening::<QSha256Hasher>(block.height)?;
+        record_opening.verify::<QSha256Hasher>(commitment)?;
+        assert_eq!(record_opening.record.tx_tree_merkle_root, block.block_header.header.merkle_root);
+
+        let mut bad_record_opening = record_opening.clone();
+        bad_record_opening.record.tx_tree_merkle_root[0] ^= 1;
+        assert_eq!(bad_record_opening.verify::<QSha256Hasher>(commitment), Err(DogeBridgeError::StateCommitmentInvalidFieldProof));
+        bad_record_opening = record_opening.clone();
+        bad_record_opening.block_number -= 1;
+        assert_eq!(bad_record_opening.verify::<QSha256Hasher>(commitment), Err(DogeBridgeError::StateCommitmentInvalidFieldProof));
+        bad_record_opening.state.summary.tip_block_number += 1;
+        assert_eq!(bad_record_opening.verify::<QSha256Hasher>(commitment), Err(DogeBridgeError::StateCommitmentMismatch));
+        assert_eq!(
+            builder.get_record_opening::<QSha256Hasher>(headers[0].height).err(),
+            Some(DogeBridgeError::BlockNotInCache)
+        );
+
+        let top_level = builder.get_level_opening::<QSha256Hasher>(TREE_HEIGHT as u32 - 1)?;
+        top_level.verify::<QSha256Hasher>(commitment)?;
+        assert_eq!(top_level.level.get_hash::<QSha256Hasher>(), opening.summary.block_tree_root);
+        let mut bad_level_opening = top_level.clone();
+        bad_level_opening.level_index = TREE_HEIGHT as u32;
+        assert_eq!(bad_level_opening.verify::<QSha256Hasher>(commitment), Err(DogeBridgeError::StateCommitmentFieldIndexOutOfRange));
+
+        let old_commitment = commitment;
+        let next = &headers[CACHE_SIZE + 30];
+        state.append_block::<DogeMainNetConfig>(next.height, &next.block_header, None)?;
+        assert_ne!(state.get_state_commitment::<QSha256Hasher>(), old_commitment);
+        Ok(())
+    }
+}
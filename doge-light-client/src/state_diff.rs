@@ -0,0 +1,204 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Compact diffs between two serialized chain states.
+//!
+//! Appending a block only touches one record, the tip fields and a few block tree levels, so mirrors can
+//! stay in sync by applying the changed byte ranges instead of copying the whole state. Every diff commits
+//! to the hash of the state it was computed from and the hash of the resulting state.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshSerialize, BorshDeserialize};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use alloc::vec::Vec;
+
+use crate::{
+    core_data::QHash256,
+    error::{DogeBridgeError, QDogeResult},
+    hash::{sha256::QSha256Hasher, traits::BytesHasher},
+};
+
+/// Unchanged gaps up to this size are merged into the surrounding chunk, since starting a new chunk costs
+/// at least as many bytes (offset + length).
+pub const QDOGE_STATE_DIFF_MAX_MERGED_GAP: usize = 8;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QEDDogeStateDiffChunk {
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QEDDogeStateDiff {
+    pub state_size: u32,
+    pub old_state_hash: QHash256,
+    pub new_state_hash: QHash256,
+    pub chunks: Vec<QEDDogeStateDiffChunk>,
+}
+
+pub fn get_state_hash(data: &[u8]) -> QHash256 {
+    QSha256Hasher::hash_bytes(data)
+}
+
+impl QEDDogeStateDiff {
+    /// Computes the byte ranges of `new_data` which differ from `old_data`.
+    pub fn new(old_data: &[u8], new_data: &[u8]) -> QDogeResult<Self> {
+        if old_data.len() != new_data.len() {
+            return Err(DogeBridgeError::StateDiffSizeMismatch);
+        }
+        let mut chunks: Vec<QEDDogeStateDiffChunk> = Vec::new();
+        let mut last_changed_end = 0usize;
+        for (i, (old, new)) in old_data.iter().zip(new_data.iter()).enumerate() {
+            if old == new {
+                continue;
+            }
+            match chunks.last_mut() {
+                Some(chunk) if i - last_changed_end <= QDOGE_STATE_DIFF_MAX_MERGED_GAP => {
+                    chunk.data.extend_from_slice(&new_data[last_changed_end..=i]);
+                }
+                _ => chunks.push(QEDDogeStateDiffChunk {
+                    offset: i as u32,
+                    data: alloc::vec![*new],
+                }),
+            }
+            last_changed_end = i + 1;
+        }
+        Ok(Self {
+            state_size: new_data.len() as u32,
+            old_state_hash: get_state_hash(old_data),
+            new_state_hash: get_state_hash(new_data),
+            chunks,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Number of changed bytes carried by the diff.
+    pub fn get_data_len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.data.len()).sum()
+    }
+
+    fn ensure_chunks_in_bounds(&self) -> QDogeResult<()> {
+        let mut min_offset = 0usize;
+        for chunk in self.chunks.iter() {
+            let start = chunk.offset as usize;
+            let end = start
+                .checked_add(chunk.data.len())
+                .ok_or(DogeBridgeError::StateDiffChunkOutOfBounds)?;
+            if start < min_offset || chunk.data.is_empty() || end > self.state_size as usize {
+                return Err(DogeBridgeError::StateDiffChunkOutOfBounds);
+            }
+            min_offset = end;
+        }
+        Ok(())
+    }
+
+    /// Applies the diff to `data` in place.
+    ///
+    /// `data` must hash to `old_state_hash`, if the result does not hash to `new_state_hash` the original
+    /// bytes are restored and an error is returned.
+    pub fn apply(&self, data: &mut [u8]) -> QDogeResult<()> {
+        if data.len() != self.state_size as usize {
+            return Err(DogeBridgeError::StateDiffSizeMismatch);
+        }
+        self.ensure_chunks_in_bounds()?;
+        if get_state_hash(data) != self.old_state_hash {
+            return Err(DogeBridgeError::StateDiffBaseHashMismatch);
+        }
+
+        let mut previous_chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in self.chunks.iter() {
+            let target = &mut data[chunk.offset as usize..chunk.offset as usize + chunk.data.len()];
+            previous_chunks.push(target.to_vec());
+            target.copy_from_slice(&chunk.data);
+        }
+        if get_state_hash(data) != self.new_state_hash {
+            for (chunk, previous) in self.chunks.iter().zip(previous_chunks.iter()) {
+                data[chunk.offset as usize..chunk.offset as usize + previous.len()].copy_from_slice(previous);
+            }
+            return Err(DogeBridgeError::StateDiffResultHashMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use crate::{
+        test_fixtures::load_mainnet_headers,
+        chain_state::QEDDogeChainStateCore, constants::DogeMainNetConfig, core_data::QDogeBlockHeader, error::DogeBridgeError, init_params::InitBlockDataIBC
+    };
+
+    use super::QEDDogeStateDiff;
+
+    const CACHE_SIZE: usize = 32;
+    type TestChainState = QEDDogeChainStateCore<CACHE_SIZE, 4, 32>;
+
+    #[test]
+    fn test_state_diff_sync() -> anyhow::Result<()> {
+        let headers = load_mainnet_headers()?;
+        let base: [QDogeBlockHeader; CACHE_SIZE] = core::array::from_fn(|i| headers[i].block_header.clone());
+        let mut state = TestChainState::from_init_data(&InitBlockDataIBC::<CACHE_SIZE, 32>::new_from_block_headers_empty_tree(
+            &base,
+            headers[CACHE_SIZE - 1].height,
+        )).unwrap();
+
+        let mut mirror = state.as_bytes().to_vec();
+        for x in &headers[CACHE_SIZE..CACHE_SIZE + 20] {
+            let old_state = state;
+            state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+            let diff = state.get_state_diff(&old_state)?;
+            assert!(diff.get_data_len() < state.as_bytes().len() / 4);
+
+            // replaying the same diff fails the base hash check
+            diff.apply(&mut mirror)?;
+            assert_eq!(mirror, state.as_bytes());
+            assert_eq!(diff.apply(&mut mirror), Err(DogeBridgeError::StateDiffBaseHashMismatch));
+        }
+
+        let old_state = state;
+        state.append_block::<DogeMainNetConfig>(headers[CACHE_SIZE + 20].height, &headers[CACHE_SIZE + 20].block_header, None)?;
+        let mut diff = QEDDogeStateDiff::new(old_state.as_bytes(), state.as_bytes())?;
+        diff.chunks[0].data[0] ^= 1;
+        assert_eq!(diff.apply(&mut mirror), Err(DogeBridgeError::StateDiffResultHashMismatch));
+        assert_eq!(mirror, old_state.as_bytes());
+
+        diff.chunks[0].offset = diff.state_size;
+        assert_eq!(diff.apply(&mut mirror), Err(DogeBridgeError::StateDiffChunkOutOfBounds));
+        assert!(QEDDogeStateDiff::new(&mirror, &mirror)?.is_empty());
+        Ok(())
+    }
+}
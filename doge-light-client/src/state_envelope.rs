@@ -0,0 +1,309 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Versioned envelope for chain state account data.
+//!
+//! Every account starts with a [`QEDDogeStateEnvelopeHeader`] (magic, version and the block tree tracker and hasher
+//! the state was written with). Loading only accepts [`QDOGE_STATE_CURRENT_VERSION`] with the block tree hasher it is
+//! opened with, older data has to go through [`upgrade_chain_state_envelope`] first and unknown versions are rejected.
+//!
+//! Versions:
+//! - legacy (no envelope): the raw bytes of `QEDDogeChainStateCore`, see [`migrate_legacy_chain_state_data`]
+//! - 1: envelope, [`QEDDogeChainStateLayout`], chain state bytes (the data of a [`QEDDogeChainStateView`])
+
+use alloc::vec::Vec;
+use zerocopy::{little_endian::U32, FromBytes, IntoBytes};
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{
+    chain_state::QEDDogeChainStateCore,
+    chain_state_view::{QEDDogeChainStateLayout, QEDDogeChainStateView},
+    core_data::QHash256,
+    error::{DogeBridgeError, QDogeResult},
+    hash::{merkle::fixed_append_tree::FixedMerkleAppendTree, traits::MerkleZeroHasher},
+};
+
+pub const QDOGE_STATE_ENVELOPE_MAGIC: [u8; 8] = *b"QEDDOGES";
+pub const QDOGE_STATE_VERSION_V1: u32 = 1;
+pub const QDOGE_STATE_CURRENT_VERSION: u32 = QDOGE_STATE_VERSION_V1;
+
+/// Block tree tracker id of a [`FixedMerkleAppendTree`], the only tracker a [`QEDDogeChainStateView`] supports.
+pub const QDOGE_BLOCK_TREE_TRACKER_FIXED_APPEND_TREE: u32 = 1;
+
+/// Identifies a block tree hasher by the hash of two zero leaves.
+pub fn get_block_tree_hasher_id<BlockTreeHasher: MerkleZeroHasher<QHash256>>() -> QHash256 {
+    BlockTreeHasher::get_zero_hash(1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct QEDDogeStateEnvelopeHeader {
+    pub magic: [u8; 8],
+    pub version: U32,
+    pub block_tree_tracker: U32,
+    pub block_tree_hasher_id: QHash256,
+}
+
+pub const QDOGE_STATE_ENVELOPE_HEADER_SIZE: usize = core::mem::size_of::<QEDDogeStateEnvelopeHeader>();
+
+impl QEDDogeStateEnvelopeHeader {
+    pub fn new_current<BlockTreeHasher: MerkleZeroHasher<QHash256>>() -> Self {
+        Self {
+            magic: QDOGE_STATE_ENVELOPE_MAGIC,
+            version: QDOGE_STATE_CURRENT_VERSION.into(),
+            block_tree_tracker: QDOGE_BLOCK_TREE_TRACKER_FIXED_APPEND_TREE.into(),
+            block_tree_hasher_id: get_block_tree_hasher_id::<BlockTreeHasher>(),
+        }
+    }
+    pub fn get_version(&self) -> u32 {
+        self.version.get()
+    }
+    /// Reads the envelope header of `data`, failing if the magic is missing.
+    pub fn read_from_data(data: &[u8]) -> QDogeResult<Self> {
+        let (header, _) =
+            Self::read_from_prefix(data).map_err(|_| DogeBridgeError::ChainStateDataTooShort)?;
+        if header.magic != QDOGE_STATE_ENVELOPE_MAGIC {
+            return Err(DogeBridgeError::StateEnvelopeMagicMismatch);
+        }
+        Ok(header)
+    }
+}
+
+fn ensure_current_version<BlockTreeHasher: MerkleZeroHasher<QHash256>>(data: &[u8]) -> QDogeResult<()> {
+    let header = QEDDogeStateEnvelopeHeader::read_from_data(data)?;
+    if header.get_version() != QDOGE_STATE_CURRENT_VERSION {
+        return Err(DogeBridgeError::StateEnvelopeUnknownVersion);
+    }
+    if header.block_tree_tracker.get() != QDOGE_BLOCK_TREE_TRACKER_FIXED_APPEND_TREE {
+        Err(DogeBridgeError::StateEnvelopeBlockTreeTrackerMismatch)
+    } else if header.block_tree_hasher_id != get_block_tree_hasher_id::<BlockTreeHasher>() {
+        Err(DogeBridgeError::StateEnvelopeBlockTreeHasherMismatch)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn get_chain_state_envelope_data_size(layout: &QEDDogeChainStateLayout) -> usize {
+    QDOGE_STATE_ENVELOPE_HEADER_SIZE + layout.get_data_size()
+}
+
+/// Opens the chain state stored in a current version envelope.
+pub fn open_chain_state_envelope_mut<BlockTreeHasher: MerkleZeroHasher<QHash256>>(
+    data: &mut [u8],
+) -> QDogeResult<QEDDogeChainStateView<'_, BlockTreeHasher>> {
+    ensure_current_version::<BlockTreeHasher>(data)?;
+    QEDDogeChainStateView::from_data_mut(&mut data[QDOGE_STATE_ENVELOPE_HEADER_SIZE..])
+}
+
+/// Serializes a const generic chain state into a current version envelope.
+pub fn chain_state_to_envelope_data<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeHasher: MerkleZeroHasher<QHash256>,
+>(
+    state: &QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        BlockTreeHasher,
+    >,
+) -> QDogeResult<Vec<u8>> {
+    let layout = QEDDogeChainStateLayout::new_for::<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+    >()?;
+    let mut data = alloc::vec![0u8; get_chain_state_envelope_data_size(&layout)];
+    data[..QDOGE_STATE_ENVELOPE_HEADER_SIZE].copy_from_slice(QEDDogeStateEnvelopeHeader::new_current::<BlockTreeHasher>().as_bytes());
+    QEDDogeChainStateView::<BlockTreeHasher>::init_from_chain_state(&mut data[QDOGE_STATE_ENVELOPE_HEADER_SIZE..], state)?;
+    Ok(data)
+}
+
+/// Deserializes a const generic chain state from a current version envelope, failing if the sizes differ.
+pub fn chain_state_from_envelope_data<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    BlockTreeHasher: MerkleZeroHasher<QHash256>,
+>(
+    data: &[u8],
+) -> QDogeResult<
+    QEDDogeChainStateCore<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+        FixedMerkleAppendTree<QHash256, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        BlockTreeHasher,
+    >,
+> {
+    let mut data = data.to_vec();
+    open_chain_state_envelope_mut::<BlockTreeHasher>(&mut data)?.to_chain_state::<
+        QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
+        QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
+        QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
+    >()
+}
+
+/// Wraps the unversioned bytes of a `QEDDogeChainStateCore` with the sizes in `layout` into a current version envelope.
+///
+/// Legacy data does not record its block tree hasher, so the zero hashes of the block tree levels and the block tree
+/// root recorded for the tip are checked against `BlockTreeHasher` before the envelope is written.
+pub fn migrate_legacy_chain_state_data<BlockTreeHasher: MerkleZeroHasher<QHash256>>(
+    legacy_data: &[u8],
+    layout: QEDDogeChainStateLayout,
+) -> QDogeResult<Vec<u8>> {
+    layout.validate()?;
+    if legacy_data.len() != layout.get_state_size() {
+        return Err(DogeBridgeError::ChainStateLayoutMismatch);
+    }
+    let mut data = Vec::with_capacity(get_chain_state_envelope_data_size(&layout));
+    data.extend_from_slice(&[0u8; QDOGE_STATE_ENVELOPE_HEADER_SIZE]);
+    data.extend_from_slice(layout.as_bytes());
+    data.extend_from_slice(legacy_data);
+    QEDDogeChainStateView::<BlockTreeHasher>::from_data_mut(&mut data[QDOGE_STATE_ENVELOPE_HEADER_SIZE..])?
+        .ensure_block_tree_hasher()?;
+    data[..QDOGE_STATE_ENVELOPE_HEADER_SIZE].copy_from_slice(QEDDogeStateEnvelopeHeader::new_current::<BlockTreeHasher>().as_bytes());
+    Ok(data)
+}
+
+/// Upgrades envelope data of any known version to [`QDOGE_STATE_CURRENT_VERSION`], one version at a time.
+pub fn upgrade_chain_state_envelope(data: &[u8]) -> QDogeResult<Vec<u8>> {
+    let version = QEDDogeStateEnvelopeHeader::read_from_data(data)?.get_version();
+    match version {
+        QDOGE_STATE_VERSION_V1 => {
+            let layout = QEDDogeChainStateLayout::read_from_data(&data[QDOGE_STATE_ENVELOPE_HEADER_SIZE..])?;
+            let size = get_chain_state_envelope_data_size(&layout);
+            if data.len() < size {
+                return Err(DogeBridgeError::ChainStateDataTooShort);
+            }
+            Ok(data[..size].to_vec())
+        }
+        _ => Err(DogeBridgeError::StateEnvelopeUnknownVersion),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use crate::{
+        chain_state::{QEDDogeChainStateCore, QEDDogeChainStateStorage}, chain_state_view::QEDDogeChainStateLayout, core_data::QDogeBlockHeader, error::DogeBridgeError, hash::{poseidon_bn254::QPoseidonBN254Hasher, sha256::QSha256Hasher}, init_params::InitBlockDataIBC
+    };
+
+    use super::{
+        chain_state_from_envelope_data, chain_state_to_envelope_data, migrate_legacy_chain_state_data, open_chain_state_envelope_mut, upgrade_chain_state_envelope, QEDDogeStateEnvelopeHeader, QDOGE_STATE_ENVELOPE_HEADER_SIZE
+    };
+
+    type TestChainState = QEDDogeChainStateCore<8, 2, 16>;
+
+    fn test_state() -> TestChainState {
+        let headers: [QDogeBlockHeader; 8] = core::array::from_fn(|i| {
+            let mut header = QDogeBlockHeader::default();
+            header.header.timestamp = 1000 + i as u32;
+            header
+        });
+        TestChainState::from_init_data(&InitBlockDataIBC::<8, 16>::new_from_block_headers_empty_tree(&headers, 100)).unwrap()
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() -> anyhow::Result<()> {
+        let state = test_state();
+        let mut data = chain_state_to_envelope_data(&state)?;
+        assert_eq!(chain_state_from_envelope_data::<8, 2, 16, QSha256Hasher>(&data)?, state);
+        assert_eq!(open_chain_state_envelope_mut::<QSha256Hasher>(&mut data)?.get_tip_block_number(), 100);
+        assert_eq!(upgrade_chain_state_envelope(&data)?, data);
+        assert_eq!(
+            chain_state_from_envelope_data::<8, 2, 32, QSha256Hasher>(&data),
+            Err(DogeBridgeError::ChainStateLayoutMismatch)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_rejects_unknown_data() -> anyhow::Result<()> {
+        let state = test_state();
+        let data = chain_state_to_envelope_data(&state)?;
+
+        let mut future_data = data.clone();
+        future_data[8] = 2;
+        assert_eq!(
+            open_chain_state_envelope_mut::<QSha256Hasher>(&mut future_data).err(),
+            Some(DogeBridgeError::StateEnvelopeUnknownVersion)
+        );
+        assert_eq!(upgrade_chain_state_envelope(&future_data), Err(DogeBridgeError::StateEnvelopeUnknownVersion));
+
+        // legacy data has no envelope
+        let mut legacy_data = state.as_bytes().to_vec();
+        assert_eq!(
+            open_chain_state_envelope_mut::<QSha256Hasher>(&mut legacy_data).err(),
+            Some(DogeBridgeError::StateEnvelopeMagicMismatch)
+        );
+        let migrated = migrate_legacy_chain_state_data::<QSha256Hasher>(&legacy_data, QEDDogeChainStateLayout::new_for::<8, 2, 16>()?)?;
+        assert_eq!(migrated, data);
+        assert_eq!(
+            migrate_legacy_chain_state_data::<QSha256Hasher>(&legacy_data, QEDDogeChainStateLayout::new_for::<8, 2, 32>()?),
+            Err(DogeBridgeError::ChainStateLayoutMismatch)
+        );
+        assert_eq!(
+            open_chain_state_envelope_mut::<QSha256Hasher>(&mut future_data[..QDOGE_STATE_ENVELOPE_HEADER_SIZE - 1]).err(),
+            Some(DogeBridgeError::ChainStateDataTooShort)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_rejects_other_block_tree() -> anyhow::Result<()> {
+        let state = test_state();
+        let mut data = chain_state_to_envelope_data(&state)?;
+        assert_eq!(
+            open_chain_state_envelope_mut::<QPoseidonBN254Hasher>(&mut data).err(),
+            Some(DogeBridgeError::StateEnvelopeBlockTreeHasherMismatch)
+        );
+        assert_eq!(
+            chain_state_from_envelope_data::<8, 2, 16, QPoseidonBN254Hasher>(&data),
+            Err(DogeBridgeError::StateEnvelopeBlockTreeHasherMismatch)
+        );
+        // legacy data written with another hasher is not wrapped
+        assert_eq!(
+            migrate_legacy_chain_state_data::<QPoseidonBN254Hasher>(
+                state.as_bytes(),
+                QEDDogeChainStateLayout::new_for::<8, 2, 16>()?,
+            ),
+            Err(DogeBridgeError::StateEnvelopeBlockTreeHasherMismatch)
+        );
+
+        let mut header = QEDDogeStateEnvelopeHeader::read_from_data(&data)?;
+        header.block_tree_tracker = 2.into();
+        data[..QDOGE_STATE_ENVELOPE_HEADER_SIZE].copy_from_slice(header.as_bytes());
+        assert_eq!(
+            open_chain_state_envelope_mut::<QSha256Hasher>(&mut data).err(),
+            Some(DogeBridgeError::StateEnvelopeBlockTreeTrackerMismatch)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Shared test fixtures.
+//!
+//! The mainnet header dump is written by qed-doge-data-link with bincode's default options, so it is
+//! decoded by hand here to keep the consensus tests independent of the `serde` feature.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "script-verify")]
+use bitcoin::secp256k1::Message;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::{
+    core_data::{QAuxPow, QDogeBlockHeader, QHash256, QMerkleBranch, QStandardBlockHeader},
+    doge::{
+        script::{builder::ScriptBuilder, opcodes::Opcode},
+        transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput},
+    },
+};
+
+/// Same layout as qed-doge-data-link's BlockHeaderWithIndex.
+#[derive(Clone, Debug)]
+pub struct BlockHeaderWithIndex {
+    pub height: u32,
+    pub block_header: QDogeBlockHeader,
+}
+
+struct BincodeReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BincodeReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of header data at offset {}", self.offset))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+    fn read_u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into()?))
+    }
+    fn read_u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into()?))
+    }
+    fn read_len(&mut self) -> anyhow::Result<usize> {
+        Ok(usize::try_from(self.read_u64()?)?)
+    }
+    fn read_hash(&mut self) -> anyhow::Result<QHash256> {
+        Ok(self.read_bytes(32)?.try_into()?)
+    }
+    fn read_byte_vec(&mut self) -> anyhow::Result<Vec<u8>> {
+        let len = self.read_len()?;
+        Ok(self.read_bytes(len)?.to_vec())
+    }
+    fn read_vec<T>(&mut self, read_item: fn(&mut Self) -> anyhow::Result<T>) -> anyhow::Result<Vec<T>> {
+        let len = self.read_len()?;
+        (0..len).map(|_| read_item(self)).collect()
+    }
+    fn read_standard_header(&mut self) -> anyhow::Result<QStandardBlockHeader> {
+        Ok(QStandardBlockHeader {
+            version: self.read_u32()?,
+            previous_block_hash: self.read_hash()?,
+            merkle_root: self.read_hash()?,
+            timestamp: self.read_u32()?,
+            bits: self.read_u32()?,
+            nonce: self.read_u32()?,
+        })
+    }
+    fn read_input(&mut self) -> anyhow::Result<BTCTransactionInput> {
+        Ok(BTCTransactionInput {
+            hash: self.read_hash()?,
+            index: self.read_u32()?,
+            script: self.read_byte_vec()?,
+            sequence: self.read_u32()?,
+        })
+    }
+    fn read_output(&mut self) -> anyhow::Result<BTCTransactionOutput> {
+        Ok(BTCTransactionOutput {
+            value: self.read_u64()?,
+            script: self.read_byte_vec()?,
+        })
+    }
+    fn read_transaction(&mut self) -> anyhow::Result<BTCTransaction> {
+        Ok(BTCTransaction {
+            version: self.read_u32()?,
+            inputs: self.read_vec(Self::read_input)?,
+            outputs: self.read_vec(Self::read_output)?,
+            locktime: self.read_u32()?,
+        })
+    }
+    fn read_merkle_branch(&mut self) -> anyhow::Result<QMerkleBranch> {
+        Ok(QMerkleBranch {
+            hashes: self.read_vec(Self::read_hash)?,
+            side_mask: self.read_u32()?,
+        })
+    }
+    fn read_block_header(&mut self) -> anyhow::Result<QDogeBlockHeader> {
+        let header = self.read_standard_header()?;
+        let aux_pow = match self.read_u8()? {
+            0 => None,
+            1 => Some(QAuxPow {
+                coinbase_transaction: self.read_transaction()?,
+                block_hash: self.read_hash()?,
+                coinbase_branch: self.read_merkle_branch()?,
+                blockchain_branch: self.read_merkle_branch()?,
+                parent_block: self.read_standard_header()?,
+            }),
+            tag => anyhow::bail!("invalid option tag {}", tag),
+        };
+        Ok(QDogeBlockHeader { header, aux_pow })
+    }
+    fn read_header_with_index(&mut self) -> anyhow::Result<BlockHeaderWithIndex> {
+        Ok(BlockHeaderWithIndex {
+            height: self.read_u32()?,
+            block_header: self.read_block_header()?,
+        })
+    }
+}
+
+/// Loads the mainnet headers 5610330..=5611352 sorted by height.
+pub fn load_mainnet_headers() -> anyhow::Result<Vec<BlockHeaderWithIndex>> {
+    let data = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_data/mainnet_headers_5610330-5611352.bin"
+    ))?;
+    let mut reader = BincodeReader { data: &data, offset: 0 };
+    let mut headers = reader.read_vec(BincodeReader::read_header_with_index)?;
+    anyhow::ensure!(reader.offset == data.len(), "trailing bytes in header data");
+    headers.sort_by_key(|x| x.height);
+    Ok(headers)
+}
+
+/// Compressed public key of `secret`.
+pub fn get_pubkey(secret: &SecretKey) -> Vec<u8> {
+    PublicKey::from_secret_key(&Secp256k1::signing_only(), secret).serialize().to_vec()
+}
+
+/// `OP_m <pubkey_1> ... <pubkey_n> OP_n OP_CHECKMULTISIG`
+pub fn multisig_script(required: i64, pubkeys: &[Vec<u8>]) -> Vec<u8> {
+    let mut builder = ScriptBuilder::new().push_int(required);
+    for pubkey in pubkeys {
+        builder = builder.push_slice(pubkey);
+    }
+    builder
+        .push_int(pubkeys.len() as i64)
+        .push_opcode(Opcode::OP_CHECKMULTISIG)
+        .into_bytes()
+}
+
+/// Signs a signature hash with `secret`, in the form taken by `PartiallySignedBTCTransaction::sign_input`.
+#[cfg(feature = "script-verify")]
+pub fn signer(secret: SecretKey) -> impl FnOnce(&QHash256) -> crate::error::QDogeResult<Vec<u8>> {
+    move |sighash| {
+        let signature = Secp256k1::signing_only().sign_ecdsa(&Message::from_digest(*sighash), &secret);
+        Ok(signature.serialize_der().to_vec())
+    }
+}
@@ -0,0 +1,305 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Sweep transactions which consolidate v1 bridge deposit outputs into the bridge custody address.
+
+use doge_light_client::{
+    core_data::QHash256,
+    doge::{
+        address::{AddressToBTCScript, BTCAddress160},
+        script::builder::ScriptBuilder,
+        transaction::{BTCTransaction, BTCTransactionInput, SIGHASH_ALL},
+        tx_builder::{estimate_signed_size, BTCUtxo, DogeFeePolicy, MAX_SIGNATURE_PUSH_SIZE},
+    },
+};
+#[cfg(feature = "script-verify")]
+use doge_light_client::doge::{psbt::PartiallySignedBTCTransaction, script::interpreter::DOGE_STANDARD_SCRIPT_VERIFY_FLAGS};
+
+use crate::{
+    error::{ClaimDogeBridgeHelperError, QClaimDogeResult},
+    tx_template::get_transfer_with_message_redeem_script,
+};
+
+// dogecoin core does not relay transactions larger than this
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+// `<sig> <compressed bridge public key> <59 byte redeem script>`
+pub const DEPOSIT_V1_MAX_SCRIPT_SIG_SIZE: usize = MAX_SIGNATURE_PUSH_SIZE + 34 + 60;
+
+// version, locktime, 3 byte input count and one P2PKH output
+pub const MAX_SWEEP_INPUTS_V1: usize = (MAX_STANDARD_TX_SIZE - 8 - 3 - 1 - 34) / (40 + 1 + DEPOSIT_V1_MAX_SCRIPT_SIG_SIZE);
+
+/// An unspent output paid to the v1 deposit address of a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeDepositUtxoV1 {
+    pub hash: QHash256,
+    pub index: u32,
+    pub value: u64,
+    /// The 32 byte message of the deposit redeem script.
+    pub user_public_key: [u8; 32],
+}
+
+impl BridgeDepositUtxoV1 {
+    pub fn get_redeem_script(&self, bridge_public_key_hash: &[u8]) -> [u8; 59] {
+        get_transfer_with_message_redeem_script(&self.user_public_key, bridge_public_key_hash)
+    }
+    pub fn to_utxo(&self, bridge_public_key_hash: &[u8]) -> BTCUtxo {
+        let redeem_script = self.get_redeem_script(bridge_public_key_hash);
+        BTCUtxo::new_p2sh(self.hash, self.index, self.value, &redeem_script, MAX_SIGNATURE_PUSH_SIZE + 34)
+    }
+}
+
+/// The script sig which spends a v1 deposit output: `<signature> <bridge public key> <redeem script>`.
+pub fn get_deposit_script_sig_v1(signature: &[u8], bridge_public_key: &[u8], redeem_script: &[u8]) -> Vec<u8> {
+    ScriptBuilder::new()
+        .push_slice(signature)
+        .push_slice(bridge_public_key)
+        .push_slice(redeem_script)
+        .into_bytes()
+}
+
+/// An unsigned sweep of deposit outputs into a single custody output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeSweepTransactionV1 {
+    pub transaction: BTCTransaction,
+    pub deposits: Vec<BridgeDepositUtxoV1>,
+    pub bridge_public_key: [u8; 33],
+    pub fee: u64,
+    /// Size of the signed transaction used for the fee, signatures can make the signed transaction a few bytes smaller.
+    pub estimated_size: usize,
+}
+
+impl BridgeSweepTransactionV1 {
+    /// Spends every deposit into `custody_address`, paying the fee of `fee_policy` out of the swept amount.
+    pub fn new(
+        deposits: &[BridgeDepositUtxoV1],
+        bridge_public_key: &[u8; 33],
+        custody_address: &BTCAddress160,
+        fee_policy: &DogeFeePolicy,
+    ) -> QClaimDogeResult<Self> {
+        if deposits.is_empty() {
+            return Err(ClaimDogeBridgeHelperError::SweepNoDeposits);
+        }
+        if deposits.len() > MAX_SWEEP_INPUTS_V1 {
+            return Err(ClaimDogeBridgeHelperError::SweepTooManyInputs);
+        }
+        let total = deposits
+            .iter()
+            .try_fold(0u64, |total, x| total.checked_add(x.value))
+            .ok_or(ClaimDogeBridgeHelperError::NumericalOverflow)?;
+
+        let bridge_public_key_hash = BTCAddress160::from_p2pkh_key(bridge_public_key).address;
+        let utxos: Vec<BTCUtxo> = deposits.iter().map(|x| x.to_utxo(&bridge_public_key_hash)).collect();
+        // the custody output never pays the dust fee, the output is rejected below the soft dust limit
+        let mut output = custody_address.to_btc_output(0);
+        let estimated_size = estimate_signed_size(&utxos, core::slice::from_ref(&output));
+        let fee = fee_policy.get_fee(estimated_size, &[]);
+        output.value = total.saturating_sub(fee);
+        if fee_policy.is_dust(output.value) {
+            return Err(ClaimDogeBridgeHelperError::SweepOutputBelowDustLimit);
+        }
+
+        let inputs = deposits
+            .iter()
+            .map(|x| BTCTransactionInput {
+                hash: x.hash,
+                index: x.index,
+                script: Vec::new(),
+                sequence: 0xffffffff,
+            })
+            .collect();
+        Ok(Self {
+            transaction: BTCTransaction::from_io(inputs, vec![output]),
+            deposits: deposits.to_vec(),
+            bridge_public_key: *bridge_public_key,
+            fee,
+            estimated_size,
+        })
+    }
+
+    pub fn get_bridge_public_key_hash(&self) -> [u8; 20] {
+        BTCAddress160::from_p2pkh_key(&self.bridge_public_key).address
+    }
+
+    /// The SIGHASH_ALL message signed by the bridge key for an input.
+    pub fn get_signature_hash(&self, input_index: usize) -> QHash256 {
+        let redeem_script = self.deposits[input_index].get_redeem_script(&self.get_bridge_public_key_hash());
+        self.transaction.signature_hash(input_index, &redeem_script, SIGHASH_ALL as u32)
+    }
+
+    /// Signs every input with `sign`, which returns the DER signature of the bridge key over a signature hash.
+    ///
+    /// With the `script-verify` feature, each completed input is verified against the deposit output it spends.
+    pub fn sign<F: FnMut(&QHash256) -> QClaimDogeResult<Vec<u8>>>(&self, mut sign: F) -> QClaimDogeResult<BTCTransaction> {
+        let bridge_public_key_hash = self.get_bridge_public_key_hash();
+        let mut signed = self.transaction.clone();
+        for (input_index, deposit) in self.deposits.iter().enumerate() {
+            let mut signature = sign(&self.get_signature_hash(input_index))?;
+            signature.push(SIGHASH_ALL);
+            let redeem_script = deposit.get_redeem_script(&bridge_public_key_hash);
+            signed.inputs[input_index].script = get_deposit_script_sig_v1(&signature, &self.bridge_public_key, &redeem_script);
+        }
+        #[cfg(feature = "script-verify")]
+        for (input_index, deposit) in self.deposits.iter().enumerate() {
+            let prevout_script = deposit.to_utxo(&bridge_public_key_hash).script;
+            signed
+                .verify_input_script(input_index, &prevout_script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS)
+                .map_err(|_| ClaimDogeBridgeHelperError::SweepInvalidSignature)?;
+        }
+        Ok(signed)
+    }
+
+    /// A partially signed copy of the sweep, for bridge keys held on a separate signing machine.
+    #[cfg(feature = "script-verify")]
+    pub fn to_psbt(&self) -> PartiallySignedBTCTransaction {
+        let bridge_public_key_hash = self.get_bridge_public_key_hash();
+        let mut psbt = PartiallySignedBTCTransaction::new(
+            self.transaction.clone(),
+            self.deposits.iter().map(|x| x.to_utxo(&bridge_public_key_hash).to_output()).collect(),
+        )
+        .expect("sweep transactions have one unsigned input per deposit");
+        for (input_index, deposit) in self.deposits.iter().enumerate() {
+            psbt.set_redeem_script(input_index, deposit.get_redeem_script(&bridge_public_key_hash).to_vec())
+                .expect("redeem script matches the deposit output");
+        }
+        psbt
+    }
+}
+
+/// Splits the deposits into sweeps of at most `MAX_SWEEP_INPUTS_V1` inputs, so that every sweep is a standard transaction.
+pub fn build_sweep_transactions_v1(
+    deposits: &[BridgeDepositUtxoV1],
+    bridge_public_key: &[u8; 33],
+    custody_address: &BTCAddress160,
+    fee_policy: &DogeFeePolicy,
+) -> QClaimDogeResult<Vec<BridgeSweepTransactionV1>> {
+    deposits
+        .chunks(MAX_SWEEP_INPUTS_V1)
+        .map(|x| BridgeSweepTransactionV1::new(x, bridge_public_key, custody_address, fee_policy))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use doge_light_client::{
+        constants::DOGE_COIN,
+        core_data::QHash256,
+        doge::{address::{AddressToBTCScript, BTCAddress160}, script::instructions, tx_builder::DogeFeePolicy},
+    };
+    use libsecp256k1::{Message, PublicKey, SecretKey};
+
+    use crate::{error::ClaimDogeBridgeHelperError, tx_template::get_bridge_deposit_address_v1};
+
+    use super::{build_sweep_transactions_v1, BridgeDepositUtxoV1, BridgeSweepTransactionV1, MAX_STANDARD_TX_SIZE, MAX_SWEEP_INPUTS_V1};
+
+    fn bridge_key() -> (SecretKey, [u8; 33]) {
+        let secret = SecretKey::parse(&[7; 32]).unwrap();
+        (secret, PublicKey::from_secret_key(&secret).serialize_compressed())
+    }
+
+    fn signer(secret: SecretKey) -> impl FnMut(&QHash256) -> Result<Vec<u8>, ClaimDogeBridgeHelperError> {
+        move |sighash| Ok(libsecp256k1::sign(&Message::parse(sighash), &secret).0.serialize_der().as_ref().to_vec())
+    }
+
+    fn deposits(count: usize, value: u64) -> Vec<BridgeDepositUtxoV1> {
+        (0..count)
+            .map(|i| BridgeDepositUtxoV1 {
+                hash: [(i % 251) as u8; 32],
+                index: i as u32,
+                value,
+                user_public_key: [(i % 7) as u8 + 1; 32],
+            })
+            .collect()
+    }
+
+    fn custody_address() -> BTCAddress160 {
+        BTCAddress160::new_p2pkh([0x55; 20])
+    }
+
+    #[test]
+    fn test_sign_sweep() {
+        let (secret, bridge_public_key) = bridge_key();
+        let bridge_public_key_hash = BTCAddress160::from_p2pkh_key(&bridge_public_key).address;
+        let deposits = deposits(3, 5 * DOGE_COIN);
+        let sweep = BridgeSweepTransactionV1::new(&deposits, &bridge_public_key, &custody_address(), &DogeFeePolicy::recommended()).unwrap();
+
+        // 3 inputs of 208 bytes and one P2PKH output
+        assert_eq!(sweep.estimated_size, 8 + 1 + 3 * 208 + 1 + 34);
+        assert_eq!(sweep.fee, DOGE_COIN / 100);
+        assert_eq!(sweep.transaction.outputs, vec![custody_address().to_btc_output(15 * DOGE_COIN - sweep.fee)]);
+        for deposit in deposits.iter() {
+            let deposit_address = get_bridge_deposit_address_v1(&deposit.user_public_key, &bridge_public_key_hash);
+            assert_eq!(deposit.to_utxo(&bridge_public_key_hash).script, deposit_address.to_btc_script());
+        }
+
+        let signed = sweep.sign(signer(secret)).unwrap();
+        let size = signed.to_bytes().len();
+        assert!(size <= sweep.estimated_size && size + 3 >= sweep.estimated_size);
+        let script_sig: Vec<_> = instructions(&signed.inputs[1].script).map(Result::unwrap).collect();
+        assert_eq!(script_sig.len(), 3);
+        assert_eq!(script_sig[1].get_push_data(), Some(bridge_public_key.as_slice()));
+        assert_eq!(script_sig[2].get_push_data(), Some(deposits[1].get_redeem_script(&bridge_public_key_hash).as_slice()));
+
+        // the same signatures through a partially signed transaction
+        #[cfg(feature = "script-verify")]
+        {
+            let mut psbt = sweep.to_psbt();
+            for input_index in 0..deposits.len() {
+                psbt.sign_input(input_index, &bridge_public_key, |x| Ok(signer(secret)(x).unwrap())).unwrap();
+            }
+            psbt.finalize().unwrap();
+            assert_eq!(psbt.extract().unwrap(), signed);
+
+            let wrong_key = SecretKey::parse(&[8; 32]).unwrap();
+            assert_eq!(sweep.sign(signer(wrong_key)), Err(ClaimDogeBridgeHelperError::SweepInvalidSignature));
+        }
+    }
+
+    #[test]
+    fn test_sweep_limits() {
+        let (_, bridge_public_key) = bridge_key();
+        let policy = DogeFeePolicy::recommended();
+        assert_eq!(
+            BridgeSweepTransactionV1::new(&[], &bridge_public_key, &custody_address(), &policy),
+            Err(ClaimDogeBridgeHelperError::SweepNoDeposits)
+        );
+        assert_eq!(
+            BridgeSweepTransactionV1::new(&deposits(3, DOGE_COIN / 200), &bridge_public_key, &custody_address(), &policy),
+            Err(ClaimDogeBridgeHelperError::SweepOutputBelowDustLimit)
+        );
+
+        let many = deposits(MAX_SWEEP_INPUTS_V1 + 1, DOGE_COIN);
+        assert_eq!(
+            BridgeSweepTransactionV1::new(&many, &bridge_public_key, &custody_address(), &policy),
+            Err(ClaimDogeBridgeHelperError::SweepTooManyInputs)
+        );
+        let sweeps = build_sweep_transactions_v1(&many, &bridge_public_key, &custody_address(), &policy).unwrap();
+        assert_eq!(sweeps.len(), 2);
+        assert_eq!(sweeps[1].deposits, many[MAX_SWEEP_INPUTS_V1..]);
+        assert!(sweeps[0].estimated_size <= MAX_STANDARD_TX_SIZE);
+        assert_eq!(sweeps[0].fee, 100 * DOGE_COIN / 100);
+    }
+}
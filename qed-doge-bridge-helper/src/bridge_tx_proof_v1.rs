@@ -1,29 +1,44 @@
 use doge_light_client::{
-    chain_state::QEDDogeChainStateCore, core_data::QHash256, doge::transaction::BTCTransaction, hash::sha256_impl::hash_impl_sha256_bytes
+    block_data_tracker::BlockDataStorage, chain_state::QEDDogeChainStateCore, core_data::QHash256, doge::transaction::{decode_bip34_height, BTCTransaction}, hash::{merkle::utils::get_btc_tx_tree_depth, sha256_impl::hash_impl_sha256_bytes}
 };
 
 use crate::{
+    deposit_destination::DepositDestinationV1,
     error::{ClaimDogeBridgeHelperError, QClaimDogeResult},
-    tx_template::is_bridge_desposit_output_v1_for_user,
+    tx_template::{
+        is_bridge_desposit_output_v1_for_user, is_bridge_desposit_output_v2_for_user, BridgeDepositConfigV2, BridgeDepositTemplateV2,
+        DepositRefundV2,
+    },
 };
 
 //fn verify_merkle_proof_in_mem
 const MIN_POSSIBLE_TX_SIZE: usize = 60;
 const MAX_REASONABLE_TX_SIZE: usize = 1024 * 1024 * 10;
+// the size of an inner node of the tx merkle tree
+const TX_MERKLE_NODE_SIZE: usize = 64;
 
+// duplicated nodes are only allowed where the last node of an odd level is duplicated, so a transaction can not
+// be proven again at the phantom index of a duplicated leaf (CVE-2012-2459)
 fn compute_merkle_in_mem_btc_has256(
     value: [u8; 32],
     siblings: &[u8],
     index: u32,
     siblings_count: usize,
-) -> [u8; 32] {
+    tx_count: u32,
+) -> QClaimDogeResult<[u8; 32]> {
     assert!(siblings.len() <= 32 * siblings_count);
 
     let mut current = value;
     let mut index = index;
+    let mut level_size = tx_count;
     let mut buf = [0u8; 64];
     for i in 0..siblings_count {
         let sibling = &siblings[i * 32..(i + 1) * 32];
+        let is_duplicate = sibling == current;
+        if is_duplicate != (index & 1 == 0 && index + 1 == level_size) {
+            return Err(ClaimDogeBridgeHelperError::MutatedTxMerkleProof);
+        }
+        level_size = level_size.div_ceil(2);
         if index & 1 == 0 {
             buf[0..32].copy_from_slice(&current);
             buf[32..64].copy_from_slice(&sibling);
@@ -35,7 +50,7 @@ fn compute_merkle_in_mem_btc_has256(
         index >>= 1;
     }
     assert!(index == 0);
-    current
+    Ok(current)
 }
 
 fn compute_merkle_in_mem_sha256(
@@ -148,7 +163,25 @@ impl UserClaimStateProofV1 {
         }, offset))
     }
 
-    pub fn verify_tx_out_in_block_is_deposit_v1_with_ibc<'a, 
+    pub(crate) fn get_finalized_block_tx_merkle_root<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,>(
+        block_number: u32,
+        ibc: &QEDDogeChainStateCore<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+    ) -> QClaimDogeResult<QHash256> {
+        if ibc.block_data_tracker.get_finalized_block_number() < block_number {
+            return Err(ClaimDogeBridgeHelperError::BlockNotFinalized);
+        }
+
+        Ok(ibc.block_data_tracker.get_record(block_number).map_err(|_| {
+            ClaimDogeBridgeHelperError::BlockNotInCache
+        })?.tx_tree_merkle_root)
+    }
+
+    // <data> is prefixed with a CoinbaseInBlockProofV1 for the block, which authenticates the number of transactions
+    // in the block so the tx proof can not claim the phantom index of a duplicated leaf
+    pub fn verify_tx_out_in_block_is_deposit_v1_with_coinbase_proof_and_ibc<'a, 
     const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
     const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
     const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,>(
@@ -161,15 +194,9 @@ impl UserClaimStateProofV1 {
         known_user_claim_merkle_hash: &[u8; 32],
         data: &'a [u8],
     ) -> QClaimDogeResult<(QHash256, u64)> {
-        if ibc.block_data_tracker.get_finalized_block_number() < block_number {
-            return Err(ClaimDogeBridgeHelperError::BlockNotFinalized);
-        }
+        let known_block_tx_merkle_root = Self::get_finalized_block_tx_merkle_root(block_number, ibc)?;
 
-        let known_block_tx_merkle_root = ibc.block_data_tracker.get_record(block_number).map_err(|_| {
-            ClaimDogeBridgeHelperError::BlockNotInCache
-        })?.tx_tree_merkle_root;
-
-        let (new_user_claim_merkle_hash, amount) = Self::verify_tx_out_in_block_is_deposit_v1(
+        Self::verify_tx_out_in_block_is_deposit_v1_with_coinbase_proof(
             solana_public_key,
             bridge_public_key_hash,
             block_number,
@@ -178,12 +205,10 @@ impl UserClaimStateProofV1 {
             &known_block_tx_merkle_root,
             known_user_claim_merkle_hash,
             data,
-        )?;
-        Ok((new_user_claim_merkle_hash, amount))
-        
+        )
     }
 
-    pub fn verify_tx_out_in_block_is_deposit_v1<'a>(
+    pub fn verify_tx_out_in_block_is_deposit_v1_with_coinbase_proof<'a>(
         solana_public_key: &[u8; 32],
         bridge_public_key_hash: &[u8; 20],
         block_number: u32,
@@ -193,17 +218,122 @@ impl UserClaimStateProofV1 {
         known_user_claim_merkle_hash: &[u8; 32],
         data: &'a [u8],
     ) -> QClaimDogeResult<(QHash256, u64)> {
-        let (_, tx_bytes, read_length) =
-            TransactionInBlockProofV1::get_proof_tx_in_block(data, tx_index, known_block_tx_merkle_root)?;
-        let amount = TransactionInBlockProofV1::check_is_deposit_address_v1_mem(
-            tx_bytes,
-            output_index as usize,
-            solana_public_key,
-            bridge_public_key_hash,
+        let (tx_count, read_length) = CoinbaseInBlockProofV1::verify_coinbase_in_block_at_height(
+            data,
+            block_number,
+            known_block_tx_merkle_root,
+        )?;
+        Self::verify_tx_out_in_block_is_deposit_core(
+            |tx_bytes| TransactionInBlockProofV1::check_is_deposit_address_v1_mem(
+                tx_bytes,
+                output_index as usize,
+                solana_public_key,
+                bridge_public_key_hash,
+            ),
+            block_number,
+            tx_index,
+            output_index,
+            known_block_tx_merkle_root,
+            tx_count,
+            known_user_claim_merkle_hash,
+            &data[read_length..],
+        )
+    }
+
+    // v2 deposits are only claimable with a coinbase proof, which authenticates the number of transactions in the block.
+    // the bridge multisig comes from the trusted bridge state, the refund is chosen by the depositor and must not be due too soon.
+    // v2 deposits always commit to a DepositDestinationV1 message, raw solana public keys are only claimable from v1 deposits,
+    // so no output can be claimed both for a raw public key and for a destination
+    pub fn verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof_and_ibc<
+    const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+    const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+    const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,>(
+        destination: &DepositDestinationV1,
+        config: &BridgeDepositConfigV2,
+        refund: Option<&DepositRefundV2>,
+        block_number: u32,
+        tx_index: u32,
+        output_index: u32,
+        ibc: &QEDDogeChainStateCore<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        known_user_claim_merkle_hash: &[u8; 32],
+        data: &[u8],
+    ) -> QClaimDogeResult<(QHash256, u64)> {
+        let known_block_tx_merkle_root = Self::get_finalized_block_tx_merkle_root(block_number, ibc)?;
+
+        Self::verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+            destination,
+            config,
+            refund,
+            block_number,
+            ibc.block_data_tracker.get_finalized_block_number(),
+            tx_index,
+            output_index,
+            &known_block_tx_merkle_root,
+            known_user_claim_merkle_hash,
+            data,
+        )
+    }
+
+    pub fn verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+        destination: &DepositDestinationV1,
+        config: &BridgeDepositConfigV2,
+        refund: Option<&DepositRefundV2>,
+        block_number: u32,
+        current_block_number: u32,
+        tx_index: u32,
+        output_index: u32,
+        known_block_tx_merkle_root: &[u8; 32],
+        known_user_claim_merkle_hash: &[u8; 32],
+        data: &[u8],
+    ) -> QClaimDogeResult<(QHash256, u64)> {
+        destination.validate()?;
+        let template = BridgeDepositTemplateV2::new(config.clone(), refund.cloned())?;
+        if let Some(refund) = refund {
+            refund.check_claim(block_number, current_block_number)?;
+        }
+        let (tx_count, read_length) = CoinbaseInBlockProofV1::verify_coinbase_in_block_at_height(
+            data,
+            block_number,
+            known_block_tx_merkle_root,
         )?;
+        Self::verify_tx_out_in_block_is_deposit_core(
+            |tx_bytes| TransactionInBlockProofV1::check_is_deposit_address_v2_mem(
+                tx_bytes,
+                output_index as usize,
+                &destination.get_message(),
+                &template,
+            ),
+            block_number,
+            tx_index,
+            output_index,
+            known_block_tx_merkle_root,
+            tx_count,
+            known_user_claim_merkle_hash,
+            &data[read_length..],
+        )
+    }
+
+    // <check_deposit_output> returns the amount of the deposit output in the proven transaction
+    fn verify_tx_out_in_block_is_deposit_core<'a, F: FnOnce(&[u8]) -> QClaimDogeResult<u64>>(
+        check_deposit_output: F,
+        block_number: u32,
+        tx_index: u32,
+        output_index: u32,
+        known_block_tx_merkle_root: &[u8; 32],
+        known_block_tx_count: u32,
+        known_user_claim_merkle_hash: &[u8; 32],
+        data: &'a [u8],
+    ) -> QClaimDogeResult<(QHash256, u64)> {
+        let (_, tx_bytes, read_length) = TransactionInBlockProofV1::get_proof_tx_in_block(
+            data,
+            tx_index,
+            known_block_tx_merkle_root,
+            known_block_tx_count,
+        )?;
+        let amount = check_deposit_output(tx_bytes)?;
 
         // we now know there exists a transaction with an output (block_number, tx_index, output_index) in a block with the known block merkle root of known_block_tx_merkle_root
-        // we also know it is a valid deposit for a bridge with the given <bridge_public_key_hash> and the user with address <solana_public_key> of amount <amount> DOGE
+        // we also know it is a valid deposit for the bridge and the user checked by <check_deposit_output> of amount <amount> DOGE
         // BUT: we still need to make sure it hasn't been claimed by the user before, see code below
 
         // TODO: change this to a sparse merkle proof or similar construction to reduce the size of the proof
@@ -305,10 +435,13 @@ impl TransactionInBlockProofV1 {
     }
 
     // returns the (tx_hash, tx_bytes, read_length)
+    // <known_block_tx_count> must be authenticated (see CoinbaseInBlockProofV1), the index and depth of the proof are checked
+    // against it so a 64 byte transaction can not be passed off as an inner node and a duplicated leaf can not be claimed twice
     pub fn get_proof_tx_in_block<'a>(
         data: &'a [u8],
         index_in_block: u32,
         known_block_tx_merkle_root: &[u8],
+        known_block_tx_count: u32,
     ) -> QClaimDogeResult<(QHash256, &'a [u8], usize)> {
         if data.len() < MIN_POSSIBLE_TX_SIZE {
             return Err(ClaimDogeBridgeHelperError::InvalidTransactionProofV1Blob);
@@ -321,6 +454,9 @@ impl TransactionInBlockProofV1 {
         if siblings_len >= 30 || index_in_block >= (1u32 << siblings_len) {
             return Err(ClaimDogeBridgeHelperError::InvalidTransactionProofV1Blob);
         }
+        if index_in_block >= known_block_tx_count || siblings_len as usize != get_btc_tx_tree_depth(known_block_tx_count) {
+            return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape);
+        }
 
         let siblings_start = 1;
         let tx_size_start = siblings_start + siblings_len as usize * 32;
@@ -336,6 +472,9 @@ impl TransactionInBlockProofV1 {
         if data.len() < tx_start + tx_size {
             return Err(ClaimDogeBridgeHelperError::InvalidTransactionProofV1Blob);
         }
+        if tx_size == TX_MERKLE_NODE_SIZE {
+            return Err(ClaimDogeBridgeHelperError::InvalidProofTransactionSize64Bytes);
+        }
 
         let tx_hash =
             hash_impl_sha256_bytes(&hash_impl_sha256_bytes(&data[tx_start..tx_start + tx_size]));
@@ -345,7 +484,8 @@ impl TransactionInBlockProofV1 {
             &data[siblings_start..tx_size_start],
             index_in_block,
             siblings_len as usize,
-        );
+            known_block_tx_count,
+        )?;
         if computed_tx_merkle_root != known_block_tx_merkle_root {
             return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleRoots);
         }
@@ -384,12 +524,510 @@ impl TransactionInBlockProofV1 {
             Err(_) => Err(ClaimDogeBridgeHelperError::InvalidProofTransactionData),
         }
     }
+
+    pub fn check_is_deposit_address_v2_mem(
+        tx_data: &[u8],
+        output_index: usize,
+        message: &[u8; 32],
+        template: &BridgeDepositTemplateV2,
+    ) -> QClaimDogeResult<u64> {
+        template.validate()?;
+        match BTCTransaction::get_output_skip_decode(tx_data, 0, output_index) {
+            Ok((version, locktime, output)) => {
+                if version != 1 && version != 2 {
+                    Err(ClaimDogeBridgeHelperError::InvaildProofTransactionVersion)
+                } else if locktime != 0 {
+                    Err(ClaimDogeBridgeHelperError::InvaildProofTransactionLocktime)
+                } else if is_bridge_desposit_output_v2_for_user(&output, message, template) {
+                    Ok(output.value)
+                } else {
+                    Err(ClaimDogeBridgeHelperError::InvalidProofTransactionOutput)
+                }
+            }
+            Err(_) => Err(ClaimDogeBridgeHelperError::InvalidProofTransactionData),
+        }
+    }
+}
+
+// proves the coinbase transaction of a block (index 0) and the last transaction hash of the block against the tx merkle root.
+// the depth of the coinbase branch and the duplicated nodes on the right edge of the tree fix the number of transactions,
+// so the block height (BIP34) and tx count can be authenticated from the tx_tree_merkle_root stored in a BlockDataRecord
+pub struct CoinbaseInBlockProofV1 {
+    pub tx_count: u32,
+    pub coinbase_merkle_proof_siblings: Vec<QHash256>,
+    pub last_tx_hash: QHash256,
+    pub last_tx_merkle_proof_siblings: Vec<QHash256>,
+    pub coinbase_transaction: BTCTransaction,
+}
+
+impl CoinbaseInBlockProofV1 {
+    pub fn new(
+        tx_count: u32,
+        coinbase_merkle_proof_siblings: Vec<QHash256>,
+        last_tx_hash: QHash256,
+        last_tx_merkle_proof_siblings: Vec<QHash256>,
+        coinbase_transaction: BTCTransaction,
+    ) -> Self {
+        CoinbaseInBlockProofV1 {
+            tx_count,
+            coinbase_merkle_proof_siblings,
+            last_tx_hash,
+            last_tx_merkle_proof_siblings,
+            coinbase_transaction,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tx_bytes = self.coinbase_transaction.to_bytes();
+        let size = 4
+            + 1
+            + self.coinbase_merkle_proof_siblings.len() * 32
+            + 32
+            + 1
+            + self.last_tx_merkle_proof_siblings.len() * 32
+            + 4
+            + tx_bytes.len();
+        let mut bytes = Vec::with_capacity(size);
+        bytes.extend_from_slice(&self.tx_count.to_le_bytes());
+        bytes.push(self.coinbase_merkle_proof_siblings.len() as u8);
+        for sibling in self.coinbase_merkle_proof_siblings.iter() {
+            bytes.extend_from_slice(sibling);
+        }
+        bytes.extend_from_slice(&self.last_tx_hash);
+        bytes.push(self.last_tx_merkle_proof_siblings.len() as u8);
+        for sibling in self.last_tx_merkle_proof_siblings.iter() {
+            bytes.extend_from_slice(sibling);
+        }
+        let tx_len_u32 = tx_bytes.len() as u32;
+        bytes.extend_from_slice(&tx_len_u32.to_le_bytes());
+        bytes.extend_from_slice(&tx_bytes);
+
+        assert!(bytes.len() == size);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<(Self, usize)> {
+        let mut offset = 0;
+        let tx_count = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        let read_siblings = |offset: &mut usize| {
+            let siblings_len = bytes[*offset];
+            *offset += 1;
+            let mut siblings = Vec::with_capacity(siblings_len as usize);
+            for _ in 0..siblings_len {
+                let mut sibling = [0u8; 32];
+                sibling.copy_from_slice(&bytes[*offset..*offset + 32]);
+                *offset += 32;
+                siblings.push(sibling);
+            }
+            siblings
+        };
+        let coinbase_merkle_proof_siblings = read_siblings(&mut offset);
+        let mut last_tx_hash = [0u8; 32];
+        last_tx_hash.copy_from_slice(&bytes[offset..offset + 32]);
+        offset += 32;
+        let last_tx_merkle_proof_siblings = read_siblings(&mut offset);
+        let tx_len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        offset += 4;
+        let coinbase_transaction = BTCTransaction::from_bytes(&bytes[offset..offset + tx_len])?;
+        offset += tx_len;
+
+        Ok((CoinbaseInBlockProofV1 {
+            tx_count,
+            coinbase_merkle_proof_siblings,
+            last_tx_hash,
+            last_tx_merkle_proof_siblings,
+            coinbase_transaction,
+        }, offset))
+    }
+
+    // returns the (block_height, tx_count, read_length)
+    pub fn verify_coinbase_in_block(
+        data: &[u8],
+        known_block_tx_merkle_root: &[u8],
+    ) -> QClaimDogeResult<(u32, u32, usize)> {
+        if known_block_tx_merkle_root.len() != 32 || data.len() < 5 {
+            return Err(ClaimDogeBridgeHelperError::InvalidCoinbaseProofV1Blob);
+        }
+        let tx_count = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        let depth = get_btc_tx_tree_depth(tx_count);
+        if tx_count == 0 || depth >= 30 {
+            return Err(ClaimDogeBridgeHelperError::InvalidCoinbaseProofV1Blob);
+        }
+
+        let coinbase_siblings_start = 5;
+        if data[4] as usize != depth {
+            return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape);
+        }
+        let last_tx_hash_start = coinbase_siblings_start + depth * 32;
+        let last_siblings_start = last_tx_hash_start + 33;
+        let tx_size_start = last_siblings_start + depth * 32;
+        let tx_start = tx_size_start + 4;
+        if data.len() < tx_start {
+            return Err(ClaimDogeBridgeHelperError::InvalidCoinbaseProofV1Blob);
+        }
+        if data[last_tx_hash_start + 32] as usize != depth {
+            return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape);
+        }
+        let tx_size =
+            u32::from_le_bytes(data[tx_size_start..tx_size_start + 4].try_into().unwrap()) as usize;
+        if tx_size < MIN_POSSIBLE_TX_SIZE || tx_size > MAX_REASONABLE_TX_SIZE || data.len() < tx_start + tx_size {
+            return Err(ClaimDogeBridgeHelperError::InvalidCoinbaseProofV1Blob);
+        }
+        if tx_size == TX_MERKLE_NODE_SIZE {
+            return Err(ClaimDogeBridgeHelperError::InvalidProofTransactionSize64Bytes);
+        }
+        let tx_data = &data[tx_start..tx_start + tx_size];
+
+        let coinbase_hash = hash_impl_sha256_bytes(&hash_impl_sha256_bytes(tx_data));
+        let computed_tx_merkle_root = compute_merkle_in_mem_btc_has256(
+            coinbase_hash,
+            &data[coinbase_siblings_start..last_tx_hash_start],
+            0,
+            depth,
+            tx_count,
+        )?;
+        if computed_tx_merkle_root != known_block_tx_merkle_root {
+            return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleRoots);
+        }
+
+        // the coinbase branch fixes the depth of the tree, the last tx branch (with duplicates exactly where the
+        // last node of a level is odd) fixes the number of transactions in the last subtree
+        let last_tx_hash: [u8; 32] = data[last_tx_hash_start..last_tx_hash_start + 32].try_into().unwrap();
+        let computed_tx_merkle_root = compute_merkle_in_mem_btc_has256(
+            last_tx_hash,
+            &data[last_siblings_start..tx_size_start],
+            tx_count - 1,
+            depth,
+            tx_count,
+        )?;
+        if computed_tx_merkle_root != known_block_tx_merkle_root {
+            return Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleRoots);
+        }
+
+        let coinbase_transaction = BTCTransaction::from_bytes(tx_data)
+            .map_err(|_| ClaimDogeBridgeHelperError::InvalidProofTransactionData)?;
+        let coinbase_script = coinbase_transaction
+            .get_coinbase_script()
+            .ok_or(ClaimDogeBridgeHelperError::ProofTransactionNotCoinbase)?;
+        let block_height = decode_bip34_height(coinbase_script)
+            .map_err(|_| ClaimDogeBridgeHelperError::InvalidCoinbaseHeight)?;
+
+        Ok((block_height, tx_count, tx_start + tx_size))
+    }
+
+    // returns the (tx_count, read_length) of the block after checking the coinbase commits to <block_number>.
+    // every block is required to have a BIP34 height, so blocks below the BIP34 activation height can not be proven
+    pub fn verify_coinbase_in_block_at_height(
+        data: &[u8],
+        block_number: u32,
+        known_block_tx_merkle_root: &[u8],
+    ) -> QClaimDogeResult<(u32, usize)> {
+        let (block_height, tx_count, read_length) = Self::verify_coinbase_in_block(data, known_block_tx_merkle_root)?;
+        if block_height != block_number {
+            return Err(ClaimDogeBridgeHelperError::MismatchedCoinbaseHeight);
+        }
+        Ok((tx_count, read_length))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use doge_light_client::{
+        core_data::QHash256,
+        doge::{address::gen_p2sh_script, transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput}},
+        hash::{merkle::utils::{compute_btc_merkle_root_from_leaves, get_btc_merkle_proof_siblings_from_leaves}, sha256::QBTCHash256Hasher, sha256_impl::hash_impl_sha256_bytes},
+    };
+
+    use crate::{
+        deposit_destination::{DepositDestinationV1, DEPOSIT_CHAIN_ID_ETHEREUM},
+        error::ClaimDogeBridgeHelperError,
+        tx_template::{BridgeDepositConfigV2, BridgeDepositTemplateV2},
+    };
+
+    use super::{compute_merkle_in_mem_sha256, get_user_claimed_combined_index, CoinbaseInBlockProofV1, TransactionInBlockProofV1, UserClaimStateProofV1};
+
+    fn proof_blob(siblings: &[QHash256], tx: &[u8]) -> Vec<u8> {
+        let mut data = vec![siblings.len() as u8];
+        for sibling in siblings {
+            data.extend_from_slice(sibling);
+        }
+        data.extend_from_slice(&(tx.len() as u32).to_le_bytes());
+        data.extend_from_slice(tx);
+        data
+    }
+
+    fn bridge_config() -> BridgeDepositConfigV2 {
+        let mut public_key = [2u8; 33];
+        public_key[1..].copy_from_slice(&[9u8; 32]);
+        BridgeDepositConfigV2::new(1, vec![public_key]).unwrap()
+    }
+
+    fn coinbase_transaction() -> BTCTransaction {
+        BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput {
+                hash: [0u8; 32],
+                index: u32::MAX,
+                // height 1034383 followed by an extra nonce
+                script: vec![0x03, 0x8f, 0xc8, 0x0f, 0x01, 0x02, 0x03, 0x04],
+                sequence: u32::MAX,
+            }],
+            outputs: vec![BTCTransactionOutput {
+                value: 10_000 * 100_000_000,
+                script: vec![0x76, 0xa9, 0x14, 0x00, 0x88, 0xac],
+            }],
+            locktime: 0,
+        }
+    }
+
+    fn coinbase_proof_blob(tx_count: u32, txids: &[QHash256]) -> Vec<u8> {
+        CoinbaseInBlockProofV1::new(
+            tx_count,
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(txids, 0),
+            txids[tx_count as usize - 1],
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(txids, tx_count as usize - 1),
+            coinbase_transaction(),
+        )
+        .to_bytes()
+    }
+
     #[test]
     pub fn test_v1() -> anyhow::Result<()> {
         Ok(())
     }
+
+    #[test]
+    pub fn test_tx_in_block_rejects_mutated_proofs() {
+        let tx = [7u8; 100];
+        let mut txids: Vec<QHash256> = (0..4u8).map(|i| [i; 32]).collect();
+        txids.push(hash_impl_sha256_bytes(&hash_impl_sha256_bytes(&tx)));
+        let (root, _) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+
+        let data = proof_blob(&get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 4), &tx);
+        let (tx_hash, tx_bytes, read_length) = TransactionInBlockProofV1::get_proof_tx_in_block(&data, 4, &root, 5).unwrap();
+        assert_eq!((tx_hash, tx_bytes, read_length), (txids[4], &tx[..], data.len()));
+        assert_eq!(
+            TransactionInBlockProofV1::get_proof_tx_in_block(&data, 4, &root, 6).err(),
+            Some(ClaimDogeBridgeHelperError::MutatedTxMerkleProof)
+        );
+        assert_eq!(
+            TransactionInBlockProofV1::get_proof_tx_in_block(&data, 4, &root, 16).err(),
+            Some(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape)
+        );
+
+        // the same transaction claimed a second time at the duplicated index 5 == tx_count, the proof
+        // matches the root so it is only rejected by the tx count
+        let mut mutated_txids = txids.clone();
+        mutated_txids.push(txids[4]);
+        let data = proof_blob(&get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&mutated_txids, 5), &tx);
+        assert_eq!(
+            TransactionInBlockProofV1::get_proof_tx_in_block(&data, 5, &root, 5).err(),
+            Some(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape)
+        );
+        assert_eq!(
+            TransactionInBlockProofV1::get_proof_tx_in_block(&data, 5, &root, 6).err(),
+            Some(ClaimDogeBridgeHelperError::MutatedTxMerkleProof)
+        );
+
+        let data = proof_blob(&[[0u8; 32]; 3], &[0u8; 64]);
+        assert_eq!(
+            TransactionInBlockProofV1::get_proof_tx_in_block(&data, 0, &root, 5).err(),
+            Some(ClaimDogeBridgeHelperError::InvalidProofTransactionSize64Bytes)
+        );
+    }
+
+    #[test]
+    pub fn test_coinbase_in_block_proof() {
+        let coinbase = coinbase_transaction();
+
+        let mut txids: Vec<QHash256> = vec![coinbase.get_hash()];
+        txids.extend((1..7u8).map(|i| [i; 32]));
+        let (root, _) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+
+        let data = coinbase_proof_blob(7, &txids);
+        assert_eq!(CoinbaseInBlockProofV1::verify_coinbase_in_block(&data, &root), Ok((1034383, 7, data.len())));
+        assert_eq!(CoinbaseInBlockProofV1::verify_coinbase_in_block_at_height(&data, 1034383, &root), Ok((7, data.len())));
+        assert_eq!(
+            CoinbaseInBlockProofV1::verify_coinbase_in_block_at_height(&data, 1034384, &root).err(),
+            Some(ClaimDogeBridgeHelperError::MismatchedCoinbaseHeight)
+        );
+        let (decoded, read_length) = CoinbaseInBlockProofV1::from_bytes(&data).unwrap();
+        assert_eq!((decoded.tx_count, decoded.coinbase_transaction, read_length), (7, coinbase.clone(), data.len()));
+
+        // a smaller tx count with the same tree depth requires a duplicated node where the real tree has a distinct one
+        let data = coinbase_proof_blob(6, &txids);
+        assert_eq!(
+            CoinbaseInBlockProofV1::verify_coinbase_in_block(&data, &root).err(),
+            Some(ClaimDogeBridgeHelperError::MutatedTxMerkleProof)
+        );
+        // a larger tx count needs a right child equal to its sibling
+        let mut extended_txids = txids.clone();
+        extended_txids.push(txids[6]);
+        let data = coinbase_proof_blob(8, &extended_txids);
+        assert_eq!(
+            CoinbaseInBlockProofV1::verify_coinbase_in_block(&data, &root).err(),
+            Some(ClaimDogeBridgeHelperError::MutatedTxMerkleProof)
+        );
+        // a tx count with a different tree depth does not match the depth of the branches
+        let data = coinbase_proof_blob(4, &txids);
+        assert_eq!(
+            CoinbaseInBlockProofV1::verify_coinbase_in_block(&data, &root).err(),
+            Some(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape)
+        );
+
+        // a block with only the coinbase
+        let data = coinbase_proof_blob(1, &txids[0..1]);
+        assert_eq!(CoinbaseInBlockProofV1::verify_coinbase_in_block(&data, &txids[0]), Ok((1034383, 1, data.len())));
+    }
+
+    #[test]
+    pub fn test_claim_deposit_for_evm_destination() {
+        let config = bridge_config();
+        let destination = DepositDestinationV1::new_evm(DEPOSIT_CHAIN_ID_ETHEREUM, [0xab; 20]);
+        let coinbase = coinbase_transaction();
+        let deposit = BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput {
+                hash: [1u8; 32],
+                index: 0,
+                script: vec![0x51],
+                sequence: u32::MAX,
+            }],
+            outputs: vec![BTCTransactionOutput {
+                value: 50 * 100_000_000,
+                script: gen_p2sh_script(&destination.get_deposit_address_v2(&BridgeDepositTemplateV2::new(config.clone(), None).unwrap()).address).to_vec(),
+            }],
+            locktime: 0,
+        };
+        let txids = vec![coinbase.get_hash(), deposit.get_hash()];
+        let (root, _) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+
+        let mut data = CoinbaseInBlockProofV1::new(
+            2,
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 0),
+            txids[1],
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 1),
+            coinbase,
+        )
+        .to_bytes();
+        data.extend(proof_blob(
+            &get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 1),
+            &deposit.to_bytes(),
+        ));
+        // nothing claimed yet
+        data.extend_from_slice(&[0u8; 32 * 65]);
+        let (combined_index, _) = get_user_claimed_combined_index(1034383, 1, 0);
+        let known_user_claim_merkle_hash = compute_merkle_in_mem_sha256([0u8; 32], &[0u8; 32 * 64], combined_index, 64);
+
+        let (new_user_claim_merkle_hash, amount) =
+            UserClaimStateProofV1::verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+                &destination,
+                &config,
+                None,
+                1034383,
+                1034383,
+                1,
+                0,
+                &root,
+                &known_user_claim_merkle_hash,
+                &data,
+            )
+            .unwrap();
+        assert_eq!(amount, 50 * 100_000_000);
+        assert_ne!(new_user_claim_merkle_hash, known_user_claim_merkle_hash);
+
+        // the same deposit does not pay a different chain, a destination with a payload or a solana key equal to the message
+        for other in [
+            DepositDestinationV1::new_evm(137, [0xab; 20]),
+            destination.clone().with_payload_hash([1u8; 32]),
+            DepositDestinationV1::new_solana(destination.get_message()),
+        ] {
+            assert_eq!(
+                UserClaimStateProofV1::verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+                    &other,
+                    &config,
+                    None,
+                    1034383,
+                    1034383,
+                    1,
+                    0,
+                    &root,
+                    &known_user_claim_merkle_hash,
+                    &data,
+                )
+                .err(),
+                Some(ClaimDogeBridgeHelperError::InvalidProofTransactionOutput)
+            );
+        }
+        // and raw solana public keys are only claimable from v1 deposits
+        assert_eq!(
+            UserClaimStateProofV1::verify_tx_out_in_block_is_deposit_v1_with_coinbase_proof(
+                &destination.get_message(),
+                &[9u8; 20],
+                1034383,
+                1,
+                0,
+                &root,
+                &known_user_claim_merkle_hash,
+                &data,
+            )
+            .err(),
+            Some(ClaimDogeBridgeHelperError::InvalidProofTransactionOutput)
+        );
+    }
+
+    #[test]
+    pub fn test_claim_deposit_rejects_duplicated_leaf() {
+        let config = bridge_config();
+        let destination = DepositDestinationV1::new_evm(DEPOSIT_CHAIN_ID_ETHEREUM, [0xab; 20]);
+        let deposit = BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput {
+                hash: [1u8; 32],
+                index: 0,
+                script: vec![0x51],
+                sequence: u32::MAX,
+            }],
+            outputs: vec![BTCTransactionOutput {
+                value: 50 * 100_000_000,
+                script: gen_p2sh_script(&destination.get_deposit_address_v2(&BridgeDepositTemplateV2::new(config.clone(), None).unwrap()).address).to_vec(),
+            }],
+            locktime: 0,
+        };
+        // the deposit is the last transaction of a block with an odd number of transactions, so its leaf is duplicated
+        let txids = vec![coinbase_transaction().get_hash(), [1u8; 32], deposit.get_hash()];
+        let (root, _) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+        let mut phantom_txids = txids.clone();
+        phantom_txids.push(txids[2]);
+
+        let (combined_index, _) = get_user_claimed_combined_index(1034383, 3, 0);
+        let known_user_claim_merkle_hash = compute_merkle_in_mem_sha256([0u8; 32], &[0u8; 32 * 64], combined_index, 64);
+        let claim = |coinbase_proof: Vec<u8>, tx_index: u32| {
+            let mut data = coinbase_proof;
+            data.extend(proof_blob(
+                &get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&phantom_txids, tx_index as usize),
+                &deposit.to_bytes(),
+            ));
+            data.extend_from_slice(&[0u8; 32 * 65]);
+            UserClaimStateProofV1::verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+                &destination,
+                &config,
+                None,
+                1034383,
+                1034383,
+                tx_index,
+                0,
+                &root,
+                &known_user_claim_merkle_hash,
+                &data,
+            )
+            .err()
+        };
+
+        // the deposit proven at the phantom index tx_count against the authenticated tx count
+        assert_eq!(claim(coinbase_proof_blob(3, &txids), 3), Some(ClaimDogeBridgeHelperError::MismatchedTxMerkleProofShape));
+        // a coinbase proof can not extend the tx count over the duplicated leaf
+        assert_eq!(claim(coinbase_proof_blob(4, &phantom_txids), 3), Some(ClaimDogeBridgeHelperError::MutatedTxMerkleProof));
+    }
 }
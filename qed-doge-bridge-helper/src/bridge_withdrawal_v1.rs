@@ -0,0 +1,311 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Withdrawal transactions paying a user from the bridge custody, committing to the bridge withdrawal request id in an OP_RETURN output.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+use doge_light_client::{
+    chain_state::QEDDogeChainStateCore,
+    core_data::QHash256,
+    doge::{
+        address::{AddressToBTCScript, BTCAddress160},
+        transaction::{BTCTransaction, BTCTransactionOutput},
+        tx_builder::{BTCTransactionBuilder, BTCUtxo, DogeFeePolicy, UnsignedBTCTransaction},
+    },
+    error::DogeBridgeError,
+};
+
+use crate::{
+    bridge_tx_proof_v1::{CoinbaseInBlockProofV1, TransactionInBlockProofV1, UserClaimStateProofV1},
+    error::{ClaimDogeBridgeHelperError, QClaimDogeResult},
+    tx_template::{get_withdrawal_op_return_script_v1, get_withdrawal_request_id_v1},
+};
+
+/// A withdrawal requested on the bridge (for example by burning the wrapped tokens) which is still to be paid on dogecoin.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingWithdrawalV1 {
+    pub request_id: [u8; 32],
+    pub recipient: BTCAddress160,
+    /// Amount received by the recipient, the fee is paid by the custody.
+    pub amount: u64,
+}
+
+impl PendingWithdrawalV1 {
+    pub fn new(request_id: [u8; 32], recipient: BTCAddress160, amount: u64) -> Self {
+        Self {
+            request_id,
+            recipient,
+            amount,
+        }
+    }
+
+    pub fn get_op_return_output(&self) -> BTCTransactionOutput {
+        BTCTransactionOutput {
+            value: 0,
+            script: get_withdrawal_op_return_script_v1(&self.request_id).to_vec(),
+        }
+    }
+
+    /// Pays the withdrawal from custody utxos: output 0 pays the recipient, output 1 commits to the request id
+    /// and the change, if any, goes back to the custody address as output 2.
+    pub fn build_transaction(
+        &self,
+        custody_utxos: &[BTCUtxo],
+        custody_address: &BTCAddress160,
+        fee_policy: &DogeFeePolicy,
+    ) -> QClaimDogeResult<UnsignedBTCTransaction> {
+        BTCTransactionBuilder::new(*custody_address, *fee_policy)
+            .add_utxos(custody_utxos)
+            .add_recipient(&self.recipient, self.amount)
+            .add_output(self.get_op_return_output())
+            .build()
+            .map_err(|e| match e {
+                DogeBridgeError::TxBuilderInsufficientFunds => ClaimDogeBridgeHelperError::WithdrawalInsufficientFunds,
+                _ => ClaimDogeBridgeHelperError::InvalidWithdrawalAmount,
+            })
+    }
+
+    /// Checks that the transaction commits to this request id in exactly one withdrawal output and pays the
+    /// recipient at least the withdrawal amount. The outputs can be in any order.
+    pub fn verify_transaction(&self, transaction: &BTCTransaction) -> QClaimDogeResult<()> {
+        let mut request_ids = transaction.outputs.iter().filter_map(get_withdrawal_request_id_v1);
+        let request_id = request_ids.next().ok_or(ClaimDogeBridgeHelperError::WithdrawalRequestIdNotFound)?;
+        if request_ids.next().is_some() {
+            return Err(ClaimDogeBridgeHelperError::AmbiguousWithdrawalRequestId);
+        }
+        if request_id != self.request_id {
+            return Err(ClaimDogeBridgeHelperError::MismatchedWithdrawalRequestId);
+        }
+
+        let recipient_script = self.recipient.to_btc_script();
+        let paid = transaction
+            .outputs
+            .iter()
+            .filter(|x| x.script == recipient_script)
+            .try_fold(0u64, |total, x| total.checked_add(x.value))
+            .ok_or(ClaimDogeBridgeHelperError::NumericalOverflow)?;
+        if paid < self.amount {
+            return Err(ClaimDogeBridgeHelperError::WithdrawalRecipientUnderpaid);
+        }
+        Ok(())
+    }
+
+    pub fn verify_transaction_mem(&self, tx_data: &[u8]) -> QClaimDogeResult<()> {
+        let transaction = BTCTransaction::from_bytes(tx_data).map_err(|_| ClaimDogeBridgeHelperError::InvalidProofTransactionData)?;
+        self.verify_transaction(&transaction)
+    }
+
+    // <data> is a CoinbaseInBlockProofV1 which authenticates the number of transactions in the block followed by a
+    // TransactionInBlockProofV1 of the withdrawal transaction, returns the hash of the withdrawal transaction
+    pub fn verify_withdrawal_in_block_with_coinbase_proof(
+        &self,
+        block_number: u32,
+        tx_index: u32,
+        known_block_tx_merkle_root: &[u8; 32],
+        data: &[u8],
+    ) -> QClaimDogeResult<QHash256> {
+        let (tx_count, read_length) =
+            CoinbaseInBlockProofV1::verify_coinbase_in_block_at_height(data, block_number, known_block_tx_merkle_root)?;
+        let (tx_hash, tx_bytes, _) = TransactionInBlockProofV1::get_proof_tx_in_block(
+            &data[read_length..],
+            tx_index,
+            known_block_tx_merkle_root,
+            tx_count,
+        )?;
+        self.verify_transaction_mem(tx_bytes)?;
+        Ok(tx_hash)
+    }
+
+    pub fn verify_withdrawal_in_block_with_coinbase_proof_and_ibc<
+        const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
+        const QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS: usize,
+        const QDOGE_BRIDGE_BLOCK_TREE_HEIGHT: usize,
+    >(
+        &self,
+        block_number: u32,
+        tx_index: u32,
+        ibc: &QEDDogeChainStateCore<QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE, QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS, QDOGE_BRIDGE_BLOCK_TREE_HEIGHT>,
+        data: &[u8],
+    ) -> QClaimDogeResult<QHash256> {
+        let known_block_tx_merkle_root = UserClaimStateProofV1::get_finalized_block_tx_merkle_root(block_number, ibc)?;
+        self.verify_withdrawal_in_block_with_coinbase_proof(block_number, tx_index, &known_block_tx_merkle_root, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doge_light_client::{
+        constants::DOGE_COIN,
+        core_data::QHash256,
+        doge::{
+            address::{AddressToBTCScript, BTCAddress160},
+            psbt::PartiallySignedBTCTransaction,
+            script::interpreter::DOGE_STANDARD_SCRIPT_VERIFY_FLAGS,
+            transaction::{BTCTransaction, BTCTransactionInput, BTCTransactionOutput},
+            tx_builder::{BTCUtxo, DogeFeePolicy},
+        },
+        hash::{merkle::utils::{compute_btc_merkle_root_from_leaves, get_btc_merkle_proof_siblings_from_leaves}, sha256::QBTCHash256Hasher},
+    };
+    use libsecp256k1::{Message, PublicKey, SecretKey};
+
+    use crate::{
+        bridge_tx_proof_v1::{CoinbaseInBlockProofV1, TransactionInBlockProofV1},
+        error::ClaimDogeBridgeHelperError,
+        tx_template::{get_withdrawal_op_return_script_v1, get_withdrawal_request_id_v1},
+    };
+
+    use super::PendingWithdrawalV1;
+
+    fn withdrawal() -> PendingWithdrawalV1 {
+        PendingWithdrawalV1::new([0xab; 32], BTCAddress160::new_p2pkh([0x11; 20]), 25 * DOGE_COIN)
+    }
+
+    fn signed_withdrawal_transaction() -> BTCTransaction {
+        let secret = SecretKey::parse(&[9; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secret).serialize_compressed();
+        let custody_address = BTCAddress160::from_p2pkh_key(&public_key);
+        let utxos = [
+            BTCUtxo::new_p2pkh([1; 32], 0, 20 * DOGE_COIN, &public_key),
+            BTCUtxo::new_p2pkh([2; 32], 1, 20 * DOGE_COIN, &public_key),
+        ];
+        let unsigned = withdrawal().build_transaction(&utxos, &custody_address, &DogeFeePolicy::recommended()).unwrap();
+        let outputs = &unsigned.transaction.outputs;
+        assert_eq!(outputs[0], withdrawal().recipient.to_btc_output(25 * DOGE_COIN));
+        assert_eq!(outputs[1], withdrawal().get_op_return_output());
+        assert_eq!(outputs[2].script, custody_address.to_btc_script());
+        // the OP_RETURN output is below the soft dust limit and pays the dust fee
+        assert_eq!(unsigned.fee, 2 * DOGE_COIN / 100);
+
+        let mut psbt = PartiallySignedBTCTransaction::from_unsigned(&unsigned);
+        for input_index in 0..utxos.len() {
+            psbt.sign_input(input_index, &public_key, |sighash| {
+                Ok(libsecp256k1::sign(&Message::parse(sighash), &secret).0.serialize_der().as_ref().to_vec())
+            })
+            .unwrap();
+        }
+        psbt.finalize().unwrap();
+        let signed = psbt.extract().unwrap();
+        for (i, utxo) in utxos.iter().enumerate() {
+            signed.verify_input_script(i, &utxo.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+        }
+        signed
+    }
+
+    #[test]
+    fn test_withdrawal_template() {
+        let script = get_withdrawal_op_return_script_v1(&[0xab; 32]);
+        assert_eq!(&script[0..6], &[0x6a, 0x24, b'Q', b'D', b'W', b'1']);
+        assert_eq!(get_withdrawal_request_id_v1(&withdrawal().get_op_return_output()), Some([0xab; 32]));
+        assert_eq!(get_withdrawal_request_id_v1(&withdrawal().recipient.to_btc_output(0)), None);
+
+        let custody = BTCAddress160::new_p2sh([0x22; 20]);
+        let utxo = BTCUtxo::new_p2pkh([1; 32], 0, 25 * DOGE_COIN, &[2; 33]);
+        assert_eq!(
+            withdrawal().build_transaction(std::slice::from_ref(&utxo), &custody, &DogeFeePolicy::recommended()),
+            Err(ClaimDogeBridgeHelperError::WithdrawalInsufficientFunds)
+        );
+        let dust = PendingWithdrawalV1::new([0xab; 32], withdrawal().recipient, 1000);
+        assert_eq!(
+            dust.build_transaction(&[utxo], &custody, &DogeFeePolicy::recommended()),
+            Err(ClaimDogeBridgeHelperError::InvalidWithdrawalAmount)
+        );
+    }
+
+    #[test]
+    fn test_verify_withdrawal() {
+        let tx = signed_withdrawal_transaction();
+        withdrawal().verify_transaction(&tx).unwrap();
+        withdrawal().verify_transaction_mem(&tx.to_bytes()).unwrap();
+
+        let other_request = PendingWithdrawalV1::new([0xac; 32], withdrawal().recipient, withdrawal().amount);
+        assert_eq!(other_request.verify_transaction(&tx), Err(ClaimDogeBridgeHelperError::MismatchedWithdrawalRequestId));
+        let larger = PendingWithdrawalV1::new([0xab; 32], withdrawal().recipient, withdrawal().amount + 1);
+        assert_eq!(larger.verify_transaction(&tx), Err(ClaimDogeBridgeHelperError::WithdrawalRecipientUnderpaid));
+        let other_recipient = PendingWithdrawalV1::new([0xab; 32], BTCAddress160::new_p2sh([0x11; 20]), withdrawal().amount);
+        assert_eq!(other_recipient.verify_transaction(&tx), Err(ClaimDogeBridgeHelperError::WithdrawalRecipientUnderpaid));
+
+        let mut without_request = tx.clone();
+        without_request.outputs.remove(1);
+        assert_eq!(withdrawal().verify_transaction(&without_request), Err(ClaimDogeBridgeHelperError::WithdrawalRequestIdNotFound));
+        let mut two_requests = tx.clone();
+        two_requests.outputs.push(other_request.get_op_return_output());
+        assert_eq!(withdrawal().verify_transaction(&two_requests), Err(ClaimDogeBridgeHelperError::AmbiguousWithdrawalRequestId));
+    }
+
+    #[test]
+    fn test_verify_withdrawal_in_block() {
+        let tx = signed_withdrawal_transaction();
+        let coinbase = BTCTransaction {
+            version: 1,
+            inputs: vec![BTCTransactionInput {
+                hash: [0u8; 32],
+                index: u32::MAX,
+                // height 1034383 followed by an extra nonce
+                script: vec![0x03, 0x8f, 0xc8, 0x0f, 0x01, 0x02, 0x03, 0x04],
+                sequence: u32::MAX,
+            }],
+            outputs: vec![BTCTransactionOutput {
+                value: 10_000 * DOGE_COIN,
+                script: vec![0x76, 0xa9, 0x14, 0x00, 0x88, 0xac],
+            }],
+            locktime: 0,
+        };
+        let txids: Vec<QHash256> = vec![coinbase.get_hash(), [1; 32], tx.get_hash(), [3; 32]];
+        let (root, _) = compute_btc_merkle_root_from_leaves::<QHash256, QBTCHash256Hasher>(&txids);
+        let mut data = CoinbaseInBlockProofV1::new(
+            4,
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 0),
+            txids[3],
+            get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 3),
+            coinbase,
+        )
+        .to_bytes();
+        data.extend(
+            TransactionInBlockProofV1::new(
+                get_btc_merkle_proof_siblings_from_leaves::<QHash256, QBTCHash256Hasher>(&txids, 2),
+                tx.clone(),
+            )
+            .to_bytes(),
+        );
+
+        assert_eq!(withdrawal().verify_withdrawal_in_block_with_coinbase_proof(1034383, 2, &root, &data), Ok(tx.get_hash()));
+        assert_eq!(
+            withdrawal().verify_withdrawal_in_block_with_coinbase_proof(1034383, 3, &root, &data),
+            Err(ClaimDogeBridgeHelperError::MismatchedTxMerkleRoots)
+        );
+        assert_eq!(
+            withdrawal().verify_withdrawal_in_block_with_coinbase_proof(1034384, 2, &root, &data),
+            Err(ClaimDogeBridgeHelperError::MismatchedCoinbaseHeight)
+        );
+        let other_request = PendingWithdrawalV1::new([0xac; 32], withdrawal().recipient, withdrawal().amount);
+        assert_eq!(
+            other_request.verify_withdrawal_in_block_with_coinbase_proof(1034383, 2, &root, &data),
+            Err(ClaimDogeBridgeHelperError::MismatchedWithdrawalRequestId)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+//! Destination descriptors for bridge deposits, so the 32 byte message slot of the v2 deposit redeem scripts can commit to
+//! recipients on chains other than Solana.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+use doge_light_client::{doge::address::BTCAddress160, hash::sha256_impl::hash_impl_sha256_bytes};
+
+use crate::{
+    error::{ClaimDogeBridgeHelperError, QClaimDogeResult},
+    tx_template::{get_bridge_deposit_address_v2, BridgeDepositTemplateV2},
+};
+
+// EIP-155 chain ids start at 1, so 0 is free for solana
+pub const DEPOSIT_CHAIN_ID_SOLANA: u64 = 0;
+pub const DEPOSIT_CHAIN_ID_ETHEREUM: u64 = 1;
+
+pub const MAX_DEPOSIT_RECIPIENT_SIZE: usize = 64;
+
+pub const DEPOSIT_DESTINATION_V1_TAG: [u8; 4] = *b"QDD1";
+
+/// The recipient of a deposit: a chain id, the recipient on that chain (20 byte EVM address, 32 byte public key, ...)
+/// and an optional hash of a payload to be delivered along with the deposit.
+///
+/// The message of every destination, solana included, is `sha256("QDD1" || chain_id (u64 le) || recipient length (u8) || recipient || 0x00)`,
+/// or `... || 0x01 || payload_hash` with a payload hash. Destinations are only used by v2 deposits, the raw solana public key
+/// messages of v1 deposits keep their own claim path, so a deposit output belongs to exactly one of the two identities.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepositDestinationV1 {
+    pub chain_id: u64,
+    pub recipient: Vec<u8>,
+    pub payload_hash: Option<[u8; 32]>,
+}
+
+impl DepositDestinationV1 {
+    pub fn new(chain_id: u64, recipient: Vec<u8>, payload_hash: Option<[u8; 32]>) -> QClaimDogeResult<Self> {
+        let destination = Self {
+            chain_id,
+            recipient,
+            payload_hash,
+        };
+        destination.validate()?;
+        Ok(destination)
+    }
+    pub fn new_solana(public_key: [u8; 32]) -> Self {
+        Self {
+            chain_id: DEPOSIT_CHAIN_ID_SOLANA,
+            recipient: public_key.to_vec(),
+            payload_hash: None,
+        }
+    }
+    pub fn new_evm(chain_id: u64, address: [u8; 20]) -> Self {
+        Self {
+            chain_id,
+            recipient: address.to_vec(),
+            payload_hash: None,
+        }
+    }
+    pub fn with_payload_hash(mut self, payload_hash: [u8; 32]) -> Self {
+        self.payload_hash = Some(payload_hash);
+        self
+    }
+    pub fn validate(&self) -> QClaimDogeResult<()> {
+        if self.recipient.is_empty() || self.recipient.len() > MAX_DEPOSIT_RECIPIENT_SIZE {
+            return Err(ClaimDogeBridgeHelperError::InvalidDepositDestination);
+        }
+        Ok(())
+    }
+    /// The 32 byte message embedded in the v2 deposit redeem scripts.
+    pub fn get_message(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(4 + 8 + 1 + self.recipient.len() + 1 + 32);
+        data.extend_from_slice(&DEPOSIT_DESTINATION_V1_TAG);
+        data.extend_from_slice(&self.chain_id.to_le_bytes());
+        data.push(self.recipient.len() as u8);
+        data.extend_from_slice(&self.recipient);
+        match &self.payload_hash {
+            Some(payload_hash) => {
+                data.push(1);
+                data.extend_from_slice(payload_hash);
+            }
+            None => data.push(0),
+        }
+        hash_impl_sha256_bytes(&data)
+    }
+    pub fn get_deposit_address_v2(&self, template: &BridgeDepositTemplateV2) -> BTCAddress160 {
+        get_bridge_deposit_address_v2(&self.get_message(), template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doge_light_client::hash::sha256_impl::hash_impl_sha256_bytes;
+
+    use crate::{
+        error::ClaimDogeBridgeHelperError,
+        tx_template::{get_bridge_deposit_address_v2, BridgeDepositConfigV2, BridgeDepositTemplateV2},
+    };
+
+    use super::{DepositDestinationV1, DEPOSIT_CHAIN_ID_ETHEREUM, DEPOSIT_CHAIN_ID_SOLANA, MAX_DEPOSIT_RECIPIENT_SIZE};
+
+    #[test]
+    fn test_deposit_destination_message() {
+        let template = BridgeDepositTemplateV2::new(BridgeDepositConfigV2::new(1, vec![[2u8; 33]]).unwrap(), None).unwrap();
+
+        // solana destinations are domain separated like every other destination, so they never equal a raw public key
+        let solana = DepositDestinationV1::new_solana([7u8; 32]);
+        let mut data = b"QDD1".to_vec();
+        data.extend_from_slice(&DEPOSIT_CHAIN_ID_SOLANA.to_le_bytes());
+        data.push(32);
+        data.extend_from_slice(&[7u8; 32]);
+        data.push(0);
+        assert_eq!(solana.get_message(), hash_impl_sha256_bytes(&data));
+        assert_ne!(solana.get_deposit_address_v2(&template), get_bridge_deposit_address_v2(&[7u8; 32], &template));
+
+        // the same key on another chain, or with a payload, must not share the solana deposit address
+        let other_chain = DepositDestinationV1::new(5, vec![7u8; 32], None).unwrap();
+        let with_payload = solana.clone().with_payload_hash([1u8; 32]);
+        assert_ne!(other_chain.get_message(), solana.get_message());
+        assert_ne!(with_payload.get_message(), solana.get_message());
+        assert_ne!(with_payload.get_message(), with_payload.clone().with_payload_hash([2u8; 32]).get_message());
+
+        let evm = DepositDestinationV1::new_evm(DEPOSIT_CHAIN_ID_ETHEREUM, [7u8; 20]);
+        assert_ne!(evm.get_message(), DepositDestinationV1::new_evm(137, [7u8; 20]).get_message());
+        assert_ne!(evm.get_message(), DepositDestinationV1::new(DEPOSIT_CHAIN_ID_ETHEREUM, vec![7u8; 21], None).unwrap().get_message());
+        assert_ne!(evm.get_deposit_address_v2(&template), solana.get_deposit_address_v2(&template));
+    }
+
+    #[test]
+    fn test_deposit_destination_validation() {
+        assert_eq!(
+            DepositDestinationV1::new(DEPOSIT_CHAIN_ID_SOLANA, vec![], None).err(),
+            Some(ClaimDogeBridgeHelperError::InvalidDepositDestination)
+        );
+        assert_eq!(
+            DepositDestinationV1::new(DEPOSIT_CHAIN_ID_SOLANA, vec![1u8; MAX_DEPOSIT_RECIPIENT_SIZE + 1], None).err(),
+            Some(ClaimDogeBridgeHelperError::InvalidDepositDestination)
+        );
+        assert!(DepositDestinationV1::new(DEPOSIT_CHAIN_ID_SOLANA, vec![1u8; MAX_DEPOSIT_RECIPIENT_SIZE], None).is_ok());
+    }
+}
@@ -109,6 +109,69 @@ pub enum ClaimDogeBridgeHelperError {
 
     #[error("Block not yet finalized")]
     BlockNotFinalized = 610,
+
+    #[error("Transaction in proof is 64 bytes long and could be an inner node of the merkle tree")]
+    InvalidProofTransactionSize64Bytes = 611,
+
+    #[error("Tx merkle proof contains a duplicated node outside of the last node of a level (CVE-2012-2459)")]
+    MutatedTxMerkleProof = 612,
+
+    #[error("Tx merkle proof index or depth does not match the number of transactions in the block")]
+    MismatchedTxMerkleProofShape = 613,
+
+    #[error("Invalid coinbase proof V1 blob")]
+    InvalidCoinbaseProofV1Blob = 614,
+
+    #[error("Transaction in coinbase proof is not a coinbase transaction")]
+    ProofTransactionNotCoinbase = 615,
+
+    #[error("Coinbase in proof does not start with a valid serialized block height (BIP34)")]
+    InvalidCoinbaseHeight = 616,
+
+    #[error("Coinbase block height does not match the block number")]
+    MismatchedCoinbaseHeight = 617,
+
+    #[error("Sweep transaction has no deposits to spend")]
+    SweepNoDeposits = 618,
+
+    #[error("Sweep transaction has more inputs than fit in a standard transaction")]
+    SweepTooManyInputs = 619,
+
+    #[error("Swept amount after fees is below the dust limit")]
+    SweepOutputBelowDustLimit = 620,
+
+    #[error("Signature from the signing hook does not spend the deposit output")]
+    SweepInvalidSignature = 621,
+
+    #[error("Custody utxos do not cover the withdrawal amount and fee")]
+    WithdrawalInsufficientFunds = 622,
+
+    #[error("Withdrawal amount is below the dust limit or too large")]
+    InvalidWithdrawalAmount = 623,
+
+    #[error("Transaction has no withdrawal request id output")]
+    WithdrawalRequestIdNotFound = 624,
+
+    #[error("Transaction commits to more than one withdrawal request id")]
+    AmbiguousWithdrawalRequestId = 625,
+
+    #[error("Withdrawal request id does not match the pending withdrawal")]
+    MismatchedWithdrawalRequestId = 626,
+
+    #[error("Transaction pays the withdrawal recipient less than the withdrawal amount")]
+    WithdrawalRecipientUnderpaid = 627,
+
+    #[error("Invalid v2 deposit template (signature count, number of bridge keys, refund locktime or script size)")]
+    InvalidDepositTemplateV2 = 628,
+
+    #[error("Refund locktime of the deposit is too close to the block of the deposit")]
+    DepositRefundDelayTooShort = 629,
+
+    #[error("Deposit is claimed too close to its refund locktime")]
+    DepositRefundTooClose = 630,
+
+    #[error("Invalid deposit destination (recipient must be 1 to 64 bytes)")]
+    InvalidDepositDestination = 631,
 }
 
 
@@ -26,4 +26,7 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 
 pub mod tx_template;
 pub mod bridge_tx_proof_v1;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod bridge_sweep_v1;
+pub mod bridge_withdrawal_v1;
+pub mod deposit_destination;
@@ -24,29 +24,31 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use doge_light_client::{
     doge::{
         address::{BTCAddress160, BTCAddressType},
+        script::{builder::ScriptBuilder, opcodes::Opcode, LOCKTIME_THRESHOLD, MAX_SCRIPT_ELEMENT_SIZE},
         transaction::BTCTransactionOutput,
+        tx_builder::MAX_SIGNATURE_PUSH_SIZE,
     },
     hash::{ripemd160::QBTCHash160Hasher, traits::BytesHasher},
 };
 
+use crate::error::{ClaimDogeBridgeHelperError, QClaimDogeResult};
+
 const OP_PUSHBYTES_32: u8 = 0x20;
-const OP_DROP: u8 = 117;
-const OP_DUP: u8 = 118;
-const OP_EQUALVERIFY: u8 = 136;
-const OP_HASH160: u8 = 169;
-const OP_CHECKSIG: u8 = 172;
 const OP_PUSHBYTES_20: u8 = 0x14;
+const OP_PUSHBYTES_36: u8 = 0x24;
 
 //  size = 1 + 32 + 4 + 20 + 2 = 59
 pub const STANDARD_TRANSFER_WITH_MESSAGE_TEMPLATE: [u8; 59] = qed_doge_macros::const_concat_arrays!(
     [OP_PUSHBYTES_32],
     [0; 32], // 1..33
-    [OP_DROP, OP_DUP, OP_HASH160, OP_PUSHBYTES_20],
+    [Opcode::OP_DROP as u8, Opcode::OP_DUP as u8, Opcode::OP_HASH160 as u8, OP_PUSHBYTES_20],
     [0; 20], // 37..57
-    [OP_EQUALVERIFY, OP_CHECKSIG]
+    [Opcode::OP_EQUALVERIFY as u8, Opcode::OP_CHECKSIG as u8]
 );
 
 pub fn get_transfer_with_message_redeem_script(
@@ -92,3 +94,400 @@ pub fn is_bridge_desposit_output_v1_for_user(
         false
     }
 }
+
+// standard P2SH redeem scripts may not have more than 15 signature operations
+pub const MAX_DEPOSIT_V2_BRIDGE_KEYS: usize = 15;
+
+// `<sig> <compressed refund public key> OP_0`, selecting the refund branch
+pub const DEPOSIT_V2_REFUND_ARGUMENTS_SIZE: usize = MAX_SIGNATURE_PUSH_SIZE + 34 + 1;
+
+// a refund locktime must be at least this many blocks (~7 days) after the block of the deposit
+pub const MIN_REFUND_DELAY: u32 = 10_080;
+
+// a deposit with a refund must be claimed at least this many blocks (~1 day) before its refund locktime,
+// leaving the bridge time to sweep it before the depositor can take it back
+pub const MIN_REFUND_CLAIM_MARGIN: u32 = 1_440;
+
+/// Bridge multisig of the v2 deposit redeem script, owned by the bridge and read from its trusted state when claiming.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeDepositConfigV2 {
+    pub required_signatures: u8,
+    pub bridge_public_keys: Vec<[u8; 33]>,
+}
+
+impl BridgeDepositConfigV2 {
+    pub fn new(required_signatures: u8, bridge_public_keys: Vec<[u8; 33]>) -> QClaimDogeResult<Self> {
+        let config = Self {
+            required_signatures,
+            bridge_public_keys,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> QClaimDogeResult<()> {
+        let key_count = self.bridge_public_keys.len();
+        if self.required_signatures >= 1 && self.required_signatures as usize <= key_count && key_count <= MAX_DEPOSIT_V2_BRIDGE_KEYS {
+            Ok(())
+        } else {
+            Err(ClaimDogeBridgeHelperError::InvalidDepositTemplateV2)
+        }
+    }
+}
+
+/// Timelocked refund of a single deposit, chosen by the depositor.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepositRefundV2 {
+    pub public_key_hash: [u8; 20],
+    /// Block height from which the depositor can take the deposit back, enforced with OP_CHECKLOCKTIMEVERIFY.
+    pub locktime: u32,
+}
+
+impl DepositRefundV2 {
+    /// Checks that a deposit made in `block_number` can still be claimed at the chain height `current_block_number`.
+    pub fn check_claim(&self, block_number: u32, current_block_number: u32) -> QClaimDogeResult<()> {
+        if (self.locktime as u64) < block_number as u64 + MIN_REFUND_DELAY as u64 {
+            Err(ClaimDogeBridgeHelperError::DepositRefundDelayTooShort)
+        } else if (self.locktime as u64) < current_block_number as u64 + MIN_REFUND_CLAIM_MARGIN as u64 {
+            Err(ClaimDogeBridgeHelperError::DepositRefundTooClose)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Options of the v2 deposit redeem script: the m-of-n bridge multisig and an optional timelocked refund to the depositor.
+///
+/// Without a refund the redeem script is `<32 byte message> OP_DROP <m> <bridge keys...> <n> OP_CHECKMULTISIG`, with a refund it is
+/// `<32 byte message> OP_DROP OP_IF <multisig> OP_ELSE <locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_DUP OP_HASH160 <refund pkh> OP_EQUALVERIFY OP_CHECKSIG OP_ENDIF`.
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeDepositTemplateV2 {
+    pub config: BridgeDepositConfigV2,
+    pub refund: Option<DepositRefundV2>,
+}
+
+impl BridgeDepositTemplateV2 {
+    pub fn new(config: BridgeDepositConfigV2, refund: Option<DepositRefundV2>) -> QClaimDogeResult<Self> {
+        let template = Self { config, refund };
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn validate(&self) -> QClaimDogeResult<()> {
+        self.config.validate()?;
+        let valid = self.refund.as_ref().map_or(true, |x| x.locktime > 0 && (x.locktime as i64) < LOCKTIME_THRESHOLD)
+            && self.get_redeem_script(&[0; 32]).len() <= MAX_SCRIPT_ELEMENT_SIZE;
+        if valid {
+            Ok(())
+        } else {
+            Err(ClaimDogeBridgeHelperError::InvalidDepositTemplateV2)
+        }
+    }
+
+    pub fn get_redeem_script(&self, message: &[u8]) -> Vec<u8> {
+        let mut builder = ScriptBuilder::new().push_slice(message).push_opcode(Opcode::OP_DROP);
+        if self.refund.is_some() {
+            builder = builder.push_opcode(Opcode::OP_IF);
+        }
+        builder = builder.push_int(self.config.required_signatures as i64);
+        for public_key in self.config.bridge_public_keys.iter() {
+            builder = builder.push_slice(public_key);
+        }
+        builder = builder
+            .push_int(self.config.bridge_public_keys.len() as i64)
+            .push_opcode(Opcode::OP_CHECKMULTISIG);
+        if let Some(refund) = &self.refund {
+            builder = builder
+                .push_opcode(Opcode::OP_ELSE)
+                .push_int(refund.locktime as i64)
+                .push_opcode(Opcode::OP_CHECKLOCKTIMEVERIFY)
+                .push_opcode(Opcode::OP_DROP)
+                .push_opcode(Opcode::OP_DUP)
+                .push_opcode(Opcode::OP_HASH160)
+                .push_slice(&refund.public_key_hash)
+                .push_opcode(Opcode::OP_EQUALVERIFY)
+                .push_opcode(Opcode::OP_CHECKSIG)
+                .push_opcode(Opcode::OP_ENDIF);
+        }
+        builder.into_bytes()
+    }
+
+    // `OP_0 <sig_1> ... <sig_m>`, followed by OP_1 to select the bridge branch when there is a refund
+    pub fn get_bridge_spend_arguments_size(&self) -> usize {
+        1 + self.config.required_signatures as usize * MAX_SIGNATURE_PUSH_SIZE + self.refund.is_some() as usize
+    }
+
+    /// Script sig spending a deposit with the bridge keys, `signatures` must be in the order of the bridge public keys.
+    pub fn get_bridge_script_sig(&self, signatures: &[&[u8]], redeem_script: &[u8]) -> Vec<u8> {
+        let mut builder = ScriptBuilder::new().push_int(0);
+        for signature in signatures {
+            builder = builder.push_slice(signature);
+        }
+        if self.refund.is_some() {
+            builder = builder.push_int(1);
+        }
+        builder.push_slice(redeem_script).into_bytes()
+    }
+}
+
+/// Script sig of the refund branch, only valid in a transaction with a locktime of at least the refund locktime and a non-final input sequence.
+pub fn get_deposit_v2_refund_script_sig(signature: &[u8], public_key: &[u8], redeem_script: &[u8]) -> Vec<u8> {
+    ScriptBuilder::new()
+        .push_slice(signature)
+        .push_slice(public_key)
+        .push_int(0)
+        .push_slice(redeem_script)
+        .into_bytes()
+}
+
+pub fn get_bridge_deposit_address_hash_v2(user_public_key: &[u8], template: &BridgeDepositTemplateV2) -> [u8; 20] {
+    QBTCHash160Hasher::hash_bytes(&template.get_redeem_script(user_public_key))
+}
+
+pub fn get_bridge_deposit_address_v2(user_public_key: &[u8], template: &BridgeDepositTemplateV2) -> BTCAddress160 {
+    BTCAddress160 {
+        address_type: BTCAddressType::P2SH,
+        address: get_bridge_deposit_address_hash_v2(user_public_key, template),
+    }
+}
+
+pub fn is_bridge_desposit_output_v2_for_user(
+    output: &BTCTransactionOutput,
+    user_public_key: &[u8],
+    template: &BridgeDepositTemplateV2,
+) -> bool {
+    if output.is_p2sh_output() {
+        let output_addr: [u8; 20] = output.script[2..22].try_into().unwrap();
+        get_bridge_deposit_address_hash_v2(user_public_key, template) == output_addr
+    } else {
+        false
+    }
+}
+
+pub const WITHDRAWAL_V1_TAG: [u8; 4] = *b"QDW1";
+
+//  size = 1 + 1 + 4 + 32 = 38
+pub const WITHDRAWAL_V1_OP_RETURN_TEMPLATE: [u8; 38] = qed_doge_macros::const_concat_arrays!(
+    [Opcode::OP_RETURN as u8, OP_PUSHBYTES_36],
+    WITHDRAWAL_V1_TAG, // 2..6
+    [0; 32] // 6..38
+);
+
+pub fn get_withdrawal_op_return_script_v1(request_id: &[u8]) -> [u8; 38] {
+    let mut base = WITHDRAWAL_V1_OP_RETURN_TEMPLATE;
+    base[6..38].copy_from_slice(request_id);
+
+    base
+}
+
+// returns the request id committed to by a withdrawal OP_RETURN output
+pub fn get_withdrawal_request_id_v1(output: &BTCTransactionOutput) -> Option<[u8; 32]> {
+    if output.script.len() == WITHDRAWAL_V1_OP_RETURN_TEMPLATE.len() && output.script[0..6] == WITHDRAWAL_V1_OP_RETURN_TEMPLATE[0..6] {
+        Some(output.script[6..38].try_into().unwrap())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doge_light_client::{
+        constants::DOGE_COIN,
+        doge::{
+            address::{AddressToBTCScript, BTCAddress160},
+            script::interpreter::DOGE_STANDARD_SCRIPT_VERIFY_FLAGS,
+            transaction::{BTCTransaction, SIGHASH_ALL},
+            tx_builder::{BTCTransactionBuilder, BTCUtxo, DogeFeePolicy, UnsignedBTCTransaction},
+        },
+        error::DogeBridgeError,
+    };
+    use libsecp256k1::{Message, PublicKey, SecretKey};
+
+    use crate::{
+        bridge_tx_proof_v1::{TransactionInBlockProofV1, UserClaimStateProofV1},
+        deposit_destination::DepositDestinationV1,
+        error::ClaimDogeBridgeHelperError,
+    };
+
+    use super::*;
+
+    const USER: [u8; 32] = [0x5a; 32];
+
+    fn key(i: u8) -> (SecretKey, [u8; 33]) {
+        let secret = SecretKey::parse(&[i; 32]).unwrap();
+        (secret, PublicKey::from_secret_key(&secret).serialize_compressed())
+    }
+
+    fn sign(tx: &BTCTransaction, redeem_script: &[u8], secret: &SecretKey) -> Vec<u8> {
+        let sighash = tx.signature_hash(0, redeem_script, SIGHASH_ALL as u32);
+        let mut signature = libsecp256k1::sign(&Message::parse(&sighash), secret).0.serialize_der().as_ref().to_vec();
+        signature.push(SIGHASH_ALL);
+        signature
+    }
+
+    fn config() -> BridgeDepositConfigV2 {
+        BridgeDepositConfigV2::new(2, (1..=3).map(|i| key(i).1).collect()).unwrap()
+    }
+
+    fn refund(locktime: u32) -> DepositRefundV2 {
+        DepositRefundV2 {
+            public_key_hash: BTCAddress160::from_p2pkh_key(&key(9).1).address,
+            locktime,
+        }
+    }
+
+    fn template(refund_locktime: Option<u32>) -> BridgeDepositTemplateV2 {
+        BridgeDepositTemplateV2::new(config(), refund_locktime.map(refund)).unwrap()
+    }
+
+    fn spend_deposit(template: &BridgeDepositTemplateV2, max_arguments_size: usize, locktime: u32) -> (BTCUtxo, UnsignedBTCTransaction) {
+        let utxo = BTCUtxo::new_p2sh([1; 32], 0, 10 * DOGE_COIN, &template.get_redeem_script(&USER), max_arguments_size);
+        assert_eq!(utxo.script, get_bridge_deposit_address_v2(&USER, template).to_btc_script());
+        let unsigned = BTCTransactionBuilder::new(BTCAddress160::new_p2pkh([0x33; 20]), DogeFeePolicy::recommended())
+            .add_utxo(utxo.clone())
+            .add_recipient(&BTCAddress160::new_p2pkh([0x44; 20]), 9 * DOGE_COIN)
+            .with_locktime(locktime)
+            .build()
+            .unwrap();
+        (utxo, unsigned)
+    }
+
+    #[test]
+    fn test_deposit_v2_template_validation() {
+        let keys: Vec<[u8; 33]> = (1..=15).map(|i| key(i).1).collect();
+        let refund = Some(DepositRefundV2 {
+            public_key_hash: [0; 20],
+            locktime: 5_000_000,
+        });
+        let new_template = |required, key_count: usize, refund| {
+            BridgeDepositConfigV2::new(required, keys[..key_count].to_vec()).and_then(|config| BridgeDepositTemplateV2::new(config, refund))
+        };
+        assert!(new_template(1, 1, None).is_ok());
+        assert!(new_template(7, 14, None).is_ok());
+        assert!(new_template(7, 13, refund.clone()).is_ok());
+        for (required, key_count, refund_locktime) in [(0, 3, None), (4, 3, None), (8, 15, None), (7, 14, Some(5_000_000)), (2, 3, Some(0)), (2, 3, Some(500_000_000))] {
+            let refund = refund_locktime.map(|locktime| DepositRefundV2 {
+                public_key_hash: [0; 20],
+                locktime,
+            });
+            assert_eq!(new_template(required, key_count, refund), Err(ClaimDogeBridgeHelperError::InvalidDepositTemplateV2));
+        }
+
+        let template = template(Some(5_000_000));
+        let deposit = get_bridge_deposit_address_v2(&USER, &template).to_btc_output(DOGE_COIN);
+        assert!(is_bridge_desposit_output_v2_for_user(&deposit, &USER, &template));
+        assert!(!is_bridge_desposit_output_v2_for_user(&deposit, &[0x5b; 32], &template));
+        let without_refund = BridgeDepositTemplateV2 {
+            refund: None,
+            ..template.clone()
+        };
+        assert!(!is_bridge_desposit_output_v2_for_user(&deposit, &USER, &without_refund));
+    }
+
+    #[test]
+    fn test_deposit_v2_spend_paths() {
+        for template in [template(None), template(Some(5_000_000))] {
+            let redeem_script = template.get_redeem_script(&USER);
+            let (utxo, unsigned) = spend_deposit(&template, template.get_bridge_spend_arguments_size(), 0);
+            let mut tx = unsigned.transaction.clone();
+            let signatures = [sign(&tx, &redeem_script, &key(1).0), sign(&tx, &redeem_script, &key(3).0)];
+            tx.inputs[0].script = template.get_bridge_script_sig(&[&signatures[0], &signatures[1]], &redeem_script);
+            tx.verify_input_script(0, &utxo.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS).unwrap();
+            assert!(tx.to_bytes().len() <= unsigned.estimated_size);
+
+            tx.inputs[0].script = template.get_bridge_script_sig(&[&signatures[1], &signatures[0]], &redeem_script);
+            assert!(tx.verify_input_script(0, &utxo.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS).is_err());
+        }
+
+        // the depositor takes the deposit back once the refund height is reached
+        let template = template(Some(5_000_000));
+        let redeem_script = template.get_redeem_script(&USER);
+        let (refund_secret, refund_public_key) = key(9);
+        for (locktime, expected) in [
+            (5_000_000, Ok(())),
+            (5_000_001, Ok(())),
+            (4_999_999, Err(DogeBridgeError::ScriptUnsatisfiedLocktime)),
+            (0, Err(DogeBridgeError::ScriptUnsatisfiedLocktime)),
+        ] {
+            let (utxo, unsigned) = spend_deposit(&template, DEPOSIT_V2_REFUND_ARGUMENTS_SIZE, locktime);
+            let mut tx = unsigned.transaction;
+            tx.inputs[0].script = get_deposit_v2_refund_script_sig(&sign(&tx, &redeem_script, &refund_secret), &refund_public_key, &redeem_script);
+            assert_eq!(tx.verify_input_script(0, &utxo.script, DOGE_STANDARD_SCRIPT_VERIFY_FLAGS), expected);
+            assert!(tx.to_bytes().len() <= unsigned.estimated_size);
+        }
+    }
+
+    #[test]
+    fn test_deposit_v2_refund_claim_window() {
+        let block_number = 4_000_000;
+        let claim = |refund: Option<&DepositRefundV2>, current_block_number| {
+            UserClaimStateProofV1::verify_tx_out_in_block_is_deposit_v2_for_destination_with_coinbase_proof(
+                &DepositDestinationV1::new_solana(USER),
+                &config(),
+                refund,
+                block_number,
+                current_block_number,
+                1,
+                1,
+                &[0; 32],
+                &[0; 32],
+                &[],
+            )
+        };
+        let is_refund_error = |result: QClaimDogeResult<_>| {
+            matches!(
+                result,
+                Err(ClaimDogeBridgeHelperError::DepositRefundDelayTooShort | ClaimDogeBridgeHelperError::DepositRefundTooClose)
+            )
+        };
+
+        // the refund must not be due before the minimum delay after the deposit block
+        let locktime = block_number + MIN_REFUND_DELAY;
+        assert_eq!(
+            claim(Some(&refund(locktime - 1)), block_number),
+            Err(ClaimDogeBridgeHelperError::DepositRefundDelayTooShort)
+        );
+        assert_eq!(refund(locktime).check_claim(block_number, block_number), Ok(()));
+
+        // and the claim must leave the bridge time to sweep the deposit before the refund is due
+        let last_claim_block = locktime - MIN_REFUND_CLAIM_MARGIN;
+        assert_eq!(refund(locktime).check_claim(block_number, last_claim_block), Ok(()));
+        assert!(!is_refund_error(claim(Some(&refund(locktime)), last_claim_block)));
+        for current_block_number in [last_claim_block + 1, locktime, u32::MAX] {
+            assert_eq!(
+                claim(Some(&refund(locktime)), current_block_number),
+                Err(ClaimDogeBridgeHelperError::DepositRefundTooClose)
+            );
+        }
+        assert!(!is_refund_error(claim(None, u32::MAX)));
+    }
+
+    #[test]
+    fn test_check_is_deposit_address_v2_mem() {
+        let template = template(Some(5_000_000));
+        let mut tx = BTCTransaction::dummy();
+        tx.outputs.push(BTCAddress160::new_p2pkh([0x33; 20]).to_btc_output(DOGE_COIN));
+        tx.outputs.push(get_bridge_deposit_address_v2(&USER, &template).to_btc_output(7 * DOGE_COIN));
+        let tx_data = tx.to_bytes();
+
+        assert_eq!(TransactionInBlockProofV1::check_is_deposit_address_v2_mem(&tx_data, 1, &USER, &template), Ok(7 * DOGE_COIN));
+        assert_eq!(
+            TransactionInBlockProofV1::check_is_deposit_address_v2_mem(&tx_data, 0, &USER, &template),
+            Err(ClaimDogeBridgeHelperError::InvalidProofTransactionOutput)
+        );
+        let invalid = BridgeDepositTemplateV2 {
+            config: BridgeDepositConfigV2 {
+                required_signatures: 4,
+                ..template.config.clone()
+            },
+            ..template
+        };
+        assert_eq!(
+            TransactionInBlockProofV1::check_is_deposit_address_v2_mem(&tx_data, 1, &USER, &invalid),
+            Err(ClaimDogeBridgeHelperError::InvalidDepositTemplateV2)
+        );
+    }
+}
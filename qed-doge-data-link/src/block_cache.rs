@@ -44,6 +44,10 @@ pub struct BlockFetcher {
     pub store: HashMap<u32, QDogeBlock>,
 }
 
+fn validate_block(height: u32, block: &QDogeBlock) -> anyhow::Result<()> {
+    block.validate().map_err(|e| anyhow::anyhow!("block {} failed validation: {:?}", height, e))
+}
+
 impl BlockFetcher {
     pub fn new(client: DogeLinkElectrsClient) -> Self {
         BlockFetcher {
@@ -55,11 +59,17 @@ impl BlockFetcher {
         if let Some(block) = self.store.get(&height) {
             return Ok(block.clone());
         }
-        let block = self.client.get_qdoge_block(height as u32)?;
+        let block = self.fetch_validated_block(height)?;
         self.store.insert(height, block.clone());
         Ok(block)
     }
 
+    fn fetch_validated_block(&self, height: u32) -> anyhow::Result<QDogeBlock> {
+        let block = self.client.get_qdoge_block(height)?;
+        validate_block(height, &block)?;
+        Ok(block)
+    }
+
     pub fn get_blocks(&mut self, heights: &[u32]) -> anyhow::Result<Vec<QDogeBlock>> {
         let mut blocks = Vec::with_capacity(heights.len());
         for h in heights.iter(){
@@ -71,7 +81,7 @@ impl BlockFetcher {
         if let Some(block) = self.store.get(&height) {
             return Ok(block.clone());
         }
-        let block = self.client.get_qdoge_block(height as u32)?;
+        let block = self.fetch_validated_block(height)?;
         Ok(block)
     }
 
@@ -97,6 +107,7 @@ impl BlockFetcher {
         let data = std::fs::read_to_string(path)?;
         let blocks: Vec<BlockWithIndex> = serde_json::from_str(&data)?;
         for b in blocks {
+            validate_block(b.height, &b.block)?;
             self.store.insert(b.height, b.block);
         }
         Ok(())
@@ -111,6 +122,7 @@ impl BlockFetcher {
         let data = std::fs::read(path)?;
         let blocks: Vec<BlockWithIndex> = bincode::deserialize(&data)?;
         for b in blocks {
+            validate_block(b.height, &b.block)?;
             self.store.insert(b.height, b.block);
         }
         Ok(())
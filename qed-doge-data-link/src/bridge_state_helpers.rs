@@ -24,10 +24,9 @@ substantial portions of the software:
 with contributions from Carter Feldman (https://x.com/cmpeq)."
 */
 
-use doge_light_client::{chain_state::QEDDogeChainStateCore, init_params::InitBlockDataIBC};
+use doge_light_client::{chain_state::QEDDogeChainStateCore, init_params::InitBlockDataIBC, state_envelope::chain_state_to_envelope_data};
 
 use crate::traits::QDogeBlockHeaderFetcher;
-use zerocopy::IntoBytes;
 
 pub fn gen_bridge_initial_state<
     HF: QDogeBlockHeaderFetcher,
@@ -66,9 +65,10 @@ pub fn gen_bridge_initial_state<
         QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
         QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
         QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
-    >::from_init_data(&init_data))
+    >::from_init_data(&init_data)?)
 }
 
+/// Initial state wrapped in a versioned state envelope.
 pub fn gen_bridge_initial_state_data<
     HF: QDogeBlockHeaderFetcher,
     const QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE: usize,
@@ -78,12 +78,11 @@ pub fn gen_bridge_initial_state_data<
     fetcher: &mut HF,
     new_tip: u32,
 ) -> anyhow::Result<Vec<u8>> {
-    let state_data = gen_bridge_initial_state::<
+    let state = gen_bridge_initial_state::<
         HF,
         QDOGE_BRIDGE_BLOCK_HASH_CACHE_SIZE,
         QDOGE_BRIDGE_REQUIRED_CONFIRMATIONS,
         QDOGE_BRIDGE_BLOCK_TREE_HEIGHT,
     >(fetcher, new_tip)?;
-    let state_data_bytes = state_data.as_bytes().to_vec().clone();
-    Ok(state_data_bytes)
+    Ok(chain_state_to_envelope_data(&state)?)
 }
@@ -27,7 +27,7 @@ with contributions from Carter Feldman (https://x.com/cmpeq)."
 use std::time::Duration;
 
 use bitcoin::{block::SimpleHeader, hashes::Hash, Block};
-use doge_light_client::{core_data::{QAuxPow, QDogeBlock, QDogeBlockHeader, QHash256, QMerkleBranch, QStandardBlockHeader}, doge::transaction::BTCTransaction, hash::{merkle::{merkle_proof::MerkleProofCore, utils::compute_root_merkle_proof_generic}, sha256::QBTCHash256Hasher}, network_params::DogeNetworkType};
+use doge_light_client::{core_data::{QAuxPow, QDogeBlock, QDogeBlockHeader, QHash256, QMerkleBranch, QStandardBlockHeader}, doge::transaction::BTCTransaction, error::QDogeResult, hash::{merkle::{merkle_proof::MerkleProofCore, utils::compute_root_merkle_proof_generic}, sha256::QBTCHash256Hasher}, network_params::DogeNetworkType};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use ureq::Agent;
 
@@ -67,7 +67,7 @@ impl ElectrumMerkleProofOutput {
 
 fn btc_block_to_qdoge(btc_block: &Block) -> anyhow::Result<QDogeBlock> {
 
-    let txs = btc_block.txdata.iter().map(|x|BTCTransaction::from_bytes(&bitcoin::consensus::encode::serialize(&x))).collect::<anyhow::Result<Vec<BTCTransaction>>>()?;
+    let txs = btc_block.txdata.iter().map(|x|BTCTransaction::from_bytes(&bitcoin::consensus::encode::serialize(&x))).collect::<QDogeResult<Vec<BTCTransaction>>>()?;
     let header_bytes: Vec<u8> = bitcoin::consensus::encode::serialize::<SimpleHeader>(&btc_block.header.to_simple_header());
 
     
@@ -142,7 +142,7 @@ impl DogeLinkElectrsClient {
     pub fn get_q_tx_from_txid(&self, txid: QHash256) -> anyhow::Result<BTCTransaction> {
         let txid_str = hex::encode(txid);
         let bytes = self.get_bytes(&format!("tx/{}/raw", txid_str))?;
-        BTCTransaction::from_bytes(&bytes)
+        Ok(BTCTransaction::from_bytes(&bytes)?)
     }
     pub fn get_electrum_merkle_proof_from_txid(&self, txid: QHash256) -> anyhow::Result<ElectrumMerkleProofOutput> {
         let txid_str = hex::encode(txid);
@@ -31,6 +31,7 @@ pub mod block_cache;
 pub mod chain_state_helper;
 pub mod block_header_cache;
 pub mod bridge_state_helpers;
+pub mod state_audit;
 pub mod hex_helpers;
 pub mod wrapped_hash_256;
 pub mod simple_merkle_node;
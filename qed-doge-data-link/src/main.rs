@@ -0,0 +1,155 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use std::collections::HashMap;
+
+use doge_light_client::{
+    chain_state_view::QEDDogeChainStateLayout,
+    constants::{DogeMainNetConfig, DogeRegTestConfig, DogeTestNetConfig},
+    network_params::DogeNetworkType,
+};
+use qed_doge_data_link::{
+    block_header_cache::BlockHeaderFetcher,
+    electrs_link::DogeLinkElectrsClient,
+    state_audit::{audit_chain_state_data, StateAuditReport},
+};
+
+const DEFAULT_ELECTRS_URL: &str = "https://doge-electrs-demo.qed.me";
+
+const USAGE: &str = "usage: qed-doge-data-link <command> [options]
+
+commands:
+  audit-state   replay block headers and diff them against a serialized chain state
+      --state <path>                   chain state bytes (raw or hex), envelope or legacy format
+      --init-tip <height>              tip block number of the initial bridge state
+      --headers <path>                 bincode header file to load before querying electrs
+      --electrs <url>                  electrs endpoint for headers missing from the header file
+      --network <mainnet|testnet|regtest>
+      --cache-size <n>                 legacy layout block hash cache size (default 32)
+      --required-confirmations <n>     legacy layout required confirmations (default 4)
+      --tree-height <n>                legacy layout block tree height (default 32)";
+
+fn parse_options(args: &[String]) -> anyhow::Result<HashMap<String, String>> {
+    let mut options = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let name = arg
+            .strip_prefix("--")
+            .ok_or_else(|| anyhow::anyhow!("unexpected argument '{}'\n\n{}", arg, USAGE))?;
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("missing value for --{}", name))?;
+        options.insert(name.to_string(), value.clone());
+    }
+    Ok(options)
+}
+
+fn get_option<T: std::str::FromStr>(options: &HashMap<String, String>, name: &str, default: Option<T>) -> anyhow::Result<T> {
+    match options.get(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid value '{}' for --{}", value, name)),
+        None => default.ok_or_else(|| anyhow::anyhow!("missing required option --{}\n\n{}", name, USAGE)),
+    }
+}
+
+fn read_state_file(path: &str) -> anyhow::Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    match std::str::from_utf8(&data).ok().map(|s| hex::decode(s.trim())) {
+        Some(Ok(decoded)) => Ok(decoded),
+        _ => Ok(data),
+    }
+}
+
+fn print_report(report: &StateAuditReport) {
+    println!(
+        "layout: cache_size={} required_confirmations={} tree_height={}",
+        report.layout.get_block_hash_cache_size(),
+        report.layout.get_required_confirmations(),
+        report.layout.get_block_tree_height()
+    );
+    println!("init tip: {}", report.init_tip_block_number);
+    println!("state tip: {}", report.tip_block_number);
+    println!("replayed blocks: {}", report.replayed_blocks);
+    match &report.divergence {
+        Some(divergence) => println!("{}", divergence),
+        None => println!("state matches the replayed headers"),
+    }
+}
+
+fn audit_state(args: &[String]) -> anyhow::Result<bool> {
+    let options = parse_options(args)?;
+    let state_path: String = get_option(&options, "state", None)?;
+    let init_tip: u32 = get_option(&options, "init-tip", None)?;
+    let electrs_url: String = get_option(&options, "electrs", Some(DEFAULT_ELECTRS_URL.to_string()))?;
+    let network: String = get_option(&options, "network", Some("mainnet".to_string()))?;
+    let legacy_layout = QEDDogeChainStateLayout::new(
+        get_option(&options, "cache-size", Some(32))?,
+        get_option(&options, "required-confirmations", Some(4))?,
+        get_option(&options, "tree-height", Some(32))?,
+    )?;
+
+    let network_type = match network.as_str() {
+        "mainnet" => DogeNetworkType::MainNet,
+        "testnet" => DogeNetworkType::TestNet,
+        "regtest" => DogeNetworkType::RegTest,
+        _ => anyhow::bail!("unknown network '{}'", network),
+    };
+    let mut fetcher = BlockHeaderFetcher::new(DogeLinkElectrsClient::new(electrs_url, network_type));
+    if let Some(headers_path) = options.get("headers") {
+        fetcher.load_block_headers_bin(headers_path)?;
+    }
+
+    let state_data = read_state_file(&state_path)?;
+    let report = match network_type {
+        DogeNetworkType::MainNet => {
+            audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &state_data, legacy_layout, init_tip)?
+        }
+        DogeNetworkType::TestNet => {
+            audit_chain_state_data::<DogeTestNetConfig, _>(&mut fetcher, &state_data, legacy_layout, init_tip)?
+        }
+        DogeNetworkType::RegTest => {
+            audit_chain_state_data::<DogeRegTestConfig, _>(&mut fetcher, &state_data, legacy_layout, init_tip)?
+        }
+    };
+    print_report(&report);
+    Ok(report.is_ok())
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let ok = match args.first().map(|s| s.as_str()) {
+        Some("audit-state") => audit_state(&args[1..])?,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
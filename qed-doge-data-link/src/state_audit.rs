@@ -0,0 +1,368 @@
+/*
+Copyright (C) 2025 Zero Knowledge Labs Limited, QED Protocol
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+Additional terms under GNU AGPL version 3 section 7:
+
+As permitted by section 7(b) of the GNU Affero General Public License, 
+you must retain the following attribution notice in all copies or 
+substantial portions of the software:
+
+"This software was created by QED (https://qedprotocol.com)
+with contributions from Carter Feldman (https://x.com/cmpeq)."
+*/
+
+use doge_light_client::{
+    block_data_tracker::BlockDataRecord,
+    chain_state::QEDDogeChainStateStorage,
+    chain_state_view::{QEDDogeChainStateLayout, QEDDogeChainStateView},
+    constants::DogeNetworkConfig,
+    core_data::{QDogeBlockHeader, QHash256},
+    hash::{sha256::QSha256Hasher, traits::get_zero_hashes},
+    init_params::InitBlockDataRecord,
+    state_envelope::{migrate_legacy_chain_state_data, open_chain_state_envelope_mut, upgrade_chain_state_envelope, QDOGE_STATE_ENVELOPE_MAGIC},
+};
+
+use crate::traits::QDogeBlockHeaderFetcher;
+
+const AUDIT_HEADER_BATCH_SIZE: u32 = 1000;
+
+/// The first place where a serialized chain state disagrees with the state rebuilt from the header source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateAuditDivergence {
+    pub height: u32,
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for StateAuditDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "divergence at height {} in {}: expected {}, actual {}",
+            self.height, self.field, self.expected, self.actual
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateAuditReport {
+    pub layout: QEDDogeChainStateLayout,
+    pub init_tip_block_number: u32,
+    pub tip_block_number: u32,
+    pub replayed_blocks: u32,
+    pub divergence: Option<StateAuditDivergence>,
+}
+
+impl StateAuditReport {
+    pub fn is_ok(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Opens serialized chain state bytes as a current version envelope.
+///
+/// Envelope data of an older version is upgraded, data without the envelope magic is treated as a legacy
+/// unversioned state with the sizes in `legacy_layout`.
+pub fn load_chain_state_envelope_data(state_data: &[u8], legacy_layout: QEDDogeChainStateLayout) -> anyhow::Result<Vec<u8>> {
+    if state_data.starts_with(&QDOGE_STATE_ENVELOPE_MAGIC) {
+        Ok(upgrade_chain_state_envelope(state_data)?)
+    } else {
+        Ok(migrate_legacy_chain_state_data::<QSha256Hasher>(state_data, legacy_layout)?)
+    }
+}
+
+fn fmt_hash(hash: &QHash256) -> String {
+    hex::encode(hash)
+}
+
+fn diff_record(height: u32, expected: &BlockDataRecord, actual: &BlockDataRecord) -> Option<StateAuditDivergence> {
+    let fields: [(&'static str, String, String); 5] = [
+        ("block_hash", fmt_hash(&expected.block_hash), fmt_hash(&actual.block_hash)),
+        ("block_hash_tree_root", fmt_hash(&expected.block_hash_tree_root), fmt_hash(&actual.block_hash_tree_root)),
+        ("tx_tree_merkle_root", fmt_hash(&expected.tx_tree_merkle_root), fmt_hash(&actual.tx_tree_merkle_root)),
+        ("timestamp", expected.timestamp.get().to_string(), actual.timestamp.get().to_string()),
+        ("bits", format!("{:08x}", expected.bits.get()), format!("{:08x}", actual.bits.get())),
+    ];
+    fields
+        .into_iter()
+        .find(|(_, expected, actual)| expected != actual)
+        .map(|(field, expected, actual)| StateAuditDivergence { height, field, expected, actual })
+}
+
+/// Rebuilds the chain state from headers and reports the first divergence from `state_data`.
+///
+/// The bridge block tree starts empty at the initial state, so the replay starts from the block hash cache
+/// ending at `init_tip_block_number` and appends every header up to the tip of the audited state.
+pub fn audit_chain_state_data<NC: DogeNetworkConfig, HF: QDogeBlockHeaderFetcher>(
+    fetcher: &mut HF,
+    state_data: &[u8],
+    legacy_layout: QEDDogeChainStateLayout,
+    init_tip_block_number: u32,
+) -> anyhow::Result<StateAuditReport> {
+    let mut actual_data = load_chain_state_envelope_data(state_data, legacy_layout)?;
+    let actual = open_chain_state_envelope_mut::<QSha256Hasher>(&mut actual_data)?;
+    let layout = actual.get_layout();
+    let cache_size = layout.get_block_hash_cache_size() as u32;
+    let tip_block_number = actual.get_tip_block_number();
+
+    let mut report = StateAuditReport {
+        layout,
+        init_tip_block_number,
+        tip_block_number,
+        replayed_blocks: 0,
+        divergence: None,
+    };
+
+    if let Err(err) = actual.ensure_internal_consistency() {
+        report.divergence = Some(StateAuditDivergence {
+            height: tip_block_number,
+            field: "internal_consistency",
+            expected: "ok".to_string(),
+            actual: err.to_string(),
+        });
+        return Ok(report);
+    }
+    if init_tip_block_number < cache_size - 1 {
+        anyhow::bail!("init tip block number must be greater or equal to {}", cache_size - 1);
+    }
+    if tip_block_number < init_tip_block_number {
+        report.divergence = Some(StateAuditDivergence {
+            height: tip_block_number,
+            field: "tip_block_number",
+            expected: format!(">= {}", init_tip_block_number),
+            actual: tip_block_number.to_string(),
+        });
+        return Ok(report);
+    }
+
+    let start_block = init_tip_block_number - (cache_size - 1);
+    let base_headers = fetcher.get_qdoge_block_headers_cache(&(start_block..=init_tip_block_number).collect::<Vec<u32>>())?;
+    let init_records = base_headers
+        .iter()
+        .map(|header| InitBlockDataRecord {
+            block_hash: header.header.get_hash(),
+            tx_tree_merkle_root: header.header.merkle_root,
+            timestamp: header.header.timestamp,
+            bits: header.header.bits,
+        })
+        .collect::<Vec<_>>();
+
+    let mut expected_data = vec![0u8; layout.get_data_size()];
+    let mut expected = QEDDogeChainStateView::<QSha256Hasher>::init_from_init_data(
+        &mut expected_data,
+        layout,
+        &init_records,
+        &get_zero_hashes::<QHash256, QSha256Hasher>(layout.get_block_tree_height()),
+        init_tip_block_number,
+    )?;
+
+    // records older than the block hash cache of the audited state have already been evicted
+    let first_audited_block = tip_block_number.saturating_sub(cache_size - 1).max(start_block);
+    let check_height = |expected: &QEDDogeChainStateView<QSha256Hasher>, height: u32| -> anyhow::Result<Option<StateAuditDivergence>> {
+        if height < first_audited_block {
+            return Ok(None);
+        }
+        Ok(diff_record(height, &expected.get_record(height)?, &actual.get_record(height)?))
+    };
+
+    for height in start_block..=init_tip_block_number {
+        if let Some(divergence) = check_height(&expected, height)? {
+            report.divergence = Some(divergence);
+            return Ok(report);
+        }
+    }
+
+    let mut batch_start = init_tip_block_number + 1;
+    while batch_start <= tip_block_number {
+        let batch_end = tip_block_number.min(batch_start.saturating_add(AUDIT_HEADER_BATCH_SIZE - 1));
+        let headers: Vec<QDogeBlockHeader> =
+            fetcher.get_qdoge_block_headers_cache(&(batch_start..=batch_end).collect::<Vec<u32>>())?;
+        for (height, header) in (batch_start..=batch_end).zip(headers.iter()) {
+            if let Err(err) = expected.append_block::<NC>(height, header, None) {
+                report.divergence = Some(StateAuditDivergence {
+                    height,
+                    field: "block_header",
+                    expected: "valid header".to_string(),
+                    actual: err.to_string(),
+                });
+                return Ok(report);
+            }
+            report.replayed_blocks += 1;
+            if let Some(divergence) = check_height(&expected, height)? {
+                report.divergence = Some(divergence);
+                return Ok(report);
+            }
+        }
+        batch_start = batch_end + 1;
+    }
+
+    let expected_root = expected.get_block_tree_root();
+    let actual_root = actual.get_block_tree_root();
+    if expected_root != actual_root {
+        report.divergence = Some(StateAuditDivergence {
+            height: tip_block_number,
+            field: "block_tree_root",
+            expected: fmt_hash(&expected_root),
+            actual: fmt_hash(&actual_root),
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use doge_light_client::{
+        chain_state::{QEDDogeChainStateCore, QEDDogeChainStateStorage},
+        chain_state_view::QEDDogeChainStateLayout,
+        constants::DogeMainNetConfig,
+        core_data::QDogeBlockHeader,
+        hash::sha256::QSha256Hasher,
+        init_params::{get_empty_siblings, InitBlockDataIBC},
+        state_envelope::{chain_state_to_envelope_data, open_chain_state_envelope_mut},
+    };
+    use serde::Deserialize;
+    use zerocopy::IntoBytes;
+
+    use crate::traits::QDogeBlockHeaderFetcher;
+
+    use super::audit_chain_state_data;
+
+    const CACHE_SIZE: usize = 32;
+    const REQUIRED_CONFIRMATIONS: usize = 4;
+    const TREE_HEIGHT: usize = 32;
+    type TestChainState = QEDDogeChainStateCore<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>;
+
+    #[derive(Deserialize)]
+    struct BlockHeaderWithIndex {
+        height: u32,
+        block_header: QDogeBlockHeader,
+    }
+
+    struct TestHeaderFetcher {
+        headers: HashMap<u32, QDogeBlockHeader>,
+    }
+
+    impl QDogeBlockHeaderFetcher for TestHeaderFetcher {
+        fn get_qdoge_block_header(&self, height: u32) -> anyhow::Result<QDogeBlockHeader> {
+            self.headers
+                .get(&height)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("missing header {}", height))
+        }
+        fn get_qdoge_block_headers(&self, heights: &[u32]) -> anyhow::Result<Vec<QDogeBlockHeader>> {
+            heights.iter().map(|height| self.get_qdoge_block_header(*height)).collect()
+        }
+        fn get_qdoge_block_header_cache(&mut self, height: u32) -> anyhow::Result<QDogeBlockHeader> {
+            self.get_qdoge_block_header(height)
+        }
+        fn get_qdoge_block_headers_cache(&mut self, heights: &[u32]) -> anyhow::Result<Vec<QDogeBlockHeader>> {
+            self.get_qdoge_block_headers(heights)
+        }
+    }
+
+    fn load_mainnet_headers() -> anyhow::Result<Vec<BlockHeaderWithIndex>> {
+        let data = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../test_data/mainnet_headers_5610330-5611352.bin"
+        ))?;
+        let mut headers: Vec<BlockHeaderWithIndex> = bincode::deserialize(&data)?;
+        headers.sort_by_key(|x| x.height);
+        Ok(headers)
+    }
+
+    /// Returns the header source, the init tip and the envelope data of a state synced 40 blocks past it.
+    fn synced_state() -> anyhow::Result<(TestHeaderFetcher, u32, Vec<u8>)> {
+        let headers = load_mainnet_headers()?;
+        let base: [QDogeBlockHeader; CACHE_SIZE] = core::array::from_fn(|i| headers[i].block_header.clone());
+        let init_tip_block_number = headers[CACHE_SIZE - 1].height;
+        let mut state = TestChainState::from_init_data(&InitBlockDataIBC::<CACHE_SIZE, TREE_HEIGHT>::new_from_block_headers(
+            &base,
+            get_empty_siblings::<TREE_HEIGHT>(),
+            init_tip_block_number,
+        )).unwrap();
+        for x in &headers[CACHE_SIZE..CACHE_SIZE + 40] {
+            state.append_block::<DogeMainNetConfig>(x.height, &x.block_header, None)?;
+        }
+        let fetcher = TestHeaderFetcher {
+            headers: headers.into_iter().map(|x| (x.height, x.block_header)).collect(),
+        };
+        Ok((fetcher, init_tip_block_number, chain_state_to_envelope_data(&state)?))
+    }
+
+    fn legacy_layout() -> QEDDogeChainStateLayout {
+        QEDDogeChainStateLayout::new_for::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>().unwrap()
+    }
+
+    #[test]
+    fn test_audit_synced_state() -> anyhow::Result<()> {
+        let (mut fetcher, init_tip_block_number, data) = synced_state()?;
+        let report = audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &data, legacy_layout(), init_tip_block_number)?;
+        assert!(report.is_ok(), "{:?}", report.divergence);
+        assert_eq!(report.replayed_blocks, 40);
+        assert_eq!(report.tip_block_number, init_tip_block_number + 40);
+
+        // unversioned data of the same state is audited the same way
+        let mut envelope_data = data.clone();
+        let legacy_data = open_chain_state_envelope_mut::<QSha256Hasher>(&mut envelope_data)?
+            .to_chain_state::<CACHE_SIZE, REQUIRED_CONFIRMATIONS, TREE_HEIGHT>()?
+            .as_bytes()
+            .to_vec();
+        let report = audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &legacy_data, legacy_layout(), init_tip_block_number)?;
+        assert!(report.is_ok(), "{:?}", report.divergence);
+
+        assert!(audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &data, legacy_layout(), CACHE_SIZE as u32 - 2).is_err());
+        Ok(())
+    }
+
+    fn corrupt_record(data: &mut [u8], block_number: u32, offset: usize) -> anyhow::Result<()> {
+        let mut envelope_data = data.to_vec();
+        let record = open_chain_state_envelope_mut::<QSha256Hasher>(&mut envelope_data)?.get_record(block_number)?;
+        let position = data
+            .windows(record.as_bytes().len())
+            .position(|x| x == record.as_bytes())
+            .ok_or_else(|| anyhow::anyhow!("record not found"))?;
+        data[position + offset] ^= 1;
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_corrupted_state() -> anyhow::Result<()> {
+        let (mut fetcher, init_tip_block_number, data) = synced_state()?;
+        let tip_block_number = init_tip_block_number + 40;
+
+        // timestamp of the record before the tip
+        let mut corrupted = data.clone();
+        corrupt_record(&mut corrupted, tip_block_number - 1, 96)?;
+        let report = audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &corrupted, legacy_layout(), init_tip_block_number)?;
+        let divergence = report.divergence.unwrap();
+        assert_eq!((divergence.height, divergence.field), (tip_block_number - 1, "timestamp"));
+
+        // tip block hash no longer matches the block tree
+        let mut corrupted = data.clone();
+        corrupt_record(&mut corrupted, tip_block_number, 32)?;
+        let report = audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &corrupted, legacy_layout(), init_tip_block_number)?;
+        assert_eq!(report.divergence.unwrap().field, "internal_consistency");
+
+        // audited against a different init tip the rebuilt block tree roots differ from the first record on
+        let report = audit_chain_state_data::<DogeMainNetConfig, _>(&mut fetcher, &data, legacy_layout(), init_tip_block_number + 1)?;
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.field, "block_hash_tree_root");
+        Ok(())
+    }
+}